@@ -1,13 +1,27 @@
+use shader::ShaderFeatures;
 use spirv_builder::{MetadataPrintout, SpirvBuilder, SpirvMetadata};
 
-use std::fs::copy;
+use std::fs::{copy, write};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let result = SpirvBuilder::new("../shader", "spirv-unknown-vulkan1.2")
-        .print_metadata(MetadataPrintout::Full)
-        .spirv_metadata(SpirvMetadata::Full)
-        .build()?;
+    // Build a module for every feature permutation so toggles are resolved at compile time
+    // instead of branching in every fragment
+    for features in ShaderFeatures::permutations() {
+        let result = SpirvBuilder::new("../shader", "spirv-unknown-vulkan1.2")
+            .print_metadata(MetadataPrintout::Full)
+            .spirv_metadata(SpirvMetadata::Full)
+            .shader_crate_default_features(false)
+            .shader_crate_features(features.cargo_features().into_iter().map(String::from))
+            .build()?;
 
-    copy(result.module.unwrap_single(), "./spirv/shader.spv")?;
+        copy(
+            result.module.unwrap_single(),
+            format!("./spirv/{}", features.spirv_file_name()),
+        )?;
+    }
+
+    // Record which version of the shader abi the spirv was built from so the host can detect
+    // a stale shader at startup
+    write("./spirv/shader.abi", shader::SHADER_ABI_VERSION.to_string())?;
     Ok(())
 }
@@ -0,0 +1,606 @@
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use glam::{Vec2, Vec4};
+use winit::window::Window;
+
+use crate::{
+    easing::Easing,
+    scene::{HitItem, KeyframeTrack, KeyframeValue, Layer, Scene},
+};
+
+// What a tween animates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnimationTarget {
+    // The layer itself. Only blur radius tweens apply to layers, animating their content blur
+    Layer(usize),
+    Item { layer: usize, item: HitItem },
+}
+
+// Property a tween animates along with the values it moves between. Properties an item
+// doesn't have are left alone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TweenValue {
+    // Top left of quads, sprites, and shader quads, the baseline start of texts, and the start
+    // of paths, which move as a whole
+    Position { from: Vec2, to: Vec2 },
+    // Color of quads, texts, and sprites, and the fill of paths
+    Color { from: Vec4, to: Vec4 },
+    // Alpha of the colors animated by Color, plus path strokes
+    Opacity { from: f32, to: f32 },
+    // Blur of quads, or the content blur of layers
+    BlurRadius { from: f32, to: f32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tween {
+    pub target: AnimationTarget,
+    pub value: TweenValue,
+    pub duration: Duration,
+    // Time after the tween is added before it starts moving. The start value is held until then
+    pub delay: Duration,
+    pub easing: Easing,
+}
+
+impl Tween {
+    pub fn new(target: AnimationTarget, value: TweenValue, duration: Duration) -> Self {
+        Self {
+            target,
+            value,
+            duration,
+            delay: Duration::ZERO,
+            easing: Easing::default(),
+        }
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    // Eased progress between 0 and 1 at `elapsed` after the tween was added
+    pub fn progress(&self, elapsed: Duration) -> f32 {
+        let elapsed = elapsed.saturating_sub(self.delay);
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+        self.easing
+            .apply(elapsed.as_secs_f32() / self.duration.as_secs_f32())
+    }
+
+    pub fn finished(&self, elapsed: Duration) -> bool {
+        elapsed >= self.delay + self.duration
+    }
+
+    // Sets the target's property to its value at the given progress
+    pub fn apply(&self, scene: &mut Scene, progress: f32) {
+        apply_value(scene, self.target, self.value, progress);
+    }
+}
+
+fn apply_value(scene: &mut Scene, target: AnimationTarget, value: TweenValue, progress: f32) {
+    let (layer_index, item) = match target {
+        AnimationTarget::Layer(layer_index) => (layer_index, None),
+        AnimationTarget::Item { layer, item } => (layer, Some(item)),
+    };
+    let Some(layer) = scene.layers.get_mut(layer_index) else {
+        return;
+    };
+    let layer = Arc::make_mut(layer);
+
+    match item {
+        Some(item) => apply_to_item(layer, item, value, progress),
+        None => {
+            if let TweenValue::BlurRadius { from, to } = value {
+                layer.content_blur_radius = lerp(from, to, progress);
+            }
+        }
+    }
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+fn apply_to_item(layer: &mut Layer, item: HitItem, value: TweenValue, t: f32) {
+    match item {
+        HitItem::Quad(index) => {
+            let Some(quad) = layer.quads.get_mut(index) else {
+                return;
+            };
+            match value {
+                TweenValue::Position { from, to } => quad.set_top_left(from.lerp(to, t)),
+                TweenValue::Color { from, to } => quad.set_color(from.lerp(to, t)),
+                TweenValue::Opacity { from, to } => {
+                    let mut color = quad.color();
+                    color.w = lerp(from, to, t);
+                    quad.set_color(color);
+                }
+                TweenValue::BlurRadius { from, to } => quad.set_blur(lerp(from, to, t)),
+            }
+        }
+        HitItem::Ellipse(index) => {
+            let Some(ellipse) = layer.ellipses.get_mut(index) else {
+                return;
+            };
+            match value {
+                TweenValue::Position { from, to } => ellipse.center = from.lerp(to, t),
+                TweenValue::Color { from, to } => ellipse.fill = Some(from.lerp(to, t)),
+                TweenValue::Opacity { from, to } => {
+                    let opacity = lerp(from, to, t);
+                    if let Some(fill) = ellipse.fill.as_mut() {
+                        fill.w = opacity;
+                    }
+                    if let Some((_, stroke)) = ellipse.stroke.as_mut() {
+                        stroke.w = opacity;
+                    }
+                }
+                TweenValue::BlurRadius { .. } => {}
+            }
+        }
+        HitItem::Text(index) => {
+            let Some(text) = layer.texts.get_mut(index) else {
+                return;
+            };
+            match value {
+                TweenValue::Position { from, to } => text.bottom_left = from.lerp(to, t),
+                TweenValue::Color { from, to } => text.color = Some(from.lerp(to, t)),
+                TweenValue::Opacity { from, to } => {
+                    // Texts inheriting their color keep the layer's color with the new alpha
+                    let mut color = text.color.or(layer.text_style.color).unwrap_or(Vec4::W);
+                    color.w = lerp(from, to, t);
+                    text.color = Some(color);
+                }
+                TweenValue::BlurRadius { .. } => {}
+            }
+        }
+        HitItem::TextLog(index) => {
+            let Some(text_log) = layer.text_logs.get_mut(index) else {
+                return;
+            };
+            match value {
+                TweenValue::Position { from, to } => text_log.top_left = from.lerp(to, t),
+                TweenValue::Color { from, to } => text_log.color = from.lerp(to, t),
+                TweenValue::Opacity { from, to } => text_log.color.w = lerp(from, to, t),
+                TweenValue::BlurRadius { .. } => {}
+            }
+        }
+        HitItem::Path(index) => {
+            let Some(path) = layer.paths.get_mut(index) else {
+                return;
+            };
+            match value {
+                TweenValue::Position { from, to } => {
+                    let offset = from.lerp(to, t) - path.start;
+                    path.translate(offset);
+                }
+                TweenValue::Color { from, to } => path.fill = Some(from.lerp(to, t)),
+                TweenValue::Opacity { from, to } => {
+                    let opacity = lerp(from, to, t);
+                    if let Some(fill) = path.fill.as_mut() {
+                        fill.w = opacity;
+                    }
+                    if let Some((_, stroke)) = path.stroke.as_mut() {
+                        stroke.w = opacity;
+                    }
+                }
+                TweenValue::BlurRadius { .. } => {}
+            }
+        }
+        HitItem::Polyline(index) => {
+            let Some(polyline) = layer.polylines.get_mut(index) else {
+                return;
+            };
+            match value {
+                // Moves the line so its first point lands on the position
+                TweenValue::Position { from, to } => {
+                    let Some(first) = polyline.points.first().copied() else {
+                        return;
+                    };
+                    let offset = from.lerp(to, t) - first;
+                    for point in polyline.points.iter_mut() {
+                        *point += offset;
+                    }
+                }
+                TweenValue::Color { from, to } => polyline.color = from.lerp(to, t),
+                TweenValue::Opacity { from, to } => polyline.color.w = lerp(from, to, t),
+                TweenValue::BlurRadius { .. } => {}
+            }
+        }
+        HitItem::Mesh(index) => {
+            let Some(mesh) = layer.meshes.get_mut(index) else {
+                return;
+            };
+            match value {
+                // Moves the mesh so its first vertex lands on the position
+                TweenValue::Position { from, to } => {
+                    let Some(first) = mesh.vertices.first().map(|vertex| vertex.position) else {
+                        return;
+                    };
+                    let offset = from.lerp(to, t) - first;
+                    for vertex in mesh.vertices.iter_mut() {
+                        vertex.position += offset;
+                    }
+                }
+                TweenValue::Color { from, to } => {
+                    for vertex in mesh.vertices.iter_mut() {
+                        vertex.color = from.lerp(to, t);
+                    }
+                }
+                TweenValue::Opacity { from, to } => {
+                    for vertex in mesh.vertices.iter_mut() {
+                        vertex.color.w = lerp(from, to, t);
+                    }
+                }
+                TweenValue::BlurRadius { .. } => {}
+            }
+        }
+        HitItem::Sprite(index) => {
+            let Some(sprite) = layer.sprites.get_mut(index) else {
+                return;
+            };
+            match value {
+                TweenValue::Position { from, to } => sprite.top_left = from.lerp(to, t),
+                TweenValue::Color { from, to } => sprite.color = from.lerp(to, t),
+                TweenValue::Opacity { from, to } => sprite.color.w = lerp(from, to, t),
+                TweenValue::BlurRadius { .. } => {}
+            }
+        }
+        HitItem::ShaderQuad(index) => {
+            if let (Some(quad), TweenValue::Position { from, to }) =
+                (layer.shader_quads.get_mut(index), value)
+            {
+                quad.top_left = from.lerp(to, t);
+            }
+        }
+        HitItem::MaterialQuad(index) => {
+            if let (Some(quad), TweenValue::Position { from, to }) =
+                (layer.material_quads.get_mut(index), value)
+            {
+                quad.top_left = from.lerp(to, t);
+            }
+        }
+        HitItem::Pyramid(_) | HitItem::Mirror(_) => {}
+    }
+}
+
+// Runs tweens against a retained scene. Call `drive` from the event loop before drawing each
+// frame, or `update` when the app schedules its own redraws.
+#[derive(Debug, Clone, Default)]
+pub struct Animator {
+    tweens: Vec<(Instant, Tween)>,
+}
+
+impl Animator {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn add(&mut self, tween: Tween) {
+        self.add_at(tween, Instant::now());
+    }
+
+    pub fn add_at(&mut self, tween: Tween, start: Instant) {
+        self.tweens.push((start, tween));
+    }
+
+    // Stops every tween on the target, leaving it wherever it was last set
+    pub fn cancel(&mut self, target: AnimationTarget) {
+        self.tweens.retain(|(_, tween)| tween.target != target);
+    }
+
+    pub fn is_animating(&self) -> bool {
+        !self.tweens.is_empty()
+    }
+
+    // Applies every tween at `now` in the order they were added, so later tweens win when they
+    // animate the same property. Finished tweens are applied at their end value and removed.
+    // Returns whether any tweens are still running
+    pub fn update(&mut self, scene: &mut Scene, now: Instant) -> bool {
+        self.tweens.retain(|(start, tween)| {
+            let elapsed = now.saturating_duration_since(*start);
+            tween.apply(scene, tween.progress(elapsed));
+            !tween.finished(elapsed)
+        });
+        self.is_animating()
+    }
+
+    // Updates the scene to the current time and asks the window for another frame while tweens
+    // are still running
+    pub fn drive(&mut self, scene: &mut Scene, window: &Window) -> bool {
+        let animating = self.update(scene, Instant::now());
+        if animating {
+            window.request_redraw();
+        }
+        animating
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyframeError {
+    // The path doesn't name a layer or item property tweens can animate
+    InvalidProperty(String),
+    // A keyframe's value doesn't fit its property, such as a color given as a single number
+    MismatchedValue { property: String, time: f32 },
+}
+
+impl fmt::Display for KeyframeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidProperty(property) => {
+                write!(f, "\"{}\" is not an animatable property", property)
+            }
+            Self::MismatchedValue { property, time } => write!(
+                f,
+                "keyframe at {}s has the wrong kind of value for \"{}\"",
+                time, property
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KeyframeError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TrackProperty {
+    Position,
+    Color,
+    Opacity,
+    BlurRadius,
+}
+
+impl TrackProperty {
+    fn tween_value(self, from: KeyframeValue, to: KeyframeValue) -> Option<TweenValue> {
+        Some(match (self, from, to) {
+            (Self::Position, KeyframeValue::Vec2(from), KeyframeValue::Vec2(to)) => {
+                TweenValue::Position { from, to }
+            }
+            (Self::Color, KeyframeValue::Vec4(from), KeyframeValue::Vec4(to)) => {
+                TweenValue::Color { from, to }
+            }
+            (Self::Opacity, KeyframeValue::Scalar(from), KeyframeValue::Scalar(to)) => {
+                TweenValue::Opacity { from, to }
+            }
+            (Self::BlurRadius, KeyframeValue::Scalar(from), KeyframeValue::Scalar(to)) => {
+                TweenValue::BlurRadius { from, to }
+            }
+            _ => return None,
+        })
+    }
+}
+
+// Parses a track's dotted property path into what it animates
+fn parse_property(path: &str) -> Option<(AnimationTarget, TrackProperty)> {
+    let parts: Vec<&str> = path.split('.').collect();
+    match parts.as_slice() {
+        // Only blur applies to layers themselves
+        ["layers", layer, "blur_radius"] => Some((
+            AnimationTarget::Layer(layer.parse().ok()?),
+            TrackProperty::BlurRadius,
+        )),
+        ["layers", layer, items, index, property] => {
+            let index = index.parse().ok()?;
+            let item = match *items {
+                "material_quads" => HitItem::MaterialQuad(index),
+                "quads" => HitItem::Quad(index),
+                "ellipses" => HitItem::Ellipse(index),
+                "texts" => HitItem::Text(index),
+                "paths" => HitItem::Path(index),
+                "polylines" => HitItem::Polyline(index),
+                "meshes" => HitItem::Mesh(index),
+                "sprites" => HitItem::Sprite(index),
+                "shader_quads" => HitItem::ShaderQuad(index),
+                _ => return None,
+            };
+            let property = match *property {
+                "position" => TrackProperty::Position,
+                "color" => TrackProperty::Color,
+                "opacity" => TrackProperty::Opacity,
+                "blur_radius" => TrackProperty::BlurRadius,
+                _ => return None,
+            };
+            let target = AnimationTarget::Item {
+                layer: layer.parse().ok()?,
+                item,
+            };
+            Some((target, property))
+        }
+        _ => None,
+    }
+}
+
+struct PlayerTrack {
+    target: AnimationTarget,
+    property: TrackProperty,
+    track: KeyframeTrack,
+}
+
+// Plays the keyframe tracks saved in a scene against it. Tracks set absolute values, so the
+// scene can be seeked to any time in any order.
+pub struct ScenePlayer {
+    scene: Scene,
+    tracks: Vec<PlayerTrack>,
+    start: Instant,
+    looping: bool,
+}
+
+impl ScenePlayer {
+    // Checks every track of the scene and starts playing from the scene's first frame
+    pub fn new(scene: Scene) -> Result<Self, KeyframeError> {
+        let mut tracks = Vec::new();
+        for track in &scene.tracks {
+            let (target, property) = parse_property(&track.property)
+                .ok_or_else(|| KeyframeError::InvalidProperty(track.property.clone()))?;
+            if let Some(keyframe) = track.keyframes.iter().find(|keyframe| {
+                property
+                    .tween_value(keyframe.value, keyframe.value)
+                    .is_none()
+            }) {
+                return Err(KeyframeError::MismatchedValue {
+                    property: track.property.clone(),
+                    time: keyframe.time,
+                });
+            }
+            tracks.push(PlayerTrack {
+                target,
+                property,
+                track: track.clone(),
+            });
+        }
+
+        let mut player = Self {
+            scene,
+            tracks,
+            start: Instant::now(),
+            looping: false,
+        };
+        player.seek(Duration::ZERO);
+        Ok(player)
+    }
+
+    // Starts over from the beginning once the last keyframe is reached
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn restart(&mut self) {
+        self.restart_at(Instant::now());
+    }
+
+    pub fn restart_at(&mut self, start: Instant) {
+        self.start = start;
+    }
+
+    // Time of the last keyframe of any track
+    pub fn duration(&self) -> Duration {
+        let seconds = self
+            .tracks
+            .iter()
+            .map(|track| track.track.duration())
+            .fold(0.0, f32::max);
+        Duration::from_secs_f32(seconds.max(0.0))
+    }
+
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    // Sets every animated property to its value at the time
+    pub fn seek(&mut self, time: Duration) {
+        let seconds = time.as_secs_f32();
+        for track in &self.tracks {
+            let Some((from, to, progress)) = track.track.segment(seconds) else {
+                continue;
+            };
+            if let Some(value) = track.property.tween_value(from.value, to.value) {
+                apply_value(
+                    &mut self.scene,
+                    track.target,
+                    value,
+                    from.easing.apply(progress),
+                );
+            }
+        }
+    }
+
+    // Seeks to the time since the player started. Returns whether it is still playing
+    pub fn update(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.start);
+        let duration = self.duration();
+        if self.looping && !duration.is_zero() {
+            let seconds = elapsed.as_secs_f64() % duration.as_secs_f64();
+            self.seek(Duration::from_secs_f64(seconds));
+            return true;
+        }
+        self.seek(elapsed);
+        elapsed < duration
+    }
+
+    // Updates the scene to the current time and asks the window for another frame while the
+    // animation is still playing
+    pub fn drive(&mut self, window: &Window) -> bool {
+        let playing = self.update(Instant::now());
+        if playing {
+            window.request_redraw();
+        }
+        playing
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+    use crate::scene::{Keyframe, Quad};
+
+    #[test]
+    fn test_tweens_move_items_and_finish() {
+        let mut scene =
+            Scene::new().with_quad(Quad::new(vec2(0.0, 0.0), vec2(10.0, 10.0), Vec4::ONE));
+        let target = AnimationTarget::Item {
+            layer: 0,
+            item: HitItem::Quad(0),
+        };
+        let tween = Tween::new(
+            target,
+            TweenValue::Position {
+                from: vec2(0.0, 0.0),
+                to: vec2(100.0, 0.0),
+            },
+            Duration::from_secs(1),
+        )
+        .with_easing(Easing::Linear);
+
+        let start = Instant::now();
+        let mut animator = Animator::new();
+        animator.add_at(tween, start);
+
+        assert!(animator.update(&mut scene, start + Duration::from_millis(500)));
+        assert_eq!(scene.layer().quads[0].top_left(), vec2(50.0, 0.0));
+
+        assert!(!animator.update(&mut scene, start + Duration::from_secs(2)));
+        assert_eq!(scene.layer().quads[0].top_left(), vec2(100.0, 0.0));
+    }
+
+    #[test]
+    fn test_scene_player_plays_keyframe_tracks() {
+        let scene = Scene::new()
+            .with_quad(Quad::new(vec2(0.0, 0.0), vec2(10.0, 10.0), Vec4::ONE))
+            .with_track(
+                KeyframeTrack::new("layers.0.quads.0.position")
+                    .with_keyframe(
+                        Keyframe::new(0.0, KeyframeValue::Vec2(vec2(0.0, 0.0)))
+                            .with_easing(Easing::Linear),
+                    )
+                    .with_keyframe(Keyframe::new(2.0, KeyframeValue::Vec2(vec2(100.0, 0.0)))),
+            );
+        let mut player = ScenePlayer::new(scene.clone()).unwrap();
+        assert_eq!(player.duration(), Duration::from_secs(2));
+
+        let start = Instant::now();
+        player.restart_at(start);
+        assert!(player.update(start + Duration::from_secs(1)));
+        assert_eq!(player.scene().layer().quads[0].top_left(), vec2(50.0, 0.0));
+        assert!(!player.update(start + Duration::from_secs(3)));
+        assert_eq!(player.scene().layer().quads[0].top_left(), vec2(100.0, 0.0));
+
+        let invalid = scene.with_track(
+            KeyframeTrack::new("layers.0.quads.0.color")
+                .with_keyframe(Keyframe::new(0.0, KeyframeValue::Scalar(1.0))),
+        );
+        assert!(matches!(
+            ScenePlayer::new(invalid),
+            Err(KeyframeError::MismatchedValue { .. })
+        ));
+    }
+}
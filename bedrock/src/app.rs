@@ -0,0 +1,191 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures::executor::block_on;
+use glam::{vec2, Vec2};
+use rust_embed::RustEmbed;
+use winit::{
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::{Window, WindowBuilder},
+};
+
+use crate::{Renderer, Scene};
+
+// When the app draws new frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FramePacing {
+    // Draws a frame every time the surface is ready for one, for animated content
+    #[default]
+    Continuous,
+    // Only draws after input, resizes, or when the previous frame called `request_redraw`
+    OnDemand,
+}
+
+// Everything the frame callback needs to build the next scene
+pub struct FrameContext<'a> {
+    // Time since the app started
+    pub time: Duration,
+    // Time since the previous frame was drawn
+    pub delta: Duration,
+    pub frame: u64,
+    // Window size in physical pixels, which is the coordinate space scenes are drawn in
+    pub size: Vec2,
+    pub scale_factor: f64,
+    // Last known cursor position, or None when it is outside the window
+    pub cursor: Option<Vec2>,
+    // Window events received since the previous frame, oldest first
+    pub events: Vec<WindowEvent>,
+    pub window: &'a Window,
+    pub renderer: &'a mut Renderer,
+    redraw_requested: bool,
+    exit_requested: bool,
+}
+
+impl<'a> FrameContext<'a> {
+    // Asks for another frame after this one. Only needed with on demand pacing
+    pub fn request_redraw(&mut self) {
+        self.redraw_requested = true;
+    }
+
+    // Closes the window and returns from `run` after this frame is drawn
+    pub fn exit(&mut self) {
+        self.exit_requested = true;
+    }
+}
+
+// Optional harness which owns the event loop, window, and renderer so small apps only have to
+// describe each frame
+pub struct App {
+    title: String,
+    pacing: FramePacing,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            title: "bedrock".to_string(),
+            pacing: FramePacing::default(),
+        }
+    }
+}
+
+impl App {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn with_pacing(mut self, pacing: FramePacing) -> Self {
+        self.pacing = pacing;
+        self
+    }
+
+    // Opens the window and calls the callback for every frame until the window is closed or
+    // the callback calls `exit`. Sprite textures are loaded from the embedded assets A
+    pub fn run<A: RustEmbed + 'static>(
+        self,
+        mut frame_callback: impl FnMut(&mut FrameContext) -> Scene,
+    ) {
+        let pacing = self.pacing;
+        let event_loop = EventLoop::new().expect("Couldn't create event loop");
+        event_loop.set_control_flow(ControlFlow::Wait);
+
+        let window = Arc::new(
+            WindowBuilder::new()
+                .with_title(self.title)
+                .build(&event_loop)
+                .expect("Couldn't create window"),
+        );
+        let mut renderer = block_on(Renderer::new(window.clone())).with_default_drawables::<A>();
+        if pacing == FramePacing::OnDemand {
+            // Input often doesn't change what's on screen, so skip frames identical to the last
+            renderer = renderer.with_redraw_on_demand();
+        }
+
+        let start = Instant::now();
+        let mut last_frame = start;
+        let mut frame = 0;
+        let mut cursor = None;
+        let mut events = Vec::new();
+        let mut suspended = false;
+
+        event_loop
+            .run(|event, target| {
+                renderer.handle_event(&event);
+
+                match event {
+                    Event::Suspended => suspended = true,
+                    Event::Resumed => {
+                        suspended = false;
+                        window.request_redraw();
+                    }
+                    Event::WindowEvent { event, window_id } if window_id == window.id() => {
+                        match event {
+                            WindowEvent::CloseRequested => target.exit(),
+                            WindowEvent::RedrawRequested => {
+                                if suspended {
+                                    return;
+                                }
+
+                                let now = Instant::now();
+                                let size = window.inner_size();
+                                let mut context = FrameContext {
+                                    time: now - start,
+                                    delta: now - last_frame,
+                                    frame,
+                                    size: vec2(size.width as f32, size.height as f32),
+                                    scale_factor: window.scale_factor(),
+                                    cursor,
+                                    events: std::mem::take(&mut events),
+                                    window: &window,
+                                    renderer: &mut renderer,
+                                    redraw_requested: false,
+                                    exit_requested: false,
+                                };
+                                let scene = frame_callback(&mut context);
+                                let redraw_requested = context.redraw_requested;
+                                let exit_requested = context.exit_requested;
+
+                                renderer.draw_scene(&scene);
+                                last_frame = now;
+                                frame += 1;
+
+                                if exit_requested {
+                                    target.exit();
+                                } else if pacing == FramePacing::Continuous || redraw_requested {
+                                    window.request_redraw();
+                                }
+                            }
+                            event => {
+                                match event {
+                                    WindowEvent::CursorMoved { position, .. } => {
+                                        cursor = Some(vec2(position.x as f32, position.y as f32))
+                                    }
+                                    WindowEvent::CursorLeft { .. } => cursor = None,
+                                    _ => {}
+                                }
+                                // Input and resizes may change the scene, so on demand apps
+                                // draw a frame to show them
+                                events.push(event);
+                                window.request_redraw();
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            })
+            .expect("Event loop failed");
+    }
+}
+
+// Runs a continuously paced app with default settings. Shorthand for `App::new().run::<A>`
+pub fn run<A: RustEmbed + 'static>(frame_callback: impl FnMut(&mut FrameContext) -> Scene) {
+    App::new().run::<A>(frame_callback);
+}
@@ -0,0 +1,652 @@
+use std::{collections::HashMap, sync::Arc};
+
+use glam::{vec2, vec4, Vec2, Vec4};
+use shader::ShaderConstants;
+use wgpu::*;
+
+use crate::{
+    redraw::hash_layer,
+    scene::{BlurQuality, BlurResolution, Layer},
+};
+
+// Number of mip levels in the backdrop texture. The first level is half the surface size, so
+// the smallest is a thirty second.
+pub const BACKDROP_LEVELS: u32 = 5;
+// Furthest apart the samples of a pass are spread, relative to the standard dual filter
+// offsets. Radii larger than the last level can cover are reached by spreading further which
+// eventually produces visible banding.
+const MAX_SPREAD: f32 = 4.0;
+// Gaussian kernels are cut off this many standard deviations from the center
+const GAUSSIAN_EXTENT: f32 = 3.0;
+// Half rate blurs which go unused for this many blurs are dropped
+const CACHE_LIFETIME: u64 = 600;
+// Blur targets are kept for this many source and destination pairs, which covers the
+// backdrop and content blurs of a couple of windows
+const MAX_TARGETS: usize = 4;
+
+// Blurs the backdrop of a layer into the surface's backdrop texture using dual filtering. The
+// image is downsampled until the blur is wide enough and then upsampled back to the requested
+// resolution, so the cost stays roughly constant as the radius grows instead of growing with
+// the square of the radius like the inline box blur. Higher qualities finish the blur with a
+// separable gaussian at the downsampled level instead of spreading the dual filter samples.
+pub struct BackdropBlur {
+    bind_group_layout: BindGroupLayout,
+    sampler: Sampler,
+    downsample_pipeline: Option<RenderPipeline>,
+    upsample_pipeline: Option<RenderPipeline>,
+    horizontal_pipeline: Option<RenderPipeline>,
+    vertical_pipeline: Option<RenderPipeline>,
+    targets: Vec<BlurTargets>,
+
+    // Results of half rate blurs, reused on the frames they aren't updated
+    cache: HashMap<BlurKey, CachedBlur>,
+    blurs: u64,
+}
+
+// How far, at what resolution, and how carefully to blur
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlurSettings {
+    pub radius: f32,
+    pub resolution: BlurResolution,
+    pub quality: BlurQuality,
+}
+
+// The layer a half rate blur belongs to. Layer indices are only unique within one scene, so
+// the render target the layer is drawn into is part of the key, and named layers keep their
+// blur when layers are added or removed beneath them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BlurKey {
+    backdrop: Id<Texture>,
+    target: Option<String>,
+    layer: LayerKey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum LayerKey {
+    Named(String),
+    Index(usize),
+}
+
+// What is drawn beneath a layer: the scene's clear color and the layers before it in the scene
+pub struct Beneath<'a> {
+    pub clear_color: Vec4,
+    pub layers: &'a [Arc<Layer>],
+}
+
+// A half rate blur's result is only reused while everything beneath its layer is unchanged
+pub struct HalfRate<'a> {
+    pub key: BlurKey,
+    pub beneath: Beneath<'a>,
+}
+
+struct CachedBlur {
+    texture: Texture,
+    settings: BlurSettings,
+    clear_color: Vec4,
+    beneath: Vec<Arc<Layer>>,
+    beneath_hashes: Vec<u64>,
+    // Set when the cached result was reused, so the next blur is a real one
+    reused: bool,
+    last_used: u64,
+}
+
+impl BackdropBlur {
+    pub fn new(device: &Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Backdrop blur bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Backdrop blur sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            bind_group_layout,
+            sampler,
+            downsample_pipeline: None,
+            upsample_pipeline: None,
+            horizontal_pipeline: None,
+            vertical_pipeline: None,
+            targets: Vec::new(),
+
+            cache: HashMap::new(),
+            blurs: 0,
+        }
+    }
+
+    pub fn surface_updated(
+        &mut self,
+        device: &Device,
+        shader: &ShaderModule,
+        format: TextureFormat,
+    ) {
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Backdrop blur pipeline layout"),
+            bind_group_layouts: &[&self.bind_group_layout],
+            push_constant_ranges: &[PushConstantRange {
+                stages: ShaderStages::all(),
+                range: 0..std::mem::size_of::<ShaderConstants>() as u32,
+            }],
+        });
+
+        let create_pipeline = |label, entry_point| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: VertexState {
+                    module: shader,
+                    entry_point: shader::BLUR_VERTEX,
+                    buffers: &[],
+                },
+                fragment: Some(FragmentState {
+                    module: shader,
+                    entry_point,
+                    targets: &[Some(ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                multiview: None,
+            })
+        };
+
+        self.downsample_pipeline = Some(create_pipeline(
+            "Backdrop downsample pipeline",
+            shader::BLUR_DOWNSAMPLE,
+        ));
+        self.upsample_pipeline = Some(create_pipeline(
+            "Backdrop upsample pipeline",
+            shader::BLUR_UPSAMPLE,
+        ));
+        self.horizontal_pipeline = Some(create_pipeline(
+            "Backdrop horizontal gaussian pipeline",
+            shader::BLUR_HORIZONTAL,
+        ));
+        self.vertical_pipeline = Some(create_pipeline(
+            "Backdrop vertical gaussian pipeline",
+            shader::BLUR_VERTICAL,
+        ));
+    }
+
+    // Blurs the source texture into the backdrop texture with the layer's background blur
+    // settings. Returns the mip level of the backdrop texture holding the result, or None if
+    // the blur should be done inline at full resolution.
+    //
+    // Half rate blurs are only updated every other call for the same layer, with the previous
+    // result copied back in between. Changing the blur's settings or redrawing anything beneath
+    // the layer updates it right away.
+    pub fn blur(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        source: &Texture,
+        backdrop: &Texture,
+        layer: &Layer,
+        beneath: Beneath,
+    ) -> Option<u32> {
+        let settings = BlurSettings {
+            radius: layer.background_blur_radius,
+            resolution: layer.background_blur_resolution,
+            quality: layer.background_blur_quality,
+        };
+        let half_rate = layer.background_blur_half_rate.then(|| HalfRate {
+            key: BlurKey::new(backdrop, layer, beneath.layers.len()),
+            beneath,
+        });
+        self.blur_texture(device, encoder, source, backdrop, settings, half_rate)
+    }
+
+    // Blurs the source texture into a mip chain shaped like the backdrop texture and returns
+    // the level holding the result.
+    //
+    // The radius only changes how many levels are used and how far apart the samples are, so
+    // it can be animated every frame without allocating anything.
+    pub fn blur_texture(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        source: &Texture,
+        backdrop: &Texture,
+        settings: BlurSettings,
+        half_rate: Option<HalfRate>,
+    ) -> Option<u32> {
+        let BlurSettings {
+            radius,
+            resolution,
+            quality,
+        } = settings;
+
+        let last_level = backdrop.mip_level_count() - 1;
+        let result_level = match resolution {
+            BlurResolution::Full => return None,
+            BlurResolution::Half => 0,
+            BlurResolution::Quarter => 1,
+        }
+        .min(last_level);
+
+        self.blurs += 1;
+        if self.blurs % CACHE_LIFETIME == 0 {
+            let blurs = self.blurs;
+            self.cache
+                .retain(|_, cached| blurs - cached.last_used < CACHE_LIFETIME);
+        }
+
+        let cached = half_rate
+            .as_ref()
+            .and_then(|half_rate| Some((half_rate, self.cache.get_mut(&half_rate.key)?)));
+        if let Some((half_rate, cached)) = cached {
+            if !cached.reused && cached.settings == settings && cached.same_backdrop(half_rate) {
+                cached.reused = true;
+                cached.last_used = self.blurs;
+                copy_level(encoder, &cached.texture, 0, backdrop, result_level);
+                return Some(result_level);
+            }
+        }
+
+        let radius = radius.abs();
+        let (blur_level, spread, gaussian) = match quality.gaussian_taps() {
+            // Each level of downsampling followed by upsampling roughly doubles the blur
+            // width, starting at two pixels for the first level. The remainder is made up by
+            // spreading the samples of every pass further apart.
+            None => {
+                let blur_level =
+                    ((radius / 2.0).max(1.0).log2().floor() as u32).clamp(result_level, last_level);
+                let spread = (radius / (1 << (blur_level + 1)) as f32).clamp(0.0, MAX_SPREAD);
+                (blur_level, spread, None)
+            }
+            // Downsample until the kernel covers the radius, then blur there with unspread
+            // dual filter passes on either side
+            Some(taps) => {
+                let (blur_level, params) = gaussian_params(radius, taps, result_level, last_level);
+                (blur_level, 1.0, Some(params))
+            }
+        };
+
+        let targets_index = match self
+            .targets
+            .iter()
+            .position(|targets| targets.matches(source, backdrop))
+        {
+            Some(index) => index,
+            None => {
+                // Targets for textures from before a resize are never matched again
+                if self.targets.len() == MAX_TARGETS {
+                    self.targets.remove(0);
+                }
+                self.targets.push(BlurTargets::new(
+                    device,
+                    &self.bind_group_layout,
+                    &self.sampler,
+                    source,
+                    backdrop,
+                ));
+                self.targets.len() - 1
+            }
+        };
+        let targets = &self.targets[targets_index];
+        let dual_params = vec4(0.0, spread, 0.0, 0.0);
+
+        let level_size = |level: u32| {
+            vec2(
+                (backdrop.width() >> level).max(1) as f32,
+                (backdrop.height() >> level).max(1) as f32,
+            )
+        };
+
+        let mut source_bind_group = &targets.source_bind_group;
+        let mut source_size = vec2(source.width() as f32, source.height() as f32);
+        for level in 0..=blur_level {
+            self.pass(
+                encoder,
+                self.downsample_pipeline.as_ref().unwrap(),
+                source_bind_group,
+                source_size,
+                dual_params,
+                &targets.level_views[level as usize],
+            );
+            source_bind_group = &targets.level_bind_groups[level as usize];
+            source_size = level_size(level);
+        }
+
+        if let Some(params) = gaussian {
+            let level = blur_level as usize;
+            self.pass(
+                encoder,
+                self.horizontal_pipeline.as_ref().unwrap(),
+                &targets.level_bind_groups[level],
+                source_size,
+                params,
+                &targets.scratch_views[level],
+            );
+            self.pass(
+                encoder,
+                self.vertical_pipeline.as_ref().unwrap(),
+                &targets.scratch_bind_groups[level],
+                source_size,
+                params,
+                &targets.level_views[level],
+            );
+        }
+
+        for level in (result_level..blur_level).rev() {
+            self.pass(
+                encoder,
+                self.upsample_pipeline.as_ref().unwrap(),
+                source_bind_group,
+                source_size,
+                dual_params,
+                &targets.level_views[level as usize],
+            );
+            source_bind_group = &targets.level_bind_groups[level as usize];
+            source_size = level_size(level);
+        }
+
+        if let Some(half_rate) = half_rate {
+            let size = level_size(result_level);
+            let cached = self
+                .cache
+                .entry(half_rate.key.clone())
+                .or_insert_with(|| CachedBlur {
+                    texture: create_cache_texture(device, backdrop.format(), size),
+                    settings,
+                    clear_color: half_rate.beneath.clear_color,
+                    beneath: Vec::new(),
+                    beneath_hashes: Vec::new(),
+                    reused: false,
+                    last_used: 0,
+                });
+            if cached.texture.width() != size.x as u32 || cached.texture.height() != size.y as u32 {
+                cached.texture = create_cache_texture(device, backdrop.format(), size);
+            }
+            cached.settings = settings;
+            cached.remember_backdrop(&half_rate);
+            cached.reused = false;
+            cached.last_used = self.blurs;
+            copy_level(encoder, backdrop, result_level, &cached.texture, 0);
+        }
+
+        Some(result_level)
+    }
+
+    fn pass(
+        &self,
+        encoder: &mut CommandEncoder,
+        pipeline: &RenderPipeline,
+        source: &BindGroup,
+        source_size: Vec2,
+        backdrop: Vec4,
+        target: &TextureView,
+    ) {
+        let constants = ShaderConstants {
+            surface_size: source_size,
+            backdrop,
+            ..bytemuck::Zeroable::zeroed()
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Backdrop blur pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::TRANSPARENT),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, source, &[]);
+        render_pass.set_push_constants(ShaderStages::all(), 0, bytemuck::cast_slice(&[constants]));
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+impl BlurKey {
+    pub fn new(backdrop: &Texture, layer: &Layer, layer_index: usize) -> Self {
+        Self {
+            backdrop: backdrop.global_id(),
+            target: layer.render_target.clone(),
+            layer: match &layer.name {
+                Some(name) => LayerKey::Named(name.clone()),
+                None => LayerKey::Index(layer_index),
+            },
+        }
+    }
+}
+
+impl CachedBlur {
+    // Whether the backdrop is the one last blurred. Layers shared with that frame are compared
+    // by pointer and the rest by content, like the redraw tracker does.
+    fn same_backdrop(&self, half_rate: &HalfRate) -> bool {
+        let beneath = &half_rate.beneath;
+        self.clear_color == beneath.clear_color
+            && self.beneath.len() == beneath.layers.len()
+            && beneath
+                .layers
+                .iter()
+                .zip(self.beneath.iter().zip(self.beneath_hashes.iter()))
+                .all(|(layer, (blurred, blurred_hash))| {
+                    Arc::ptr_eq(layer, blurred)
+                        || (layer.custom.same_items(&blurred.custom)
+                            && hash_layer(layer) == *blurred_hash)
+                })
+    }
+
+    fn remember_backdrop(&mut self, half_rate: &HalfRate) {
+        let beneath = &half_rate.beneath;
+        let hashes = beneath
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(index, layer)| {
+                // Layers kept from the last blur don't need hashing again
+                match self.beneath.get(index) {
+                    Some(blurred) if Arc::ptr_eq(layer, blurred) => self.beneath_hashes[index],
+                    _ => hash_layer(layer),
+                }
+            })
+            .collect();
+        self.clear_color = beneath.clear_color;
+        self.beneath = beneath.layers.to_vec();
+        self.beneath_hashes = hashes;
+    }
+}
+
+fn create_cache_texture(device: &Device, format: TextureFormat, size: Vec2) -> Texture {
+    device.create_texture(&TextureDescriptor {
+        label: Some("Half rate blur cache"),
+        size: Extent3d {
+            width: size.x as u32,
+            height: size.y as u32,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        usage: TextureUsages::COPY_SRC | TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
+
+// Copies a whole mip level between textures whose levels are the same size
+fn copy_level(
+    encoder: &mut CommandEncoder,
+    source: &Texture,
+    source_level: u32,
+    target: &Texture,
+    target_level: u32,
+) {
+    encoder.copy_texture_to_texture(
+        ImageCopyTexture {
+            texture: source,
+            mip_level: source_level,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        ImageCopyTexture {
+            texture: target,
+            mip_level: target_level,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        Extent3d {
+            width: (source.width() >> source_level).max(1),
+            height: (source.height() >> source_level).max(1),
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+// Level of the backdrop texture to run the gaussian passes at, along with their spread,
+// standard deviation, and sample count in that level's texels
+fn gaussian_params(radius: f32, taps: u32, result_level: u32, last_level: u32) -> (u32, Vec4) {
+    // Texels of the first level are two surface pixels wide
+    let sigma = radius / 2.0;
+    let mut level = result_level;
+    while level < last_level && sigma / (2 << level) as f32 * GAUSSIAN_EXTENT > taps as f32 {
+        level += 1;
+    }
+    let level_sigma = sigma / (2 << level) as f32;
+    // Radii too large for the last level are covered by spacing the samples out
+    let step = (level_sigma * GAUSSIAN_EXTENT / taps as f32).max(1.0);
+    (level, vec4(0.0, step, level_sigma, taps as f32))
+}
+
+// Views and bind groups for every level of the backdrop texture, plus a matching scratch
+// texture the gaussian passes ping pong through. Built once per surface texture and reused
+// for every blur.
+struct BlurTargets {
+    source_id: Id<Texture>,
+    backdrop_id: Id<Texture>,
+    level_views: Vec<TextureView>,
+    source_bind_group: BindGroup,
+    level_bind_groups: Vec<BindGroup>,
+    scratch_views: Vec<TextureView>,
+    scratch_bind_groups: Vec<BindGroup>,
+}
+
+impl BlurTargets {
+    fn new(
+        device: &Device,
+        bind_group_layout: &BindGroupLayout,
+        sampler: &Sampler,
+        source: &Texture,
+        backdrop: &Texture,
+    ) -> Self {
+        let create_bind_group = |view: &TextureView| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Backdrop blur bind group"),
+                layout: bind_group_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(view),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(sampler),
+                    },
+                ],
+            })
+        };
+
+        let create_level_views = |texture: &Texture| -> Vec<TextureView> {
+            (0..texture.mip_level_count())
+                .map(|level| {
+                    texture.create_view(&TextureViewDescriptor {
+                        base_mip_level: level,
+                        mip_level_count: Some(1),
+                        ..Default::default()
+                    })
+                })
+                .collect()
+        };
+
+        let scratch = device.create_texture(&TextureDescriptor {
+            label: Some("Backdrop blur scratch texture"),
+            size: backdrop.size(),
+            mip_level_count: backdrop.mip_level_count(),
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: backdrop.format(),
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        let level_views = create_level_views(backdrop);
+        let source_bind_group = create_bind_group(&source.create_view(&Default::default()));
+        let level_bind_groups = level_views.iter().map(create_bind_group).collect();
+        let scratch_views = create_level_views(&scratch);
+        let scratch_bind_groups = scratch_views.iter().map(create_bind_group).collect();
+
+        Self {
+            source_id: source.global_id(),
+            backdrop_id: backdrop.global_id(),
+            level_views,
+            source_bind_group,
+            level_bind_groups,
+            scratch_views,
+            scratch_bind_groups,
+        }
+    }
+
+    fn matches(&self, source: &Texture, backdrop: &Texture) -> bool {
+        self.source_id == source.global_id() && self.backdrop_id == backdrop.global_id()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_gaussian_level() {
+        // Small radii stay at the requested level without spreading samples
+        let (level, params) = gaussian_params(8.0, 8, 0, BACKDROP_LEVELS - 1);
+        assert_eq!(level, 0);
+        assert_eq!(params.y, 1.0);
+
+        // Larger radii downsample further and more taps need less downsampling
+        let (medium, _) = gaussian_params(60.0, 8, 0, BACKDROP_LEVELS - 1);
+        let (high, _) = gaussian_params(60.0, 16, 0, BACKDROP_LEVELS - 1);
+        assert!(medium > high);
+
+        // Past the last level the samples are spread to cover the kernel
+        let (level, params) = gaussian_params(10000.0, 8, 0, BACKDROP_LEVELS - 1);
+        assert_eq!(level, BACKDROP_LEVELS - 1);
+        assert!(params.y > 1.0);
+    }
+}
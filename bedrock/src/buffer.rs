@@ -0,0 +1,275 @@
+use std::{num::NonZeroU64, ops::Range};
+
+use bytemuck::Pod;
+use wgpu::*;
+
+const INITIAL_CAPACITY: u64 = 1024;
+// Unchanged runs shorter than this are uploaded along with the changed items around them
+// rather than splitting the upload into many small writes
+const MERGE_GAP: usize = 16;
+
+// Gpu buffer which is recreated with a larger size whenever the data written to it would
+// overflow the current allocation. Storage bindings are limited in size by the device, so
+// storage buffers are bound in fixed size chunks using dynamic offsets and instanced draws
+// are split across those chunks.
+//
+// A copy of the most recently uploaded items is kept on the cpu so that each upload only
+// writes the ranges which changed since the previous frame. Changed ranges are written
+// directly into wgpu's staging memory to avoid an extra copy.
+pub struct GrowableBuffer<T: Pod> {
+    label: &'static str,
+    usage: BufferUsages,
+    buffer: Buffer,
+    chunk_len: u64,
+    uploaded: Vec<T>,
+}
+
+impl<T: Pod> GrowableBuffer<T> {
+    pub fn new(device: &Device, label: &'static str, usage: BufferUsages) -> Self {
+        let chunk_len = if usage.contains(BufferUsages::STORAGE) {
+            storage_chunk_len::<T>(&device.limits())
+        } else {
+            u64::MAX
+        };
+
+        Self {
+            label,
+            usage,
+            buffer: create_buffer(device, label, usage, INITIAL_CAPACITY * Self::item_size()),
+            chunk_len,
+            uploaded: Vec::new(),
+        }
+    }
+
+    fn item_size() -> u64 {
+        std::mem::size_of::<T>() as u64
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    // Most items a single binding of the buffer can cover
+    pub fn chunk_len(&self) -> u64 {
+        self.chunk_len
+    }
+
+    // Number of items written by the most recent upload
+    pub fn len(&self) -> u64 {
+        self.uploaded.len() as u64
+    }
+
+    // Part of the range covered by the most recent upload. Uploads past the maximum buffer size
+    // drop items, so draws use this to avoid reading past what was written
+    pub fn uploaded_range(&self, range: Range<u32>) -> Range<u32> {
+        let len = self.uploaded.len().min(u32::MAX as usize) as u32;
+        range.start.min(len)..range.end.min(len)
+    }
+
+    // Binding for use in a bind group layout entry with a dynamic offset. Covers a single chunk
+    // of the buffer.
+    pub fn binding(&self) -> BindingResource {
+        let chunk_size = self.chunk_len.saturating_mul(Self::item_size());
+        BindingResource::Buffer(BufferBinding {
+            buffer: &self.buffer,
+            offset: 0,
+            size: NonZeroU64::new(chunk_size.min(self.buffer.size())),
+        })
+    }
+
+    // Uploads the items to the start of the buffer, growing it if necessary. Returns true if
+    // the buffer was recreated in which case any bind groups referencing it must be rebuilt.
+    pub fn upload(&mut self, device: &Device, queue: &Queue, items: &[T]) -> bool {
+        let mut items = items;
+        let item_size = Self::item_size();
+        let chunk_size = self.chunk_len.saturating_mul(item_size);
+        let max_size = max_size(device.limits().max_buffer_size, chunk_size);
+        let max_len = max_size / item_size;
+        if items.len() as u64 > max_len {
+            tracing::warn!(
+                label = self.label,
+                dropped = items.len() as u64 - max_len,
+                "Buffer exceeded the maximum buffer size"
+            );
+            items = &items[..max_len as usize];
+        }
+
+        let required_size = items.len() as u64 * item_size;
+
+        let recreated = if required_size > self.buffer.size() {
+            let mut size = required_size.next_power_of_two();
+            if size > chunk_size {
+                // Every chunk must be fully backed by the buffer for the dynamic offsets to
+                // be valid
+                size = (required_size + chunk_size - 1) / chunk_size * chunk_size;
+            }
+            let size = size.min(max_size);
+            tracing::debug!(
+                label = self.label,
+                old_size = self.buffer.size(),
+                new_size = size,
+                "Growing buffer"
+            );
+            self.buffer = create_buffer(device, self.label, self.usage, size);
+            // The new buffer starts out empty so everything needs uploading
+            self.uploaded.clear();
+            true
+        } else {
+            false
+        };
+
+        let ranges = dirty_ranges(&self.uploaded, items);
+        for range in ranges.iter().cloned() {
+            let data: &[u8] = bytemuck::cast_slice(&items[range.clone()]);
+            if let Some(size) = NonZeroU64::new(data.len() as u64) {
+                if let Some(mut view) =
+                    queue.write_buffer_with(&self.buffer, range.start as u64 * item_size, size)
+                {
+                    view.copy_from_slice(data);
+                }
+            }
+        }
+
+        update_shadow(&mut self.uploaded, items, &ranges);
+        recreated
+    }
+
+    // Issues one instanced draw per chunk of uploaded items, rebinding the bind group at the
+    // chunk's offset each time.
+    pub fn draw_chunks<'a>(
+        &self,
+        render_pass: &mut RenderPass<'a>,
+        bind_group_index: u32,
+        bind_group: &'a BindGroup,
+        vertices: Range<u32>,
+    ) {
+        let len = self.len();
+        let mut start = 0;
+        while start < len {
+            let count = (len - start).min(self.chunk_len);
+            let offset = start * Self::item_size();
+            render_pass.set_bind_group(bind_group_index, bind_group, &[offset as DynamicOffset]);
+            render_pass.draw(vertices.clone(), 0..count as u32);
+            start += count;
+        }
+    }
+}
+
+// Ranges of items in next which differ from previous. Items past the end of previous are
+// always considered changed. Ranges separated by small unchanged gaps are merged.
+fn dirty_ranges<T: Pod>(previous: &[T], next: &[T]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (index, item) in next.iter().enumerate() {
+        let changed = previous
+            .get(index)
+            .map(|previous| bytemuck::bytes_of(previous) != bytemuck::bytes_of(item))
+            .unwrap_or(true);
+        if !changed {
+            continue;
+        }
+
+        match ranges.last_mut() {
+            Some(last) if index - last.end < MERGE_GAP => last.end = index + 1,
+            _ => ranges.push(index..index + 1),
+        }
+    }
+    ranges
+}
+
+// Brings the cpu copy up to date by copying only the dirty ranges. Items past the end of the
+// copy are always dirty, so the ranges cover everything it needs to grow by
+fn update_shadow<T: Pod>(shadow: &mut Vec<T>, next: &[T], ranges: &[Range<usize>]) {
+    shadow.truncate(next.len());
+    for range in ranges {
+        let overlap_end = range.end.min(shadow.len());
+        if range.start < overlap_end {
+            shadow[range.start..overlap_end].copy_from_slice(&next[range.start..overlap_end]);
+        }
+        if range.end > shadow.len() {
+            let start = shadow.len();
+            shadow.extend_from_slice(&next[start..range.end]);
+        }
+    }
+}
+
+// Largest buffer allowed by the device, rounded down to whole chunks so every dynamic offset
+// binding stays within the buffer
+fn max_size(max_buffer_size: u64, chunk_size: u64) -> u64 {
+    if chunk_size < max_buffer_size {
+        max_buffer_size / chunk_size * chunk_size
+    } else {
+        max_buffer_size
+    }
+}
+
+fn create_buffer(device: &Device, label: &str, usage: BufferUsages, size: u64) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some(label),
+        size,
+        usage: usage | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+// Number of items which fit in a single storage binding while keeping every chunk offset
+// aligned to the device's storage offset alignment
+fn storage_chunk_len<T>(limits: &Limits) -> u64 {
+    let item_size = std::mem::size_of::<T>() as u64;
+    let alignment = limits.min_storage_buffer_offset_alignment as u64;
+    let step = item_size / gcd(item_size, alignment) * alignment;
+    let chunk_size = (limits.max_storage_buffer_binding_size as u64 / step).max(1) * step;
+    chunk_size / item_size
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dirty_ranges() {
+        let previous: Vec<u32> = (0..100).collect();
+
+        assert!(dirty_ranges(&previous, &previous).is_empty());
+        assert_eq!(dirty_ranges(&[], &previous[..10]), vec![0..10]);
+
+        let mut next = previous.clone();
+        next[3] = 0;
+        next[5] = 0;
+        next[50] = 0;
+        next.push(100);
+        assert_eq!(dirty_ranges(&previous, &next), vec![3..6, 50..51, 100..101]);
+    }
+
+    #[test]
+    fn test_shadow_copies_dirty_ranges() {
+        let mut shadow: Vec<u32> = (0..100).collect();
+
+        let mut next = shadow.clone();
+        next[3] = 0;
+        next[50] = 0;
+        next.extend([100, 101]);
+        let ranges = dirty_ranges(&shadow, &next);
+        update_shadow(&mut shadow, &next, &ranges);
+        assert_eq!(shadow, next);
+
+        next.truncate(40);
+        next[10] = 0;
+        let ranges = dirty_ranges(&shadow, &next);
+        update_shadow(&mut shadow, &next, &ranges);
+        assert_eq!(shadow, next);
+    }
+
+    #[test]
+    fn test_max_size_is_whole_chunks() {
+        assert_eq!(max_size(1000, 300), 900);
+        assert_eq!(max_size(1000, u64::MAX), 1000);
+    }
+}
@@ -0,0 +1,169 @@
+use std::ops::Range;
+
+use glam::vec2;
+use lyon::lyon_tessellation::{
+    BuffersBuilder, FillOptions, FillTessellator, FillVertex, VertexBuffers,
+};
+use shader::{PathVertex, ShaderConstants};
+use wgpu::*;
+
+use crate::{
+    buffer::GrowableBuffer,
+    path::{build_lyon_path, lyon_fill_rule},
+    scene::{Layer, Path},
+};
+
+// Writes a layer's clip paths into the stencil buffer before its items are drawn. Each clip
+// only increments pixels which passed every clip before it, so after all of them are drawn
+// the pixels inside every clip hold the clip count. Item pipelines then compare against that
+// count, so nested clips compose across every drawable in the layer.
+pub struct ClipStencil {
+    vertex_buffer: GrowableBuffer<PathVertex>,
+    index_buffer: GrowableBuffer<u32>,
+    render_pipeline: Option<RenderPipeline>,
+    // Index range of each clip path in the uploaded geometry
+    ranges: Vec<Range<u32>>,
+}
+
+impl ClipStencil {
+    pub fn new(device: &Device) -> Self {
+        Self {
+            vertex_buffer: GrowableBuffer::new(device, "Clip Vertex Buffer", BufferUsages::VERTEX),
+            index_buffer: GrowableBuffer::new(device, "Clip Index Buffer", BufferUsages::INDEX),
+            render_pipeline: None,
+            ranges: Vec::new(),
+        }
+    }
+
+    pub fn surface_updated(
+        &mut self,
+        device: &Device,
+        shader: &ShaderModule,
+        format: TextureFormat,
+        depth_format: TextureFormat,
+        sample_count: u32,
+    ) {
+        let increment = StencilFaceState {
+            compare: CompareFunction::Equal,
+            fail_op: StencilOperation::Keep,
+            depth_fail_op: StencilOperation::Keep,
+            pass_op: StencilOperation::IncrementClamp,
+        };
+
+        self.render_pipeline = Some(device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Clip render pipeline"),
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Clip Pipeline layout"),
+                bind_group_layouts: &[],
+                push_constant_ranges: &[PushConstantRange {
+                    stages: ShaderStages::all(),
+                    range: 0..std::mem::size_of::<ShaderConstants>() as u32,
+                }],
+            })),
+            vertex: VertexState {
+                module: shader,
+                entry_point: shader::PATH_VERTEX,
+                buffers: &[VertexBufferLayout {
+                    array_stride: std::mem::size_of::<PathVertex>() as BufferAddress,
+                    step_mode: VertexStepMode::Vertex,
+                    attributes: &vertex_attr_array![0 => Float32x4, 1 => Float32x2, 2 => Float32],
+                }],
+            },
+            // Only the stencil is written
+            fragment: Some(FragmentState {
+                module: shader,
+                entry_point: shader::PATH_FRAGMENT,
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::empty(),
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Always,
+                stencil: StencilState {
+                    front: increment,
+                    back: increment,
+                    read_mask: !0,
+                    write_mask: !0,
+                },
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        }));
+    }
+
+    // Tessellates and uploads the layer's clip paths. Must be called before `draw`
+    pub fn prepare(&mut self, device: &Device, queue: &Queue, layer: &Layer) {
+        self.ranges.clear();
+        if layer.clip_paths.is_empty() {
+            return;
+        }
+
+        let mut tessellator = FillTessellator::new();
+        let mut geometry: VertexBuffers<PathVertex, u32> = VertexBuffers::new();
+        for path in layer.clip_paths.iter() {
+            let start = geometry.indices.len() as u32;
+            tessellate_clip(path, &mut tessellator, &mut geometry);
+            self.ranges.push(start..geometry.indices.len() as u32);
+        }
+
+        self.vertex_buffer.upload(device, queue, &geometry.vertices);
+        self.index_buffer.upload(device, queue, &geometry.indices);
+    }
+
+    // Writes the prepared clips into a freshly cleared stencil and leaves the stencil
+    // reference set to the value items inside every clip are compared against
+    pub fn draw<'b, 'a: 'b>(
+        &'a self,
+        render_pass: &mut RenderPass<'b>,
+        constants: ShaderConstants,
+    ) {
+        if self.ranges.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(self.render_pipeline.as_ref().unwrap());
+        render_pass.set_push_constants(ShaderStages::all(), 0, bytemuck::cast_slice(&[constants]));
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.buffer().slice(..));
+        render_pass.set_index_buffer(self.index_buffer.buffer().slice(..), IndexFormat::Uint32);
+        for (level, range) in self.ranges.iter().enumerate() {
+            render_pass.set_stencil_reference(level as u32);
+            render_pass.draw_indexed(self.index_buffer.uploaded_range(range.clone()), 0, 0..1);
+        }
+        render_pass.set_stencil_reference(self.ranges.len() as u32);
+    }
+}
+
+// Clips always use the path's fill area and rule, whether or not the path is filled when drawn
+fn tessellate_clip(
+    path: &Path,
+    tessellator: &mut FillTessellator,
+    geometry: &mut VertexBuffers<PathVertex, u32>,
+) {
+    tessellator
+        .tessellate_path(
+            &build_lyon_path(path),
+            &FillOptions::default().with_fill_rule(lyon_fill_rule(path.fill_rule)),
+            &mut BuffersBuilder::new(geometry, |vertex: FillVertex| PathVertex {
+                position: vec2(vertex.position().x, vertex.position().y),
+                ..Default::default()
+            }),
+        )
+        .expect("Could not tesselate clip path");
+}
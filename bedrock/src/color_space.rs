@@ -0,0 +1,220 @@
+use glam::{Mat3, Vec3};
+use wgpu::*;
+
+// Gamut of the monitor a window is shown on. Scene colors are always authored in srgb
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    DisplayP3,
+}
+
+impl ColorSpace {
+    // Matrix taking linear srgb colors to the same colors in this space's linear primaries
+    pub fn srgb_matrix(self) -> Mat3 {
+        match self {
+            ColorSpace::Srgb => Mat3::IDENTITY,
+            // Columns are the srgb primaries expressed in display p3
+            ColorSpace::DisplayP3 => Mat3::from_cols(
+                Vec3::new(0.8225, 0.0332, 0.0171),
+                Vec3::new(0.1774, 0.9669, 0.0724),
+                Vec3::new(0.0001, -0.0001, 0.9105),
+            ),
+        }
+    }
+}
+
+// Fullscreen pass which reinterprets a finished srgb frame in a wider monitor gamut so
+// saturated colors aren't stretched across the larger gamut
+pub(crate) struct ColorConversion {
+    bind_group_layout: BindGroupLayout,
+    sampler: Sampler,
+    pipeline: Option<(TextureFormat, RenderPipeline)>,
+    // Copy of the frame the pass reads from, recreated when the frame size changes
+    source: Option<Texture>,
+}
+
+impl ColorConversion {
+    pub(crate) fn new(device: &Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Color conversion bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::NonFiltering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            bind_group_layout,
+            sampler,
+            pipeline: None,
+            source: None,
+        }
+    }
+
+    pub(crate) fn pipeline(&mut self, device: &Device, format: TextureFormat) -> &RenderPipeline {
+        if !matches!(&self.pipeline, Some((built, _)) if *built == format) {
+            let module = device.create_shader_module(ShaderModuleDescriptor {
+                label: Some("Color conversion shader"),
+                source: ShaderSource::Wgsl(include_str!("color_space.wgsl").into()),
+            });
+            let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Color conversion Pipeline Layout"),
+                bind_group_layouts: &[&self.bind_group_layout],
+                push_constant_ranges: &[PushConstantRange {
+                    stages: ShaderStages::FRAGMENT,
+                    range: 0..std::mem::size_of::<[f32; 16]>() as u32,
+                }],
+            });
+            let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("Color conversion Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    module: &module,
+                    entry_point: "vertex",
+                    buffers: &[],
+                },
+                fragment: Some(FragmentState {
+                    module: &module,
+                    entry_point: "fragment",
+                    targets: &[Some(ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                multiview: None,
+            });
+            self.pipeline = Some((format, pipeline));
+        }
+        &self.pipeline.as_ref().unwrap().1
+    }
+
+    // Converts the target in place from srgb to the given color space
+    pub(crate) fn draw(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        target: &Texture,
+        color_space: ColorSpace,
+    ) {
+        if color_space == ColorSpace::Srgb {
+            return;
+        }
+
+        if self.source.as_ref().map_or(true, |source| {
+            source.size() != target.size() || source.format() != target.format()
+        }) {
+            self.source = Some(device.create_texture(&TextureDescriptor {
+                size: target.size(),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: target.format(),
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                label: Some("Color conversion source"),
+                view_formats: &[],
+            }));
+        }
+        let source = self.source.as_ref().unwrap();
+
+        // Shaders approximate the srgb curve by squaring, so non srgb frames are decoded the
+        // same way before converting
+        let encoded = !target.format().is_srgb();
+        let matrix = color_space.srgb_matrix().to_cols_array();
+        let mut constants = [0.0; 16];
+        for column in 0..3 {
+            constants[column * 4..column * 4 + 3]
+                .copy_from_slice(&matrix[column * 3..column * 3 + 3]);
+        }
+        constants[12] = if encoded { 1.0 } else { 0.0 };
+
+        let source_view = source.create_view(&Default::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Color conversion bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&source_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Color conversion Encoder"),
+        });
+        encoder.copy_texture_to_texture(
+            target.as_image_copy(),
+            source.as_image_copy(),
+            target.size(),
+        );
+
+        let pipeline = self.pipeline(device, target.format());
+        let view = target.create_view(&Default::default());
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Color conversion Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.set_push_constants(ShaderStages::FRAGMENT, 0, bytemuck::cast_slice(&constants));
+        render_pass.draw(0..4, 0..1);
+        drop(render_pass);
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_white_is_preserved() {
+        let white = ColorSpace::DisplayP3.srgb_matrix() * Vec3::ONE;
+        assert!((white - Vec3::ONE).abs().max_element() < 0.001);
+
+        // Pure srgb red is inside the p3 gamut, so it needs less than full p3 red
+        let red = ColorSpace::DisplayP3.srgb_matrix() * Vec3::X;
+        assert!(red.x < 1.0 && red.y > 0.0);
+    }
+}
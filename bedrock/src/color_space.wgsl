@@ -0,0 +1,51 @@
+// Converts a finished srgb frame to the primaries of a wider gamut monitor. The frame is
+// premultiplied, but the conversion is linear so alpha can be left as is.
+
+struct ConversionConstants {
+    // Columns of the linear srgb to monitor matrix
+    matrix: mat3x4<f32>,
+    // x: 1 if the frame is stored gamma encoded rather than in an srgb format
+    params: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+var<push_constant> constants: ConversionConstants;
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(source, source_sampler, in.uv);
+    let matrix = mat3x3<f32>(
+        constants.matrix[0].xyz,
+        constants.matrix[1].xyz,
+        constants.matrix[2].xyz,
+    );
+
+    if constants.params.x == 0.0 {
+        return vec4<f32>(max(matrix * color.rgb, vec3<f32>(0.0)), color.a);
+    }
+
+    // Gamma encoded frames are decoded by squaring to match the primitive shaders
+    if color.a == 0.0 {
+        return color;
+    }
+    let straight = color.rgb / color.a;
+    let converted = sqrt(max(matrix * (straight * straight), vec3<f32>(0.0)));
+    return vec4<f32>(converted * color.a, color.a);
+}
@@ -0,0 +1,260 @@
+use glam::Vec4;
+use wgpu::*;
+
+use crate::{
+    blur::{BackdropBlur, BlurSettings},
+    scene::{BlurQuality, BlurResolution, ColorAdjustments, Layer},
+    surface_wrapper::create_backdrop_texture,
+};
+
+// Layers with a content blur or color filter are drawn into their own textures instead of the
+// frame. Once every drawable has run, the layer is blurred with the backdrop blur passes if
+// needed and composited over the frame with its filter applied.
+pub(crate) struct LayerCompositor {
+    bind_group_layout: BindGroupLayout,
+    sampler: Sampler,
+    pipeline: Option<(TextureFormat, u32, RenderPipeline)>,
+    textures: Option<ContentTextures>,
+}
+
+struct ContentTextures {
+    // Only exists while multisampling. Otherwise the layer is drawn straight into `resolved`
+    multisampled: Option<Texture>,
+    resolved: Texture,
+    // Mip chain shaped like the backdrop texture which the blur is written to
+    blurred: Texture,
+}
+
+impl LayerCompositor {
+    pub(crate) fn new(device: &Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Layer composite bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        // Blurs are stored at reduced resolution, so filter them back up
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            bind_group_layout,
+            sampler,
+            pipeline: None,
+            textures: None,
+        }
+    }
+
+    // Color and resolve views a composited layer is drawn into in place of the frame's. They
+    // match the target's size and format. There is nothing to resolve without multisampling
+    pub(crate) fn views(
+        &mut self,
+        device: &Device,
+        target: &Texture,
+        sample_count: u32,
+    ) -> (TextureView, Option<TextureView>) {
+        if self.textures.as_ref().map_or(true, |textures| {
+            textures.resolved.size() != target.size()
+                || textures.resolved.format() != target.format()
+                || textures
+                    .multisampled
+                    .as_ref()
+                    .map_or(1, |multisampled| multisampled.sample_count())
+                    != sample_count
+        }) {
+            let create_texture = |sample_count, label| {
+                device.create_texture(&TextureDescriptor {
+                    size: target.size(),
+                    mip_level_count: 1,
+                    sample_count,
+                    dimension: TextureDimension::D2,
+                    format: target.format(),
+                    usage: TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
+                    label: Some(label),
+                    view_formats: &[],
+                })
+            };
+            self.textures = Some(ContentTextures {
+                multisampled: (sample_count > 1)
+                    .then(|| create_texture(sample_count, "Layer composite multisampled texture")),
+                resolved: create_texture(1, "Layer composite texture"),
+                blurred: create_backdrop_texture(
+                    device,
+                    target.width(),
+                    target.height(),
+                    target.format(),
+                ),
+            });
+        }
+
+        let textures = self.textures.as_ref().unwrap();
+        let resolved_view = textures.resolved.create_view(&Default::default());
+        match textures.multisampled.as_ref() {
+            Some(multisampled) => (
+                multisampled.create_view(&Default::default()),
+                Some(resolved_view),
+            ),
+            None => (resolved_view, None),
+        }
+    }
+
+    pub(crate) fn pipeline(
+        &mut self,
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+    ) -> &RenderPipeline {
+        if !matches!(&self.pipeline, Some((built_format, built_sample_count, _))
+            if *built_format == format && *built_sample_count == sample_count)
+        {
+            let module = device.create_shader_module(ShaderModuleDescriptor {
+                label: Some("Layer composite shader"),
+                source: ShaderSource::Wgsl(include_str!("composite.wgsl").into()),
+            });
+            let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Layer composite Pipeline Layout"),
+                bind_group_layouts: &[&self.bind_group_layout],
+                push_constant_ranges: &[PushConstantRange {
+                    stages: ShaderStages::FRAGMENT,
+                    range: 0..std::mem::size_of::<[f32; 12]>() as u32,
+                }],
+            });
+            let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("Layer composite Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    module: &module,
+                    entry_point: "vertex",
+                    buffers: &[],
+                },
+                fragment: Some(FragmentState {
+                    module: &module,
+                    entry_point: "fragment",
+                    targets: &[Some(ColorTargetState {
+                        format,
+                        blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+            });
+            self.pipeline = Some((format, sample_count, pipeline));
+        }
+        &self.pipeline.as_ref().unwrap().2
+    }
+
+    // Blurs and filters the layer drawn into `views` and composites it over the frame's color
+    // and resolve views
+    pub(crate) fn composite(
+        &mut self,
+        device: &Device,
+        blur: &mut BackdropBlur,
+        encoder: &mut CommandEncoder,
+        layer: &Layer,
+        (color_view, resolve_target): (&TextureView, Option<&TextureView>),
+        load: LoadOp<Color>,
+    ) {
+        let Some(textures) = self.textures.as_ref() else {
+            return;
+        };
+
+        let (source_view, level) = if layer.content_blur_radius != 0.0 {
+            let settings = BlurSettings {
+                radius: layer.content_blur_radius,
+                resolution: BlurResolution::Half,
+                quality: BlurQuality::Medium,
+            };
+            let level = blur
+                .blur_texture(
+                    device,
+                    encoder,
+                    &textures.resolved,
+                    &textures.blurred,
+                    settings,
+                    None,
+                )
+                .unwrap_or(0);
+            (textures.blurred.create_view(&Default::default()), level)
+        } else {
+            (textures.resolved.create_view(&Default::default()), 0)
+        };
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Layer composite bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&source_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        let format = textures.resolved.format();
+        let (adjustments, tint) = layer
+            .color_filter
+            .map(|filter| filter.parameters())
+            .unwrap_or((ColorAdjustments::default().to_vec4(), Vec4::ONE));
+        let mut constants = [0.0; 12];
+        constants[0] = level as f32;
+        constants[4..8].copy_from_slice(&adjustments.to_array());
+        constants[8..12].copy_from_slice(&tint.to_array());
+
+        let sample_count = textures
+            .multisampled
+            .as_ref()
+            .map_or(1, |multisampled| multisampled.sample_count());
+        let pipeline = self.pipeline(device, format, sample_count);
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Layer composite pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: color_view,
+                resolve_target,
+                ops: Operations {
+                    load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.set_push_constants(ShaderStages::FRAGMENT, 0, bytemuck::cast_slice(&constants));
+        render_pass.draw(0..4, 0..1);
+    }
+}
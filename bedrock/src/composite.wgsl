@@ -0,0 +1,60 @@
+// Composites a layer which was rendered on its own, and possibly blurred, over the frame with
+// its color filter applied.
+
+struct CompositeConstants {
+    // x: mip level of the layer texture to sample
+    params: vec4<f32>,
+    // x: brightness offset, y: contrast, z: saturation, w: hue rotation in radians
+    adjustments: vec4<f32>,
+    // Multiplied with the filtered color, including alpha
+    tint: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+var<push_constant> constants: CompositeConstants;
+
+@group(0) @binding(0) var layer_texture: texture_2d<f32>;
+@group(0) @binding(1) var layer_sampler: sampler;
+
+// Luminance weights for linear rec. 709 colors
+const LUMA = vec3<f32>(0.2126, 0.7152, 0.0722);
+
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// Matches the sprite shader's color adjustments
+fn adjust_color(color: vec3<f32>, adjustments: vec4<f32>) -> vec3<f32> {
+    let axis = vec3<f32>(0.57735027);
+    let s = sin(adjustments.w);
+    let c = cos(adjustments.w);
+    let rotated = color * c + cross(axis, color) * s + axis * dot(axis, color) * (1.0 - c);
+
+    let luma = dot(rotated, LUMA);
+    let saturated = mix(vec3<f32>(luma), rotated, adjustments.z);
+    let contrasted = (saturated - 0.5) * adjustments.y + 0.5;
+    return clamp(contrasted + adjustments.x, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(layer_texture, layer_sampler, in.uv, constants.params.x);
+    if color.a == 0.0 {
+        return color;
+    }
+
+    // The layer was drawn premultiplied, so filter the straight color and premultiply again
+    let straight = adjust_color(color.rgb / color.a, constants.adjustments) * constants.tint.rgb;
+    let alpha = color.a * constants.tint.a;
+    return vec4<f32>(straight * alpha, alpha);
+}
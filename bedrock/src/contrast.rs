@@ -0,0 +1,189 @@
+use glam::{Vec3, Vec4};
+use wgpu::*;
+
+use crate::pixel_probe::decode_pixel;
+
+// Contrast ratio of 4.5 recommended for body text by WCAG
+pub const MINIMUM_TEXT_CONTRAST: f32 = 4.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextTone {
+    Light,
+    Dark,
+}
+
+// Summary of the pixels behind a region of text. Backdrops are rarely uniform, so contrast is
+// measured against the brightest pixel for light text and the darkest for dark text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackdropContrast {
+    // Average straight alpha color of the region
+    pub average: Vec4,
+    pub min_luminance: f32,
+    pub max_luminance: f32,
+}
+
+impl BackdropContrast {
+    // Contrast ratio of white text against the brightest part of the backdrop
+    pub fn light_ratio(&self) -> f32 {
+        contrast_ratio(1.0, self.max_luminance)
+    }
+
+    // Contrast ratio of black text against the darkest part of the backdrop
+    pub fn dark_ratio(&self) -> f32 {
+        contrast_ratio(self.min_luminance, 0.0)
+    }
+
+    // Whether text of the tone stands out from every part of the backdrop by the ratio
+    pub fn satisfies(&self, tone: TextTone, minimum_ratio: f32) -> bool {
+        match tone {
+            TextTone::Light => self.light_ratio() >= minimum_ratio,
+            TextTone::Dark => self.dark_ratio() >= minimum_ratio,
+        }
+    }
+
+    // Tone with the most contrast, whether or not it meets any particular ratio
+    pub fn best_tone(&self) -> TextTone {
+        if self.light_ratio() >= self.dark_ratio() {
+            TextTone::Light
+        } else {
+            TextTone::Dark
+        }
+    }
+
+    // Best tone if it meets the ratio. None means neither does, and the text needs a backing
+    // such as a shadow or a translucent quad
+    pub fn pick(&self, minimum_ratio: f32) -> Option<TextTone> {
+        let tone = self.best_tone();
+        self.satisfies(tone, minimum_ratio).then_some(tone)
+    }
+
+    fn from_colors(colors: impl Iterator<Item = Vec4>) -> Option<Self> {
+        let mut count = 0;
+        let mut total = Vec4::ZERO;
+        let mut min_luminance = f32::MAX;
+        let mut max_luminance = f32::MIN;
+        for color in colors {
+            let luminance = relative_luminance(color.truncate());
+            min_luminance = min_luminance.min(luminance);
+            max_luminance = max_luminance.max(luminance);
+            total += color;
+            count += 1;
+        }
+        (count > 0).then(|| Self {
+            average: total / count as f32,
+            min_luminance,
+            max_luminance,
+        })
+    }
+}
+
+// WCAG relative luminance of an srgb encoded color
+pub fn relative_luminance(color: Vec3) -> f32 {
+    let linear = color.to_array().map(|channel| {
+        if channel <= 0.04045 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    0.2126 * linear[0] + 0.7152 * linear[1] + 0.0722 * linear[2]
+}
+
+// WCAG contrast ratio between two relative luminances, from 1 to 21
+pub fn contrast_ratio(a: f32, b: f32) -> f32 {
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+// Reads back the region of the texture and summarizes it. Waits for the gpu, so it is meant
+// for occasional checks rather than every frame. Returns None if the region is entirely
+// outside of the texture or the format can't be read.
+pub(crate) fn read_backdrop(
+    device: &Device,
+    queue: &Queue,
+    texture: &Texture,
+    rect: Vec4,
+) -> Option<BackdropContrast> {
+    let left = rect.x.max(0.0) as u32;
+    let top = rect.y.max(0.0) as u32;
+    let right = ((rect.x + rect.z).ceil().max(0.0) as u32).min(texture.width());
+    let bottom = ((rect.y + rect.w).ceil().max(0.0) as u32).min(texture.height());
+    if left >= right || top >= bottom {
+        return None;
+    }
+    let (width, height) = (right - left, bottom - top);
+
+    let padded_row = (width * 4).next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("Backdrop readback buffer"),
+        size: (padded_row * height) as u64,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Backdrop Readback Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: Origin3d {
+                x: left,
+                y: top,
+                z: 0,
+            },
+            aspect: Default::default(),
+        },
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row),
+                rows_per_image: None,
+            },
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    slice.map_async(MapMode::Read, |_| ());
+    device.poll(Maintain::Wait);
+    let data = slice.get_mapped_range();
+    let mut colors = Vec::with_capacity((width * height) as usize);
+    for row in data.chunks_exact(padded_row as usize) {
+        for pixel in row[..(width * 4) as usize].chunks_exact(4) {
+            colors.push(decode_pixel(
+                texture.format(),
+                [pixel[0], pixel[1], pixel[2], pixel[3]],
+            )?);
+        }
+    }
+    BackdropContrast::from_colors(colors.into_iter())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tone_against_mixed_backdrop() {
+        assert!((contrast_ratio(1.0, 0.0) - 21.0).abs() < 0.001);
+
+        let backdrop = BackdropContrast::from_colors(
+            [Vec4::new(0.1, 0.1, 0.1, 1.0), Vec4::new(0.3, 0.3, 0.3, 1.0)].into_iter(),
+        )
+        .unwrap();
+        assert_eq!(backdrop.pick(MINIMUM_TEXT_CONTRAST), Some(TextTone::Light));
+        assert!(!backdrop.satisfies(TextTone::Dark, MINIMUM_TEXT_CONTRAST));
+
+        // A white patch in the region rules out light text
+        let backdrop =
+            BackdropContrast::from_colors([Vec4::new(0.5, 0.5, 0.5, 1.0), Vec4::ONE].into_iter())
+                .unwrap();
+        assert_eq!(backdrop.pick(MINIMUM_TEXT_CONTRAST), Some(TextTone::Dark));
+    }
+}
@@ -0,0 +1,125 @@
+use glam::{vec4, Vec2, Vec4, Vec4Swizzles};
+
+use crate::scene::{Layer, Text};
+
+// Rectangles here are stored as Vec4s of (x, y, width, height) to match layer clips.
+
+// Region of the surface a layer can draw into. Items entirely outside of it are culled before
+// being uploaded so that off screen content costs nothing on the gpu.
+pub fn visible_rect(layer: &Layer, surface_size: Vec2) -> Vec4 {
+    let surface = vec4(0.0, 0.0, surface_size.x, surface_size.y);
+    let visible = match layer.clip {
+        Some(clip) => intersection(surface, clip),
+        None => surface,
+    };
+    // Clip paths are stenciled, but their bounds still limit what can show
+    layer.clip_paths.iter().fold(visible, |visible, path| {
+        intersection(visible, path.bounds())
+    })
+}
+
+// Pixel bounds of a clip within the surface as (x, y, width, height), grown outward to whole
+// pixels. None when nothing of the clip is on the surface, since wgpu rejects scissor rects
+// reaching past the target and empty ones leave what's drawn up to the backend.
+pub fn scissor_rect(clip: Vec4, surface_width: u32, surface_height: u32) -> Option<[u32; 4]> {
+    let left = clip.x.floor().max(0.0);
+    let top = clip.y.floor().max(0.0);
+    let right = (clip.x + clip.z).ceil().min(surface_width as f32);
+    let bottom = (clip.y + clip.w).ceil().min(surface_height as f32);
+    // Written so that NaN clips count as empty too
+    if !(right > left && bottom > top) {
+        return None;
+    }
+    Some([
+        left as u32,
+        top as u32,
+        (right - left) as u32,
+        (bottom - top) as u32,
+    ])
+}
+
+pub fn intersects(a: Vec4, b: Vec4) -> bool {
+    a.x < b.x + b.z && b.x < a.x + a.z && a.y < b.y + b.w && b.y < a.y + a.w
+}
+
+pub fn intersection(a: Vec4, b: Vec4) -> Vec4 {
+    let top_left = a.xy().max(b.xy());
+    let bottom_right = (a.xy() + a.zw()).min(b.xy() + b.zw());
+    let size = (bottom_right - top_left).max(Vec2::ZERO);
+    vec4(top_left.x, top_left.y, size.x, size.y)
+}
+
+pub fn union(a: Vec4, b: Vec4) -> Vec4 {
+    let top_left = a.xy().min(b.xy());
+    let bottom_right = (a.xy() + a.zw()).max(b.xy() + b.zw());
+    let size = bottom_right - top_left;
+    vec4(top_left.x, top_left.y, size.x, size.y)
+}
+
+// Texts are culled before shaping, so the width isn't known. Lines are assumed to extend from
+// their origin to the right edge of the visible area and to span a font size above and half
+// a font size below the baseline.
+pub fn text_visible(text: &Text, visible: Vec4) -> bool {
+    // Fonts rarely space lines further apart than this when the text doesn't set a height
+    let line_advance = text.line_height.unwrap_or(1.5) * text.size;
+    let extra_lines = text.text.matches('\n').count() as f32;
+    let top = text.bottom_left.y - text.size;
+    let bottom = text.bottom_left.y + text.size * 0.5 + extra_lines * line_advance;
+    top < visible.y + visible.w && bottom > visible.y && text.bottom_left.x < visible.x + visible.z
+}
+
+// Area a glyph's ink could cover given its origin on the baseline. Glyphs rarely reach more
+// than an em beside their advance, an em and a half above the baseline, or an em below it, so
+// glyphs whose padded box misses the visible area are skipped before they're rasterized.
+pub fn glyph_bounds(origin: Vec2, advance: f32, size: f32) -> Vec4 {
+    vec4(
+        origin.x - size,
+        origin.y - size * 1.5,
+        advance.max(0.0) + size * 2.0,
+        size * 2.5,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+    use crate::scene::Path;
+
+    #[test]
+    fn test_clip_paths_limit_visible_rect() {
+        let mut layer = Layer::default().with_clip_path(
+            Path::new_fill(Vec4::ONE, vec2(10.0, 10.0))
+                .line_to(vec2(50.0, 10.0))
+                .line_to(vec2(10.0, 50.0)),
+        );
+        assert_eq!(
+            visible_rect(&layer, vec2(100.0, 100.0)),
+            vec4(10.0, 10.0, 40.0, 40.0)
+        );
+
+        layer.pop_clip();
+        assert_eq!(
+            visible_rect(&layer, vec2(100.0, 100.0)),
+            vec4(0.0, 0.0, 100.0, 100.0)
+        );
+    }
+
+    #[test]
+    fn test_scissor_rect_is_clamped_to_surface() {
+        // Clips are clamped by their right and bottom edges rather than their size
+        assert_eq!(
+            scissor_rect(vec4(60.0, 70.0, 80.0, 80.0), 100, 100),
+            Some([60, 70, 40, 30])
+        );
+        assert_eq!(
+            scissor_rect(vec4(-10.5, 0.5, 20.0, 9.0), 100, 100),
+            Some([0, 0, 10, 10])
+        );
+
+        assert_eq!(scissor_rect(vec4(10.0, 10.0, 0.0, 50.0), 100, 100), None);
+        assert_eq!(scissor_rect(vec4(10.0, 10.0, -5.0, 50.0), 100, 100), None);
+        assert_eq!(scissor_rect(vec4(120.0, 10.0, 50.0, 50.0), 100, 100), None);
+    }
+}
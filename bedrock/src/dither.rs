@@ -0,0 +1,170 @@
+use wgpu::*;
+
+// Fullscreen pass which adds ordered noise below the precision of 8 bit swapchains so smooth
+// gradients and blurs don't break into visible bands
+pub(crate) struct Dither {
+    bind_group_layout: BindGroupLayout,
+    pipeline: Option<(TextureFormat, RenderPipeline)>,
+    // Copy of the frame the pass reads from, recreated when the frame size changes
+    source: Option<Texture>,
+}
+
+impl Dither {
+    pub(crate) fn new(device: &Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Dither bind group layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+
+        Self {
+            bind_group_layout,
+            pipeline: None,
+            source: None,
+        }
+    }
+
+    pub(crate) fn pipeline(&mut self, device: &Device, format: TextureFormat) -> &RenderPipeline {
+        if !matches!(&self.pipeline, Some((built, _)) if *built == format) {
+            let module = device.create_shader_module(ShaderModuleDescriptor {
+                label: Some("Dither shader"),
+                source: ShaderSource::Wgsl(include_str!("dither.wgsl").into()),
+            });
+            let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Dither Pipeline Layout"),
+                bind_group_layouts: &[&self.bind_group_layout],
+                push_constant_ranges: &[PushConstantRange {
+                    stages: ShaderStages::FRAGMENT,
+                    range: 0..std::mem::size_of::<[f32; 4]>() as u32,
+                }],
+            });
+            let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("Dither Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    module: &module,
+                    entry_point: "vertex",
+                    buffers: &[],
+                },
+                fragment: Some(FragmentState {
+                    module: &module,
+                    entry_point: "fragment",
+                    targets: &[Some(ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                multiview: None,
+            });
+            self.pipeline = Some((format, pipeline));
+        }
+        &self.pipeline.as_ref().unwrap().1
+    }
+
+    // Dithers the target in place. Higher precision targets don't band, so they are skipped
+    pub(crate) fn draw(&mut self, device: &Device, queue: &Queue, target: &Texture) {
+        let Some(encoded) = eight_bit_encoding(target.format()) else {
+            return;
+        };
+
+        if self.source.as_ref().map_or(true, |source| {
+            source.size() != target.size() || source.format() != target.format()
+        }) {
+            self.source = Some(device.create_texture(&TextureDescriptor {
+                size: target.size(),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: target.format(),
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                label: Some("Dither source"),
+                view_formats: &[],
+            }));
+        }
+        let source = self.source.as_ref().unwrap();
+
+        let constants = [if encoded { 1.0f32 } else { 0.0 }, 0.0, 0.0, 0.0];
+
+        let source_view = source.create_view(&Default::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Dither bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&source_view),
+            }],
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Dither Encoder"),
+        });
+        encoder.copy_texture_to_texture(
+            target.as_image_copy(),
+            source.as_image_copy(),
+            target.size(),
+        );
+
+        let pipeline = self.pipeline(device, target.format());
+        let view = target.create_view(&Default::default());
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Dither Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.set_push_constants(ShaderStages::FRAGMENT, 0, bytemuck::cast_slice(&constants));
+        render_pass.draw(0..4, 0..1);
+        drop(render_pass);
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+// Whether an 8 bit format stores gamma encoded values directly, or None for formats with
+// enough precision to not need dithering
+fn eight_bit_encoding(format: TextureFormat) -> Option<bool> {
+    match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Bgra8Unorm => Some(true),
+        TextureFormat::Rgba8UnormSrgb | TextureFormat::Bgra8UnormSrgb => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_only_eight_bit_formats_dither() {
+        assert_eq!(eight_bit_encoding(TextureFormat::Bgra8Unorm), Some(true));
+        assert_eq!(
+            eight_bit_encoding(TextureFormat::Bgra8UnormSrgb),
+            Some(false)
+        );
+        assert_eq!(eight_bit_encoding(TextureFormat::Rgba16Float), None);
+    }
+}
@@ -0,0 +1,54 @@
+// Adds an 8x8 bayer pattern scaled to a single 8 bit step to the finished frame. The noise is
+// added in gamma space so each step is the same size as the swapchain's quantization.
+
+struct DitherConstants {
+    // x: 1 if the frame is stored gamma encoded rather than in an srgb format
+    params: vec4<f32>,
+}
+
+var<push_constant> constants: DitherConstants;
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+// Threshold between 0 and 1 for the pixel's position in an 8x8 bayer matrix
+fn bayer(pixel: vec2<u32>) -> f32 {
+    var x = pixel.x;
+    var y = pixel.y;
+    var value = 0u;
+    for (var bit = 0u; bit < 3u; bit++) {
+        let xb = x & 1u;
+        let yb = y & 1u;
+        value = (value << 2u) | ((xb ^ yb) << 1u) | yb;
+        x = x >> 1u;
+        y = y >> 1u;
+    }
+    // The lowest coordinate bits end up most significant, spreading neighbors far apart
+    return f32(value) / 64.0;
+}
+
+@fragment
+fn fragment(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let color = textureLoad(source, vec2<i32>(position.xy), 0);
+    let noise = (bayer(vec2<u32>(position.xy)) - 0.5 + 0.5 / 64.0) / 255.0;
+
+    // Srgb frames are encoded by the approximation the primitive shaders use
+    var encoded = color.rgb;
+    if constants.params.x == 0.0 {
+        encoded = sqrt(color.rgb);
+    }
+
+    // Scale by alpha so transparent edges aren't brightened
+    encoded = clamp(encoded + noise * color.a, vec3<f32>(0.0), vec3<f32>(1.0));
+
+    if constants.params.x == 0.0 {
+        encoded = encoded * encoded;
+    }
+    // Premultiplied colors can't exceed their alpha
+    return vec4<f32>(min(encoded, vec3<f32>(color.a)), color.a);
+}
@@ -0,0 +1,224 @@
+use std::ops::{Add, Mul, Sub};
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+// Angular frequency of the spring easing, which brings a critically damped spring within 0.1%
+// of its target at the end of the animation
+const SPRING_EASING_FREQUENCY: f32 = 9.23;
+
+// Maps an animation's linear progress to the progress shown on screen
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Easing {
+    Linear,
+    // Cubic ease in, out, and in and out
+    EaseIn,
+    EaseOut,
+    #[default]
+    EaseInOut,
+    // Curve from (0, 0) to (1, 1) through the two control points, like css cubic-bezier().
+    // Control point x coordinates are clamped to [0, 1], but y coordinates outside of it
+    // overshoot
+    CubicBezier(Vec2, Vec2),
+    // Jumps between the given number of equal steps, holding each until its end like css
+    // steps(n)
+    Steps(u32),
+    // Critically damped spring, which starts quickly and settles into place without overshoot
+    Spring,
+}
+
+impl Easing {
+    // Css named easings
+    pub const EASE: Self = Self::cubic_bezier(0.25, 0.1, 0.25, 1.0);
+    pub const CSS_EASE_IN: Self = Self::cubic_bezier(0.42, 0.0, 1.0, 1.0);
+    pub const CSS_EASE_OUT: Self = Self::cubic_bezier(0.0, 0.0, 0.58, 1.0);
+    pub const CSS_EASE_IN_OUT: Self = Self::cubic_bezier(0.42, 0.0, 0.58, 1.0);
+
+    // Common curves approximated by cubic beziers
+    pub const EASE_IN_SINE: Self = Self::cubic_bezier(0.12, 0.0, 0.39, 0.0);
+    pub const EASE_OUT_SINE: Self = Self::cubic_bezier(0.61, 1.0, 0.88, 1.0);
+    pub const EASE_IN_OUT_SINE: Self = Self::cubic_bezier(0.37, 0.0, 0.63, 1.0);
+    pub const EASE_IN_QUAD: Self = Self::cubic_bezier(0.11, 0.0, 0.5, 0.0);
+    pub const EASE_OUT_QUAD: Self = Self::cubic_bezier(0.5, 1.0, 0.89, 1.0);
+    pub const EASE_IN_OUT_QUAD: Self = Self::cubic_bezier(0.45, 0.0, 0.55, 1.0);
+    pub const EASE_IN_EXPO: Self = Self::cubic_bezier(0.7, 0.0, 0.84, 0.0);
+    pub const EASE_OUT_EXPO: Self = Self::cubic_bezier(0.16, 1.0, 0.3, 1.0);
+    pub const EASE_IN_OUT_EXPO: Self = Self::cubic_bezier(0.87, 0.0, 0.13, 1.0);
+    // Pull back before moving, overshoot at the end, or both
+    pub const EASE_IN_BACK: Self = Self::cubic_bezier(0.36, 0.0, 0.66, -0.56);
+    pub const EASE_OUT_BACK: Self = Self::cubic_bezier(0.34, 1.56, 0.64, 1.0);
+    pub const EASE_IN_OUT_BACK: Self = Self::cubic_bezier(0.68, -0.6, 0.32, 1.6);
+
+    pub const fn cubic_bezier(x1: f32, y1: f32, x2: f32, y2: f32) -> Self {
+        Self::CubicBezier(Vec2::new(x1, y1), Vec2::new(x2, y2))
+    }
+
+    // Parses a css easing such as "ease-out", "cubic-bezier(0.2, 0, 0, 1)", or "steps(4)"
+    pub fn from_css(css: &str) -> Option<Self> {
+        let css = css.trim();
+        match css {
+            "linear" => return Some(Self::Linear),
+            "ease" => return Some(Self::EASE),
+            "ease-in" => return Some(Self::CSS_EASE_IN),
+            "ease-out" => return Some(Self::CSS_EASE_OUT),
+            "ease-in-out" => return Some(Self::CSS_EASE_IN_OUT),
+            "step-end" => return Some(Self::Steps(1)),
+            _ => {}
+        }
+
+        let (function, arguments) = css.strip_suffix(')')?.split_once('(')?;
+        let arguments = arguments
+            .split(',')
+            .map(|argument| argument.trim().parse::<f32>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        match (function.trim(), arguments.as_slice()) {
+            ("cubic-bezier", [x1, y1, x2, y2]) => Some(Self::cubic_bezier(*x1, *y1, *x2, *y2)),
+            ("steps", [steps]) if *steps >= 1.0 && steps.fract() == 0.0 => {
+                Some(Self::Steps(*steps as u32))
+            }
+            _ => None,
+        }
+    }
+
+    // Eased progress for a linear progress between 0 and 1
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::CubicBezier(control1, control2) => cubic_bezier(t, control1, control2),
+            Easing::Steps(steps) => {
+                let steps = steps.max(1) as f32;
+                (t * steps).floor().min(steps) / steps
+            }
+            Easing::Spring => {
+                // Scaled so the curve ends exactly on 1
+                let spring = Spring::new(SPRING_EASING_FREQUENCY);
+                spring.progress(t) / spring.progress(1.0)
+            }
+        }
+    }
+}
+
+// Evaluates the cubic bezier easing curve from (0, 0) to (1, 1) with the given control points
+// at `x`
+pub(crate) fn cubic_bezier(x: f32, control1: Vec2, control2: Vec2) -> f32 {
+    let bezier = |t: f32, a: f32, b: f32| {
+        let inverse = 1.0 - t;
+        3.0 * inverse * inverse * t * a + 3.0 * inverse * t * t * b + t * t * t
+    };
+
+    // Bisect for the curve parameter which produces x. The x coordinates of the control points
+    // are within [0, 1], so the curve's x is monotonic
+    let (control1_x, control2_x) = (control1.x.clamp(0.0, 1.0), control2.x.clamp(0.0, 1.0));
+    let x = x.clamp(0.0, 1.0);
+    let (mut low, mut high) = (0.0, 1.0);
+    let mut t = x;
+    for _ in 0..20 {
+        let estimate = bezier(t, control1_x, control2_x);
+        if (estimate - x).abs() < 1e-4 {
+            break;
+        }
+        if estimate < x {
+            low = t;
+        } else {
+            high = t;
+        }
+        t = (low + high) / 2.0;
+    }
+    bezier(t, control1.y, control2.y)
+}
+
+// Critically damped spring pulling a value towards a target. Easings follow a fixed path,
+// while springs can be stepped every frame towards a target which keeps moving, such as an
+// item following the cursor, and carry their velocity over smoothly when it changes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Spring {
+    // Angular frequency in radians per second. Higher is snappier
+    pub frequency: f32,
+}
+
+impl Spring {
+    pub fn new(frequency: f32) -> Self {
+        Self { frequency }
+    }
+
+    // Spring which comes within 0.1% of its target the given number of seconds after starting
+    // at rest
+    pub fn settling_in(seconds: f32) -> Self {
+        Self::new(SPRING_EASING_FREQUENCY / seconds.max(f32::EPSILON))
+    }
+
+    // Fraction of the way to the target after the number of seconds, starting at rest
+    pub fn progress(&self, seconds: f32) -> f32 {
+        let decay = self.frequency * seconds.max(0.0);
+        1.0 - (1.0 + decay) * (-decay).exp()
+    }
+
+    // Advances a value and its velocity towards the target by `delta` seconds, returning the
+    // new value and velocity. Solved exactly rather than integrated, so large or uneven steps
+    // stay stable
+    pub fn step<T>(&self, value: T, velocity: T, target: T, delta: f32) -> (T, T)
+    where
+        T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+    {
+        let offset = value - target;
+        let decay = (-self.frequency * delta).exp();
+        let rate = velocity + offset * self.frequency;
+        let value = target + (offset + rate * delta) * decay;
+        let velocity = (velocity - rate * (self.frequency * delta)) * decay;
+        (value, velocity)
+    }
+
+    // Whether the value is within `tolerance` of the target and nearly at rest
+    pub fn settled(&self, value: f32, velocity: f32, target: f32, tolerance: f32) -> bool {
+        (value - target).abs() <= tolerance && velocity.abs() <= tolerance * self.frequency
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_easings_start_and_end_in_place() {
+        for easing in [
+            Easing::EASE,
+            Easing::EASE_OUT_BACK,
+            Easing::Steps(3),
+            Easing::Spring,
+        ] {
+            assert!(easing.apply(0.0).abs() < 1e-3, "{:?}", easing);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-3, "{:?}", easing);
+        }
+        assert_eq!(Easing::Steps(4).apply(0.3), 0.25);
+        assert_eq!(
+            Easing::from_css("cubic-bezier(0.25, 0.1, 0.25, 1)"),
+            Some(Easing::EASE)
+        );
+        assert_eq!(Easing::from_css("steps(2.5)"), None);
+    }
+
+    #[test]
+    fn test_spring_steps_match_progress() {
+        let spring = Spring::settling_in(0.5);
+        let (mut value, mut velocity) = (0.0, 0.0);
+        for _ in 0..30 {
+            (value, velocity) = spring.step(value, velocity, 1.0, 0.01);
+        }
+        assert!((value - spring.progress(0.3)).abs() < 1e-4);
+
+        for _ in 0..100 {
+            (value, velocity) = spring.step(value, velocity, 1.0, 0.1);
+        }
+        assert!(spring.settled(value, velocity, 1.0, 1e-3));
+    }
+}
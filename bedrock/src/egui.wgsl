@@ -0,0 +1,70 @@
+// Draws egui meshes. SRGB_TARGET is prepended when the pipeline is created.
+//
+// Egui colors are premultiplied and gamma encoded, and its textures are uploaded as srgb so they
+// sample as linear. Everything is blended in the space the target stores, matching egui's own
+// renderer.
+
+// Matches ShaderConstants in the shader crate
+struct Constants {
+    surface_size: vec2<f32>,
+    atlas_size: vec2<f32>,
+    clip: vec4<f32>,
+    backdrop: vec4<f32>,
+}
+
+var<push_constant> constants: Constants;
+
+@group(0) @binding(0) var egui_texture: texture_2d<f32>;
+@group(0) @binding(1) var egui_sampler: sampler;
+
+struct VertexInput {
+    // Pixels from the top left of the surface
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+fn linear_from_gamma(color: vec3<f32>) -> vec3<f32> {
+    let lower = color / 12.92;
+    let higher = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(higher, lower, color < vec3<f32>(0.04045));
+}
+
+fn gamma_from_linear(color: vec3<f32>) -> vec3<f32> {
+    let lower = color * 12.92;
+    let higher = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(higher, lower, color < vec3<f32>(0.0031308));
+}
+
+@vertex
+fn vertex(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(
+        in.position.x / constants.surface_size.x * 2.0 - 1.0,
+        1.0 - in.position.y / constants.surface_size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.uv = in.uv;
+    if SRGB_TARGET {
+        out.color = vec4<f32>(linear_from_gamma(in.color.rgb), in.color.a);
+    } else {
+        out.color = in.color;
+    }
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    var texel = textureSample(egui_texture, egui_sampler, in.uv);
+    if !SRGB_TARGET {
+        texel = vec4<f32>(gamma_from_linear(texel.rgb), texel.a);
+    }
+    return in.color * texel;
+}
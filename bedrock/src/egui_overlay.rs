@@ -0,0 +1,387 @@
+use std::{collections::HashMap, ops::Range};
+
+use bytemuck::{Pod, Zeroable};
+use egui::{
+    epaint::{ImageData, Primitive},
+    ClippedPrimitive, TextureFilter, TextureId, TexturesDelta,
+};
+use glam::vec4;
+use shader::ShaderConstants;
+use wgpu::*;
+
+use crate::{
+    buffer::GrowableBuffer,
+    culling::scissor_rect,
+    renderer::{Drawable, Resources},
+    scene::Layer,
+    Renderer, Scene,
+};
+
+// Output of one egui frame, tessellated by the app with `Context::tessellate`
+#[derive(Clone)]
+pub struct EguiFrame {
+    pub primitives: Vec<ClippedPrimitive>,
+    pub textures_delta: TexturesDelta,
+    pub pixels_per_point: f32,
+}
+
+impl EguiFrame {
+    pub fn new(
+        primitives: Vec<ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+        pixels_per_point: f32,
+    ) -> Self {
+        Self {
+            primitives,
+            textures_delta,
+            pixels_per_point,
+        }
+    }
+}
+
+impl Scene {
+    // Draws the egui frame in a transparent layer above every layer added so far
+    pub fn add_egui(&mut self, frame: EguiFrame) {
+        let mut layer = Layer {
+            name: Some("egui".to_string()),
+            background_color: None,
+            ..Default::default()
+        };
+        layer.add_custom(frame);
+        self.add_layer(layer);
+    }
+
+    pub fn with_egui(mut self, frame: EguiFrame) -> Self {
+        self.add_egui(frame);
+        self
+    }
+}
+
+impl Renderer {
+    // Adds the drawable which draws egui frames added to scenes with `Scene::add_egui`
+    pub fn with_egui(self) -> Self {
+        self.with_drawable::<EguiState>()
+    }
+}
+
+// Matches VertexInput in egui.wgsl
+#[derive(Copy, Clone, Default, Pod, Zeroable)]
+#[repr(C)]
+struct EguiVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [u8; 4],
+}
+
+const VERTEX_ATTRIBUTES: [VertexAttribute; 3] =
+    vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Unorm8x4];
+
+struct EguiDraw {
+    indices: Range<u32>,
+    base_vertex: i32,
+    texture: TextureId,
+    scissor: [u32; 4],
+}
+
+// Draws egui's meshes with the same device and surface as the rest of the scene. Textures are
+// kept between frames and updated from each frame's texture delta. Paint callbacks and user
+// textures aren't supported and are skipped.
+pub struct EguiState {
+    vertices: GrowableBuffer<EguiVertex>,
+    indices: GrowableBuffer<u32>,
+    bind_group_layout: BindGroupLayout,
+    pipeline_layout: PipelineLayout,
+    render_pipeline: Option<RenderPipeline>,
+    textures: HashMap<TextureId, (Texture, BindGroup)>,
+}
+
+impl EguiState {
+    fn update_textures(&mut self, device: &Device, queue: &Queue, delta: &TexturesDelta) {
+        for (id, image_delta) in delta.set.iter() {
+            let [width, height] = image_delta.image.size().map(|size| size as u32);
+            let pixels: Vec<u8> = match &image_delta.image {
+                ImageData::Color(image) => image
+                    .pixels
+                    .iter()
+                    .flat_map(|pixel| pixel.to_array())
+                    .collect(),
+                ImageData::Font(image) => image
+                    .srgba_pixels(None)
+                    .flat_map(|pixel| pixel.to_array())
+                    .collect(),
+            };
+            let size = Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            };
+
+            // Partial updates write into the existing texture
+            let origin = match image_delta.pos {
+                Some([x, y]) => {
+                    if !self.textures.contains_key(id) {
+                        continue;
+                    }
+                    Origin3d {
+                        x: x as u32,
+                        y: y as u32,
+                        z: 0,
+                    }
+                }
+                None => {
+                    let texture = device.create_texture(&TextureDescriptor {
+                        label: Some("Egui texture"),
+                        size,
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: TextureDimension::D2,
+                        format: TextureFormat::Rgba8UnormSrgb,
+                        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                        view_formats: &[],
+                    });
+                    let filter = |filter: TextureFilter| match filter {
+                        TextureFilter::Nearest => FilterMode::Nearest,
+                        TextureFilter::Linear => FilterMode::Linear,
+                    };
+                    let sampler = device.create_sampler(&SamplerDescriptor {
+                        label: Some("Egui sampler"),
+                        mag_filter: filter(image_delta.options.magnification),
+                        min_filter: filter(image_delta.options.minification),
+                        ..Default::default()
+                    });
+                    let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                        label: Some("Egui texture bind group"),
+                        layout: &self.bind_group_layout,
+                        entries: &[
+                            BindGroupEntry {
+                                binding: 0,
+                                resource: BindingResource::TextureView(
+                                    &texture.create_view(&Default::default()),
+                                ),
+                            },
+                            BindGroupEntry {
+                                binding: 1,
+                                resource: BindingResource::Sampler(&sampler),
+                            },
+                        ],
+                    });
+                    self.textures.insert(*id, (texture, bind_group));
+                    Origin3d::ZERO
+                }
+            };
+
+            queue.write_texture(
+                ImageCopyTexture {
+                    texture: &self.textures[id].0,
+                    mip_level: 0,
+                    origin,
+                    aspect: TextureAspect::All,
+                },
+                &pixels,
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(width * 4),
+                    rows_per_image: Some(height),
+                },
+                size,
+            );
+        }
+    }
+}
+
+impl Drawable for EguiState {
+    fn new(Resources { device, .. }: &Resources) -> Self {
+        let vertices = GrowableBuffer::new(device, "Egui vertex buffer", BufferUsages::VERTEX);
+        let indices = GrowableBuffer::new(device, "Egui index buffer", BufferUsages::INDEX);
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Egui bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Egui Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[PushConstantRange {
+                stages: ShaderStages::all(),
+                range: 0..std::mem::size_of::<ShaderConstants>() as u32,
+            }],
+        });
+
+        Self {
+            vertices,
+            indices,
+            bind_group_layout,
+            pipeline_layout,
+            render_pipeline: None,
+            textures: HashMap::new(),
+        }
+    }
+
+    fn surface_updated(
+        &mut self,
+        Resources {
+            device,
+            surface_resources_manager,
+            ..
+        }: &Resources,
+    ) {
+        let Some(format) = surface_resources_manager.format() else {
+            return;
+        };
+        let source = format!(
+            "const SRGB_TARGET: bool = {};\n{}",
+            format.is_srgb(),
+            include_str!("egui.wgsl")
+        );
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Egui shader"),
+            source: ShaderSource::Wgsl(source.into()),
+        });
+        self.render_pipeline = Some(device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Egui Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "vertex",
+                buffers: &[VertexBufferLayout {
+                    array_stride: std::mem::size_of::<EguiVertex>() as BufferAddress,
+                    step_mode: VertexStepMode::Vertex,
+                    attributes: &VERTEX_ATTRIBUTES,
+                }],
+            },
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: "fragment",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: Some(surface_resources_manager.depth_stencil_state()),
+            multisample: MultisampleState {
+                count: surface_resources_manager.sample_count(),
+                ..Default::default()
+            },
+            multiview: None,
+        }));
+    }
+
+    fn needs_draw(&self, layer: &Layer) -> bool {
+        layer.custom.contains::<EguiFrame>()
+    }
+
+    fn name(&self) -> &'static str {
+        "Egui"
+    }
+
+    fn instance_count(&self) -> u64 {
+        self.indices.len() / 3
+    }
+
+    fn draw<'b, 'a: 'b>(
+        &'a mut self,
+        Resources { device, queue, .. }: &'a Resources,
+        render_pass: &mut RenderPass<'b>,
+        constants: ShaderConstants,
+        layer: &Layer,
+    ) {
+        let surface_width = constants.surface_size.x as u32;
+        let surface_height = constants.surface_size.y as u32;
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut draws = Vec::new();
+        for frame in layer.custom.get::<EguiFrame>() {
+            self.update_textures(device, queue, &frame.textures_delta);
+
+            let scale = frame.pixels_per_point;
+            for ClippedPrimitive {
+                clip_rect,
+                primitive,
+            } in frame.primitives.iter()
+            {
+                let Primitive::Mesh(mesh) = primitive else {
+                    continue;
+                };
+                let clip = vec4(
+                    clip_rect.min.x * scale,
+                    clip_rect.min.y * scale,
+                    clip_rect.width() * scale,
+                    clip_rect.height() * scale,
+                );
+                let Some(scissor) = scissor_rect(clip, surface_width, surface_height) else {
+                    continue;
+                };
+                if mesh.indices.is_empty() || !self.textures.contains_key(&mesh.texture_id) {
+                    continue;
+                }
+
+                let base_vertex = vertices.len() as i32;
+                let first_index = indices.len() as u32;
+                vertices.extend(mesh.vertices.iter().map(|vertex| EguiVertex {
+                    position: [vertex.pos.x * scale, vertex.pos.y * scale],
+                    uv: [vertex.uv.x, vertex.uv.y],
+                    color: vertex.color.to_array(),
+                }));
+                indices.extend_from_slice(&mesh.indices);
+                draws.push(EguiDraw {
+                    indices: first_index..indices.len() as u32,
+                    base_vertex,
+                    texture: mesh.texture_id,
+                    scissor,
+                });
+            }
+
+            for id in frame.textures_delta.free.iter() {
+                if !draws.iter().any(|draw| draw.texture == *id) {
+                    self.textures.remove(id);
+                }
+            }
+        }
+        if draws.is_empty() {
+            return;
+        }
+
+        self.vertices.upload(device, queue, &vertices);
+        self.indices.upload(device, queue, &indices);
+
+        render_pass.set_pipeline(self.render_pipeline.as_ref().unwrap());
+        render_pass.set_push_constants(ShaderStages::all(), 0, bytemuck::cast_slice(&[constants]));
+        render_pass.set_vertex_buffer(0, self.vertices.buffer().slice(..));
+        render_pass.set_index_buffer(self.indices.buffer().slice(..), IndexFormat::Uint32);
+        for draw in draws {
+            let [x, y, width, height] = draw.scissor;
+            render_pass.set_scissor_rect(x, y, width, height);
+            render_pass.set_bind_group(0, &self.textures[&draw.texture].1, &[]);
+            render_pass.draw_indexed(
+                self.indices.uploaded_range(draw.indices),
+                draw.base_vertex,
+                0..1,
+            );
+        }
+        // Leave the scissor covering the surface for any drawables after this one
+        render_pass.set_scissor_rect(0, 0, surface_width, surface_height);
+    }
+}
@@ -0,0 +1,178 @@
+use bytemuck::{Pod, Zeroable};
+use shader::ShaderConstants;
+use wgpu::*;
+
+use crate::{
+    buffer::GrowableBuffer,
+    culling::{intersects, visible_rect},
+    renderer::{Drawable, Resources},
+    scene::{Ellipse, Layer},
+};
+
+// Matches Ellipse in ellipse.wgsl
+#[derive(Copy, Clone, Default, Pod, Zeroable)]
+#[repr(C)]
+struct InstancedEllipse {
+    center: [f32; 2],
+    radii: [f32; 2],
+    fill: [f32; 4],
+    stroke_color: [f32; 4],
+    stroke_width: f32,
+    depth: f32,
+    _padding: [f32; 2],
+}
+
+impl InstancedEllipse {
+    fn new(ellipse: &Ellipse) -> Self {
+        let (stroke_width, stroke_color) = ellipse.stroke.unwrap_or_default();
+        Self {
+            center: ellipse.center.to_array(),
+            radii: ellipse.radii.to_array(),
+            fill: ellipse.fill.unwrap_or_default().to_array(),
+            stroke_color: stroke_color.to_array(),
+            stroke_width,
+            depth: ellipse.depth,
+            ..Default::default()
+        }
+    }
+}
+
+pub struct EllipseState {
+    buffer: GrowableBuffer<InstancedEllipse>,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    pipeline_layout: PipelineLayout,
+    render_pipeline: Option<RenderPipeline>,
+}
+
+impl Drawable for EllipseState {
+    fn new(Resources { device, .. }: &Resources) -> Self {
+        let buffer = GrowableBuffer::new(device, "Ellipse buffer", BufferUsages::STORAGE);
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Ellipse bind group layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: true,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = create_bind_group(device, &bind_group_layout, &buffer);
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Ellipse Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[PushConstantRange {
+                stages: ShaderStages::all(),
+                range: 0..std::mem::size_of::<ShaderConstants>() as u32,
+            }],
+        });
+
+        Self {
+            buffer,
+            bind_group_layout,
+            bind_group,
+            pipeline_layout,
+            render_pipeline: None,
+        }
+    }
+
+    fn surface_updated(
+        &mut self,
+        Resources {
+            device,
+            surface_resources_manager,
+            ..
+        }: &Resources,
+    ) {
+        let Some(format) = surface_resources_manager.format() else {
+            return;
+        };
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Ellipse shader"),
+            source: ShaderSource::Wgsl(include_str!("ellipse.wgsl").into()),
+        });
+        self.render_pipeline = Some(device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Ellipse Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "vertex",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: "fragment",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: Some(surface_resources_manager.depth_stencil_state()),
+            multisample: MultisampleState {
+                count: surface_resources_manager.sample_count(),
+                ..Default::default()
+            },
+            multiview: None,
+        }));
+    }
+
+    fn needs_draw(&self, layer: &Layer) -> bool {
+        !layer.ellipses.is_empty()
+    }
+
+    fn instance_count(&self) -> u64 {
+        self.buffer.len()
+    }
+
+    fn draw<'b, 'a: 'b>(
+        &'a mut self,
+        Resources { device, queue, .. }: &'a Resources,
+        render_pass: &mut RenderPass<'b>,
+        constants: ShaderConstants,
+        layer: &Layer,
+    ) {
+        let visible = visible_rect(layer, constants.surface_size);
+        let ellipses: Vec<_> = layer
+            .ellipses
+            .iter()
+            .filter(|ellipse| intersects(ellipse.bounds(), visible))
+            .map(InstancedEllipse::new)
+            .collect();
+
+        render_pass.set_pipeline(self.render_pipeline.as_ref().unwrap());
+        render_pass.set_push_constants(ShaderStages::all(), 0, bytemuck::cast_slice(&[constants]));
+
+        if self.buffer.upload(device, queue, &ellipses) {
+            self.bind_group = create_bind_group(device, &self.bind_group_layout, &self.buffer);
+        }
+        self.buffer
+            .draw_chunks(render_pass, 0, &self.bind_group, 0..6);
+    }
+}
+
+fn create_bind_group(
+    device: &Device,
+    bind_group_layout: &BindGroupLayout,
+    buffer: &GrowableBuffer<InstancedEllipse>,
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Ellipse bind group"),
+        layout: bind_group_layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: buffer.binding(),
+        }],
+    })
+}
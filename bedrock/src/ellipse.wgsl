@@ -0,0 +1,91 @@
+// Draws ellipses from an approximate distance to their edge, which is exact for circles and
+// close enough near the edge of ellipses for antialiasing
+
+struct Ellipse {
+    center: vec2<f32>,
+    radii: vec2<f32>,
+    // Straight alpha, transparent when unfilled
+    fill: vec4<f32>,
+    stroke_color: vec4<f32>,
+    stroke_width: f32,
+    depth: f32,
+    padding: vec2<f32>,
+}
+
+// Matches ShaderConstants in the shader crate
+struct Constants {
+    surface_size: vec2<f32>,
+    atlas_size: vec2<f32>,
+    clip: vec4<f32>,
+    backdrop: vec4<f32>,
+}
+
+@group(0) @binding(0) var<storage, read> ellipses: array<Ellipse>;
+
+var<push_constant> constants: Constants;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // Offset from the ellipse's center in pixels
+    @location(0) offset: vec2<f32>,
+    @location(1) @interpolate(flat) instance: u32,
+}
+
+@vertex
+fn vertex(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    let ellipse = ellipses[instance_index];
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    // Padded by a pixel so the antialiased edge isn't cut off
+    let extent = abs(ellipse.radii) + ellipse.stroke_width / 2.0 + 1.0;
+    let offset = corners[vertex_index] * extent;
+    let pixel = ellipse.center + offset;
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(
+        pixel.x / constants.surface_size.x * 2.0 - 1.0,
+        1.0 - pixel.y / constants.surface_size.y * 2.0,
+        ellipse.depth,
+        1.0,
+    );
+    out.offset = offset;
+    out.instance = instance_index;
+    return out;
+}
+
+// Signed distance in pixels, negative inside
+fn ellipse_distance(offset: vec2<f32>, radii: vec2<f32>) -> f32 {
+    let safe_radii = max(radii, vec2<f32>(0.0001, 0.0001));
+    let scaled = offset / safe_radii;
+    let gradient = length(offset / (safe_radii * safe_radii));
+    if gradient < 0.0001 {
+        // The very center, which is as far inside as the smaller radius
+        return -min(safe_radii.x, safe_radii.y);
+    }
+    return (length(scaled) - 1.0) * length(scaled) / gradient;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let ellipse = ellipses[in.instance];
+    let edge_distance = ellipse_distance(in.offset, abs(ellipse.radii));
+
+    let fill_alpha = ellipse.fill.a * clamp(0.5 - edge_distance, 0.0, 1.0);
+    let stroke_alpha = ellipse.stroke_color.a
+        * clamp(ellipse.stroke_width / 2.0 - abs(edge_distance) + 0.5, 0.0, 1.0);
+
+    // Stroke over fill, premultiplied
+    let color = ellipse.stroke_color.rgb * stroke_alpha
+        + ellipse.fill.rgb * fill_alpha * (1.0 - stroke_alpha);
+    let alpha = stroke_alpha + fill_alpha * (1.0 - stroke_alpha);
+    return vec4<f32>(color, alpha);
+}
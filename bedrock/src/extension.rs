@@ -0,0 +1,129 @@
+use std::fmt;
+
+use naga::{
+    front::{spv, wgsl},
+    valid::{Capabilities, ValidationFlags, Validator},
+    Module, ShaderStage,
+};
+use wgpu::{util::make_spirv, Device, ShaderModule, ShaderModuleDescriptor, ShaderSource};
+
+// What an extension module is used for. Each kind expects a particular set of entry points
+// so the host can build pipelines for it without any other description. Custom geometry is
+// drawn with shader quads, or with a `Drawable` which builds its own pipelines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtensionKind {
+    // Fills shader quads. Registered with `Renderer::register_quad_shader`, which prepends the
+    // declarations in shader_quad.wgsl including the `vertex` entry point, so the module
+    // itself only needs a `fragment` entry point.
+    Quad,
+    // Run over the whole frame. Needs a `fragment` entry point. The host supplies the full
+    // screen quad. See `Renderer::add_post_effect` for the bindings it's given.
+    PostEffect,
+}
+
+impl ExtensionKind {
+    fn required_entry_points(&self) -> &'static [(&'static str, ShaderStage)] {
+        match self {
+            Self::Quad => &[
+                ("vertex", ShaderStage::Vertex),
+                ("fragment", ShaderStage::Fragment),
+            ],
+            Self::PostEffect => &[("fragment", ShaderStage::Fragment)],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ShaderExtensionError {
+    Parse(String),
+    Validation(String),
+    MissingEntryPoint {
+        name: &'static str,
+        stage: ShaderStage,
+    },
+}
+
+impl fmt::Display for ShaderExtensionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(error) => write!(f, "could not parse wgsl: {}", error),
+            Self::Validation(error) => write!(f, "invalid wgsl module: {}", error),
+            Self::MissingEntryPoint { name, stage } => {
+                write!(f, "missing {:?} entry point `{}`", stage, name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShaderExtensionError {}
+
+// A user supplied wgsl or spirv module loaded at runtime
+pub struct ShaderExtension {
+    pub kind: ExtensionKind,
+    pub module: ShaderModule,
+}
+
+impl ShaderExtension {
+    // Parses and validates the module with naga before handing it to wgpu so that mistakes
+    // are reported as errors instead of device validation panics
+    pub fn load(
+        device: &Device,
+        name: &str,
+        kind: ExtensionKind,
+        source: &str,
+    ) -> Result<Self, ShaderExtensionError> {
+        let module = wgsl::parse_str(source)
+            .map_err(|error| ShaderExtensionError::Parse(error.emit_to_string(source)))?;
+
+        Validator::new(ValidationFlags::all(), Capabilities::PUSH_CONSTANT)
+            .validate(&module)
+            .map_err(|error| ShaderExtensionError::Validation(error.emit_to_string(source)))?;
+        check_entry_points(&module, kind)?;
+
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some(name),
+            source: ShaderSource::Wgsl(source.into()),
+        });
+
+        Ok(Self { kind, module })
+    }
+
+    // Same as `load` for modules compiled to spirv ahead of time
+    pub fn load_spirv(
+        device: &Device,
+        name: &str,
+        kind: ExtensionKind,
+        data: &[u8],
+    ) -> Result<Self, ShaderExtensionError> {
+        let module = spv::parse_u8_slice(data, &spv::Options::default())
+            .map_err(|error| ShaderExtensionError::Parse(error.to_string()))?;
+
+        Validator::new(ValidationFlags::all(), Capabilities::PUSH_CONSTANT)
+            .validate(&module)
+            .map_err(|error| ShaderExtensionError::Validation(error.into_inner().to_string()))?;
+        check_entry_points(&module, kind)?;
+
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some(name),
+            source: make_spirv(data),
+        });
+
+        Ok(Self { kind, module })
+    }
+}
+
+fn check_entry_points(module: &Module, kind: ExtensionKind) -> Result<(), ShaderExtensionError> {
+    for (entry_point, stage) in kind.required_entry_points() {
+        if !module
+            .entry_points
+            .iter()
+            .any(|candidate| candidate.name == *entry_point && candidate.stage == *stage)
+        {
+            return Err(ShaderExtensionError::MissingEntryPoint {
+                name: *entry_point,
+                stage: *stage,
+            });
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,199 @@
+// C api for hosts written in other languages (C++, Swift, C#) which own their own windows.
+// Scenes are submitted as the same json `Scene::to_writer` produces, so hosts only need a json
+// writer rather than bindings for every scene type. Headers can be generated with cbindgen, and
+// hosts link the crate built with `--crate-type staticlib` or `cdylib`.
+
+use std::{
+    ffi::{c_int, c_void},
+    num::{NonZeroIsize, NonZeroU32},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr::{self, NonNull},
+    slice,
+};
+
+use futures::executor::block_on;
+use winit::raw_window_handle::{
+    AndroidDisplayHandle, AndroidNdkWindowHandle, AppKitDisplayHandle, AppKitWindowHandle,
+    RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle,
+    Win32WindowHandle, WindowsDisplayHandle, XcbDisplayHandle, XcbWindowHandle, XlibDisplayHandle,
+    XlibWindowHandle,
+};
+
+use crate::{Asset, Renderer, Scene};
+
+pub const BEDROCK_PLATFORM_WIN32: u32 = 0;
+pub const BEDROCK_PLATFORM_XLIB: u32 = 1;
+pub const BEDROCK_PLATFORM_XCB: u32 = 2;
+pub const BEDROCK_PLATFORM_WAYLAND: u32 = 3;
+pub const BEDROCK_PLATFORM_APPKIT: u32 = 4;
+pub const BEDROCK_PLATFORM_ANDROID: u32 = 5;
+
+pub const BEDROCK_OK: c_int = 0;
+pub const BEDROCK_INVALID_ARGUMENT: c_int = 1;
+pub const BEDROCK_INVALID_SCENE: c_int = 2;
+pub const BEDROCK_RENDER_FAILED: c_int = 3;
+// The renderer panicked and may be left in a bad state. It should be destroyed
+pub const BEDROCK_PANICKED: c_int = 4;
+
+// Native window to render into. Which fields are used depends on the platform:
+// - Win32: `window` is the HWND and `display` the optional HINSTANCE
+// - Xlib: `window_id` is the Window and `display` the Display pointer
+// - Xcb: `window_id` is the xcb_window_t and `display` the connection pointer
+// - Wayland: `window` is the wl_surface and `display` the wl_display
+// - AppKit: `window` is the NSView
+// - Android: `window` is the ANativeWindow
+#[repr(C)]
+pub struct BedrockWindowHandle {
+    pub platform: u32,
+    pub display: *mut c_void,
+    pub window: *mut c_void,
+    pub window_id: u64,
+    // X11 screen number. Ignored on other platforms
+    pub screen: c_int,
+}
+
+impl BedrockWindowHandle {
+    // None if the platform is unknown or a required pointer is null
+    fn raw_handles(&self) -> Option<(RawDisplayHandle, RawWindowHandle)> {
+        let display = NonNull::new(self.display);
+        let window = NonNull::new(self.window);
+        Some(match self.platform {
+            BEDROCK_PLATFORM_WIN32 => {
+                let mut handle = Win32WindowHandle::new(NonZeroIsize::new(self.window as isize)?);
+                handle.hinstance = NonZeroIsize::new(self.display as isize);
+                (WindowsDisplayHandle::new().into(), handle.into())
+            }
+            BEDROCK_PLATFORM_XLIB => (
+                XlibDisplayHandle::new(display, self.screen).into(),
+                XlibWindowHandle::new(self.window_id as _).into(),
+            ),
+            BEDROCK_PLATFORM_XCB => (
+                XcbDisplayHandle::new(display, self.screen).into(),
+                XcbWindowHandle::new(NonZeroU32::new(self.window_id as u32)?).into(),
+            ),
+            BEDROCK_PLATFORM_WAYLAND => (
+                WaylandDisplayHandle::new(display?).into(),
+                WaylandWindowHandle::new(window?).into(),
+            ),
+            BEDROCK_PLATFORM_APPKIT => (
+                AppKitDisplayHandle::new().into(),
+                AppKitWindowHandle::new(window?).into(),
+            ),
+            BEDROCK_PLATFORM_ANDROID => (
+                AndroidDisplayHandle::new().into(),
+                AndroidNdkWindowHandle::new(window?).into(),
+            ),
+            _ => return None,
+        })
+    }
+}
+
+// Creates a renderer drawing into the window, or returns null if the handle is invalid or no
+// suitable gpu was found. Sprite textures can't be embedded through the C api, so sprites draw
+// the missing texture placeholder.
+//
+// Safety: the handle must point to a valid BedrockWindowHandle, and the window it describes
+// must outlive the renderer
+#[no_mangle]
+pub unsafe extern "C" fn bedrock_renderer_create(
+    handle: *const BedrockWindowHandle,
+    width: u32,
+    height: u32,
+) -> *mut Renderer {
+    let Some((display, window)) = handle.as_ref().and_then(BedrockWindowHandle::raw_handles) else {
+        return ptr::null_mut();
+    };
+    catch_unwind(AssertUnwindSafe(|| {
+        let renderer = block_on(Renderer::from_raw_handle(display, window, width, height))
+            .with_default_drawables::<Asset>();
+        Box::into_raw(Box::new(renderer))
+    }))
+    .unwrap_or(ptr::null_mut())
+}
+
+// Draws the json scene in `bytes` into the renderer's window
+//
+// Safety: the renderer must come from `bedrock_renderer_create` and `bytes` must point to
+// `length` readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn bedrock_renderer_submit_scene(
+    renderer: *mut Renderer,
+    bytes: *const u8,
+    length: usize,
+) -> c_int {
+    let Some(renderer) = renderer.as_mut() else {
+        return BEDROCK_INVALID_ARGUMENT;
+    };
+    if bytes.is_null() {
+        return BEDROCK_INVALID_ARGUMENT;
+    }
+    let Ok(scene) = Scene::from_reader(slice::from_raw_parts(bytes, length)) else {
+        return BEDROCK_INVALID_SCENE;
+    };
+    catch_unwind(AssertUnwindSafe(|| match renderer.try_draw_scene(&scene) {
+        Ok(()) => BEDROCK_OK,
+        Err(render_error) => {
+            eprintln!("Render error: {}", render_error);
+            BEDROCK_RENDER_FAILED
+        }
+    }))
+    .unwrap_or(BEDROCK_PANICKED)
+}
+
+// Must be called whenever the host's window changes size
+//
+// Safety: the renderer must come from `bedrock_renderer_create`
+#[no_mangle]
+pub unsafe extern "C" fn bedrock_renderer_resize(
+    renderer: *mut Renderer,
+    width: u32,
+    height: u32,
+) -> c_int {
+    let Some(renderer) = renderer.as_mut() else {
+        return BEDROCK_INVALID_ARGUMENT;
+    };
+    catch_unwind(AssertUnwindSafe(|| {
+        renderer.resize_surface(width, height);
+        BEDROCK_OK
+    }))
+    .unwrap_or(BEDROCK_PANICKED)
+}
+
+// Safety: the renderer must come from `bedrock_renderer_create` and not be used afterwards.
+// Null is ignored
+#[no_mangle]
+pub unsafe extern "C" fn bedrock_renderer_destroy(renderer: *mut Renderer) {
+    if !renderer.is_null() {
+        drop(Box::from_raw(renderer));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_invalid_handles_are_rejected() {
+        let handle = |platform, window: *mut c_void| BedrockWindowHandle {
+            platform,
+            display: ptr::null_mut(),
+            window,
+            window_id: 0,
+            screen: 0,
+        };
+        let window = NonNull::<c_void>::dangling().as_ptr();
+
+        assert!(handle(BEDROCK_PLATFORM_APPKIT, window)
+            .raw_handles()
+            .is_some());
+        assert!(handle(BEDROCK_PLATFORM_APPKIT, ptr::null_mut())
+            .raw_handles()
+            .is_none());
+        assert!(handle(BEDROCK_PLATFORM_WAYLAND, window)
+            .raw_handles()
+            .is_none());
+        assert!(handle(BEDROCK_PLATFORM_XCB, window).raw_handles().is_none());
+        assert!(handle(42, window).raw_handles().is_none());
+        assert!(unsafe { bedrock_renderer_create(ptr::null(), 100, 100) }.is_null());
+    }
+}
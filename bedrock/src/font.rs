@@ -1,31 +1,83 @@
 use std::sync::Arc;
+#[cfg(feature = "system-raster")]
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
-use font_kit::{handle::Handle, source::SystemSource};
-use swash::FontRef;
+#[cfg(feature = "system-raster")]
+use font_kit::font::Font as NativeFont;
+use font_kit::{
+    family_name::FamilyName, handle::Handle, properties::Properties, source::SystemSource,
+};
+use swash::{shape::cluster::Glyph, FontRef};
+
+// Whether fonts are matched and measured by the platform's text stack rather than swash.
+// Shared by every renderer in the process
+#[cfg(feature = "system-raster")]
+static SYSTEM_TEXT: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "system-raster")]
+thread_local! {
+    // Platform fonts by family name and index within the font file. None if the platform
+    // couldn't load the font
+    static NATIVE_FONTS: RefCell<HashMap<(Arc<str>, usize), Option<NativeFont>>> =
+        RefCell::new(HashMap::new());
+}
+
+#[cfg(feature = "system-raster")]
+pub fn system_text() -> bool {
+    SYSTEM_TEXT.load(Ordering::Relaxed)
+}
+
+#[cfg(not(feature = "system-raster"))]
+pub fn system_text() -> bool {
+    false
+}
+
+#[cfg(feature = "system-raster")]
+pub fn set_system_text(enabled: bool) {
+    SYSTEM_TEXT.store(enabled, Ordering::Relaxed);
+}
 
 #[derive(Clone)]
 pub struct Font {
+    // Only needed to cache native fonts
+    #[cfg(feature = "system-raster")]
+    name: Arc<str>,
     index: usize,
     data: Arc<Vec<u8>>,
 }
 
 impl Font {
     pub fn from_name(font_name: &str) -> Option<Self> {
-        let font = &SystemSource::new()
-            .select_family_by_name(font_name)
-            .ok()?
-            .fonts()[0]
-            .clone();
+        let source = SystemSource::new();
+        let font = &if system_text() {
+            // Resolves names the way native apps do, including aliases and style fallbacks
+            source
+                .select_best_match(
+                    &[FamilyName::Title(font_name.to_string())],
+                    &Properties::new(),
+                )
+                .ok()?
+        } else {
+            source.select_family_by_name(font_name).ok()?.fonts()[0].clone()
+        };
 
         match font {
             Handle::Path { path, font_index } => {
                 let data = std::fs::read(path).ok()?;
                 Some(Self {
+                    #[cfg(feature = "system-raster")]
+                    name: Arc::from(font_name),
                     data: Arc::new(data),
                     index: *font_index as usize,
                 })
             }
             Handle::Memory { bytes, font_index } => Some(Self {
+                #[cfg(feature = "system-raster")]
+                name: Arc::from(font_name),
                 data: bytes.clone(),
                 index: *font_index as usize,
             }),
@@ -35,4 +87,52 @@ impl Font {
     pub fn as_ref<'a>(&'a self) -> Option<FontRef<'a>> {
         FontRef::from_index(self.data.as_ref(), self.index)
     }
+
+    // Runs the callback with the platform's version of the font, loading it the first time
+    // it's needed on each thread. Returns None if the platform couldn't load the font
+    #[cfg(feature = "system-raster")]
+    pub fn with_native<T>(&self, callback: impl FnOnce(&NativeFont) -> T) -> Option<T> {
+        NATIVE_FONTS.with(|fonts| {
+            fonts
+                .borrow_mut()
+                .entry((self.name.clone(), self.index))
+                .or_insert_with(|| {
+                    NativeFont::from_bytes(self.data.clone(), self.index as u32).ok()
+                })
+                .as_ref()
+                .map(callback)
+        })
+    }
+
+    // Replaces the shaped advances with the platform's and returns its ascent and descent, so
+    // text measures the same as it does in native apps. Returns None if the platform couldn't
+    // load the font
+    #[cfg(feature = "system-raster")]
+    pub fn apply_system_metrics(&self, size: f32, glyphs: &mut [Glyph]) -> Option<(f32, f32)> {
+        self.with_native(|native| {
+            let metrics = native.metrics();
+            let scale = size / metrics.units_per_em as f32;
+            for glyph in glyphs.iter_mut() {
+                if let Ok(advance) = native.advance(glyph.id as u32) {
+                    glyph.advance = advance.x() * scale;
+                }
+            }
+            (metrics.ascent.abs() * scale, metrics.descent.abs() * scale)
+        })
+    }
+
+    #[cfg(not(feature = "system-raster"))]
+    pub fn apply_system_metrics(&self, _size: f32, _glyphs: &mut [Glyph]) -> Option<(f32, f32)> {
+        None
+    }
+
+    // Whether the font has color outlines or bitmaps, as emoji fonts do
+    #[cfg(feature = "system-raster")]
+    pub fn has_color_glyphs(&self) -> bool {
+        self.as_ref().map_or(false, |font| {
+            [b"COLR", b"CBDT", b"sbix"]
+                .iter()
+                .any(|tag| font.table(swash::tag_from_bytes(tag)).is_some())
+        })
+    }
 }
@@ -0,0 +1,34 @@
+use wgpu::*;
+
+use crate::{scene::Layer, surface_wrapper::MULTISAMPLE_COUNT};
+
+// Where a frame hook is called and what it can draw into
+pub struct FrameHookContext<'a> {
+    pub device: &'a Device,
+    pub queue: &'a Queue,
+    // Layer which was just drawn, or None once every layer and post effect has been drawn.
+    // Layers drawn into a render target hand over that target rather than the frame
+    pub layer: Option<&'a Layer>,
+    // Texture to draw into. Between layers this is the multisampled texture later layers load
+    // from, so passes must use the same sample count and resolve into `resolve_target`. At the
+    // end of the frame, or while degraded rendering has turned multisampling off, it is the
+    // frame itself and there is nothing to resolve
+    pub view: &'a TextureView,
+    pub resolve_target: Option<&'a TextureView>,
+    pub format: TextureFormat,
+    pub size: Extent3d,
+}
+
+impl FrameHookContext<'_> {
+    pub fn sample_count(&self) -> u32 {
+        if self.resolve_target.is_some() {
+            MULTISAMPLE_COUNT
+        } else {
+            1
+        }
+    }
+}
+
+// Called after each layer and at the end of every frame with command buffers to submit in
+// between, such as an egui overlay or a 3d viewport sharing the swapchain
+pub type FrameHook = Box<dyn FnMut(&FrameHookContext) -> Vec<CommandBuffer> + Send + Sync>;
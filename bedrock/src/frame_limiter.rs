@@ -0,0 +1,174 @@
+use std::{
+    collections::VecDeque,
+    thread,
+    time::{Duration, Instant},
+};
+
+// Frames kept in the frame time history. Two seconds at 60hz
+const HISTORY_LENGTH: usize = 120;
+// Sleeping can overshoot by about a scheduler tick, so the hybrid strategy wakes this early and
+// spins for the rest
+const SPIN_MARGIN: Duration = Duration::from_millis(2);
+
+// How the limiter waits out the rest of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameLimitStrategy {
+    // Cheapest, but frames can start a millisecond or two late depending on the os scheduler
+    Sleep,
+    // Exact, but keeps a cpu core busy
+    Spin,
+    // Sleeps for most of the wait and spins for the last couple of milliseconds
+    #[default]
+    SleepThenSpin,
+}
+
+// Caps the frame rate independently of vsync by waiting before each frame starts
+pub(crate) struct FrameLimiter {
+    interval: Duration,
+    strategy: FrameLimitStrategy,
+    next_frame: Option<Instant>,
+}
+
+impl FrameLimiter {
+    pub fn new(fps: f32, strategy: FrameLimitStrategy) -> Self {
+        Self {
+            interval: Duration::from_secs_f32(1.0 / fps.max(1.0)),
+            strategy,
+            next_frame: None,
+        }
+    }
+
+    // Blocks until the next frame is due
+    pub fn wait(&mut self) {
+        if let Some(deadline) = self.next_frame {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.strategy {
+                FrameLimitStrategy::Sleep => thread::sleep(remaining),
+                FrameLimitStrategy::Spin => spin_until(deadline),
+                FrameLimitStrategy::SleepThenSpin => {
+                    thread::sleep(remaining.saturating_sub(SPIN_MARGIN));
+                    spin_until(deadline);
+                }
+            }
+        }
+
+        // Frames are scheduled from the previous deadline so small oversleeps don't add up, but
+        // after a long frame the schedule restarts rather than rushing to catch up
+        let start = Instant::now();
+        self.next_frame = Some(match self.next_frame {
+            Some(deadline) if start.saturating_duration_since(deadline) < self.interval => {
+                deadline + self.interval
+            }
+            _ => start + self.interval,
+        });
+    }
+}
+
+fn spin_until(deadline: Instant) {
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
+
+// Time between the starts of recent frames drawn into the primary window, for fps counters and
+// noticing stutter. Frames skipped by redraw on demand aren't counted, so idle periods show up
+// as a single long frame.
+#[derive(Debug, Clone, Default)]
+pub struct FrameStats {
+    frame_times: VecDeque<Duration>,
+    last_frame: Option<Instant>,
+}
+
+impl FrameStats {
+    pub(crate) fn frame_started(&mut self, now: Instant) {
+        if let Some(last_frame) = self.last_frame {
+            if self.frame_times.len() == HISTORY_LENGTH {
+                self.frame_times.pop_front();
+            }
+            self.frame_times
+                .push_back(now.saturating_duration_since(last_frame));
+        }
+        self.last_frame = Some(now);
+    }
+
+    // Oldest first
+    pub fn frame_times(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.frame_times.iter().copied()
+    }
+
+    pub fn average(&self) -> Option<Duration> {
+        if self.frame_times.is_empty() {
+            return None;
+        }
+        Some(self.frame_times.iter().sum::<Duration>() / self.frame_times.len() as u32)
+    }
+
+    pub fn fps(&self) -> Option<f32> {
+        self.average()
+            .filter(|average| !average.is_zero())
+            .map(|average| 1.0 / average.as_secs_f32())
+    }
+
+    // Frame time which the given fraction of frames (between 0 and 1) were faster than. The
+    // 0.99 percentile is a common measure of how smooth animation feels
+    pub fn percentile(&self, percentile: f32) -> Option<Duration> {
+        let mut sorted: Vec<Duration> = self.frame_times.iter().copied().collect();
+        sorted.sort();
+        let index = ((sorted.len() as f32 - 1.0) * percentile.clamp(0.0, 1.0)).round() as usize;
+        sorted.get(index).copied()
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.frame_times.iter().max().copied()
+    }
+
+    // Frames which took more than `factor` times the median, such as 1.5 for frames which
+    // missed a vsync interval
+    pub fn stutters(&self, factor: f32) -> usize {
+        let Some(median) = self.percentile(0.5) else {
+            return 0;
+        };
+        let threshold = median.mul_f32(factor);
+        self.frame_times
+            .iter()
+            .filter(|frame_time| **frame_time > threshold)
+            .count()
+    }
+
+    pub fn clear(&mut self) {
+        self.frame_times.clear();
+        self.last_frame = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stats_summarize_history() {
+        let mut stats = FrameStats::default();
+        let start = Instant::now();
+        let mut time = start;
+        for frame in 0..10 {
+            stats.frame_started(time);
+            time += Duration::from_millis(if frame == 5 { 50 } else { 10 });
+        }
+
+        assert_eq!(stats.frame_times().count(), 9);
+        assert_eq!(stats.percentile(0.5), Some(Duration::from_millis(10)));
+        assert_eq!(stats.max(), Some(Duration::from_millis(50)));
+        assert_eq!(stats.stutters(1.5), 1);
+        assert_eq!(stats.average(), Some(Duration::from_millis(130) / 9));
+    }
+
+    #[test]
+    fn test_limiter_spaces_frames() {
+        let mut limiter = FrameLimiter::new(100.0, FrameLimitStrategy::Spin);
+        let start = Instant::now();
+        limiter.wait();
+        limiter.wait();
+        limiter.wait();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+}
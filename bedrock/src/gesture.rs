@@ -0,0 +1,389 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use glam::{vec2, Vec2, Vec4};
+use winit::{
+    event::{MouseScrollDelta, Touch, TouchPhase, WindowEvent},
+    window::Window,
+};
+
+use crate::{easing::Spring, scene::Camera};
+
+// Window of recent drag movement used to measure the velocity a fling starts with
+const FLING_SAMPLE_WINDOW: Duration = Duration::from_millis(100);
+// Flings slower than this many screen pixels per second stop
+const MIN_FLING_SPEED: f32 = 10.0;
+// Screen distance past an edge at which dragging further moves the camera at half speed
+const RUBBER_BAND_DISTANCE: f32 = 120.0;
+// Longest step taken by `update`, so a stalled frame doesn't fling the camera across the world
+const MAX_STEP: Duration = Duration::from_millis(100);
+// Zoom factor of one line of mouse wheel scrolling
+const WHEEL_ZOOM: f32 = 1.1;
+
+// Turns touch, trackpad, and mouse wheel input into camera movement with the feel of native
+// scroll views. Dragging pans, pinching zooms around the fingers, released drags keep gliding
+// and slow down with friction, and the camera can be pulled past the edges of its bounds with
+// increasing resistance before springing back.
+//
+// Pass window events to `handle_event` and call `drive` before drawing each frame.
+#[derive(Debug, Clone)]
+pub struct CameraController {
+    camera: Camera,
+    // World rectangle the view is kept within. Unbounded when None
+    bounds: Option<Vec4>,
+    viewport: Vec2,
+    min_zoom: f32,
+    max_zoom: f32,
+    // Rate the fling velocity decays at per second
+    friction: f32,
+    spring: Spring,
+    // Camera velocity in world units per second
+    velocity: Vec2,
+    // Recent drag movements in screen pixels, for measuring fling velocity
+    samples: VecDeque<(Instant, Vec2)>,
+    touches: HashMap<u64, Vec2>,
+    cursor: Vec2,
+    // Trackpad scroll in progress
+    scrolling: bool,
+    last_update: Option<Instant>,
+}
+
+impl CameraController {
+    pub fn new(camera: Camera) -> Self {
+        Self {
+            camera,
+            bounds: None,
+            viewport: Vec2::ZERO,
+            min_zoom: 0.01,
+            max_zoom: 100.0,
+            friction: 4.0,
+            spring: Spring::settling_in(0.4),
+            velocity: Vec2::ZERO,
+            samples: VecDeque::new(),
+            touches: HashMap::new(),
+            cursor: Vec2::ZERO,
+            scrolling: false,
+            last_update: None,
+        }
+    }
+
+    pub fn with_bounds(mut self, bounds: Vec4) -> Self {
+        self.set_bounds(Some(bounds));
+        self
+    }
+
+    pub fn set_bounds(&mut self, bounds: Option<Vec4>) {
+        self.bounds = bounds;
+    }
+
+    pub fn with_zoom_range(mut self, min_zoom: f32, max_zoom: f32) -> Self {
+        self.set_zoom_range(min_zoom, max_zoom);
+        self
+    }
+
+    pub fn set_zoom_range(&mut self, min_zoom: f32, max_zoom: f32) {
+        self.min_zoom = min_zoom.max(f32::EPSILON);
+        self.max_zoom = max_zoom.max(self.min_zoom);
+        self.camera.zoom = self.camera.zoom.clamp(self.min_zoom, self.max_zoom);
+    }
+
+    // Higher friction stops flings sooner
+    pub fn with_friction(mut self, friction: f32) -> Self {
+        self.friction = friction.max(0.0);
+        self
+    }
+
+    // Size of the window in pixels. Kept up to date by resize events
+    pub fn with_viewport(mut self, viewport: Vec2) -> Self {
+        self.set_viewport(viewport);
+        self
+    }
+
+    pub fn set_viewport(&mut self, viewport: Vec2) {
+        self.viewport = viewport;
+    }
+
+    pub fn camera(&self) -> Camera {
+        self.camera
+    }
+
+    // Moves the camera immediately, stopping any fling
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = camera;
+        self.velocity = Vec2::ZERO;
+    }
+
+    // Whether a gesture is in progress or the camera is still gliding or springing back
+    pub fn is_moving(&self) -> bool {
+        !self.touches.is_empty()
+            || self.scrolling
+            || self.velocity.length() * self.camera.zoom > MIN_FLING_SPEED
+            || self.overshoot() != Vec2::ZERO
+    }
+
+    // Updates the gesture from a window event. Returns whether the event moved the camera
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        self.handle_event_at(event, Instant::now())
+    }
+
+    pub fn handle_event_at(&mut self, event: &WindowEvent, now: Instant) -> bool {
+        match event {
+            WindowEvent::Resized(size) => {
+                self.set_viewport(vec2(size.width as f32, size.height as f32));
+                false
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = vec2(position.x as f32, position.y as f32);
+                false
+            }
+            WindowEvent::Touch(touch) => self.touch(touch, now),
+            // Pinches on macOS trackpads
+            WindowEvent::TouchpadMagnify { delta, .. } => {
+                self.zoom_by(self.cursor, 1.0 + *delta as f32);
+                true
+            }
+            // Trackpads scroll by pixels and pan, while mouse wheels scroll by lines and zoom
+            WindowEvent::MouseWheel { delta, phase, .. } => match delta {
+                MouseScrollDelta::PixelDelta(delta) => {
+                    match phase {
+                        TouchPhase::Started => self.begin_drag(),
+                        TouchPhase::Ended | TouchPhase::Cancelled => {
+                            self.end_drag(now);
+                            return false;
+                        }
+                        TouchPhase::Moved => {}
+                    }
+                    self.scrolling = true;
+                    self.drag(vec2(delta.x as f32, delta.y as f32), now);
+                    true
+                }
+                MouseScrollDelta::LineDelta(_, lines) => {
+                    self.zoom_by(self.cursor, WHEEL_ZOOM.powf(*lines));
+                    true
+                }
+            },
+            _ => false,
+        }
+    }
+
+    fn touch(&mut self, touch: &Touch, now: Instant) -> bool {
+        let location = vec2(touch.location.x as f32, touch.location.y as f32);
+        match touch.phase {
+            TouchPhase::Started => {
+                self.begin_drag();
+                self.touches.insert(touch.id, location);
+                false
+            }
+            TouchPhase::Moved => {
+                let Some(previous) = self.touches.get(&touch.id).copied() else {
+                    return false;
+                };
+                // Pinches zoom by how much the distance to the other finger changed, around
+                // the point between them
+                let other = self
+                    .touches
+                    .iter()
+                    .find(|(id, _)| **id != touch.id)
+                    .map(|(_, position)| *position);
+                self.touches.insert(touch.id, location);
+                match other {
+                    Some(other) => {
+                        let before = previous.distance(other);
+                        let after = location.distance(other);
+                        if before > 0.0 {
+                            self.zoom_by((location + other) / 2.0, after / before);
+                        }
+                        self.drag((location - previous) / 2.0, now);
+                    }
+                    None => self.drag(location - previous, now),
+                }
+                true
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.touches.remove(&touch.id);
+                if self.touches.is_empty() {
+                    self.end_drag(now);
+                } else {
+                    // Lifting one finger of a pinch shouldn't fling with the other's movement
+                    self.samples.clear();
+                }
+                false
+            }
+        }
+    }
+
+    fn begin_drag(&mut self) {
+        self.velocity = Vec2::ZERO;
+        self.samples.clear();
+    }
+
+    // Pans by the drag, with resistance once past the edge of the bounds
+    fn drag(&mut self, delta: Vec2, now: Instant) {
+        let overshoot = self.overshoot() * self.camera.zoom;
+        // Only movement further out is resisted
+        let resist = |delta: f32, overshoot: f32| {
+            if delta * overshoot < 0.0 {
+                delta / (1.0 + overshoot.abs() / RUBBER_BAND_DISTANCE)
+            } else {
+                delta
+            }
+        };
+        let delta = vec2(resist(delta.x, overshoot.x), resist(delta.y, overshoot.y));
+        self.camera.pan(delta);
+
+        self.samples.push_back((now, delta));
+        while let Some((time, _)) = self.samples.front() {
+            if now.saturating_duration_since(*time) <= FLING_SAMPLE_WINDOW {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    // Starts gliding with the speed the drag was moving at
+    fn end_drag(&mut self, now: Instant) {
+        self.scrolling = false;
+        self.last_update = Some(now);
+        let Some((start, _)) = self.samples.front() else {
+            return;
+        };
+        let seconds = now.saturating_duration_since(*start).as_secs_f32();
+        if seconds > 0.0 {
+            let distance: Vec2 = self.samples.iter().map(|(_, delta)| *delta).sum();
+            self.fling(distance / seconds);
+        }
+        self.samples.clear();
+    }
+
+    // Pans by the distance in screen pixels, as when dragging
+    pub fn pan_by(&mut self, delta: Vec2) {
+        self.camera.pan(delta);
+    }
+
+    // Zooms around the screen point, keeping within the zoom range
+    pub fn zoom_by(&mut self, anchor: Vec2, factor: f32) {
+        if !factor.is_finite() || factor <= 0.0 {
+            return;
+        }
+        let zoom = (self.camera.zoom * factor).clamp(self.min_zoom, self.max_zoom);
+        self.camera.zoom_around(anchor, zoom / self.camera.zoom);
+    }
+
+    // Glides at the velocity in screen pixels per second, as when releasing a drag
+    pub fn fling(&mut self, velocity: Vec2) {
+        // Panning moves the world the opposite way to the camera
+        self.velocity = -velocity / self.camera.zoom;
+    }
+
+    // Position the camera would be clamped to by the bounds
+    fn clamped_position(&self) -> Vec2 {
+        let Some(bounds) = self.bounds else {
+            return self.camera.position;
+        };
+        let visible = self.viewport / self.camera.zoom;
+        let clamp_axis = |position: f32, start: f32, size: f32, visible: f32| {
+            if visible >= size {
+                // Worlds smaller than the view are centered
+                start + (size - visible) / 2.0
+            } else {
+                position.clamp(start, start + size - visible)
+            }
+        };
+        vec2(
+            clamp_axis(self.camera.position.x, bounds.x, bounds.z, visible.x),
+            clamp_axis(self.camera.position.y, bounds.y, bounds.w, visible.y),
+        )
+    }
+
+    // How far past the bounds the camera is in world units, pointing back inside
+    fn overshoot(&self) -> Vec2 {
+        self.clamped_position() - self.camera.position
+    }
+
+    // Advances flings and springs back from the edges. Returns whether the camera is still
+    // moving
+    pub fn update(&mut self, now: Instant) -> bool {
+        let delta = self
+            .last_update
+            .map_or(Duration::ZERO, |last| now.saturating_duration_since(last))
+            .min(MAX_STEP)
+            .as_secs_f32();
+        self.last_update = Some(now);
+        // Gestures move the camera directly
+        if !self.touches.is_empty() || self.scrolling {
+            return true;
+        }
+
+        // Axes past the edge spring back while the others glide to a stop
+        let target = self.clamped_position();
+        let (sprung, sprung_velocity) =
+            self.spring
+                .step(self.camera.position, self.velocity, target, delta);
+        let zoom = self.camera.zoom;
+        for axis in 0..2 {
+            if target[axis] != self.camera.position[axis] {
+                self.camera.position[axis] = sprung[axis];
+                self.velocity[axis] = sprung_velocity[axis];
+                // Close enough to stop, in screen pixels
+                if (target[axis] - sprung[axis]).abs() * zoom < 0.5
+                    && sprung_velocity[axis].abs() * zoom < MIN_FLING_SPEED
+                {
+                    self.camera.position[axis] = target[axis];
+                    self.velocity[axis] = 0.0;
+                }
+            } else {
+                self.camera.position[axis] += self.velocity[axis] * delta;
+                self.velocity[axis] *= (-self.friction * delta).exp();
+            }
+        }
+        if self.velocity.length() * zoom < MIN_FLING_SPEED && self.overshoot() == Vec2::ZERO {
+            self.velocity = Vec2::ZERO;
+        }
+        self.is_moving()
+    }
+
+    // Updates the camera to the current time and asks the window for another frame while it
+    // is still moving
+    pub fn drive(&mut self, window: &Window) -> bool {
+        let moving = self.update(Instant::now());
+        if moving {
+            window.request_redraw();
+        }
+        moving
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_flings_slow_down_and_spring_back_inside_bounds() {
+        let mut controller = CameraController::new(Camera::default())
+            .with_viewport(vec2(100.0, 100.0))
+            .with_bounds(Vec4::new(0.0, 0.0, 1000.0, 1000.0));
+
+        let start = Instant::now();
+        controller.update(start);
+        controller.fling(vec2(-500.0, 0.0));
+        let mut now = start;
+        for _ in 0..200 {
+            now += Duration::from_millis(16);
+            controller.update(now);
+        }
+        assert!(!controller.is_moving());
+        let glided = controller.camera().position.x;
+        assert!(glided > 50.0 && glided < 200.0);
+
+        // Flinging past the top left edge comes back to it
+        controller.fling(vec2(2000.0, 2000.0));
+        for _ in 0..200 {
+            now += Duration::from_millis(16);
+            controller.update(now);
+        }
+        assert!(!controller.is_moving());
+        assert_eq!(controller.camera().position, Vec2::ZERO);
+    }
+}
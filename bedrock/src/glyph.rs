@@ -1,57 +1,99 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
 
 use etagere::{size2, AllocId, AtlasAllocator};
 use glam::{vec2, Vec2, Vec4};
 use ordered_float::OrderedFloat;
 use shader::{InstancedGlyph, ShaderConstants};
 use swash::{
-    scale::{Render, ScaleContext, Source, StrikeWith},
     shape::{cluster::Glyph, ShapeContext},
-    zeno::{Format, Placement, Vector},
+    zeno::Placement,
     CacheKey, FontRef, GlyphId,
 };
 use wgpu::*;
 
 use crate::{
-    font::Font,
+    buffer::GrowableBuffer,
+    culling::{glyph_bounds, intersection, intersects, text_visible, visible_rect},
+    font::{system_text, Font},
+    placeholder::{estimated_text_bounds, MissingContent},
+    raster::{default_rasterizer, GlyphRasterizer},
     renderer::{Drawable, Resources},
-    scene::{Layer, Text},
+    scene::{FontFeature, Layer, ResolvedTextStyle, Text, TextDirection, TextLog},
+    shaper::shape_bidi,
     ATLAS_SIZE,
 };
 
+// Order of the color channels within each of the display's pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SubpixelOrder {
+    #[default]
+    Rgb,
+    Bgr,
+    // Always use grayscale antialiasing
+    Off,
+}
+
+// Corrections applied to glyph coverage so that text keeps a consistent weight regardless of
+// its color, similar to the contrast and gamma settings of DirectWrite and Skia. Values around
+// a contrast of 0.5 and a gamma of 1.8 work well with srgb surfaces.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextRendering {
+    // Thickens antialiased edges of dark text. 0 disables it
+    pub contrast: f32,
+    // Raises the coverage of dark text and lowers the coverage of light text. 1 disables it
+    pub gamma: f32,
+}
+
+impl Default for TextRendering {
+    fn default() -> Self {
+        Self {
+            contrast: 0.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+// Shaped lines of text logs kept between frames. Logs often show lines only once, so unlike
+// texts their shaping is kept in a ring buffer rather than forever
+const LOG_LINE_CACHE_SIZE: usize = 4096;
+
 pub struct GlyphState {
-    buffer: Buffer,
+    buffer: GrowableBuffer<InstancedGlyph>,
     atlas_texture: Texture,
     bind_group_layout: BindGroupLayout,
     bind_group: BindGroup,
     render_pipeline: Option<RenderPipeline>,
+    subpixel_order: SubpixelOrder,
+    text_rendering: TextRendering,
 
-    scale_context: ScaleContext,
+    rasterizer: Box<dyn GlyphRasterizer>,
     shaping_context: ShapeContext,
     glyph_lookup: HashMap<GlyphKey, (Placement, AllocId)>,
-    shaped_text_lookup: HashMap<ShapeKey, Vec<Glyph>>,
+    // Glyphs of each line of a text
+    shaped_text_lookup: HashMap<ShapeKey, Vec<Vec<Glyph>>>,
+    shaped_log_lines: HashMap<ShapeKey, Vec<Glyph>>,
+    // Keys of the shaped log lines, oldest first
+    shaped_log_order: VecDeque<ShapeKey>,
     atlas_allocator: AtlasAllocator,
+    // Bounds of texts whose font couldn't be loaded during the last draw
+    missing: Vec<MissingContent>,
 }
 
 impl GlyphState {
-    fn prepare_glyph<'a, 'b: 'a>(
-        &'b mut self,
+    fn prepare_glyph(
+        &mut self,
         queue: &Queue,
         font_name: &str,
-        font_ref: FontRef<'a>,
+        font: &Font,
         glyph: swash::GlyphId,
         bottom_left: Vec2,
         size: f32,
         color: Vec4,
+        subpixel: bool,
     ) -> Option<InstancedGlyph> {
-        // Create a font scaler for the given font and size
-        let mut scaler = self
-            .scale_context
-            .builder(font_ref)
-            .size(size)
-            .hint(true)
-            .build();
-
         let glyph_key = GlyphKey::new(font_name, glyph, size, bottom_left);
 
         // Get or find atlas allocation
@@ -59,30 +101,35 @@ impl GlyphState {
             if let Some((placement, alloc_id)) = self.glyph_lookup.get(&glyph_key) {
                 (*placement, self.atlas_allocator.get(*alloc_id))
             } else {
-                let image = Render::new(&[
-                    Source::ColorOutline(0),
-                    Source::ColorBitmap(StrikeWith::BestFit),
-                    Source::Outline,
-                ])
-                // Select a subpixel format
-                .format(Format::Subpixel)
-                // Apply the fractional offset
-                .offset(glyph_key.quantized_offset())
-                // Render the image
-                .render(&mut scaler, glyph)
-                .expect("Could not render glyph into an image");
+                let image =
+                    self.rasterizer
+                        .rasterize(font, glyph, size, glyph_key.quantized_offset())?;
 
                 if image.placement.width == 0 || image.placement.height == 0 {
                     return None;
                 }
 
-                let allocation = self
-                    .atlas_allocator
-                    .allocate(size2(
-                        image.placement.width as i32,
-                        image.placement.height as i32,
-                    ))
-                    .expect("Could not allocate glyph to atlas");
+                let Some(allocation) = self.atlas_allocator.allocate(size2(
+                    image.placement.width as i32,
+                    image.placement.height as i32,
+                )) else {
+                    tracing::warn!(
+                        font_name,
+                        glyph,
+                        size,
+                        cached = self.glyph_lookup.len(),
+                        "Glyph atlas is full. Skipping glyph"
+                    );
+                    return None;
+                };
+                tracing::trace!(
+                    font_name,
+                    glyph,
+                    width = image.placement.width,
+                    height = image.placement.height,
+                    cached = self.glyph_lookup.len() + 1,
+                    "Added glyph to atlas"
+                );
 
                 self.glyph_lookup
                     .insert(glyph_key, (image.placement, allocation.id));
@@ -126,71 +173,214 @@ impl GlyphState {
                 allocation_rectangle.min.y as f32,
             ),
             atlas_size: vec2(placement.width as f32, placement.height as f32),
-            _padding: Default::default(),
+            subpixel: match (subpixel, self.subpixel_order) {
+                (false, _) | (_, SubpixelOrder::Off) => 0,
+                (true, SubpixelOrder::Rgb) => 1,
+                (true, SubpixelOrder::Bgr) => 2,
+            },
+            _padding: 0,
             color,
+            contrast: self.text_rendering.contrast,
+            gamma: self.text_rendering.gamma.max(0.01),
+            ..Default::default()
         })
     }
 
-    pub fn shape_and_rasterize_text<'a, 'b: 'a>(
+    pub fn shape_and_rasterize_text(
         &mut self,
         queue: &Queue,
         font_name: &str,
-        font_ref: FontRef<'a>,
+        font: &Font,
+        font_ref: FontRef,
         text: &Text,
+        style: &ResolvedTextStyle,
+        visible: Vec4,
     ) -> Vec<InstancedGlyph> {
-        let key = ShapeKey::new(Arc::from(text.text.as_str()), font_ref, text.size.into());
-
-        let mut shaper = self
-            .shaping_context
-            .builder(font_ref)
-            .size(text.size)
-            .build();
-        let glyphs = self
+        let key = ShapeKey::new(
+            Arc::from(text.text.as_str()),
+            font_ref,
+            text.size,
+            text.direction,
+            style.features.clone(),
+        );
+
+        let shaping_context = &mut self.shaping_context;
+        let lines = self
             .shaped_text_lookup
             .entry(key)
             .or_insert_with(|| {
-                shaper.add_str(&text.text);
-
-                let mut glyphs = Vec::new();
-
-                shaper.shape_with(|cluster| {
-                    for glyph in cluster.glyphs {
-                        glyphs.push(*glyph);
-                    }
-                });
-
-                glyphs
+                text.text
+                    .split('\n')
+                    .map(|line| {
+                        let mut glyphs = shape_bidi(
+                            shaping_context,
+                            font_ref,
+                            line,
+                            text.size,
+                            text.direction,
+                            &style.features,
+                        );
+                        if system_text() {
+                            font.apply_system_metrics(text.size, &mut glyphs);
+                        }
+                        glyphs
+                    })
+                    .collect::<Vec<_>>()
             })
             .clone();
 
-        let mut current_x = 0.;
-        glyphs
-            .iter()
-            .filter_map(|glyph| {
-                let instance = self.prepare_glyph(
-                    queue,
-                    font_name,
+        let metrics = font_ref.metrics(&[]).scale(text.size);
+        let line_advance = style.line_height.map_or(
+            metrics.ascent + metrics.descent + metrics.leading,
+            |line_height| line_height * text.size,
+        );
+
+        // Vertical text stacks glyphs downwards from the first baseline, centering each one in
+        // a column an em wide. Its lines are columns which continue to the left
+        let glyph_metrics = font_ref.glyph_metrics(&[]).scale(text.size);
+        let mut instances = Vec::new();
+        for (line_index, glyphs) in lines.iter().enumerate() {
+            let mut pen = if text.vertical {
+                vec2(-line_advance * line_index as f32, 0.0)
+            } else {
+                vec2(0.0, line_advance * line_index as f32)
+            };
+            for glyph in glyphs {
+                let offset = if text.vertical {
+                    vec2((text.size - glyph.advance) / 2.0, 0.0)
+                } else {
+                    Vec2::ZERO
+                };
+                let origin = text.bottom_left + pen + offset + vec2(glyph.x, -glyph.y);
+
+                // Pens only move right or down, so once a glyph starts past the visible area
+                // the rest of the line can't be seen either
+                let bounds = glyph_bounds(origin, glyph.advance, text.size);
+                let past_end = if text.vertical {
+                    bounds.y > visible.y + visible.w
+                } else {
+                    bounds.x > visible.x + visible.z
+                };
+                if past_end && style.letter_spacing >= 0.0 {
+                    break;
+                }
+
+                let instance = if intersects(bounds, visible) {
+                    self.prepare_glyph(
+                        queue,
+                        font_name,
+                        font,
+                        glyph.id,
+                        origin,
+                        text.size,
+                        style.color,
+                        style.subpixel,
+                    )
+                } else {
+                    None
+                };
+                if text.vertical {
+                    let advance = glyph_metrics.advance_height(glyph.id);
+                    pen.y += if advance > 0.0 { advance } else { text.size };
+                    pen.y += style.letter_spacing;
+                } else if glyph.advance > 0.0 {
+                    // Marks attached to the previous glyph don't advance, so they aren't spaced
+                    pen.x += glyph.advance + style.letter_spacing;
+                }
+                instances.extend(instance.map(|instance| InstancedGlyph {
+                    depth: text.depth,
+                    ..instance
+                }));
+            }
+        }
+        instances
+    }
+
+    // Only shapes and rasterizes the lines scrolled into the log's area
+    fn shape_and_rasterize_log(
+        &mut self,
+        queue: &Queue,
+        font_name: &str,
+        font: &Font,
+        font_ref: FontRef,
+        log: &TextLog,
+        subpixel: bool,
+        visible: Vec4,
+    ) -> Vec<InstancedGlyph> {
+        let visible = intersection(visible, log.bounds());
+        let metrics = font_ref.metrics(&[]).scale(log.font_size);
+        let line_advance = log.line_height.map_or(
+            metrics.ascent + metrics.descent + metrics.leading,
+            |line_height| line_height * log.font_size,
+        );
+
+        let mut instances = Vec::new();
+        for index in log.visible_lines(line_advance, metrics.ascent, metrics.descent) {
+            let Some(line) = log.lines.get(index) else {
+                continue;
+            };
+            let key = ShapeKey::new(
+                line.clone(),
+                font_ref,
+                log.font_size,
+                TextDirection::Auto,
+                Vec::new(),
+            );
+            if !self.shaped_log_lines.contains_key(&key) {
+                let mut glyphs = shape_bidi(
+                    &mut self.shaping_context,
                     font_ref,
-                    glyph.id,
-                    text.bottom_left + vec2(current_x + glyph.x, -glyph.y),
-                    text.size,
-                    text.color,
+                    line,
+                    log.font_size,
+                    TextDirection::Auto,
+                    &[],
                 );
-                current_x += glyph.advance;
-                instance
-            })
-            .collect()
+                if system_text() {
+                    font.apply_system_metrics(log.font_size, &mut glyphs);
+                }
+                if self.shaped_log_order.len() == LOG_LINE_CACHE_SIZE {
+                    if let Some(oldest) = self.shaped_log_order.pop_front() {
+                        self.shaped_log_lines.remove(&oldest);
+                    }
+                }
+                self.shaped_log_order.push_back(key.clone());
+                self.shaped_log_lines.insert(key.clone(), glyphs);
+            }
+            let glyphs = self.shaped_log_lines[&key].clone();
+
+            let baseline = vec2(
+                log.top_left.x,
+                log.baseline(index, line_advance, metrics.descent),
+            );
+            let mut pen = 0.0;
+            for glyph in glyphs.iter() {
+                let origin = baseline + vec2(pen + glyph.x, -glyph.y);
+                let bounds = glyph_bounds(origin, glyph.advance, log.font_size);
+                if bounds.x > visible.x + visible.z {
+                    break;
+                }
+                if intersects(bounds, visible) {
+                    instances.extend(self.prepare_glyph(
+                        queue,
+                        font_name,
+                        font,
+                        glyph.id,
+                        origin,
+                        log.font_size,
+                        log.color,
+                        subpixel,
+                    ));
+                }
+                pen += glyph.advance;
+            }
+        }
+        instances
     }
 }
 
 impl Drawable for GlyphState {
     fn new(Resources { device, .. }: &Resources) -> Self {
-        let buffer = device.create_buffer(&BufferDescriptor {
-            label: Some("Glyph buffer"),
-            size: std::mem::size_of::<InstancedGlyph>() as u64 * 100000,
-            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        let buffer = GrowableBuffer::new(device, "Glyph buffer", BufferUsages::STORAGE);
 
         let atlas_texture = device.create_texture(&TextureDescriptor {
             label: Some("Glyph atlas texture descriptor"),
@@ -215,7 +405,7 @@ impl Drawable for GlyphState {
                     visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
                     ty: BindingType::Buffer {
                         ty: BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: false,
+                        has_dynamic_offset: true,
                         min_binding_size: None,
                     },
                     count: None,
@@ -233,22 +423,7 @@ impl Drawable for GlyphState {
             ],
         });
 
-        let atlas_texture_view = atlas_texture.create_view(&TextureViewDescriptor::default());
-
-        let bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: Some("Glyph bind group"),
-            layout: &bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: buffer.as_entire_binding(),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::TextureView(&atlas_texture_view),
-                },
-            ],
-        });
+        let bind_group = create_bind_group(device, &bind_group_layout, &buffer, &atlas_texture);
 
         Self {
             buffer,
@@ -256,12 +431,17 @@ impl Drawable for GlyphState {
             bind_group_layout,
             bind_group,
             render_pipeline: None,
+            subpixel_order: SubpixelOrder::default(),
+            text_rendering: TextRendering::default(),
 
-            scale_context: ScaleContext::new(),
+            rasterizer: default_rasterizer(),
             shaping_context: ShapeContext::new(),
             atlas_allocator: AtlasAllocator::new(size2(ATLAS_SIZE.x as i32, ATLAS_SIZE.y as i32)),
             glyph_lookup: HashMap::new(),
             shaped_text_lookup: HashMap::new(),
+            shaped_log_lines: HashMap::new(),
+            shaped_log_order: VecDeque::new(),
+            missing: Vec::new(),
         }
     }
 
@@ -270,11 +450,59 @@ impl Drawable for GlyphState {
         Resources {
             device,
             shader,
+            shader_features,
             surface_resources_manager,
             universal_bind_group_layout,
+            subpixel_order,
+            text_rendering,
             ..
         }: &Resources,
     ) {
+        let Some(format) = surface_resources_manager.format() else {
+            return;
+        };
+        self.subpixel_order = *subpixel_order;
+        self.text_rendering = *text_rendering;
+
+        // With dual source blending the fragment shader hands the blend unit a coverage per
+        // channel. Otherwise the spirv fragment shader composites over a copy of the surface.
+        let dual_source_module = device
+            .features()
+            .contains(Features::DUAL_SOURCE_BLENDING)
+            .then(|| {
+                let source = format!(
+                    "const SRGB: bool = {};\nconst SUBPIXEL_TEXT: bool = {};\n{}",
+                    shader_features.srgb,
+                    shader_features.subpixel_text,
+                    include_str!("glyph.wgsl")
+                );
+                device.create_shader_module(ShaderModuleDescriptor {
+                    label: Some("Glyph dual source fragment shader"),
+                    source: ShaderSource::Wgsl(source.into()),
+                })
+            });
+        let (fragment_module, fragment_entry_point, blend) = match dual_source_module.as_ref() {
+            Some(module) => (
+                module,
+                "fragment",
+                BlendState {
+                    color: BlendComponent {
+                        src_factor: BlendFactor::One,
+                        dst_factor: BlendFactor::OneMinusSrc1,
+                        operation: BlendOperation::Add,
+                    },
+                    alpha: BlendComponent {
+                        src_factor: BlendFactor::One,
+                        dst_factor: BlendFactor::OneMinusSrcAlpha,
+                        operation: BlendOperation::Add,
+                    },
+                },
+            ),
+            // The fallback shader composites glyphs over a copy of the frame itself, so blending
+            // again would count the backdrop twice wherever it isn't opaque
+            None => (shader, shader::GLYPH_FRAGMENT, BlendState::REPLACE),
+        };
+
         let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("Glyph Pipeline Layout"),
             bind_group_layouts: &[&self.bind_group_layout, &universal_bind_group_layout],
@@ -289,15 +517,15 @@ impl Drawable for GlyphState {
             layout: Some(&render_pipeline_layout),
             vertex: VertexState {
                 module: &shader,
-                entry_point: "glyph::glyph_vertex",
+                entry_point: shader::GLYPH_VERTEX,
                 buffers: &[],
             },
             fragment: Some(FragmentState {
-                module: &shader,
-                entry_point: "glyph::glyph_fragment",
+                module: fragment_module,
+                entry_point: fragment_entry_point,
                 targets: &[Some(ColorTargetState {
-                    format: surface_resources_manager.format(),
-                    blend: Some(BlendState::ALPHA_BLENDING),
+                    format,
+                    blend: Some(blend),
                     write_mask: ColorWrites::ALL,
                 })],
             }),
@@ -310,46 +538,136 @@ impl Drawable for GlyphState {
                 polygon_mode: PolygonMode::Fill,
                 conservative: false,
             },
-            depth_stencil: None,
+            depth_stencil: Some(surface_resources_manager.depth_stencil_state()),
             multisample: MultisampleState {
-                count: 4,
+                count: surface_resources_manager.sample_count(),
                 ..Default::default()
             },
             multiview: None,
         }));
     }
 
+    fn needs_draw(&self, layer: &Layer) -> bool {
+        !layer.texts.is_empty() || !layer.text_logs.is_empty()
+    }
+
+    fn instance_count(&self) -> u64 {
+        self.buffer.len()
+    }
+
+    fn missing_content(&mut self) -> Vec<MissingContent> {
+        std::mem::take(&mut self.missing)
+    }
+
     fn draw<'b, 'a: 'b>(
         &'a mut self,
-        queue: &Queue,
+        Resources {
+            device,
+            queue,
+            surface_resources_manager,
+            ..
+        }: &'a Resources,
         render_pass: &mut RenderPass<'b>,
         constants: ShaderConstants,
-        universal_bind_group: &'a BindGroup,
         layer: &Layer,
     ) {
-        let font = Font::from_name(&layer.font_name).unwrap();
-        let font_ref = font.as_ref().unwrap();
-
-        let glyphs: Vec<_> = layer
+        let visible = visible_rect(layer, constants.surface_size);
+
+        let font = Font::from_name(&layer.font_name);
+        let Some((font, font_ref)) = font.as_ref().and_then(|font| Some((font, font.as_ref()?)))
+        else {
+            self.missing.extend(
+                layer
+                    .texts
+                    .iter()
+                    .filter(|text| text_visible(text, visible))
+                    .map(|text| MissingContent::new(estimated_text_bounds(text))),
+            );
+            self.missing.extend(
+                layer
+                    .text_logs
+                    .iter()
+                    .filter(|log| intersects(log.bounds(), visible))
+                    .map(|log| MissingContent::new(log.bounds())),
+            );
+            return;
+        };
+
+        let mut glyphs: Vec<_> = layer
             .texts
             .iter()
+            .filter(|text| text_visible(text, visible))
             .map(|text| {
-                self.shape_and_rasterize_text(queue, &layer.font_name, font_ref, &text)
-                    .into_iter()
+                let style = layer.text_style.resolve(text);
+                self.shape_and_rasterize_text(
+                    queue,
+                    &layer.font_name,
+                    font,
+                    font_ref,
+                    text,
+                    &style,
+                    visible,
+                )
+                .into_iter()
             })
             .flatten()
             .collect();
+        let subpixel = layer.text_style.subpixel.unwrap_or(true);
+        for log in layer.text_logs.iter() {
+            if intersects(log.bounds(), visible) {
+                glyphs.extend(self.shape_and_rasterize_log(
+                    queue,
+                    &layer.font_name,
+                    font,
+                    font_ref,
+                    log,
+                    subpixel,
+                    visible,
+                ));
+            }
+        }
 
         render_pass.set_pipeline(self.render_pipeline.as_ref().unwrap());
         render_pass.set_push_constants(ShaderStages::all(), 0, bytemuck::cast_slice(&[constants]));
 
-        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&glyphs[..]));
-        render_pass.set_bind_group(0, &self.bind_group, &[]);
-        render_pass.set_bind_group(1, &universal_bind_group, &[]);
-        render_pass.draw(0..6, 0..glyphs.len() as u32);
+        if self.buffer.upload(device, queue, &glyphs) {
+            self.bind_group = create_bind_group(
+                device,
+                &self.bind_group_layout,
+                &self.buffer,
+                &self.atlas_texture,
+            );
+        }
+        render_pass.set_bind_group(1, surface_resources_manager.universal_bind_group(), &[]);
+        self.buffer
+            .draw_chunks(render_pass, 0, &self.bind_group, 0..6);
     }
 }
 
+fn create_bind_group(
+    device: &Device,
+    bind_group_layout: &BindGroupLayout,
+    buffer: &GrowableBuffer<InstancedGlyph>,
+    atlas_texture: &Texture,
+) -> BindGroup {
+    let atlas_texture_view = atlas_texture.create_view(&TextureViewDescriptor::default());
+
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Glyph bind group"),
+        layout: bind_group_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: buffer.binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::TextureView(&atlas_texture_view),
+            },
+        ],
+    })
+}
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 enum SubpixelOffset {
     Zero,
@@ -407,8 +725,8 @@ impl GlyphKey {
         }
     }
 
-    fn quantized_offset(&self) -> Vector {
-        Vector::new(self.x_offset.to_f32(), self.y_offset.to_f32())
+    fn quantized_offset(&self) -> Vec2 {
+        vec2(self.x_offset.to_f32(), self.y_offset.to_f32())
     }
 }
 
@@ -417,16 +735,26 @@ struct ShapeKey {
     text: Arc<str>,
     size: OrderedFloat<f32>,
     font_cache_key: CacheKey,
+    direction: TextDirection,
+    features: Vec<FontFeature>,
 }
 
 impl ShapeKey {
-    fn new(text: Arc<str>, font_ref: FontRef, size: f32) -> Self {
+    fn new(
+        text: Arc<str>,
+        font_ref: FontRef,
+        size: f32,
+        direction: TextDirection,
+        features: Vec<FontFeature>,
+    ) -> Self {
         let font_cache_key = font_ref.key;
         let size = size.into();
         Self {
             text,
             size,
             font_cache_key,
+            direction,
+            features,
         }
     }
 }
@@ -0,0 +1,64 @@
+// Glyph fragment shader used when the adapter supports dual source blending. Outputs the
+// premultiplied text color along with a per channel coverage which the blend unit uses in
+// place of a single alpha, so subpixel text composites correctly over anything drawn before
+// it. Paired with the spirv glyph vertex shader, so the instance layout and interface
+// locations must match shader/src/glyph.rs. SRGB and SUBPIXEL_TEXT are prepended by the host
+// to match the loaded shader permutation.
+
+struct InstancedGlyph {
+    bottom_left: vec2<f32>,
+    atlas_top_left: vec2<f32>,
+    atlas_size: vec2<f32>,
+    subpixel: u32,
+    _padding: u32,
+    color: vec4<f32>,
+    contrast: f32,
+    gamma: f32,
+    depth: f32,
+    __padding: f32,
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(0) @second_blend_source coverage: vec4<f32>,
+}
+
+@group(0) @binding(0) var<storage, read> glyphs: array<InstancedGlyph>;
+@group(0) @binding(1) var atlas: texture_2d<f32>;
+@group(1) @binding(1) var atlas_sampler: sampler;
+
+// Matches correct_coverage in shader/src/glyph.rs
+fn correct_coverage(coverage: vec3<f32>, color: vec3<f32>, contrast: f32, gamma: f32) -> vec3<f32> {
+    let luminance = dot(color, vec3<f32>(0.30, 0.59, 0.11));
+    let adjusted_contrast = contrast * clamp(4.0 * (0.75 - luminance), 0.0, 1.0);
+    let enhanced = coverage * (adjusted_contrast + 1.0) / (coverage * adjusted_contrast + 1.0);
+    let exponent = 1.0 / gamma + (gamma - 1.0 / gamma) * luminance;
+    return pow(enhanced, vec3<f32>(exponent));
+}
+
+@fragment
+fn fragment(
+    @location(0) @interpolate(flat) instance_index: i32,
+    @location(1) atlas_position: vec2<f32>,
+) -> FragmentOutput {
+    let glyph = glyphs[instance_index];
+    var mask = textureSampleLevel(atlas, atlas_sampler, atlas_position, 0.0).rgb;
+    if !SUBPIXEL_TEXT || glyph.subpixel == 0u {
+        mask = vec3<f32>((mask.r + mask.g + mask.b) / 3.0);
+    } else if glyph.subpixel == 2u {
+        mask = mask.bgr;
+    }
+    mask = correct_coverage(mask, glyph.color.rgb, glyph.contrast, glyph.gamma);
+
+    var color = glyph.color;
+    if SRGB {
+        color = color * color;
+    }
+
+    let coverage = mask * color.a;
+    let alpha = max(coverage.r, max(coverage.g, coverage.b));
+    var output: FragmentOutput;
+    output.color = vec4<f32>(color.rgb * coverage, alpha);
+    output.coverage = vec4<f32>(coverage, alpha);
+    return output;
+}
@@ -0,0 +1,276 @@
+use glam::{vec2, Vec2};
+use lyon::path::{iterator::PathIterator, PathEvent};
+use shader::{InstancedPathShape, PathSegment, ShaderConstants};
+use wgpu::*;
+
+use crate::{
+    buffer::GrowableBuffer,
+    culling::{intersects, visible_rect},
+    path::build_lyon_path,
+    renderer::{Drawable, Resources},
+    scene::{FillRule, Layer, Path as ScenePath},
+};
+
+// Maximum distance between a curve and the line segments approximating it. Matches lyon's
+// default tessellation tolerance.
+const FLATTENING_TOLERANCE: f32 = 0.1;
+
+// Alternative to PathState which evaluates fills and strokes in the fragment shader. The cpu
+// only flattens curves into line segments, which is much cheaper than tessellating, so
+// animated paths don't stall the frame. Enabled with `Renderer::with_gpu_paths`.
+pub struct GpuPathState {
+    shape_buffer: GrowableBuffer<InstancedPathShape>,
+    segment_buffer: GrowableBuffer<PathSegment>,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    render_pipeline: Option<RenderPipeline>,
+    enabled: bool,
+}
+
+impl Drawable for GpuPathState {
+    fn new(
+        Resources {
+            device, gpu_paths, ..
+        }: &Resources,
+    ) -> Self {
+        let shape_buffer =
+            GrowableBuffer::new(device, "Gpu path shape buffer", BufferUsages::STORAGE);
+        let segment_buffer =
+            GrowableBuffer::new(device, "Gpu path segment buffer", BufferUsages::STORAGE);
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Gpu path bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: true,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Shapes index into the segments directly, so a single chunk starting at the
+                // beginning of the buffer is bound without an offset
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group =
+            create_bind_group(device, &bind_group_layout, &shape_buffer, &segment_buffer);
+
+        Self {
+            shape_buffer,
+            segment_buffer,
+            bind_group_layout,
+            bind_group,
+            render_pipeline: None,
+            enabled: *gpu_paths,
+        }
+    }
+
+    fn surface_updated(
+        &mut self,
+        Resources {
+            device,
+            shader,
+            surface_resources_manager,
+            gpu_paths,
+            ..
+        }: &Resources,
+    ) {
+        let Some(format) = surface_resources_manager.format() else {
+            return;
+        };
+        self.enabled = *gpu_paths;
+
+        let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Gpu Path Pipeline Layout"),
+            bind_group_layouts: &[&self.bind_group_layout],
+            push_constant_ranges: &[PushConstantRange {
+                stages: ShaderStages::all(),
+                range: 0..std::mem::size_of::<ShaderConstants>() as u32,
+            }],
+        });
+
+        self.render_pipeline = Some(device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Gpu Path Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: VertexState {
+                module: shader,
+                entry_point: shader::GPU_PATH_VERTEX,
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: shader,
+                entry_point: shader::GPU_PATH_FRAGMENT,
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(surface_resources_manager.depth_stencil_state()),
+            multisample: MultisampleState {
+                count: surface_resources_manager.sample_count(),
+                ..Default::default()
+            },
+            multiview: None,
+        }));
+    }
+
+    fn needs_draw(&self, layer: &Layer) -> bool {
+        self.enabled && !layer.paths.is_empty()
+    }
+
+    fn instance_count(&self) -> u64 {
+        self.shape_buffer.len()
+    }
+
+    fn draw<'b, 'a: 'b>(
+        &'a mut self,
+        Resources { device, queue, .. }: &'a Resources,
+        render_pass: &mut RenderPass<'b>,
+        constants: ShaderConstants,
+        layer: &Layer,
+    ) {
+        let mut shapes = Vec::new();
+        let mut segments = Vec::new();
+        let visible = visible_rect(layer, constants.surface_size);
+        let max_segments = self.segment_buffer.chunk_len().min(u32::MAX as u64) as usize;
+        for path in layer
+            .paths
+            .iter()
+            .filter(|path| intersects(path.bounds(), visible))
+        {
+            let segment_start = segments.len();
+            if let Some(shape) = flatten_path(path, &mut segments) {
+                // Segments past the bound chunk can't be read by the shader
+                if segments.len() > max_segments {
+                    segments.truncate(segment_start);
+                    tracing::warn!(
+                        max_segments,
+                        "Gpu paths exceeded the maximum storage binding size. Dropping paths"
+                    );
+                    break;
+                }
+                shapes.push(shape);
+            }
+        }
+
+        render_pass.set_pipeline(self.render_pipeline.as_ref().unwrap());
+        render_pass.set_push_constants(ShaderStages::all(), 0, bytemuck::cast_slice(&[constants]));
+
+        let shapes_recreated = self.shape_buffer.upload(device, queue, &shapes);
+        let segments_recreated = self.segment_buffer.upload(device, queue, &segments);
+        if shapes_recreated || segments_recreated {
+            self.bind_group = create_bind_group(
+                device,
+                &self.bind_group_layout,
+                &self.shape_buffer,
+                &self.segment_buffer,
+            );
+        }
+        self.shape_buffer
+            .draw_chunks(render_pass, 0, &self.bind_group, 0..6);
+    }
+}
+
+// Appends the path's flattened outline to the segments and returns the shape referencing
+// them. Paths with neither a fill nor a stroke produce nothing.
+fn flatten_path(path: &ScenePath, segments: &mut Vec<PathSegment>) -> Option<InstancedPathShape> {
+    if path.fill.is_none() && path.stroke.is_none() {
+        return None;
+    }
+
+    let segment_start = segments.len();
+    let mut min = Vec2::splat(f32::MAX);
+    let mut max = Vec2::splat(f32::MIN);
+    let mut push_segment = |from: Vec2, to: Vec2, fill_only: bool| {
+        min = min.min(from).min(to);
+        max = max.max(from).max(to);
+        segments.push(PathSegment {
+            from,
+            to,
+            fill_only: fill_only as u32,
+            _padding: 0,
+        });
+    };
+
+    for event in build_lyon_path(path).iter().flattened(FLATTENING_TOLERANCE) {
+        match event {
+            PathEvent::Line { from, to } => {
+                push_segment(vec2(from.x, from.y), vec2(to.x, to.y), false)
+            }
+            // Fills are always closed, so open subpaths still need their closing edge to bound
+            // the fill. It isn't stroked
+            PathEvent::End { last, first, close } if close || path.fill.is_some() => {
+                push_segment(vec2(last.x, last.y), vec2(first.x, first.y), !close)
+            }
+            _ => {}
+        }
+    }
+
+    let segment_count = segments.len() - segment_start;
+    if segment_count == 0 {
+        return None;
+    }
+
+    let (stroke_width, stroke) = path.stroke.unwrap_or_default();
+    Some(InstancedPathShape {
+        fill: path.fill.unwrap_or_default(),
+        stroke,
+        top_left: min,
+        size: max - min,
+        stroke_width,
+        segment_start: segment_start as u32,
+        segment_count: segment_count as u32,
+        depth: path.depth,
+        fill_rule: match path.fill_rule {
+            FillRule::EvenOdd => 0,
+            FillRule::NonZero => 1,
+        },
+        _padding: [0; 3],
+    })
+}
+
+fn create_bind_group(
+    device: &Device,
+    bind_group_layout: &BindGroupLayout,
+    shape_buffer: &GrowableBuffer<InstancedPathShape>,
+    segment_buffer: &GrowableBuffer<PathSegment>,
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Gpu path bind group"),
+        layout: bind_group_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: shape_buffer.binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: segment_buffer.binding(),
+            },
+        ],
+    })
+}
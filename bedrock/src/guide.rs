@@ -0,0 +1,194 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use bytemuck::{Pod, Zeroable};
+use shader::ShaderConstants;
+use wgpu::*;
+
+use crate::{
+    buffer::GrowableBuffer,
+    renderer::{Drawable, Resources},
+    scene::{Guide, GuideKind, Layer},
+    Scene,
+};
+
+// Matches Guide in guide.wgsl
+#[derive(Copy, Clone, Default, Pod, Zeroable)]
+#[repr(C)]
+struct InstancedGuide {
+    kind: u32,
+    width: f32,
+    dash: f32,
+    radius: f32,
+    position: [f32; 2],
+    _padding: [f32; 2],
+    color: [f32; 4],
+}
+
+impl InstancedGuide {
+    fn new(guide: &Guide) -> Self {
+        let (kind, position, radius) = match guide.kind {
+            GuideKind::Horizontal(y) => (0, [0.0, y], 0.0),
+            GuideKind::Vertical(x) => (1, [x, 0.0], 0.0),
+            GuideKind::Snap { position, radius } => (2, position.to_array(), radius),
+        };
+        Self {
+            kind,
+            width: guide.width,
+            dash: guide.dash.unwrap_or(0.0),
+            radius,
+            position,
+            color: guide.color.to_array(),
+            ..Default::default()
+        }
+    }
+}
+
+// Scene with its guides in a layer of their own above every other layer
+pub(crate) fn with_guide_layer(scene: &Scene) -> Cow<'_, Scene> {
+    if scene.guides.is_empty() {
+        return Cow::Borrowed(scene);
+    }
+    let mut scene = scene.clone();
+    scene.layers.push(Arc::new(Layer {
+        background_color: None,
+        guides: scene.guides.iter().cloned().collect(),
+        ..Default::default()
+    }));
+    Cow::Owned(scene)
+}
+
+pub struct GuideState {
+    buffer: GrowableBuffer<InstancedGuide>,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    pipeline_layout: PipelineLayout,
+    render_pipeline: Option<RenderPipeline>,
+}
+
+impl Drawable for GuideState {
+    fn new(Resources { device, .. }: &Resources) -> Self {
+        let buffer = GrowableBuffer::new(device, "Guide buffer", BufferUsages::STORAGE);
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Guide bind group layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: true,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = create_bind_group(device, &bind_group_layout, &buffer);
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Guide Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[PushConstantRange {
+                stages: ShaderStages::all(),
+                range: 0..std::mem::size_of::<ShaderConstants>() as u32,
+            }],
+        });
+
+        Self {
+            buffer,
+            bind_group_layout,
+            bind_group,
+            pipeline_layout,
+            render_pipeline: None,
+        }
+    }
+
+    fn surface_updated(
+        &mut self,
+        Resources {
+            device,
+            surface_resources_manager,
+            ..
+        }: &Resources,
+    ) {
+        let Some(format) = surface_resources_manager.format() else {
+            return;
+        };
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Guide shader"),
+            source: ShaderSource::Wgsl(include_str!("guide.wgsl").into()),
+        });
+        self.render_pipeline = Some(device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Guide Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "vertex",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: "fragment",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: Some(surface_resources_manager.depth_stencil_state()),
+            multisample: MultisampleState {
+                count: surface_resources_manager.sample_count(),
+                ..Default::default()
+            },
+            multiview: None,
+        }));
+    }
+
+    fn needs_draw(&self, layer: &Layer) -> bool {
+        !layer.guides.is_empty()
+    }
+
+    fn instance_count(&self) -> u64 {
+        self.buffer.len()
+    }
+
+    fn draw<'b, 'a: 'b>(
+        &'a mut self,
+        Resources { device, queue, .. }: &'a Resources,
+        render_pass: &mut RenderPass<'b>,
+        constants: ShaderConstants,
+        layer: &Layer,
+    ) {
+        // Guides span the whole surface, so there's nothing worth culling
+        let guides: Vec<_> = layer.guides.iter().map(InstancedGuide::new).collect();
+
+        render_pass.set_pipeline(self.render_pipeline.as_ref().unwrap());
+        render_pass.set_push_constants(ShaderStages::all(), 0, bytemuck::cast_slice(&[constants]));
+
+        if self.buffer.upload(device, queue, &guides) {
+            self.bind_group = create_bind_group(device, &self.bind_group_layout, &self.buffer);
+        }
+        self.buffer
+            .draw_chunks(render_pass, 0, &self.bind_group, 0..6);
+    }
+}
+
+fn create_bind_group(
+    device: &Device,
+    bind_group_layout: &BindGroupLayout,
+    buffer: &GrowableBuffer<InstancedGuide>,
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Guide bind group"),
+        layout: bind_group_layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: buffer.binding(),
+        }],
+    })
+}
@@ -0,0 +1,119 @@
+// Draws guide lines across the whole surface and snap crosses from their distance to each pixel
+
+struct Guide {
+    // 0 for horizontal lines, 1 for vertical lines and 2 for snap crosses
+    kind: u32,
+    width: f32,
+    // Zero for solid guides
+    dash: f32,
+    radius: f32,
+    // Center of snap crosses. Lines only use the component across them
+    position: vec2<f32>,
+    padding: vec2<f32>,
+    // Straight alpha
+    color: vec4<f32>,
+}
+
+// Matches ShaderConstants in the shader crate
+struct Constants {
+    surface_size: vec2<f32>,
+    atlas_size: vec2<f32>,
+    clip: vec4<f32>,
+    backdrop: vec4<f32>,
+}
+
+@group(0) @binding(0) var<storage, read> guides: array<Guide>;
+
+var<push_constant> constants: Constants;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) pixel: vec2<f32>,
+    @location(1) @interpolate(flat) instance: u32,
+}
+
+// Odd widths are centered on pixel centers and even widths on pixel edges, so lines cover
+// whole pixels
+fn snapped(coordinate: f32, width: f32) -> f32 {
+    if u32(round(width)) % 2u == 1u {
+        return floor(coordinate) + 0.5;
+    }
+    return round(coordinate);
+}
+
+fn guide_center(guide: Guide) -> vec2<f32> {
+    return vec2<f32>(snapped(guide.position.x, guide.width), snapped(guide.position.y, guide.width));
+}
+
+@vertex
+fn vertex(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    let guide = guides[instance_index];
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+    );
+    let center = guide_center(guide);
+    // Padded by a pixel so the antialiased edge isn't cut off
+    let extent = guide.width / 2.0 + 1.0;
+    var top_left: vec2<f32>;
+    var size: vec2<f32>;
+    if guide.kind == 0u {
+        top_left = vec2<f32>(0.0, center.y - extent);
+        size = vec2<f32>(constants.surface_size.x, extent * 2.0);
+    } else if guide.kind == 1u {
+        top_left = vec2<f32>(center.x - extent, 0.0);
+        size = vec2<f32>(extent * 2.0, constants.surface_size.y);
+    } else {
+        let half_size = guide.radius + extent;
+        top_left = center - half_size;
+        size = vec2<f32>(half_size * 2.0);
+    }
+    let pixel = top_left + corners[vertex_index] * size;
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(
+        pixel.x / constants.surface_size.x * 2.0 - 1.0,
+        1.0 - pixel.y / constants.surface_size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.pixel = pixel;
+    out.instance = instance_index;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let guide = guides[in.instance];
+    let offset = in.pixel - guide_center(guide);
+
+    var edge_distance: f32;
+    // Position along the guide, used for dashes
+    var along: f32;
+    if guide.kind == 0u {
+        edge_distance = abs(offset.y);
+        along = in.pixel.x;
+    } else if guide.kind == 1u {
+        edge_distance = abs(offset.x);
+        along = in.pixel.y;
+    } else {
+        // Distance to the nearer diagonal, cut off at the radius
+        edge_distance = min(abs(offset.x - offset.y), abs(offset.x + offset.y)) / sqrt(2.0);
+        let overshoot = max(abs(offset.x), abs(offset.y)) - guide.radius;
+        edge_distance = max(edge_distance, overshoot + guide.width / 2.0);
+        along = 0.0;
+    }
+
+    var alpha = guide.color.a * clamp(guide.width / 2.0 - edge_distance + 0.5, 0.0, 1.0);
+    if guide.dash > 0.0 && fract(along / (guide.dash * 2.0)) >= 0.5 {
+        alpha = 0.0;
+    }
+    return vec4<f32>(guide.color.rgb * alpha, alpha);
+}
@@ -0,0 +1,286 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use wgpu::*;
+
+pub const HISTOGRAM_BINS: usize = 256;
+
+// Pixel counts of a frame by value, for exposure displays and for dimming overlays over bright
+// content. Values are binned as they are encoded on screen, so bin 255 holds full intensity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub luminance: [u32; HISTOGRAM_BINS],
+    pub red: [u32; HISTOGRAM_BINS],
+    pub green: [u32; HISTOGRAM_BINS],
+    pub blue: [u32; HISTOGRAM_BINS],
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Histogram {
+    fn from_bins(bins: &[u32], width: u32, height: u32) -> Self {
+        let channel = |index: usize| {
+            let mut channel = [0; HISTOGRAM_BINS];
+            channel.copy_from_slice(&bins[index * HISTOGRAM_BINS..(index + 1) * HISTOGRAM_BINS]);
+            channel
+        };
+        Self {
+            luminance: channel(0),
+            red: channel(1),
+            green: channel(2),
+            blue: channel(3),
+            width,
+            height,
+        }
+    }
+
+    pub fn pixel_count(&self) -> u64 {
+        self.luminance.iter().map(|count| *count as u64).sum()
+    }
+
+    // Average luminance from 0 to 1
+    pub fn mean_luminance(&self) -> f32 {
+        let pixels = self.pixel_count();
+        if pixels == 0 {
+            return 0.0;
+        }
+        let total: u64 = self
+            .luminance
+            .iter()
+            .enumerate()
+            .map(|(bin, count)| bin as u64 * *count as u64)
+            .sum();
+        total as f32 / pixels as f32 / (HISTOGRAM_BINS - 1) as f32
+    }
+
+    // Luminance from 0 to 1 which the given fraction of pixels are at or below, such as 0.5 for
+    // the median
+    pub fn luminance_percentile(&self, fraction: f32) -> f32 {
+        let target = (self.pixel_count() as f64 * fraction.clamp(0.0, 1.0) as f64).ceil() as u64;
+        let mut seen = 0;
+        for (bin, count) in self.luminance.iter().enumerate() {
+            seen += *count as u64;
+            if seen >= target.max(1) {
+                return bin as f32 / (HISTOGRAM_BINS - 1) as f32;
+            }
+        }
+        1.0
+    }
+}
+
+// Bins each frame in a compute pass and reads the counts back without waiting on the gpu. The
+// result of a frame becomes available during a later frame, and frames drawn while a readback
+// is still in flight aren't counted.
+pub(crate) struct HistogramPass {
+    bind_group_layout: BindGroupLayout,
+    pipeline: ComputePipeline,
+    bins: Buffer,
+    readback: Buffer,
+    // Copy of the frame the pass reads from, recreated when the frame size changes
+    source: Option<Texture>,
+    // Size of the frame being read back
+    in_flight: Option<(u32, u32)>,
+    mapped: Arc<AtomicBool>,
+    pub(crate) latest: Option<Histogram>,
+}
+
+impl HistogramPass {
+    pub(crate) fn new(device: &Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Histogram bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Histogram shader"),
+            source: ShaderSource::Wgsl(include_str!("histogram.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Histogram Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[PushConstantRange {
+                stages: ShaderStages::COMPUTE,
+                range: 0..std::mem::size_of::<u32>() as u32,
+            }],
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Histogram Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: "count",
+        });
+
+        let size = (HISTOGRAM_BINS * 4 * std::mem::size_of::<u32>()) as BufferAddress;
+        let bins = device.create_buffer(&BufferDescriptor {
+            label: Some("Histogram bins"),
+            size,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&BufferDescriptor {
+            label: Some("Histogram readback buffer"),
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            bind_group_layout,
+            pipeline,
+            bins,
+            readback,
+            source: None,
+            in_flight: None,
+            mapped: Arc::new(AtomicBool::new(false)),
+            latest: None,
+        }
+    }
+
+    pub(crate) fn count(&mut self, device: &Device, queue: &Queue, target: &Texture) {
+        self.receive(device);
+        if self.in_flight.is_some() {
+            return;
+        }
+        let Some(encode) = needs_encoding(target.format()) else {
+            return;
+        };
+
+        if self.source.as_ref().map_or(true, |source| {
+            source.size() != target.size() || source.format() != target.format()
+        }) {
+            self.source = Some(device.create_texture(&TextureDescriptor {
+                size: target.size(),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: target.format(),
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                label: Some("Histogram source"),
+                view_formats: &[],
+            }));
+        }
+        let source = self.source.as_ref().unwrap();
+        let source_view = source.create_view(&Default::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Histogram bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&source_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: self.bins.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Histogram Encoder"),
+        });
+        encoder.copy_texture_to_texture(
+            target.as_image_copy(),
+            source.as_image_copy(),
+            target.size(),
+        );
+        encoder.clear_buffer(&self.bins, 0, None);
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("Histogram Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.set_push_constants(0, bytemuck::cast_slice(&[encode as u32]));
+            compute_pass.dispatch_workgroups(
+                (target.width() + 15) / 16,
+                (target.height() + 15) / 16,
+                1,
+            );
+        }
+        encoder.copy_buffer_to_buffer(&self.bins, 0, &self.readback, 0, self.bins.size());
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let mapped = self.mapped.clone();
+        self.readback
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                mapped.store(result.is_ok(), Ordering::Release)
+            });
+        self.in_flight = Some((target.width(), target.height()));
+    }
+
+    // Picks up the counts of an earlier frame if the gpu has finished with them
+    fn receive(&mut self, device: &Device) {
+        let Some((width, height)) = self.in_flight else {
+            return;
+        };
+        device.poll(Maintain::Poll);
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return;
+        }
+        {
+            let data = self.readback.slice(..).get_mapped_range();
+            self.latest = Some(Histogram::from_bins(
+                bytemuck::cast_slice(&data),
+                width,
+                height,
+            ));
+        }
+        self.readback.unmap();
+        self.in_flight = None;
+    }
+}
+
+// Whether the format stores linear values which are encoded to srgb on display, or None for
+// formats the pass doesn't read
+fn needs_encoding(format: TextureFormat) -> Option<bool> {
+    match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Bgra8Unorm => Some(false),
+        TextureFormat::Rgba8UnormSrgb
+        | TextureFormat::Bgra8UnormSrgb
+        | TextureFormat::Rgba16Float => Some(true),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_luminance_statistics() {
+        let mut bins = vec![0; HISTOGRAM_BINS * 4];
+        bins[0] = 3;
+        bins[255] = 1;
+        let histogram = Histogram::from_bins(&bins, 2, 2);
+        assert_eq!(histogram.pixel_count(), 4);
+        assert_eq!(histogram.mean_luminance(), 0.25);
+        assert_eq!(histogram.luminance_percentile(0.5), 0.0);
+        assert_eq!(histogram.luminance_percentile(1.0), 1.0);
+    }
+}
@@ -0,0 +1,42 @@
+// Counts the frame's pixels into 256 bins each for luminance, red, green and blue, stored one
+// after another in that order. Values are binned as they are encoded on screen.
+
+struct Constants {
+    // 1 when the frame stores linear values which need encoding to srgb first
+    encode: u32,
+}
+
+@group(0) @binding(0) var frame: texture_2d<f32>;
+@group(0) @binding(1) var<storage, read_write> bins: array<atomic<u32>, 1024>;
+
+var<push_constant> constants: Constants;
+
+fn srgb_encode(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+fn bin(value: f32) -> u32 {
+    return u32(clamp(value, 0.0, 1.0) * 255.0 + 0.5);
+}
+
+@compute @workgroup_size(16, 16)
+fn count(@builtin(global_invocation_id) id: vec3<u32>) {
+    let frame_size = textureDimensions(frame);
+    if id.x >= frame_size.x || id.y >= frame_size.y {
+        return;
+    }
+
+    var color = textureLoad(frame, id.xy, 0).rgb;
+    if constants.encode == 1u {
+        color = srgb_encode(max(color, vec3<f32>(0.0)));
+    }
+    // Rec. 709 luma
+    let luma = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+
+    atomicAdd(&bins[bin(luma)], 1u);
+    atomicAdd(&bins[256u + bin(color.r)], 1u);
+    atomicAdd(&bins[512u + bin(color.g)], 1u);
+    atomicAdd(&bins[768u + bin(color.b)], 1u);
+}
@@ -0,0 +1,150 @@
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
+
+use glam::{Vec2, Vec4};
+use smallvec::{smallvec, SmallVec};
+use wgpu::{Extent3d, Texture};
+
+use crate::{
+    redraw::hash_layer,
+    scene::{CacheHint, ColorAdjustments, Layer, Sprite, SpriteFilter},
+    Scene,
+};
+
+struct CachedLayer {
+    // Layer last drawn into the cache, kept so unchanged layers are compared by pointer
+    layer: Arc<Layer>,
+    hash: u64,
+}
+
+// Keeps static layers in render targets named after their index in the scene. Layers are
+// redrawn into their target when they change, and replaced in the frame by a sprite showing
+// the target.
+#[derive(Default)]
+pub(crate) struct LayerCache {
+    entries: HashMap<usize, CachedLayer>,
+}
+
+impl LayerCache {
+    pub(crate) fn apply<'a>(
+        &mut self,
+        scene: &'a Scene,
+        render_targets: &mut HashMap<String, Texture>,
+        size: Extent3d,
+    ) -> Cow<'a, Scene> {
+        // Forget layers which are gone or no longer static
+        self.entries.retain(|index, _| {
+            let keep = scene
+                .layers
+                .get(*index)
+                .is_some_and(|layer| cacheable(layer));
+            if !keep {
+                render_targets.remove(&target_name(*index));
+            }
+            keep
+        });
+        if !scene.layers.iter().any(|layer| cacheable(layer)) {
+            return Cow::Borrowed(scene);
+        }
+
+        let mut layers = SmallVec::new();
+        for (index, layer) in scene.layers.iter().enumerate() {
+            if !cacheable(layer) {
+                layers.push(layer.clone());
+                continue;
+            }
+
+            let name = target_name(index);
+            let drawn = render_targets
+                .get(&name)
+                .is_some_and(|texture| texture.size() == size);
+            let unchanged = match self.entries.get(&index) {
+                Some(entry) if Arc::ptr_eq(&entry.layer, layer) => true,
+                Some(entry) => entry.hash == hash_layer(layer),
+                None => false,
+            };
+            if !drawn || !unchanged {
+                let mut redrawn = (**layer).clone();
+                redrawn.render_target = Some(name.clone());
+                layers.push(Arc::new(redrawn));
+            }
+            // Hashes are only recomputed when the layer isn't the same one as last time
+            let hash = match self.entries.get(&index) {
+                Some(entry) if unchanged => entry.hash,
+                _ => hash_layer(layer),
+            };
+            self.entries.insert(
+                index,
+                CachedLayer {
+                    layer: layer.clone(),
+                    hash,
+                },
+            );
+
+            layers.push(Arc::new(cached_layer(name, size)));
+        }
+
+        let mut cached = scene.clone();
+        cached.layers = layers;
+        Cow::Owned(cached)
+    }
+}
+
+fn cacheable(layer: &Layer) -> bool {
+    layer.cache_hint == CacheHint::Static
+        && layer.render_target.is_none()
+        && layer.background_blur_radius == 0.0
+}
+
+fn target_name(index: usize) -> String {
+    format!("layer cache {}", index)
+}
+
+// Layer drawing the cached texture over the whole frame
+fn cached_layer(name: String, size: Extent3d) -> Layer {
+    Layer {
+        background_color: None,
+        sprites: smallvec![Sprite {
+            top_left: Vec2::ZERO,
+            size: Vec2::new(size.width as f32, size.height as f32),
+            color: Vec4::ONE,
+            texture: name,
+            adjustments: ColorAdjustments::default(),
+            alpha_cutoff: None,
+            filter: SpriteFilter::Nearest,
+            lod_bias: 0.0,
+            depth: 0.0,
+        }],
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+    use crate::scene::Quad;
+
+    #[test]
+    fn test_static_layers_are_drawn_once() {
+        let size = Extent3d {
+            width: 100,
+            height: 100,
+            depth_or_array_layers: 1,
+        };
+        let mut scene = Scene::new();
+        scene.layer_mut().set_cache_hint(CacheHint::Static);
+        scene.add_quad(Quad::new(vec2(0.0, 0.0), vec2(10.0, 10.0), Vec4::ONE));
+
+        let mut cache = LayerCache::default();
+        let mut render_targets = HashMap::new();
+        let applied = cache.apply(&scene, &mut render_targets, size);
+        // Without a texture yet, the layer is drawn into its target and then shown
+        assert_eq!(applied.layers.len(), 2);
+        assert_eq!(
+            applied.layers[0].render_target.as_deref(),
+            Some("layer cache 0")
+        );
+        assert_eq!(applied.layers[1].sprites[0].texture, "layer cache 0");
+    }
+}
@@ -1,19 +1,97 @@
+mod animation;
+#[cfg(feature = "app")]
+mod app;
+mod blur;
+mod buffer;
+mod clip;
+mod color_space;
+mod composite;
+mod contrast;
+mod culling;
+mod dither;
+mod easing;
+#[cfg(feature = "egui")]
+mod egui_overlay;
+mod ellipse;
+mod extension;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 mod font;
+mod frame_hook;
+mod frame_limiter;
+mod gesture;
 mod glyph;
+mod gpu_path;
+mod guide;
+mod histogram;
+mod layer_cache;
+mod lottie;
+mod mesh;
+mod mirror;
+mod particle;
 mod path;
+mod pixel_probe;
+mod placeholder;
+mod polyline;
+mod post_effect;
+mod profiler;
+mod pyramid;
 mod quad;
+mod raster;
+mod recording;
+mod redraw;
+mod redundancy;
+mod registry;
+mod remote;
 mod renderer;
 mod resources;
 mod scene;
+mod shader_abi;
+mod shader_quad;
 mod shaper;
 mod sprite;
 mod surface_wrapper;
+#[cfg(feature = "svg")]
+mod svg;
+mod transition;
+mod visual_diff;
+mod watchdog;
 
 use glam::{vec2, Vec2};
 use rust_embed::*;
 
-pub use renderer::Renderer;
+pub use animation::{AnimationTarget, Animator, KeyframeError, ScenePlayer, Tween, TweenValue};
+#[cfg(feature = "app")]
+pub use app::{run, App, FrameContext, FramePacing};
+pub use color_space::ColorSpace;
+pub use contrast::{
+    contrast_ratio, relative_luminance, BackdropContrast, TextTone, MINIMUM_TEXT_CONTRAST,
+};
+pub use easing::{Easing, Spring};
+#[cfg(feature = "egui")]
+pub use egui_overlay::{EguiFrame, EguiState};
+pub use extension::{ExtensionKind, ShaderExtension, ShaderExtensionError};
+pub use frame_hook::{FrameHook, FrameHookContext};
+pub use frame_limiter::{FrameLimitStrategy, FrameStats};
+pub use gesture::CameraController;
+pub use glyph::{SubpixelOrder, TextRendering};
+pub use histogram::{Histogram, HISTOGRAM_BINS};
+pub use lottie::{LottieAnimation, LottieError};
+pub use placeholder::Placeholder;
+pub use profiler::{ProfileEntry, ProfileReport};
+pub use recording::{RecordedFrame, Recording, RecordingError};
+pub use registry::{Registry, RegistryError};
+pub use remote::{
+    read_scene, write_scene, RemoteClient, RemoteError, RemoteServer, MAX_MESSAGE_SIZE,
+};
+pub use renderer::{Drawable, RenderError, Renderer, Resources};
 pub use scene::*;
+pub use shader::ShaderFeatures;
+pub use shader_abi::ShaderAbiError;
+#[cfg(feature = "svg")]
+pub use svg::SvgError;
+pub use transition::TransitionKind;
+pub use visual_diff::{diff_scenes, ChangeKind, PrimitiveChange, VisualDiff};
 
 pub const ATLAS_SIZE: Vec2 = vec2(1024., 1024.);
 
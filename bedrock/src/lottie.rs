@@ -0,0 +1,1144 @@
+use std::{collections::HashMap, fmt, io::Read, sync::Arc, time::Duration};
+
+use glam::{vec2, Affine2, Vec2, Vec4};
+use lyon::path::{iterator::PathIterator, PathEvent};
+use serde::Deserialize;
+use serde_json::Value;
+use smallvec::smallvec;
+
+use crate::{
+    easing::cubic_bezier,
+    path::build_lyon_path,
+    scene::{FillRule, Layer, Path, PathCommand, Scene},
+};
+
+// Distance between a curve and the line segments approximating it when measuring paths for
+// trimming
+const TRIM_TOLERANCE: f32 = 0.25;
+// Distance along the tangent to place control points for a quarter ellipse
+const KAPPA: f32 = 0.552_284_8;
+// Lottie layer type for shape layers. Null layers (3) are only used as transform parents and
+// every other type is unsupported
+const SHAPE_LAYER: u32 = 4;
+
+#[derive(Debug)]
+pub enum LottieError {
+    Json(serde_json::Error),
+    InvalidProperty(String),
+}
+
+impl fmt::Display for LottieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(error) => write!(f, "invalid lottie json: {}", error),
+            Self::InvalidProperty(property) => write!(f, "invalid lottie property: {}", property),
+        }
+    }
+}
+
+impl std::error::Error for LottieError {}
+
+impl From<serde_json::Error> for LottieError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
+
+// A Lottie (bodymovin) animation which can be sampled into a scene at any time. Supports shape
+// layers with groups, paths, rectangles, ellipses, fills, strokes, trim paths, and parented
+// transforms. Gradients, masks, mattes, precomps, images, and text are skipped.
+pub struct LottieAnimation {
+    frame_rate: f32,
+    in_point: f32,
+    out_point: f32,
+    size: Vec2,
+    layers: Vec<AnimatedLayer>,
+}
+
+impl LottieAnimation {
+    pub fn from_json(json: &str) -> Result<Self, LottieError> {
+        Self::from_raw(serde_json::from_str(json)?)
+    }
+
+    pub fn from_reader(reader: impl Read) -> Result<Self, LottieError> {
+        Self::from_raw(serde_json::from_reader(reader)?)
+    }
+
+    fn from_raw(raw: RawAnimation) -> Result<Self, LottieError> {
+        let layers = raw
+            .layers
+            .into_iter()
+            .filter(|layer| !layer.hidden)
+            .map(AnimatedLayer::parse)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            frame_rate: raw.frame_rate.max(1.0),
+            in_point: raw.in_point,
+            out_point: raw.out_point,
+            size: vec2(raw.width, raw.height),
+            layers,
+        })
+    }
+
+    // Size of the composition in pixels
+    pub fn size(&self) -> Vec2 {
+        self.size
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f32(((self.out_point - self.in_point) / self.frame_rate).max(0.0))
+    }
+
+    // Scene containing a single transparent layer with the animation's shapes at `time` after
+    // its start. Times past the end hold the last frame, so callers wanting a loop should wrap
+    // the time by the duration.
+    pub fn scene_at(&self, time: Duration) -> Scene {
+        Scene {
+            clear_color: Vec4::ONE,
+            layers: smallvec![Arc::new(self.layer_at(time))],
+            guides: Vec::new(),
+            tracks: Vec::new(),
+            theme_colors: Vec::new(),
+        }
+    }
+
+    pub fn layer_at(&self, time: Duration) -> Layer {
+        let frame = self.in_point + time.as_secs_f32() * self.frame_rate;
+        self.layer_at_frame(frame.min(self.out_point - 1.0).max(self.in_point))
+    }
+
+    pub fn layer_at_frame(&self, frame: f32) -> Layer {
+        let mut paths = Vec::new();
+        let indices: HashMap<i64, &AnimatedLayer> = self
+            .layers
+            .iter()
+            .filter_map(|layer| layer.index.map(|index| (index, layer)))
+            .collect();
+
+        // Earlier layers are drawn on top
+        for animated_layer in self.layers.iter().rev() {
+            if animated_layer.kind != SHAPE_LAYER
+                || frame < animated_layer.in_point
+                || frame >= animated_layer.out_point
+            {
+                continue;
+            }
+
+            let (_, opacity) = animated_layer.transform.evaluate(frame);
+            if opacity <= 0.0 {
+                continue;
+            }
+            let transform = animated_layer.world_transform(frame, &indices);
+            render_shapes(
+                &animated_layer.shapes,
+                frame,
+                transform,
+                opacity,
+                &mut paths,
+            );
+        }
+
+        Layer {
+            background_color: None,
+            paths: paths.into(),
+            ..Default::default()
+        }
+    }
+}
+
+struct AnimatedLayer {
+    kind: u32,
+    index: Option<i64>,
+    parent: Option<i64>,
+    in_point: f32,
+    out_point: f32,
+    transform: AnimatedTransform,
+    shapes: Vec<Shape>,
+}
+
+impl AnimatedLayer {
+    fn parse(raw: RawLayer) -> Result<Self, LottieError> {
+        Ok(Self {
+            kind: raw.kind,
+            index: raw.index,
+            parent: raw.parent,
+            in_point: raw.in_point,
+            out_point: raw.out_point,
+            transform: AnimatedTransform::parse(&raw.transform)?,
+            shapes: parse_shapes(&raw.shapes)?,
+        })
+    }
+
+    // Layer transform combined with its parents'. Parents only contribute their transform, not
+    // their opacity
+    fn world_transform(&self, frame: f32, indices: &HashMap<i64, &AnimatedLayer>) -> Affine2 {
+        let (mut transform, _) = self.transform.evaluate(frame);
+        let mut parent = self.parent;
+        // Bound the walk in case the file contains a parenting cycle
+        for _ in 0..indices.len() {
+            let Some(parent_layer) = parent.and_then(|index| indices.get(&index)) else {
+                break;
+            };
+            transform = parent_layer.transform.evaluate(frame).0 * transform;
+            parent = parent_layer.parent;
+        }
+        transform
+    }
+}
+
+#[derive(Default)]
+struct AnimatedTransform {
+    anchor: Option<Property>,
+    position: Option<Property>,
+    scale: Option<Property>,
+    rotation: Option<Property>,
+    opacity: Option<Property>,
+}
+
+impl AnimatedTransform {
+    fn parse(raw: &RawTransform) -> Result<Self, LottieError> {
+        let parse = |property: &Option<RawProperty>| property.as_ref().map(Property::parse);
+        Ok(Self {
+            anchor: parse(&raw.anchor).transpose()?,
+            position: parse(&raw.position).transpose()?,
+            scale: parse(&raw.scale).transpose()?,
+            rotation: parse(&raw.rotation).transpose()?,
+            opacity: parse(&raw.opacity).transpose()?,
+        })
+    }
+
+    // Local transform and opacity at the given frame
+    fn evaluate(&self, frame: f32) -> (Affine2, f32) {
+        let vector = |property: &Option<Property>, default: Vec2| {
+            property
+                .as_ref()
+                .map(|property| {
+                    let value = property.value(frame);
+                    vec2(
+                        value.first().copied().unwrap_or(default.x),
+                        value.get(1).copied().unwrap_or(default.y),
+                    )
+                })
+                .unwrap_or(default)
+        };
+        let scalar = |property: &Option<Property>, default: f32| {
+            property
+                .as_ref()
+                .and_then(|property| property.value(frame).first().copied())
+                .unwrap_or(default)
+        };
+
+        let anchor = vector(&self.anchor, Vec2::ZERO);
+        let position = vector(&self.position, Vec2::ZERO);
+        let scale = vector(&self.scale, Vec2::splat(100.0)) / 100.0;
+        let rotation = scalar(&self.rotation, 0.0).to_radians();
+        let opacity = scalar(&self.opacity, 100.0) / 100.0;
+
+        let transform = Affine2::from_translation(position)
+            * Affine2::from_angle(rotation)
+            * Affine2::from_scale(scale)
+            * Affine2::from_translation(-anchor);
+        (transform, opacity.clamp(0.0, 1.0))
+    }
+}
+
+enum Shape {
+    Group {
+        items: Vec<Shape>,
+        transform: AnimatedTransform,
+    },
+    Path(Property),
+    Rect {
+        position: Property,
+        size: Property,
+        roundness: Property,
+    },
+    Ellipse {
+        position: Property,
+        size: Property,
+    },
+    Fill {
+        color: Property,
+        opacity: Property,
+        fill_rule: FillRule,
+    },
+    Stroke {
+        color: Property,
+        opacity: Property,
+        width: Property,
+    },
+    Trim {
+        start: Property,
+        end: Property,
+        offset: Property,
+    },
+}
+
+fn parse_shapes(raw_shapes: &[RawShape]) -> Result<Vec<Shape>, LottieError> {
+    let mut shapes = Vec::new();
+    for raw_shape in raw_shapes {
+        let shape = match raw_shape {
+            RawShape::Group { items, hidden } => {
+                if *hidden {
+                    continue;
+                }
+                // A group's transform is stored as its last item
+                let transform = items
+                    .iter()
+                    .find_map(|item| match item {
+                        RawShape::Transform(transform) => Some(transform),
+                        _ => None,
+                    })
+                    .map(AnimatedTransform::parse)
+                    .transpose()?
+                    .unwrap_or_default();
+                Shape::Group {
+                    items: parse_shapes(items)?,
+                    transform,
+                }
+            }
+            RawShape::Path { shape } => Shape::Path(Property::parse(shape)?),
+            RawShape::Rect {
+                position,
+                size,
+                roundness,
+            } => Shape::Rect {
+                position: Property::parse(position)?,
+                size: Property::parse(size)?,
+                roundness: Property::parse_or(roundness, 0.0)?,
+            },
+            RawShape::Ellipse { position, size } => Shape::Ellipse {
+                position: Property::parse(position)?,
+                size: Property::parse(size)?,
+            },
+            RawShape::Fill {
+                color,
+                opacity,
+                fill_rule,
+            } => Shape::Fill {
+                color: Property::parse(color)?,
+                opacity: Property::parse_or(opacity, 100.0)?,
+                fill_rule: match fill_rule {
+                    2 => FillRule::EvenOdd,
+                    _ => FillRule::NonZero,
+                },
+            },
+            RawShape::Stroke {
+                color,
+                opacity,
+                width,
+            } => Shape::Stroke {
+                color: Property::parse(color)?,
+                opacity: Property::parse_or(opacity, 100.0)?,
+                width: Property::parse(width)?,
+            },
+            RawShape::Trim { start, end, offset } => Shape::Trim {
+                start: Property::parse_or(start, 0.0)?,
+                end: Property::parse_or(end, 100.0)?,
+                offset: Property::parse_or(offset, 0.0)?,
+            },
+            RawShape::Transform(_) | RawShape::Unsupported => continue,
+        };
+        shapes.push(shape);
+    }
+    Ok(shapes)
+}
+
+// Single closed or open run of curves in composition space
+#[derive(Clone)]
+struct Contour {
+    start: Vec2,
+    commands: Vec<PathCommand>,
+    closed: bool,
+}
+
+impl Contour {
+    fn from_path(path: Path, closed: bool) -> Self {
+        Self {
+            start: path.start,
+            commands: path.commands,
+            closed,
+        }
+    }
+
+    fn transformed(mut self, transform: Affine2) -> Self {
+        let map = |point: &mut Vec2| *point = transform.transform_point2(*point);
+        map(&mut self.start);
+        for command in self.commands.iter_mut() {
+            match command {
+                PathCommand::CubicBezierTo {
+                    control1,
+                    control2,
+                    to,
+                } => {
+                    map(control1);
+                    map(control2);
+                    map(to);
+                }
+                PathCommand::QuadraticBezierTo { control, to } => {
+                    map(control);
+                    map(to);
+                }
+                PathCommand::LineTo { to } => map(to),
+                PathCommand::MoveTo { start } => map(start),
+            }
+        }
+        self
+    }
+
+    fn to_path(&self) -> Path {
+        Path {
+            fill: None,
+            stroke: None,
+            start: self.start,
+            commands: self.commands.clone(),
+            open: !self.closed,
+            fill_rule: FillRule::NonZero,
+            depth: 0.0,
+        }
+    }
+
+    // Points along the flattened contour, repeating the first point at the end when closed
+    fn flatten(&self) -> Vec<Vec2> {
+        let mut points = Vec::new();
+        for event in build_lyon_path(&self.to_path())
+            .iter()
+            .flattened(TRIM_TOLERANCE)
+        {
+            match event {
+                PathEvent::Begin { at } => points.push(vec2(at.x, at.y)),
+                PathEvent::Line { to, .. } => points.push(vec2(to.x, to.y)),
+                PathEvent::End {
+                    first, close: true, ..
+                } => points.push(vec2(first.x, first.y)),
+                _ => {}
+            }
+        }
+        points
+    }
+
+    // Keeps the portion of the contour between the start and end fractions of its length.
+    // Fractions are wrapped into [0, 1) so closed contours can be trimmed across their start
+    fn trim(&self, start: f32, end: f32) -> Vec<Contour> {
+        if end - start >= 1.0 {
+            return vec![self.clone()];
+        }
+        if end <= start {
+            return Vec::new();
+        }
+
+        let points = self.flatten();
+        let mut lengths = Vec::with_capacity(points.len());
+        let mut total = 0.0;
+        for (index, point) in points.iter().enumerate() {
+            if index > 0 {
+                total += point.distance(points[index - 1]);
+            }
+            lengths.push(total);
+        }
+        if total <= 0.0 {
+            return Vec::new();
+        }
+
+        let offset = start.floor();
+        let (start, end) = (start - offset, end - offset);
+        let mut pieces = vec![polyline_between(
+            &points,
+            &lengths,
+            start * total,
+            end.min(1.0) * total,
+        )];
+        if end > 1.0 {
+            let wrapped = polyline_between(&points, &lengths, 0.0, (end - 1.0) * total);
+            if self.closed {
+                // The wrapped portion continues on from the end of the first piece
+                pieces[0].extend(wrapped.into_iter().skip(1));
+            } else {
+                pieces.push(wrapped);
+            }
+        }
+
+        pieces
+            .into_iter()
+            .filter(|points| points.len() > 1)
+            .map(|points| Contour {
+                start: points[0],
+                commands: points[1..]
+                    .iter()
+                    .map(|point| PathCommand::LineTo { to: *point })
+                    .collect(),
+                closed: false,
+            })
+            .collect()
+    }
+}
+
+// Points of the polyline between two distances along it
+fn polyline_between(points: &[Vec2], lengths: &[f32], from: f32, to: f32) -> Vec<Vec2> {
+    let point_at = |distance: f32| {
+        let index = lengths
+            .iter()
+            .position(|length| *length >= distance)
+            .unwrap_or(lengths.len() - 1)
+            .max(1);
+        let segment_length = lengths[index] - lengths[index - 1];
+        let progress = if segment_length > 0.0 {
+            (distance - lengths[index - 1]) / segment_length
+        } else {
+            0.0
+        };
+        points[index - 1].lerp(points[index], progress.clamp(0.0, 1.0))
+    };
+
+    let mut result = vec![point_at(from)];
+    result.extend(
+        points
+            .iter()
+            .zip(lengths)
+            .filter(|(_, length)| **length > from && **length < to)
+            .map(|(point, _)| *point),
+    );
+    result.push(point_at(to));
+    result
+}
+
+// Combines contours into a single path so fills respect holes
+fn combine_contours<'a>(contours: impl Iterator<Item = &'a Contour>, open: bool) -> Option<Path> {
+    let mut path: Option<Path> = None;
+    for contour in contours {
+        match path.as_mut() {
+            None => path = Some(contour.to_path().with_open(open)),
+            Some(path) => {
+                path.commands.push(PathCommand::MoveTo {
+                    start: contour.start,
+                });
+                path.commands.extend(contour.commands.iter().cloned());
+            }
+        }
+    }
+    path
+}
+
+// Draws the shapes into `output` and returns their combined geometry so styles in enclosing
+// groups can also apply to it
+fn render_shapes(
+    shapes: &[Shape],
+    frame: f32,
+    transform: Affine2,
+    opacity: f32,
+    output: &mut Vec<Path>,
+) -> Vec<Contour> {
+    let scalar = |property: &Property, default: f32| {
+        property.value(frame).first().copied().unwrap_or(default)
+    };
+    let vector = |property: &Property| {
+        let value = property.value(frame);
+        vec2(
+            value.first().copied().unwrap_or_default(),
+            value.get(1).copied().unwrap_or_default(),
+        )
+    };
+
+    // Geometry of each shape and the paths drawn by nested groups, indexed like `shapes`
+    let mut geometry: Vec<Vec<Contour>> = Vec::with_capacity(shapes.len());
+    let mut drawn: Vec<Vec<Path>> = Vec::with_capacity(shapes.len());
+    for shape in shapes {
+        let mut group_output = Vec::new();
+        let contours = match shape {
+            Shape::Group {
+                items,
+                transform: group_transform,
+            } => {
+                let (local, local_opacity) = group_transform.evaluate(frame);
+                render_shapes(
+                    items,
+                    frame,
+                    transform * local,
+                    opacity * local_opacity,
+                    &mut group_output,
+                )
+            }
+            Shape::Path(shape) => bezier_contour(&shape.value(frame))
+                .map(|contour| contour.transformed(transform))
+                .into_iter()
+                .collect(),
+            Shape::Rect {
+                position,
+                size,
+                roundness,
+            } => {
+                let size = vector(size);
+                let rect =
+                    Path::rounded_rect(vector(position) - size / 2.0, size, scalar(roundness, 0.0));
+                vec![Contour::from_path(rect, true).transformed(transform)]
+            }
+            Shape::Ellipse { position, size } => {
+                vec![ellipse(vector(position), vector(size) / 2.0).transformed(transform)]
+            }
+            Shape::Trim { start, end, offset } => {
+                let offset = scalar(offset, 0.0) / 360.0;
+                let start = scalar(start, 0.0) / 100.0 + offset;
+                let end = scalar(end, 100.0) / 100.0 + offset;
+                let (start, end) = (start.min(end), start.max(end));
+                // Trims apply to every contour above them individually
+                for contours in geometry.iter_mut() {
+                    *contours = contours
+                        .iter()
+                        .flat_map(|contour| contour.trim(start, end))
+                        .collect();
+                }
+                Vec::new()
+            }
+            Shape::Fill { .. } | Shape::Stroke { .. } => Vec::new(),
+        };
+        geometry.push(contours);
+        drawn.push(group_output);
+    }
+
+    let color = |color: &Property, style_opacity: &Property| {
+        let value = color.value(frame);
+        let channel = |index: usize| value.get(index).copied().unwrap_or(1.0);
+        Vec4::new(
+            channel(0),
+            channel(1),
+            channel(2),
+            channel(3) * scalar(style_opacity, 100.0) / 100.0 * opacity,
+        )
+    };
+
+    // Earlier shapes are drawn on top, and styles apply to the geometry above them
+    for index in (0..shapes.len()).rev() {
+        output.append(&mut drawn[index]);
+        match &shapes[index] {
+            Shape::Fill {
+                color: fill_color,
+                opacity: fill_opacity,
+                fill_rule,
+            } => {
+                if let Some(path) = combine_contours(geometry[..index].iter().flatten(), false) {
+                    output.push(
+                        path.with_fill(color(fill_color, fill_opacity))
+                            .with_fill_rule(*fill_rule),
+                    );
+                }
+            }
+            Shape::Stroke {
+                color: stroke_color,
+                opacity: stroke_opacity,
+                width,
+            } => {
+                let color = color(stroke_color, stroke_opacity);
+                let width = scalar(width, 1.0) * transform.matrix2.determinant().abs().sqrt();
+                // Closed and open contours need separate paths since openness is per path
+                for closed in [true, false] {
+                    if let Some(path) = combine_contours(
+                        geometry[..index]
+                            .iter()
+                            .flatten()
+                            .filter(|contour| contour.closed == closed),
+                        !closed,
+                    ) {
+                        output.push(path.with_stroke((width, color)));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    geometry.into_iter().flatten().collect()
+}
+
+// Lottie bezier shapes are flattened by `parse_value` into the closed flag followed by the
+// vertex, in tangent, and out tangent of each vertex. Tangents are relative to their vertex.
+fn bezier_contour(value: &[f32]) -> Option<Contour> {
+    let (closed, vertices) = value.split_first()?;
+    let vertices: Vec<[Vec2; 3]> = vertices
+        .chunks_exact(6)
+        .map(|chunk| {
+            [
+                vec2(chunk[0], chunk[1]),
+                vec2(chunk[2], chunk[3]),
+                vec2(chunk[4], chunk[5]),
+            ]
+        })
+        .collect();
+    let closed = *closed != 0.0;
+
+    let [start, ..] = *vertices.first()?;
+    let mut commands = Vec::new();
+    let segment_count = if closed {
+        vertices.len()
+    } else {
+        vertices.len() - 1
+    };
+    for index in 0..segment_count {
+        let [from, _, out_tangent] = vertices[index];
+        let [to, in_tangent, _] = vertices[(index + 1) % vertices.len()];
+        if out_tangent == Vec2::ZERO && in_tangent == Vec2::ZERO {
+            commands.push(PathCommand::LineTo { to });
+        } else {
+            commands.push(PathCommand::CubicBezierTo {
+                control1: from + out_tangent,
+                control2: to + in_tangent,
+                to,
+            });
+        }
+    }
+
+    Some(Contour {
+        start,
+        commands,
+        closed,
+    })
+}
+
+fn ellipse(center: Vec2, radii: Vec2) -> Contour {
+    let control = radii * KAPPA;
+    let point = |x: f32, y: f32| center + vec2(x, y);
+    // Lottie ellipses start at the top and run clockwise, which matters for trimming
+    let path = Path::new(point(0.0, -radii.y))
+        .cubic_bezier_to(
+            point(control.x, -radii.y),
+            point(radii.x, -control.y),
+            point(radii.x, 0.0),
+        )
+        .cubic_bezier_to(
+            point(radii.x, control.y),
+            point(control.x, radii.y),
+            point(0.0, radii.y),
+        )
+        .cubic_bezier_to(
+            point(-control.x, radii.y),
+            point(-radii.x, control.y),
+            point(-radii.x, 0.0),
+        )
+        .cubic_bezier_to(
+            point(-radii.x, -control.y),
+            point(-control.x, -radii.y),
+            point(0.0, -radii.y),
+        );
+    Contour::from_path(path, true)
+}
+
+// An animatable value. Every value is stored as a flat list of floats so scalars, vectors,
+// colors, and bezier shapes all interpolate the same way
+enum Property {
+    Keyframes(Vec<Keyframe>),
+    // Positions with separately animated x and y dimensions
+    Split(Box<Property>, Box<Property>),
+}
+
+struct Keyframe {
+    time: f32,
+    start: Vec<f32>,
+    // Older files store the segment's end value on the keyframe instead of the next one
+    end: Option<Vec<f32>>,
+    // Cubic bezier easing control points for the segment starting at this keyframe
+    easing: (Vec2, Vec2),
+    hold: bool,
+}
+
+impl Property {
+    fn parse(raw: &RawProperty) -> Result<Self, LottieError> {
+        if raw.split {
+            if let (Some(x), Some(y)) = (&raw.x, &raw.y) {
+                return Ok(Self::Split(
+                    Box::new(Self::parse(x)?),
+                    Box::new(Self::parse(y)?),
+                ));
+            }
+        }
+
+        let value = raw
+            .value
+            .as_ref()
+            .ok_or_else(|| LottieError::InvalidProperty("missing value".to_string()))?;
+        let keyframes = match value {
+            Value::Array(items) if items.iter().any(|item| item.get("t").is_some()) => {
+                let mut keyframes: Vec<Keyframe> = Vec::with_capacity(items.len());
+                for item in items {
+                    let raw_keyframe: RawKeyframe = serde_json::from_value(item.clone())?;
+                    // The final keyframe often only has a time
+                    let start = match raw_keyframe.start.as_ref().and_then(parse_value) {
+                        Some(start) => start,
+                        None => keyframes
+                            .last()
+                            .map(|previous| {
+                                previous
+                                    .end
+                                    .clone()
+                                    .unwrap_or_else(|| previous.start.clone())
+                            })
+                            .unwrap_or_default(),
+                    };
+                    keyframes.push(Keyframe {
+                        time: raw_keyframe.time,
+                        start,
+                        end: raw_keyframe.end.as_ref().and_then(parse_value),
+                        easing: (
+                            raw_keyframe
+                                .out_tangent
+                                .as_ref()
+                                .map(RawTangent::point)
+                                .unwrap_or(Vec2::ZERO),
+                            raw_keyframe
+                                .in_tangent
+                                .as_ref()
+                                .map(RawTangent::point)
+                                .unwrap_or(Vec2::ONE),
+                        ),
+                        hold: raw_keyframe.hold != 0,
+                    });
+                }
+                keyframes
+            }
+            value => vec![Keyframe {
+                time: 0.0,
+                start: parse_value(value)
+                    .ok_or_else(|| LottieError::InvalidProperty(value.to_string()))?,
+                end: None,
+                easing: (Vec2::ZERO, Vec2::ONE),
+                hold: true,
+            }],
+        };
+        Ok(Self::Keyframes(keyframes))
+    }
+
+    fn parse_or(raw: &Option<RawProperty>, default: f32) -> Result<Self, LottieError> {
+        match raw {
+            Some(raw) => Self::parse(raw),
+            None => Ok(Self::Keyframes(vec![Keyframe {
+                time: 0.0,
+                start: vec![default],
+                end: None,
+                easing: (Vec2::ZERO, Vec2::ONE),
+                hold: true,
+            }])),
+        }
+    }
+
+    fn value(&self, frame: f32) -> Vec<f32> {
+        let keyframes = match self {
+            Self::Keyframes(keyframes) => keyframes,
+            Self::Split(x, y) => {
+                let x = x.value(frame).first().copied().unwrap_or_default();
+                let y = y.value(frame).first().copied().unwrap_or_default();
+                return vec![x, y];
+            }
+        };
+
+        let Some(index) = keyframes
+            .iter()
+            .rposition(|keyframe| keyframe.time <= frame)
+        else {
+            return keyframes
+                .first()
+                .map(|keyframe| keyframe.start.clone())
+                .unwrap_or_default();
+        };
+
+        let keyframe = &keyframes[index];
+        let Some(next) = keyframes.get(index + 1) else {
+            return keyframe.start.clone();
+        };
+        if keyframe.hold {
+            return keyframe.start.clone();
+        }
+
+        let end = keyframe.end.as_ref().unwrap_or(&next.start);
+        let progress = (frame - keyframe.time) / (next.time - keyframe.time).max(f32::EPSILON);
+        let eased = cubic_bezier(progress, keyframe.easing.0, keyframe.easing.1);
+        if end.len() != keyframe.start.len() {
+            return keyframe.start.clone();
+        }
+        keyframe
+            .start
+            .iter()
+            .zip(end)
+            .map(|(start, end)| start + (end - start) * eased)
+            .collect()
+    }
+}
+
+// Flattens a static value into floats. Bezier shapes become their closed flag followed by the
+// vertex, in tangent, and out tangent of each vertex
+fn parse_value(value: &Value) -> Option<Vec<f32>> {
+    match value {
+        Value::Number(number) => Some(vec![number.as_f64()? as f32]),
+        Value::Array(items) => match items.first() {
+            // Keyframed shapes wrap their value in an array
+            Some(Value::Object(_)) => parse_value(&items[0]),
+            _ => items
+                .iter()
+                .map(|item| item.as_f64().map(|number| number as f32))
+                .collect(),
+        },
+        Value::Object(_) => {
+            let shape: RawBezier = serde_json::from_value(value.clone()).ok()?;
+            let mut flattened = vec![if shape.closed { 1.0 } else { 0.0 }];
+            for ((vertex, in_tangent), out_tangent) in shape
+                .vertices
+                .iter()
+                .zip(shape.in_tangents.iter())
+                .zip(shape.out_tangents.iter())
+            {
+                flattened.extend_from_slice(vertex);
+                flattened.extend_from_slice(in_tangent);
+                flattened.extend_from_slice(out_tangent);
+            }
+            Some(flattened)
+        }
+        _ => None,
+    }
+}
+
+#[derive(Deserialize)]
+struct RawAnimation {
+    #[serde(rename = "fr")]
+    frame_rate: f32,
+    #[serde(rename = "ip")]
+    in_point: f32,
+    #[serde(rename = "op")]
+    out_point: f32,
+    #[serde(rename = "w")]
+    width: f32,
+    #[serde(rename = "h")]
+    height: f32,
+    #[serde(default)]
+    layers: Vec<RawLayer>,
+}
+
+#[derive(Deserialize)]
+struct RawLayer {
+    #[serde(rename = "ty")]
+    kind: u32,
+    #[serde(rename = "ind", default)]
+    index: Option<i64>,
+    #[serde(default)]
+    parent: Option<i64>,
+    #[serde(rename = "ip", default)]
+    in_point: f32,
+    #[serde(rename = "op", default = "end_of_time")]
+    out_point: f32,
+    #[serde(rename = "ks", default)]
+    transform: RawTransform,
+    #[serde(default)]
+    shapes: Vec<RawShape>,
+    #[serde(rename = "hd", default)]
+    hidden: bool,
+}
+
+fn end_of_time() -> f32 {
+    f32::MAX
+}
+
+fn nonzero() -> u32 {
+    1
+}
+
+#[derive(Deserialize, Default)]
+struct RawTransform {
+    #[serde(rename = "a", default)]
+    anchor: Option<RawProperty>,
+    #[serde(rename = "p", default)]
+    position: Option<RawProperty>,
+    #[serde(rename = "s", default)]
+    scale: Option<RawProperty>,
+    #[serde(rename = "r", default)]
+    rotation: Option<RawProperty>,
+    #[serde(rename = "o", default)]
+    opacity: Option<RawProperty>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "ty")]
+enum RawShape {
+    #[serde(rename = "gr")]
+    Group {
+        #[serde(rename = "it", default)]
+        items: Vec<RawShape>,
+        #[serde(rename = "hd", default)]
+        hidden: bool,
+    },
+    #[serde(rename = "sh")]
+    Path {
+        #[serde(rename = "ks")]
+        shape: RawProperty,
+    },
+    #[serde(rename = "rc")]
+    Rect {
+        #[serde(rename = "p")]
+        position: RawProperty,
+        #[serde(rename = "s")]
+        size: RawProperty,
+        #[serde(rename = "r", default)]
+        roundness: Option<RawProperty>,
+    },
+    #[serde(rename = "el")]
+    Ellipse {
+        #[serde(rename = "p")]
+        position: RawProperty,
+        #[serde(rename = "s")]
+        size: RawProperty,
+    },
+    #[serde(rename = "fl")]
+    Fill {
+        #[serde(rename = "c")]
+        color: RawProperty,
+        #[serde(rename = "o", default)]
+        opacity: Option<RawProperty>,
+        // 1: nonzero, 2: even odd
+        #[serde(rename = "r", default = "nonzero")]
+        fill_rule: u32,
+    },
+    #[serde(rename = "st")]
+    Stroke {
+        #[serde(rename = "c")]
+        color: RawProperty,
+        #[serde(rename = "o", default)]
+        opacity: Option<RawProperty>,
+        #[serde(rename = "w")]
+        width: RawProperty,
+    },
+    #[serde(rename = "tm")]
+    Trim {
+        #[serde(rename = "s", default)]
+        start: Option<RawProperty>,
+        #[serde(rename = "e", default)]
+        end: Option<RawProperty>,
+        #[serde(rename = "o", default)]
+        offset: Option<RawProperty>,
+    },
+    #[serde(rename = "tr")]
+    Transform(RawTransform),
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Deserialize)]
+struct RawProperty {
+    #[serde(rename = "k", default)]
+    value: Option<Value>,
+    #[serde(rename = "s", default)]
+    split: bool,
+    #[serde(default)]
+    x: Option<Box<RawProperty>>,
+    #[serde(default)]
+    y: Option<Box<RawProperty>>,
+}
+
+#[derive(Deserialize)]
+struct RawKeyframe {
+    #[serde(rename = "t")]
+    time: f32,
+    #[serde(rename = "s", default)]
+    start: Option<Value>,
+    #[serde(rename = "e", default)]
+    end: Option<Value>,
+    #[serde(rename = "i", default)]
+    in_tangent: Option<RawTangent>,
+    #[serde(rename = "o", default)]
+    out_tangent: Option<RawTangent>,
+    #[serde(rename = "h", default)]
+    hold: u8,
+}
+
+// Easing tangents store either a single value or one value per dimension. Only the first
+// dimension is used
+#[derive(Deserialize)]
+struct RawTangent {
+    x: Value,
+    y: Value,
+}
+
+impl RawTangent {
+    fn point(&self) -> Vec2 {
+        let first = |value: &Value| {
+            parse_value(value)
+                .and_then(|values| values.first().copied())
+                .unwrap_or_default()
+        };
+        vec2(first(&self.x).clamp(0.0, 1.0), first(&self.y))
+    }
+}
+
+#[derive(Deserialize)]
+struct RawBezier {
+    #[serde(rename = "c", default)]
+    closed: bool,
+    #[serde(rename = "v")]
+    vertices: Vec<[f32; 2]>,
+    #[serde(rename = "i")]
+    in_tangents: Vec<[f32; 2]>,
+    #[serde(rename = "o")]
+    out_tangents: Vec<[f32; 2]>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ANIMATION: &str = r#"{
+        "fr": 10, "ip": 0, "op": 20, "w": 100, "h": 100,
+        "layers": [{
+            "ty": 4, "ind": 1, "ip": 0, "op": 20,
+            "ks": {
+                "p": { "a": 1, "k": [
+                    { "t": 0, "s": [0, 0], "i": { "x": 1, "y": 1 }, "o": { "x": 0, "y": 0 } },
+                    { "t": 10, "s": [50, 0] }
+                ] }
+            },
+            "shapes": [{
+                "ty": "gr",
+                "it": [
+                    { "ty": "rc", "p": { "k": [10, 10] }, "s": { "k": [20, 20] }, "r": { "k": 0 } },
+                    { "ty": "fl", "c": { "k": [1, 0, 0, 1] }, "o": { "k": 50 } },
+                    { "ty": "tr" }
+                ]
+            }]
+        }]
+    }"#;
+
+    #[test]
+    fn test_scene_at() {
+        let animation = LottieAnimation::from_json(ANIMATION).unwrap();
+        assert_eq!(animation.duration(), Duration::from_secs(2));
+
+        let start = animation.layer_at(Duration::ZERO);
+        assert_eq!(start.paths.len(), 1);
+        assert_eq!(start.paths[0].fill, Some(Vec4::new(1.0, 0.0, 0.0, 0.5)));
+        assert!((start.paths[0].bounds().x - 0.0).abs() < 1e-3);
+
+        let halfway = animation.layer_at(Duration::from_millis(500));
+        assert!((halfway.paths[0].bounds().x - 25.0).abs() < 1e-3);
+
+        let end = animation.layer_at(Duration::from_secs(5));
+        assert!((end.paths[0].bounds().x - 50.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_trim() {
+        let contour = Contour::from_path(Path::new(Vec2::ZERO).line_to(vec2(100.0, 0.0)), false);
+        let trimmed = contour.trim(0.25, 0.75);
+        assert_eq!(trimmed.len(), 1);
+        assert_eq!(trimmed[0].start, vec2(25.0, 0.0));
+        assert!(matches!(
+            trimmed[0].commands.last(),
+            Some(PathCommand::LineTo { to }) if *to == vec2(75.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn test_fill_rule() {
+        let animation = LottieAnimation::from_json(ANIMATION).unwrap();
+        let layer = animation.layer_at(Duration::ZERO);
+        assert_eq!(layer.paths[0].fill_rule, FillRule::NonZero);
+
+        let even_odd = ANIMATION.replace(r#""o": { "k": 50 }"#, r#""o": { "k": 50 }, "r": 2"#);
+        let animation = LottieAnimation::from_json(&even_odd).unwrap();
+        let layer = animation.layer_at(Duration::ZERO);
+        assert_eq!(layer.paths[0].fill_rule, FillRule::EvenOdd);
+    }
+}
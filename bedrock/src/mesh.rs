@@ -0,0 +1,364 @@
+use std::{
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+    ops::Range,
+};
+
+use bytemuck::{Pod, Zeroable};
+use rust_embed::RustEmbed;
+use shader::ShaderConstants;
+use wgpu::*;
+
+use crate::{
+    buffer::GrowableBuffer,
+    culling::{intersects, visible_rect},
+    placeholder::MissingContent,
+    renderer::{Drawable, Resources},
+    scene::{Layer, Mesh},
+    sprite::decode_image,
+    surface_wrapper::SurfaceResourcesManager,
+};
+
+// Matches VertexInput in mesh.wgsl
+#[derive(Copy, Clone, Default, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct MeshGpuVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+    pub depth: f32,
+}
+
+const VERTEX_ATTRIBUTES: [VertexAttribute; 4] =
+    vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4, 3 => Float32];
+
+struct MeshDraw {
+    indices: Range<u32>,
+    base_vertex: i32,
+    texture: Option<String>,
+}
+
+// Draws meshes from one shared vertex and index buffer, with a draw call per mesh so each can
+// bind its own texture. Textures are loaded from the same assets as sprites, but each gets a
+// texture of its own rather than an atlas slot so uvs can repeat.
+pub struct MeshState<A: RustEmbed> {
+    vertices: GrowableBuffer<MeshGpuVertex>,
+    indices: GrowableBuffer<u32>,
+    bind_group_layout: BindGroupLayout,
+    pipeline_layout: PipelineLayout,
+    render_pipeline: Option<RenderPipeline>,
+    sampler: Sampler,
+    // Bound for untextured meshes
+    white: BindGroup,
+    textures: HashMap<String, (Texture, BindGroup)>,
+    // Textures which are missing or couldn't be decoded. Kept so the error is only reported once
+    failed_textures: HashSet<String>,
+    missing: Vec<MissingContent>,
+    _assets: PhantomData<fn() -> A>,
+}
+
+impl<A: RustEmbed> MeshState<A> {
+    // Uploads the texture the first time it's used. Returns false if it can't be loaded
+    fn load_texture(&mut self, device: &Device, queue: &Queue, name: &str) -> bool {
+        if self.textures.contains_key(name) {
+            return true;
+        }
+        if self.failed_textures.contains(name) {
+            return false;
+        }
+        let decoded = match A::get(name) {
+            Some(file) => decode_image(file.data.as_ref()),
+            None => Err("no such asset".to_string()),
+        };
+        let image = match decoded {
+            Ok(image) => image,
+            Err(error) => {
+                eprintln!("Could not load mesh texture {}: {}", name, error);
+                self.failed_textures.insert(name.to_string());
+                return false;
+            }
+        };
+
+        let texture = create_texture(device, queue, image.width, image.height, &image.data);
+        let bind_group =
+            create_bind_group(device, &self.bind_group_layout, &texture, &self.sampler);
+        self.textures
+            .insert(name.to_string(), (texture, bind_group));
+        true
+    }
+}
+
+impl<A: RustEmbed> Drawable for MeshState<A> {
+    fn new(Resources { device, queue, .. }: &Resources) -> Self {
+        let vertices = GrowableBuffer::new(device, "Mesh vertex buffer", BufferUsages::VERTEX);
+        let indices = GrowableBuffer::new(device, "Mesh index buffer", BufferUsages::INDEX);
+
+        let bind_group_layout = create_bind_group_layout(device);
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Mesh sampler"),
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            address_mode_w: AddressMode::Repeat,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let white_texture = create_texture(device, queue, 1, 1, &[255; 4]);
+        let white = create_bind_group(device, &bind_group_layout, &white_texture, &sampler);
+
+        let pipeline_layout = create_pipeline_layout(device, &bind_group_layout);
+
+        Self {
+            vertices,
+            indices,
+            bind_group_layout,
+            pipeline_layout,
+            render_pipeline: None,
+            sampler,
+            white,
+            textures: HashMap::new(),
+            failed_textures: HashSet::new(),
+            missing: Vec::new(),
+            _assets: PhantomData,
+        }
+    }
+
+    fn surface_updated(
+        &mut self,
+        Resources {
+            device,
+            surface_resources_manager,
+            ..
+        }: &Resources,
+    ) {
+        self.render_pipeline =
+            create_render_pipeline(device, &self.pipeline_layout, surface_resources_manager);
+    }
+
+    fn needs_draw(&self, layer: &Layer) -> bool {
+        !layer.meshes.is_empty()
+    }
+
+    fn instance_count(&self) -> u64 {
+        self.indices.len() / 3
+    }
+
+    fn missing_content(&mut self) -> Vec<MissingContent> {
+        std::mem::take(&mut self.missing)
+    }
+
+    fn draw<'b, 'a: 'b>(
+        &'a mut self,
+        Resources { device, queue, .. }: &'a Resources,
+        render_pass: &mut RenderPass<'b>,
+        constants: ShaderConstants,
+        layer: &Layer,
+    ) {
+        let visible = visible_rect(layer, constants.surface_size);
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut draws = Vec::new();
+        for mesh in layer.meshes.iter() {
+            if !mesh
+                .bounds()
+                .is_some_and(|bounds| intersects(bounds, visible))
+            {
+                continue;
+            }
+            if let Some(texture) = mesh.texture.as_ref() {
+                if !self.load_texture(device, queue, texture) {
+                    if let Some(bounds) = mesh.bounds() {
+                        self.missing.push(MissingContent::texture(bounds, texture));
+                    }
+                    continue;
+                }
+            }
+
+            let base_vertex = vertices.len() as i32;
+            let first_index = indices.len() as u32;
+            vertices.extend(mesh_vertices(mesh));
+            indices.extend(mesh.valid_indices());
+            draws.push(MeshDraw {
+                indices: first_index..indices.len() as u32,
+                base_vertex,
+                texture: mesh.texture.clone(),
+            });
+        }
+        if indices.is_empty() {
+            return;
+        }
+
+        self.vertices.upload(device, queue, &vertices);
+        self.indices.upload(device, queue, &indices);
+
+        render_pass.set_pipeline(self.render_pipeline.as_ref().unwrap());
+        render_pass.set_push_constants(ShaderStages::all(), 0, bytemuck::cast_slice(&[constants]));
+        render_pass.set_vertex_buffer(0, self.vertices.buffer().slice(..));
+        render_pass.set_index_buffer(self.indices.buffer().slice(..), IndexFormat::Uint32);
+        for draw in draws {
+            let bind_group = match draw.texture {
+                Some(texture) => &self.textures[&texture].1,
+                None => &self.white,
+            };
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw_indexed(
+                self.indices.uploaded_range(draw.indices),
+                draw.base_vertex,
+                0..1,
+            );
+        }
+    }
+}
+
+fn mesh_vertices(mesh: &Mesh) -> impl Iterator<Item = MeshGpuVertex> + '_ {
+    mesh.vertices.iter().map(|vertex| MeshGpuVertex {
+        position: vertex.position.to_array(),
+        uv: vertex.uv.to_array(),
+        color: vertex.color.to_array(),
+        depth: mesh.depth,
+    })
+}
+
+// Shared with image pyramids, which draw their tiles as textured quads
+pub(crate) fn create_bind_group_layout(device: &Device) -> BindGroupLayout {
+    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("Mesh bind group layout"),
+        entries: &[
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    })
+}
+
+pub(crate) fn create_pipeline_layout(
+    device: &Device,
+    bind_group_layout: &BindGroupLayout,
+) -> PipelineLayout {
+    device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("Mesh Pipeline Layout"),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[PushConstantRange {
+            stages: ShaderStages::all(),
+            range: 0..std::mem::size_of::<ShaderConstants>() as u32,
+        }],
+    })
+}
+
+pub(crate) fn create_render_pipeline(
+    device: &Device,
+    pipeline_layout: &PipelineLayout,
+    surface_resources_manager: &SurfaceResourcesManager,
+) -> Option<RenderPipeline> {
+    let format = surface_resources_manager.format()?;
+    let module = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Mesh shader"),
+        source: ShaderSource::Wgsl(include_str!("mesh.wgsl").into()),
+    });
+    Some(device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Mesh Pipeline"),
+        layout: Some(pipeline_layout),
+        vertex: VertexState {
+            module: &module,
+            entry_point: "vertex",
+            buffers: &[VertexBufferLayout {
+                array_stride: std::mem::size_of::<MeshGpuVertex>() as BufferAddress,
+                step_mode: VertexStepMode::Vertex,
+                attributes: &VERTEX_ATTRIBUTES,
+            }],
+        },
+        fragment: Some(FragmentState {
+            module: &module,
+            entry_point: "fragment",
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            ..Default::default()
+        },
+        depth_stencil: Some(surface_resources_manager.depth_stencil_state()),
+        multisample: MultisampleState {
+            count: surface_resources_manager.sample_count(),
+            ..Default::default()
+        },
+        multiview: None,
+    }))
+}
+
+// Premultiplied rgba8 texture without mips
+pub(crate) fn create_texture(
+    device: &Device,
+    queue: &Queue,
+    width: u32,
+    height: u32,
+    data: &[u8],
+) -> Texture {
+    let size = Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("Mesh texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::Rgba8Unorm,
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    queue.write_texture(
+        texture.as_image_copy(),
+        data,
+        ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * width),
+            rows_per_image: Some(height),
+        },
+        size,
+    );
+    texture
+}
+
+pub(crate) fn create_bind_group(
+    device: &Device,
+    bind_group_layout: &BindGroupLayout,
+    texture: &Texture,
+    sampler: &Sampler,
+) -> BindGroup {
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Mesh bind group"),
+        layout: bind_group_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&view),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
@@ -0,0 +1,49 @@
+// Draws mesh triangles with interpolated vertex colors multiplied by an optional texture.
+// Untextured meshes sample a single white texel.
+
+// Matches ShaderConstants in the shader crate
+struct Constants {
+    surface_size: vec2<f32>,
+    atlas_size: vec2<f32>,
+    clip: vec4<f32>,
+    backdrop: vec4<f32>,
+}
+
+@group(0) @binding(0) var mesh_texture: texture_2d<f32>;
+@group(0) @binding(1) var mesh_sampler: sampler;
+
+var<push_constant> constants: Constants;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) depth: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vertex(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(
+        in.position.x / constants.surface_size.x * 2.0 - 1.0,
+        1.0 - in.position.y / constants.surface_size.y * 2.0,
+        in.depth,
+        1.0,
+    );
+    out.uv = in.uv;
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Textures are premultiplied, and vertex colors are straight
+    let texel = textureSample(mesh_texture, mesh_sampler, in.uv);
+    return vec4<f32>(in.color.rgb * in.color.a, in.color.a) * texel;
+}
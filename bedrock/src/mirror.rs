@@ -0,0 +1,156 @@
+use shader::{InstancedMirror, ShaderConstants};
+use wgpu::*;
+
+use crate::{
+    buffer::GrowableBuffer,
+    culling::{intersects, visible_rect},
+    renderer::{Drawable, Resources},
+    scene::Layer,
+};
+
+pub struct MirrorState {
+    buffer: GrowableBuffer<InstancedMirror>,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    render_pipeline: Option<RenderPipeline>,
+}
+
+impl Drawable for MirrorState {
+    fn new(Resources { device, .. }: &Resources) -> Self {
+        let buffer = GrowableBuffer::new(device, "Mirror buffer", BufferUsages::STORAGE);
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Mirror bind group layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: true,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = create_bind_group(device, &bind_group_layout, &buffer);
+
+        Self {
+            buffer,
+            bind_group_layout,
+            bind_group,
+            render_pipeline: None,
+        }
+    }
+
+    fn surface_updated(
+        &mut self,
+        Resources {
+            device,
+            shader,
+            universal_bind_group_layout,
+            surface_resources_manager,
+            ..
+        }: &Resources,
+    ) {
+        let Some(format) = surface_resources_manager.format() else {
+            return;
+        };
+        let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Mirror Pipeline Layout"),
+            bind_group_layouts: &[&self.bind_group_layout, &universal_bind_group_layout],
+            push_constant_ranges: &[PushConstantRange {
+                stages: ShaderStages::all(),
+                range: 0..std::mem::size_of::<ShaderConstants>() as u32,
+            }],
+        });
+
+        self.render_pipeline = Some(device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Mirror Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: VertexState {
+                module: shader,
+                entry_point: shader::MIRROR_VERTEX,
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: shader,
+                entry_point: shader::MIRROR_FRAGMENT,
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(surface_resources_manager.depth_stencil_state()),
+            multisample: MultisampleState {
+                count: surface_resources_manager.sample_count(),
+                ..Default::default()
+            },
+            multiview: None,
+        }));
+    }
+
+    fn needs_draw(&self, layer: &Layer) -> bool {
+        !layer.mirrors.is_empty()
+    }
+
+    fn instance_count(&self) -> u64 {
+        self.buffer.len()
+    }
+
+    fn draw<'b, 'a: 'b>(
+        &'a mut self,
+        Resources {
+            device,
+            queue,
+            surface_resources_manager,
+            ..
+        }: &'a Resources,
+        render_pass: &mut RenderPass<'b>,
+        constants: ShaderConstants,
+        layer: &Layer,
+    ) {
+        let visible = visible_rect(layer, constants.surface_size);
+        let mirrors: Vec<_> = layer
+            .mirrors
+            .iter()
+            .filter(|mirror| intersects(mirror.bounds(), visible))
+            .map(|mirror| mirror.to_instanced())
+            .collect();
+
+        render_pass.set_pipeline(self.render_pipeline.as_ref().unwrap());
+        render_pass.set_push_constants(ShaderStages::all(), 0, bytemuck::cast_slice(&[constants]));
+
+        if self.buffer.upload(device, queue, &mirrors) {
+            self.bind_group = create_bind_group(device, &self.bind_group_layout, &self.buffer);
+        }
+        render_pass.set_bind_group(1, surface_resources_manager.universal_bind_group(), &[]);
+        self.buffer
+            .draw_chunks(render_pass, 0, &self.bind_group, 0..6);
+    }
+}
+
+fn create_bind_group(
+    device: &Device,
+    bind_group_layout: &BindGroupLayout,
+    buffer: &GrowableBuffer<InstancedMirror>,
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Mirror bind group"),
+        layout: bind_group_layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: buffer.binding(),
+        }],
+    })
+}
@@ -0,0 +1,348 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use bytemuck::{Pod, Zeroable};
+use glam::Vec2;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use shader::ShaderConstants;
+use wgpu::*;
+
+use crate::{
+    renderer::{Drawable, Resources},
+    scene::{Layer, ParticleEmitter},
+    Scene,
+};
+
+// Emitters missing from the scene for this long have their particles dropped
+const EMITTER_TIMEOUT: Duration = Duration::from_secs(1);
+// Longest step simulated at once, so particles don't jump after a stalled frame
+const MAX_STEP: f32 = 0.1;
+// Matches the workgroup size of simulate in particle_simulate.wgsl
+const WORKGROUP_SIZE: u32 = 64;
+
+// Matches Particle in particle.wgsl
+#[derive(Copy, Clone, Default, Pod, Zeroable)]
+#[repr(C)]
+struct Particle {
+    position: [f32; 2],
+    velocity: [f32; 2],
+    gravity: [f32; 2],
+    age: f32,
+    lifetime: f32,
+    start_color: [f32; 4],
+    end_color: [f32; 4],
+    start_size: f32,
+    end_size: f32,
+    _padding: [f32; 2],
+}
+
+impl Particle {
+    fn spawn(emitter: &ParticleEmitter, rng: &mut StdRng) -> Self {
+        let jitter = Vec2::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0));
+        Self {
+            position: emitter.position.to_array(),
+            velocity: (emitter.velocity + jitter * emitter.spread).to_array(),
+            gravity: emitter.gravity.to_array(),
+            age: 0.0,
+            lifetime: emitter.lifetime,
+            start_color: emitter.color.0.to_array(),
+            end_color: emitter.color.1.to_array(),
+            start_size: emitter.size.0,
+            end_size: emitter.size.1,
+            ..Default::default()
+        }
+    }
+}
+
+// Particles belonging to one emitter. The buffer is a ring, so once full the oldest particles
+// are replaced by new ones.
+struct EmitterState {
+    buffer: Buffer,
+    bind_group: BindGroup,
+    capacity: u32,
+    next_slot: u32,
+    // Fractional particles carried over so low rates still spawn at high frame rates
+    spawn_accumulator: f32,
+    last_update: Instant,
+    burst_done: bool,
+    rng: StdRng,
+}
+
+impl EmitterState {
+    fn new(device: &Device, layout: &BindGroupLayout, emitter: &ParticleEmitter) -> Self {
+        let capacity = emitter.max_particles.max(1);
+        // Zeroed particles have no lifetime, so the buffer starts out with nothing alive
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Particle buffer"),
+            size: (capacity as usize * std::mem::size_of::<Particle>()) as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Particle bind group"),
+            layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            buffer,
+            bind_group,
+            capacity,
+            next_slot: 0,
+            spawn_accumulator: 0.0,
+            last_update: Instant::now(),
+            burst_done: false,
+            rng: StdRng::seed_from_u64(emitter.id),
+        }
+    }
+
+    // Number of particles to spawn this step
+    fn spawn_count(&mut self, emitter: &ParticleEmitter, delta: f32) -> u32 {
+        self.spawn_accumulator += emitter.rate.max(0.0) * delta;
+        let mut count = self.spawn_accumulator.floor();
+        self.spawn_accumulator -= count;
+        if !self.burst_done {
+            count += emitter.burst as f32;
+            self.burst_done = true;
+        }
+        (count as u32).min(self.capacity)
+    }
+
+    // Writes the particles into the ring, splitting the write where it wraps around
+    fn spawn(&mut self, queue: &Queue, emitter: &ParticleEmitter, count: u32) {
+        let particles: Vec<Particle> = (0..count)
+            .map(|_| Particle::spawn(emitter, &mut self.rng))
+            .collect();
+        let mut remaining = particles.as_slice();
+        while !remaining.is_empty() {
+            let run = remaining
+                .len()
+                .min((self.capacity - self.next_slot) as usize);
+            let offset = self.next_slot as usize * std::mem::size_of::<Particle>();
+            queue.write_buffer(
+                &self.buffer,
+                offset as u64,
+                bytemuck::cast_slice(&remaining[..run]),
+            );
+            remaining = &remaining[run..];
+            self.next_slot = (self.next_slot + run as u32) % self.capacity;
+        }
+    }
+}
+
+// Simulates the scene's particle emitters in a compute pass once per frame and draws their
+// particles as instanced dots. Particle state lives on the gpu between frames, keyed by emitter
+// id, so an emitter shown by several layers is only stepped once.
+pub struct ParticleState {
+    bind_group_layout: BindGroupLayout,
+    simulate_pipeline: ComputePipeline,
+    render_pipeline_layout: PipelineLayout,
+    render_pipeline: Option<RenderPipeline>,
+    emitters: HashMap<u64, EmitterState>,
+}
+
+impl Drawable for ParticleState {
+    fn new(Resources { device, .. }: &Resources) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Particle bind group layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::COMPUTE | ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let simulate_module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Particle simulation shader"),
+            source: ShaderSource::Wgsl(include_str!("particle_simulate.wgsl").into()),
+        });
+        let simulate_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Particle simulation Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[PushConstantRange {
+                stages: ShaderStages::COMPUTE,
+                range: 0..std::mem::size_of::<[f32; 4]>() as u32,
+            }],
+        });
+        let simulate_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Particle simulation Pipeline"),
+            layout: Some(&simulate_pipeline_layout),
+            module: &simulate_module,
+            entry_point: "simulate",
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Particle Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[PushConstantRange {
+                stages: ShaderStages::all(),
+                range: 0..std::mem::size_of::<ShaderConstants>() as u32,
+            }],
+        });
+
+        Self {
+            bind_group_layout,
+            simulate_pipeline,
+            render_pipeline_layout,
+            render_pipeline: None,
+            emitters: HashMap::new(),
+        }
+    }
+
+    fn surface_updated(
+        &mut self,
+        Resources {
+            device,
+            surface_resources_manager,
+            ..
+        }: &Resources,
+    ) {
+        let Some(format) = surface_resources_manager.format() else {
+            return;
+        };
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Particle shader"),
+            source: ShaderSource::Wgsl(include_str!("particle.wgsl").into()),
+        });
+        self.render_pipeline = Some(device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Particle Pipeline"),
+            layout: Some(&self.render_pipeline_layout),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "vertex",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: "fragment",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: Some(surface_resources_manager.depth_stencil_state()),
+            multisample: MultisampleState {
+                count: surface_resources_manager.sample_count(),
+                ..Default::default()
+            },
+            multiview: None,
+        }));
+    }
+
+    fn needs_draw(&self, layer: &Layer) -> bool {
+        !layer.particle_emitters.is_empty()
+    }
+
+    fn instance_count(&self) -> u64 {
+        self.emitters
+            .values()
+            .map(|emitter| emitter.capacity as u64)
+            .sum()
+    }
+
+    fn prepare_frame(
+        &mut self,
+        Resources { device, queue, .. }: &Resources,
+        encoder: &mut CommandEncoder,
+        scene: &Scene,
+    ) {
+        // Emitters are shared across windows, so only ones missing from every scene for a while
+        // are dropped
+        let now = Instant::now();
+        self.emitters
+            .retain(|_, state| now.duration_since(state.last_update) < EMITTER_TIMEOUT);
+
+        // Emitters are updated before the pass starts, since it borrows their bind groups
+        let mut stepped = HashSet::new();
+        let mut steps = Vec::new();
+        for emitter in scene
+            .layers
+            .iter()
+            .flat_map(|layer| layer.particle_emitters.iter())
+        {
+            if !stepped.insert(emitter.id) {
+                continue;
+            }
+            let state = self
+                .emitters
+                .entry(emitter.id)
+                .and_modify(|state| {
+                    // Resizing the ring drops the particles already alive
+                    if state.capacity != emitter.max_particles.max(1) {
+                        *state = EmitterState::new(device, &self.bind_group_layout, emitter);
+                    }
+                })
+                .or_insert_with(|| EmitterState::new(device, &self.bind_group_layout, emitter));
+
+            let delta = now
+                .duration_since(state.last_update)
+                .as_secs_f32()
+                .min(MAX_STEP);
+            state.last_update = now;
+
+            // New particles are written before the step so they move on their first frame
+            let count = state.spawn_count(emitter, delta);
+            state.spawn(queue, emitter, count);
+            steps.push((emitter.id, delta));
+        }
+        if steps.is_empty() {
+            return;
+        }
+
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("Particle Simulation Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.simulate_pipeline);
+        for (id, delta) in steps {
+            let state = &self.emitters[&id];
+            let simulation = [delta, state.capacity as f32, 0.0, 0.0];
+            compute_pass.set_bind_group(0, &state.bind_group, &[]);
+            compute_pass.set_push_constants(0, bytemuck::cast_slice(&simulation));
+            compute_pass.dispatch_workgroups(
+                (state.capacity + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE,
+                1,
+                1,
+            );
+        }
+    }
+
+    // Emitters which haven't been prepared yet, such as in images rendered before the first
+    // frame, have no particles to draw
+    fn draw<'b, 'a: 'b>(
+        &'a mut self,
+        _resources: &'a Resources,
+        render_pass: &mut RenderPass<'b>,
+        constants: ShaderConstants,
+        layer: &Layer,
+    ) {
+        let Some(render_pipeline) = self.render_pipeline.as_ref() else {
+            return;
+        };
+
+        render_pass.set_pipeline(render_pipeline);
+        render_pass.set_push_constants(ShaderStages::all(), 0, bytemuck::cast_slice(&[constants]));
+        for emitter in layer.particle_emitters.iter() {
+            let Some(state) = self.emitters.get(&emitter.id) else {
+                continue;
+            };
+            render_pass.set_bind_group(0, &state.bind_group, &[]);
+            render_pass.draw(0..6, 0..state.capacity);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+// Draws living particles as antialiased dots, one instance per particle in the emitter's buffer
+
+struct Particle {
+    position: vec2<f32>,
+    velocity: vec2<f32>,
+    gravity: vec2<f32>,
+    age: f32,
+    lifetime: f32,
+    start_color: vec4<f32>,
+    end_color: vec4<f32>,
+    start_size: f32,
+    end_size: f32,
+    padding: vec2<f32>,
+}
+
+// Matches ShaderConstants in the shader crate
+struct Constants {
+    surface_size: vec2<f32>,
+    atlas_size: vec2<f32>,
+    clip: vec4<f32>,
+    backdrop: vec4<f32>,
+}
+
+@group(0) @binding(0) var<storage, read_write> particles: array<Particle>;
+
+var<push_constant> constants: Constants;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // Offset from the particle's center in pixels
+    @location(0) offset: vec2<f32>,
+    @location(1) radius: f32,
+    @location(2) color: vec4<f32>,
+}
+
+@vertex
+fn vertex(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let particle = particles[instance_index];
+    if particle.age >= particle.lifetime {
+        // Dead particles collapse to a point outside the frame
+        out.position = vec4<f32>(2.0, 2.0, 0.0, 1.0);
+        return out;
+    }
+
+    let t = particle.age / max(particle.lifetime, 0.0001);
+    let radius = mix(particle.start_size, particle.end_size, t) / 2.0;
+    // Two triangles covering the dot plus a pixel for antialiasing
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let offset = corners[vertex_index] * (radius + 1.0);
+    let pixel = particle.position + offset;
+
+    out.position = vec4<f32>(
+        pixel.x / constants.surface_size.x * 2.0 - 1.0,
+        1.0 - pixel.y / constants.surface_size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.offset = offset;
+    out.radius = radius;
+    out.color = mix(particle.start_color, particle.end_color, t);
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = clamp(in.radius - length(in.offset) + 0.5, 0.0, 1.0);
+    let alpha = in.color.a * coverage;
+    return vec4<f32>(in.color.rgb * alpha, alpha);
+}
@@ -0,0 +1,41 @@
+// Steps living particles forward. New particles are written into the buffer by the cpu, so
+// only their motion happens here.
+
+struct Particle {
+    position: vec2<f32>,
+    velocity: vec2<f32>,
+    gravity: vec2<f32>,
+    age: f32,
+    lifetime: f32,
+    start_color: vec4<f32>,
+    end_color: vec4<f32>,
+    start_size: f32,
+    end_size: f32,
+    padding: vec2<f32>,
+}
+
+struct SimulationConstants {
+    // x: seconds since the last step
+    // y: number of particles in the buffer
+    params: vec4<f32>,
+}
+
+@group(0) @binding(0) var<storage, read_write> particles: array<Particle>;
+
+var<push_constant> simulation: SimulationConstants;
+
+@compute @workgroup_size(64)
+fn simulate(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= u32(simulation.params.y) {
+        return;
+    }
+    var particle = particles[id.x];
+    if particle.age >= particle.lifetime {
+        return;
+    }
+    let delta = simulation.params.x;
+    particle.velocity += particle.gravity * delta;
+    particle.position += particle.velocity * delta;
+    particle.age += delta;
+    particles[id.x] = particle;
+}
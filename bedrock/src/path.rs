@@ -1,3 +1,9 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::Hasher,
+    ops::Range,
+};
+
 use glam::vec2;
 use lyon::{
     geom::point,
@@ -5,42 +11,134 @@ use lyon::{
         BuffersBuilder, FillOptions, FillTessellator, FillVertex, StrokeOptions, StrokeTessellator,
         StrokeVertex, VertexBuffers,
     },
-    path::Path,
+    path::{FillRule as LyonFillRule, Path},
 };
+use rayon::prelude::*;
 use shader::{PathVertex, ShaderConstants};
 use wgpu::*;
 
 use crate::{
+    buffer::GrowableBuffer,
+    culling::{intersects, visible_rect},
     renderer::{Drawable, Resources},
-    scene::{Layer, PathCommand},
+    scene::{FillRule, Layer, Path as ScenePath, PathCommand},
 };
 
+// Layers with more uncached paths than this are tessellated across threads when parallel
+// encoding is enabled
+const PARALLEL_THRESHOLD: usize = 256;
+// Tessellations which go unused for this many draws are evicted from the cache. Eviction is
+// checked every EVICTION_INTERVAL draws.
+const CACHE_LIFETIME: u64 = 600;
+const EVICTION_INTERVAL: u64 = 60;
+
 pub struct PathState {
-    vertex_buffer: Buffer,
-    index_buffer: Buffer,
+    vertex_buffer: GrowableBuffer<PathVertex>,
+    index_buffer: GrowableBuffer<u32>,
     render_pipeline: Option<RenderPipeline>,
+    parallel: bool,
+    // False when paths are rendered by GpuPathState instead
+    enabled: bool,
+
+    // Tessellated geometry keyed by path content so static paths are only tessellated once.
+    // Cached geometry stays resident in the vertex and index buffers, so unchanged paths only
+    // cost a draw call. Paths whose hashes collide share a bucket.
+    tessellation_cache: HashMap<u64, Vec<CachedGeometry>>,
+    // Cpu copy of the resident geometry. Indices are absolute, so paths stored next to each
+    // other are drawn with a single call
+    resident: VertexBuffers<PathVertex, u32>,
+    // Whether the resident geometry changed since it was last uploaded
+    resident_dirty: bool,
+    draws: u64,
+    triangles: u64,
 }
 
-impl Drawable for PathState {
-    fn new(Resources { device, .. }: &Resources) -> Self {
-        let vertex_buffer = device.create_buffer(&BufferDescriptor {
-            label: Some("Path Vertex Buffer"),
-            size: std::mem::size_of::<PathVertex>() as u64 * 100000,
-            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+impl PathState {
+    fn cached(&mut self, hash: u64, path: &ScenePath) -> Option<&mut CachedGeometry> {
+        self.tessellation_cache
+            .get_mut(&hash)?
+            .iter_mut()
+            .find(|cached| cached.key.matches(path))
+    }
+
+    // Appends the geometry to the resident buffers, returning the ranges it was stored at
+    fn make_resident(&mut self, geometry: VertexBuffers<PathVertex, u32>) -> [Range<u32>; 2] {
+        let base_vertex = self.resident.vertices.len() as u32;
+        let base_index = self.resident.indices.len() as u32;
+        self.resident.vertices.extend(geometry.vertices);
+        self.resident.indices.extend(
+            geometry
+                .indices
+                .into_iter()
+                .map(|index| index + base_vertex),
+        );
+        self.resident_dirty = true;
+        [
+            base_vertex..self.resident.vertices.len() as u32,
+            base_index..self.resident.indices.len() as u32,
+        ]
+    }
 
-        let index_buffer = device.create_buffer(&BufferDescriptor {
-            label: Some("Path Index Buffer"),
-            size: std::mem::size_of::<u32>() as u64 * 100000,
-            usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
-            mapped_at_creation: false,
+    // Drops geometry which hasn't been drawn recently and packs what's left together. Geometry
+    // keeps the order it was stored in so neighbouring paths can still share draw calls
+    fn evict(&mut self) {
+        let draws = self.draws;
+        let mut evicted = false;
+        self.tessellation_cache.retain(|_, bucket| {
+            let len = bucket.len();
+            bucket.retain(|cached| draws - cached.last_used < CACHE_LIFETIME);
+            evicted |= bucket.len() != len;
+            !bucket.is_empty()
         });
+        if !evicted {
+            return;
+        }
+
+        let mut live: Vec<&mut CachedGeometry> =
+            self.tessellation_cache.values_mut().flatten().collect();
+        live.sort_by_key(|cached| cached.vertices.start);
+        let mut packed: VertexBuffers<PathVertex, u32> = VertexBuffers::new();
+        for cached in live {
+            let base_vertex = packed.vertices.len() as u32;
+            let base_index = packed.indices.len() as u32;
+            packed.vertices.extend_from_slice(
+                &self.resident.vertices
+                    [cached.vertices.start as usize..cached.vertices.end as usize],
+            );
+            packed.indices.extend(
+                self.resident.indices[cached.indices.start as usize..cached.indices.end as usize]
+                    .iter()
+                    .map(|index| index - cached.vertices.start + base_vertex),
+            );
+            cached.vertices = base_vertex..packed.vertices.len() as u32;
+            cached.indices = base_index..packed.indices.len() as u32;
+        }
+        self.resident = packed;
+        self.resident_dirty = true;
+    }
+}
+
+impl Drawable for PathState {
+    fn new(
+        Resources {
+            device, gpu_paths, ..
+        }: &Resources,
+    ) -> Self {
+        let vertex_buffer = GrowableBuffer::new(device, "Path Vertex Buffer", BufferUsages::VERTEX);
+        let index_buffer = GrowableBuffer::new(device, "Path Index Buffer", BufferUsages::INDEX);
 
         Self {
             vertex_buffer,
             index_buffer,
             render_pipeline: None,
+            parallel: false,
+            enabled: !*gpu_paths,
+
+            tessellation_cache: HashMap::new(),
+            resident: VertexBuffers::new(),
+            resident_dirty: false,
+            draws: 0,
+            triangles: 0,
         }
     }
 
@@ -50,9 +148,17 @@ impl Drawable for PathState {
             device,
             shader,
             surface_resources_manager,
+            parallel_encoding,
+            gpu_paths,
             ..
         }: &Resources,
     ) {
+        let Some(format) = surface_resources_manager.format() else {
+            return;
+        };
+        self.parallel = *parallel_encoding;
+        self.enabled = !*gpu_paths;
+
         self.render_pipeline = Some(device.create_render_pipeline(&RenderPipelineDescriptor {
             label: Some("Path render pipeline"),
             layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
@@ -65,19 +171,19 @@ impl Drawable for PathState {
             })),
             vertex: VertexState {
                 module: &shader,
-                entry_point: "path::path_vertex",
+                entry_point: shader::PATH_VERTEX,
                 buffers: &[VertexBufferLayout {
                     array_stride: std::mem::size_of::<PathVertex>() as BufferAddress,
                     step_mode: VertexStepMode::Vertex,
-                    attributes: &vertex_attr_array![0 => Float32x4, 1 => Float32x2, 2 => Float32x2],
+                    attributes: &vertex_attr_array![0 => Float32x4, 1 => Float32x2, 2 => Float32],
                 }],
             },
             fragment: Some(FragmentState {
                 module: &shader,
-                entry_point: "path::path_fragment",
+                entry_point: shader::PATH_FRAGMENT,
                 targets: &[Some(ColorTargetState {
-                    format: surface_resources_manager.format(),
-                    blend: Some(BlendState::ALPHA_BLENDING),
+                    format,
+                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
                     write_mask: ColorWrites::ALL,
                 })],
             }),
@@ -90,105 +196,308 @@ impl Drawable for PathState {
                 polygon_mode: PolygonMode::Fill,
                 conservative: false,
             },
-            depth_stencil: None,
+            depth_stencil: Some(surface_resources_manager.depth_stencil_state()),
             multisample: MultisampleState {
-                count: 4,
+                count: surface_resources_manager.sample_count(),
                 ..Default::default()
             },
             multiview: None,
         }));
     }
 
+    fn needs_draw(&self, layer: &Layer) -> bool {
+        self.enabled && !layer.paths.is_empty()
+    }
+
+    fn instance_count(&self) -> u64 {
+        self.triangles
+    }
+
     fn draw<'b, 'a: 'b>(
         &'a mut self,
-        queue: &Queue,
+        Resources { device, queue, .. }: &'a Resources,
         render_pass: &mut RenderPass<'b>,
         constants: ShaderConstants,
-        _universal_bind_group: &'a BindGroup,
         layer: &Layer,
     ) {
-        let mut geometry: VertexBuffers<PathVertex, u32> = VertexBuffers::new();
-        let mut fill_tesselator = FillTessellator::new();
-        let mut stroke_tesselator = StrokeTessellator::new();
-
-        for scene_path in layer.paths.iter() {
-            let mut builder = Path::builder();
-            builder.begin(point(scene_path.start.x, scene_path.start.y));
-            for path_command in scene_path.commands.iter() {
-                match path_command {
-                    PathCommand::LineTo { to } => {
-                        builder.line_to(point(to.x, to.y));
-                    }
-                    PathCommand::QuadraticBezierTo { control, to } => {
-                        builder.quadratic_bezier_to(point(control.x, control.y), point(to.x, to.y));
-                    }
-                    PathCommand::CubicBezierTo {
-                        control1,
-                        control2,
-                        to,
-                    } => {
-                        builder.cubic_bezier_to(
-                            point(control1.x, control1.y),
-                            point(control2.x, control2.y),
-                            point(to.x, to.y),
-                        );
+        self.draws += 1;
+        if self.draws % EVICTION_INTERVAL == 0 {
+            self.evict();
+        }
+
+        let visible = visible_rect(layer, constants.surface_size);
+        let paths: Vec<&ScenePath> = layer
+            .paths
+            .iter()
+            .filter(|path| intersects(path.bounds(), visible))
+            .collect();
+        let hashes: Vec<u64> = paths.iter().map(|path| hash_path(path)).collect();
+
+        // Only paths which haven't been seen recently need tessellating
+        let draws = self.draws;
+        let mut ranges: Vec<Option<Range<u32>>> = Vec::with_capacity(paths.len());
+        let mut missed = HashSet::new();
+        let mut misses: Vec<(u64, PathKey, &ScenePath)> = Vec::new();
+        for (hash, path) in hashes.iter().zip(paths.iter().copied()) {
+            match self.cached(*hash, path) {
+                Some(cached) => {
+                    cached.last_used = draws;
+                    ranges.push(Some(cached.indices.clone()));
+                }
+                None => {
+                    ranges.push(None);
+                    let key = PathKey::new(path);
+                    if missed.insert(key.clone()) {
+                        misses.push((*hash, key, path));
                     }
                 }
             }
-            builder.close();
-            let path = builder.build();
-
-            if let Some(fill) = scene_path.fill {
-                fill_tesselator
-                    .tessellate_path(
-                        &path,
-                        &FillOptions::default(),
-                        &mut BuffersBuilder::new(&mut geometry, |vertex: FillVertex| PathVertex {
-                            color: fill,
-                            position: vec2(vertex.position().x, vertex.position().y),
-                            ..Default::default()
-                        }),
+        }
+
+        let tessellated: Vec<VertexBuffers<PathVertex, u32>> =
+            if self.parallel && misses.len() > PARALLEL_THRESHOLD {
+                misses
+                    .par_iter()
+                    .map_init(
+                        || (FillTessellator::new(), StrokeTessellator::new()),
+                        |(fill_tessellator, stroke_tessellator), (_, _, path)| {
+                            tessellate_path(path, fill_tessellator, stroke_tessellator)
+                        },
                     )
-                    .expect("Could not tesselate path");
+                    .collect()
+            } else {
+                let mut fill_tessellator = FillTessellator::new();
+                let mut stroke_tessellator = StrokeTessellator::new();
+                misses
+                    .iter()
+                    .map(|(_, _, path)| {
+                        tessellate_path(path, &mut fill_tessellator, &mut stroke_tessellator)
+                    })
+                    .collect()
+            };
+
+        for ((hash, key, _), geometry) in misses.into_iter().zip(tessellated) {
+            let [vertices, indices] = self.make_resident(geometry);
+            self.tessellation_cache
+                .entry(hash)
+                .or_default()
+                .push(CachedGeometry {
+                    key,
+                    vertices,
+                    indices,
+                    last_used: draws,
+                });
+        }
+        for ((range, hash), path) in ranges.iter_mut().zip(hashes.iter()).zip(paths.iter()) {
+            if range.is_none() {
+                let cached = self
+                    .cached(*hash, path)
+                    .expect("Path was tessellated above");
+                *range = Some(cached.indices.clone());
             }
+        }
 
-            if let Some((width, stroke)) = scene_path.stroke {
-                stroke_tesselator
-                    .tessellate_path(
-                        &path,
-                        &StrokeOptions::default().with_line_width(width),
-                        &mut BuffersBuilder::new(&mut geometry, |vertex: StrokeVertex| {
-                            PathVertex {
-                                color: stroke,
-                                position: vec2(vertex.position().x, vertex.position().y),
-                                ..Default::default()
-                            }
-                        }),
-                    )
-                    .expect("Could not tesselate path");
+        if self.resident_dirty {
+            self.vertex_buffer
+                .upload(device, queue, &self.resident.vertices);
+            self.index_buffer
+                .upload(device, queue, &self.resident.indices);
+            self.resident_dirty = false;
+        }
+
+        render_pass.set_pipeline(self.render_pipeline.as_ref().unwrap());
+        render_pass.set_push_constants(ShaderStages::all(), 0, bytemuck::cast_slice(&[constants]));
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.buffer().slice(..));
+        render_pass.set_index_buffer(self.index_buffer.buffer().slice(..), IndexFormat::Uint32);
+
+        // Drawn in painter's order
+        self.triangles = 0;
+        for run in draw_runs(ranges.into_iter().flatten()) {
+            let run = self.index_buffer.uploaded_range(run);
+            if !run.is_empty() {
+                self.triangles += run.len() as u64 / 3;
+                render_pass.draw_indexed(run, 0, 0..1);
             }
+        }
+    }
+}
 
-            render_pass.set_pipeline(self.render_pipeline.as_ref().unwrap());
-            render_pass.set_push_constants(
-                ShaderStages::all(),
-                0,
-                bytemuck::cast_slice(&[constants]),
-            );
+// Merges index ranges which follow on from each other into a single range
+fn draw_runs(ranges: impl Iterator<Item = Range<u32>>) -> Vec<Range<u32>> {
+    let mut runs: Vec<Range<u32>> = Vec::new();
+    for range in ranges {
+        match runs.last_mut() {
+            Some(last) if last.end == range.start => last.end = range.end,
+            _ => runs.push(range),
+        }
+    }
+    runs
+}
 
-            queue.write_buffer(
-                &self.vertex_buffer,
-                0,
-                bytemuck::cast_slice(&geometry.vertices[..]),
-            );
-            queue.write_buffer(
-                &self.index_buffer,
-                0,
-                bytemuck::cast_slice(&geometry.indices[..]),
-            );
+// Key identifying a path's tessellation. Floats are compared by their bit patterns, so only
+// exactly identical paths share geometry. Keys are only built for paths missing from the cache;
+// cached paths are found by their hash and compared against the key in place.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct PathKey(Vec<u32>);
+
+impl PathKey {
+    fn new(path: &ScenePath) -> Self {
+        let mut data = Vec::with_capacity(8 + path.commands.len() * 7);
+        path_words(path, |word| data.push(word));
+        Self(data)
+    }
+
+    fn matches(&self, path: &ScenePath) -> bool {
+        let mut words = self.0.iter();
+        let mut matches = true;
+        path_words(path, |word| matches &= words.next() == Some(&word));
+        matches && words.next().is_none()
+    }
+}
+
+fn hash_path(path: &ScenePath) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    path_words(path, |word| hasher.write_u32(word));
+    hasher.finish()
+}
+
+// Visits the content of the path as a sequence of words, so keys can be built, hashed and
+// compared without allocating
+fn path_words(path: &ScenePath, mut visit: impl FnMut(u32)) {
+    // Each part of the path is a tag followed by its floats
+    let mut part = |tag: u32, values: &[f32]| {
+        visit(tag);
+        values.iter().for_each(|value| visit(value.to_bits()));
+    };
+    match path.fill {
+        Some(fill) => part(1, &fill.to_array()),
+        None => part(0, &[]),
+    }
+    match path.stroke {
+        Some((width, color)) => part(1, &[width, color.x, color.y, color.z, color.w]),
+        None => part(0, &[]),
+    }
+    part(path.open as u32, &path.start.to_array());
+    part(path.fill_rule as u32, &[]);
+    // Depth is baked into the resident vertices
+    part(0, &[path.depth]);
+    for command in path.commands.iter() {
+        match *command {
+            PathCommand::LineTo { to } => part(0, &to.to_array()),
+            PathCommand::QuadraticBezierTo { control, to } => {
+                part(1, &[control.x, control.y, to.x, to.y])
+            }
+            PathCommand::CubicBezierTo {
+                control1,
+                control2,
+                to,
+            } => part(
+                2,
+                &[control1.x, control1.y, control2.x, control2.y, to.x, to.y],
+            ),
+            PathCommand::MoveTo { start } => part(3, &start.to_array()),
+        }
+    }
+}
 
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint32);
-            render_pass.draw_indexed(0..geometry.indices.len() as u32, 0, 0..1);
+struct CachedGeometry {
+    key: PathKey,
+    // Where the geometry is stored in the resident buffers
+    vertices: Range<u32>,
+    indices: Range<u32>,
+    last_used: u64,
+}
+
+fn tessellate_path(
+    scene_path: &ScenePath,
+    fill_tesselator: &mut FillTessellator,
+    stroke_tesselator: &mut StrokeTessellator,
+) -> VertexBuffers<PathVertex, u32> {
+    let mut geometry: VertexBuffers<PathVertex, u32> = VertexBuffers::new();
+
+    let path = build_lyon_path(scene_path);
+
+    if let Some(fill) = scene_path.fill {
+        fill_tesselator
+            .tessellate_path(
+                &path,
+                &FillOptions::default().with_fill_rule(lyon_fill_rule(scene_path.fill_rule)),
+                &mut BuffersBuilder::new(&mut geometry, |vertex: FillVertex| PathVertex {
+                    color: fill,
+                    position: vec2(vertex.position().x, vertex.position().y),
+                    depth: scene_path.depth,
+                    ..Default::default()
+                }),
+            )
+            .expect("Could not tesselate path");
+    }
+
+    if let Some((width, stroke)) = scene_path.stroke {
+        stroke_tesselator
+            .tessellate_path(
+                &path,
+                &StrokeOptions::default().with_line_width(width),
+                &mut BuffersBuilder::new(&mut geometry, |vertex: StrokeVertex| PathVertex {
+                    color: stroke,
+                    position: vec2(vertex.position().x, vertex.position().y),
+                    depth: scene_path.depth,
+                    ..Default::default()
+                }),
+            )
+            .expect("Could not tesselate path");
+    }
+
+    geometry
+}
+
+pub(crate) fn lyon_fill_rule(fill_rule: FillRule) -> LyonFillRule {
+    match fill_rule {
+        FillRule::EvenOdd => LyonFillRule::EvenOdd,
+        FillRule::NonZero => LyonFillRule::NonZero,
+    }
+}
+
+pub(crate) fn build_lyon_path(scene_path: &ScenePath) -> Path {
+    let mut builder = Path::builder();
+    builder.begin(point(scene_path.start.x, scene_path.start.y));
+    for path_command in scene_path.commands.iter() {
+        match path_command {
+            PathCommand::LineTo { to } => {
+                builder.line_to(point(to.x, to.y));
+            }
+            PathCommand::QuadraticBezierTo { control, to } => {
+                builder.quadratic_bezier_to(point(control.x, control.y), point(to.x, to.y));
+            }
+            PathCommand::CubicBezierTo {
+                control1,
+                control2,
+                to,
+            } => {
+                builder.cubic_bezier_to(
+                    point(control1.x, control1.y),
+                    point(control2.x, control2.y),
+                    point(to.x, to.y),
+                );
+            }
+            PathCommand::MoveTo { start } => {
+                builder.end(!scene_path.open);
+                builder.begin(point(start.x, start.y));
+            }
         }
     }
+    builder.end(!scene_path.open);
+    builder.build()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_neighbouring_paths_share_draws() {
+        assert_eq!(
+            draw_runs([0..6, 6..9, 12..15, 3..6, 6..12].into_iter()),
+            vec![0..9, 12..15, 3..12]
+        );
+    }
 }
@@ -0,0 +1,114 @@
+use glam::{Vec2, Vec4};
+use wgpu::*;
+
+// Reads the color of a single pixel back from each frame after it is drawn. Waits for the gpu
+// to finish the frame, so it should only be enabled while something is being inspected.
+pub(crate) struct PixelProbe {
+    pub(crate) position: Vec2,
+    pub(crate) color: Option<Vec4>,
+    buffer: Buffer,
+}
+
+impl PixelProbe {
+    pub(crate) fn new(device: &Device, position: Vec2) -> Self {
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Pixel probe readback buffer"),
+            size: 4,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            position,
+            color: None,
+            buffer,
+        }
+    }
+
+    pub(crate) fn read(&mut self, device: &Device, queue: &Queue, texture: &Texture) {
+        self.color = None;
+        if self.position.x < 0.0
+            || self.position.y < 0.0
+            || self.position.x >= texture.width() as f32
+            || self.position.y >= texture.height() as f32
+        {
+            return;
+        }
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Pixel Probe Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: self.position.x as u32,
+                    y: self.position.y as u32,
+                    z: 0,
+                },
+                aspect: Default::default(),
+            },
+            ImageCopyBuffer {
+                buffer: &self.buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    // A single row doesn't need the row alignment
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = self.buffer.slice(..);
+        slice.map_async(MapMode::Read, |_| ());
+        device.poll(Maintain::Wait);
+        {
+            let data = slice.get_mapped_range();
+            self.color = decode_pixel(texture.format(), [data[0], data[1], data[2], data[3]]);
+        }
+        self.buffer.unmap();
+    }
+}
+
+// Straight alpha color of a premultiplied surface pixel. Srgb surfaces store colors close to
+// the scene's own values since the shaders convert to linear, so both are read the same way.
+// Returns None for formats other than 8 bit rgba and bgra.
+pub(crate) fn decode_pixel(format: TextureFormat, bytes: [u8; 4]) -> Option<Vec4> {
+    let [r, g, b, a] = match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => bytes,
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => {
+            [bytes[2], bytes[1], bytes[0], bytes[3]]
+        }
+        _ => return None,
+    };
+    let premultiplied = Vec4::new(r as f32, g as f32, b as f32, a as f32) / 255.0;
+    if premultiplied.w == 0.0 {
+        return Some(Vec4::ZERO);
+    }
+    Some((premultiplied.truncate() / premultiplied.w).extend(premultiplied.w))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_pixel() {
+        assert_eq!(
+            decode_pixel(TextureFormat::Bgra8Unorm, [0, 0, 255, 255]),
+            Some(Vec4::new(1.0, 0.0, 0.0, 1.0))
+        );
+        assert_eq!(
+            decode_pixel(TextureFormat::Rgba8UnormSrgb, [51, 0, 0, 102]),
+            Some(Vec4::new(0.5, 0.0, 0.0, 0.4))
+        );
+        assert_eq!(decode_pixel(TextureFormat::Rgba16Float, [0; 4]), None);
+    }
+}
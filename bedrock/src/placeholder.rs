@@ -0,0 +1,207 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use glam::{vec2, Vec2, Vec4, Vec4Swizzles};
+
+use crate::scene::{Layer, Path, Quad, Text};
+
+// Seconds per revolution of the spinner placeholder
+const SPINNER_PERIOD: f32 = 1.0;
+// Portion of the circle covered by the spinner's arc
+const SPINNER_SWEEP: f32 = TAU * 0.75;
+// Colors of the checkerboard drawn over sprites whose texture is missing
+const CHECKER_COLORS: [Vec4; 2] = [Vec4::new(1.0, 0.0, 1.0, 1.0), Vec4::new(0.0, 0.0, 0.0, 1.0)];
+// Checker cells per side of the sprite, and the smallest a cell gets in pixels
+const CHECKER_CELLS: f32 = 8.0;
+const CHECKER_MIN_CELL: f32 = 4.0;
+// Font size range of the texture name drawn over missing sprites
+const LABEL_SIZE: (f32, f32) = (10.0, 16.0);
+
+// Item from the last draw which couldn't be drawn because its assets weren't available
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingContent {
+    pub bounds: Vec4,
+    // Name of the sprite texture that couldn't be loaded. None for other items such as texts
+    // whose font is missing
+    pub texture: Option<String>,
+}
+
+impl MissingContent {
+    pub fn new(bounds: Vec4) -> Self {
+        Self {
+            bounds,
+            texture: None,
+        }
+    }
+
+    pub fn texture(bounds: Vec4, texture: impl Into<String>) -> Self {
+        Self {
+            bounds,
+            texture: Some(texture.into()),
+        }
+    }
+}
+
+// What is drawn in place of texts whose font couldn't be loaded. Sprites whose texture is
+// missing are always covered with a magenta checkerboard labeled with the texture's name so
+// broken asset paths are easy to spot. Placeholders are drawn over the rest of the item's layer
+// so they always appear in the same spot the item would have.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Placeholder {
+    // Leave the item's area empty
+    #[default]
+    Transparent,
+    // Fill the item's bounds with a solid color
+    Color(Vec4),
+    // Draw a spinning arc centered in the item's bounds. The spinner only moves when frames
+    // are drawn, so apps using it should keep redrawing while assets are loading.
+    Spinner {
+        color: Vec4,
+        width: f32,
+    },
+}
+
+impl Placeholder {
+    // Layer containing placeholders for each piece of missing content. Returns None if nothing
+    // needs to be drawn
+    pub(crate) fn layer(
+        &self,
+        parent: &Layer,
+        missing: &[MissingContent],
+        seconds: f32,
+    ) -> Option<Layer> {
+        let mut layer = Layer {
+            name: Some("Placeholders".to_string()),
+            clip: parent.clip,
+            background_color: None,
+            font_name: parent.font_name.clone(),
+            ..Default::default()
+        };
+
+        let mut rects = Vec::new();
+        for content in missing {
+            match &content.texture {
+                Some(texture) => add_missing_texture(&mut layer, content.bounds, texture),
+                None => rects.push(content.bounds),
+            }
+        }
+
+        match *self {
+            Placeholder::Transparent => {}
+            Placeholder::Color(color) => {
+                for rect in rects {
+                    layer.add_quad(Quad::new(rect.xy(), rect.zw(), color));
+                }
+            }
+            Placeholder::Spinner { color, width } => {
+                let rotation = (seconds / SPINNER_PERIOD).fract() * TAU;
+                for rect in rects {
+                    let radius = (rect.zw().min_element() / 2.0 - width).max(width);
+                    let center = rect.xy() + rect.zw() / 2.0;
+                    layer.add_path(
+                        arc(center, radius, rotation, SPINNER_SWEEP).with_stroke((width, color)),
+                    );
+                }
+            }
+        }
+
+        (!layer.is_empty()).then_some(layer)
+    }
+}
+
+// Checkerboard covering the sprite's bounds with the texture's name in the top left corner
+fn add_missing_texture(layer: &mut Layer, bounds: Vec4, texture: &str) {
+    let (top_left, size) = (bounds.xy(), bounds.zw());
+    layer.add_quad(Quad::new(top_left, size, CHECKER_COLORS[0]));
+
+    let cell = (size.max_element() / CHECKER_CELLS).max(CHECKER_MIN_CELL);
+    let (columns, rows) = (
+        (size.x / cell).ceil() as usize,
+        (size.y / cell).ceil() as usize,
+    );
+    for row in 0..rows {
+        for column in (1 - row % 2..columns).step_by(2) {
+            let cell_top_left = vec2(column as f32, row as f32) * cell;
+            // Cells on the far edges are cut short so the checkerboard stays inside the sprite
+            let cell_size = (size - cell_top_left).min(Vec2::splat(cell));
+            layer.add_quad(Quad::new(
+                top_left + cell_top_left,
+                cell_size,
+                CHECKER_COLORS[1],
+            ));
+        }
+    }
+
+    let label_size = (size.y / 4.0).clamp(LABEL_SIZE.0, LABEL_SIZE.1);
+    layer.add_text(Text::new(
+        texture.to_string(),
+        top_left + vec2(label_size * 0.25, label_size),
+        label_size,
+        Vec4::ONE,
+    ));
+}
+
+// Approximate bounds of a text whose font isn't available, so its width can't be measured.
+// Characters are assumed to be half as wide as the font size.
+pub(crate) fn estimated_text_bounds(text: &Text) -> Vec4 {
+    let width = text.text.chars().count() as f32 * text.size * 0.5;
+    Vec4::new(
+        text.bottom_left.x,
+        text.bottom_left.y - text.size,
+        width,
+        text.size * 1.5,
+    )
+}
+
+// Open circular arc built from cubic beziers of at most a quarter turn each
+fn arc(center: Vec2, radius: f32, start_angle: f32, sweep: f32) -> Path {
+    let point = |angle: f32| center + vec2(angle.cos(), angle.sin()) * radius;
+    let tangent = |angle: f32| vec2(-angle.sin(), angle.cos()) * radius;
+
+    let segments = (sweep / FRAC_PI_2).ceil().max(1.0);
+    let step = sweep / segments;
+    // Distance along the tangent to place control points for the segment's sweep
+    let handle = 4.0 / 3.0 * (step / 4.0).tan();
+
+    let mut path = Path::new(point(start_angle)).with_open(true);
+    for segment in 0..segments as usize {
+        let from = start_angle + step * segment as f32;
+        let to = from + step;
+        path = path.cubic_bezier_to(
+            point(from) + tangent(from) * handle,
+            point(to) - tangent(to) * handle,
+            point(to),
+        );
+    }
+    path
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_missing_texture_is_labeled_checkerboard() {
+        let missing = [MissingContent::texture(
+            Vec4::new(10.0, 20.0, 100.0, 50.0),
+            "missing.png",
+        )];
+        let layer = Placeholder::Transparent
+            .layer(&Layer::default(), &missing, 0.0)
+            .unwrap();
+
+        assert_eq!(layer.texts[0].text, "missing.png");
+        assert!(layer.quads.len() > 1);
+        for bounds in layer.quads.iter().map(Quad::bounds) {
+            assert!(bounds.xy().cmpge(vec2(10.0, 20.0)).all());
+            assert!((bounds.xy() + bounds.zw()).cmple(vec2(110.0, 70.0)).all());
+        }
+    }
+
+    #[test]
+    fn test_transparent_draws_nothing_for_texts() {
+        let missing = [MissingContent::new(Vec4::new(0.0, 0.0, 10.0, 10.0))];
+        assert!(Placeholder::Transparent
+            .layer(&Layer::default(), &missing, 0.0)
+            .is_none());
+    }
+}
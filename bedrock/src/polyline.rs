@@ -0,0 +1,240 @@
+use bytemuck::{Pod, Zeroable};
+use shader::ShaderConstants;
+use wgpu::*;
+
+use crate::{
+    buffer::GrowableBuffer,
+    culling::{intersects, visible_rect},
+    renderer::{Drawable, Resources},
+    scene::{JointStyle, Layer, Polyline},
+};
+
+// Matches Segment in polyline.wgsl
+#[derive(Copy, Clone, Default, Pod, Zeroable)]
+#[repr(C)]
+struct Segment {
+    point: u32,
+    line: u32,
+}
+
+// Matches Line in polyline.wgsl
+#[derive(Copy, Clone, Default, Pod, Zeroable)]
+#[repr(C)]
+struct Line {
+    color: [f32; 4],
+    width: f32,
+    joint: u32,
+    depth: f32,
+    last_point: u32,
+}
+
+impl Line {
+    fn new(polyline: &Polyline, last_point: u32) -> Self {
+        Self {
+            color: polyline.color.to_array(),
+            width: polyline.width,
+            joint: match polyline.joint {
+                JointStyle::Round => 0,
+                JointStyle::Bevel => 1,
+                JointStyle::Miter => 2,
+            },
+            depth: polyline.depth,
+            last_point,
+        }
+    }
+}
+
+// Draws every polyline in a layer with a single instanced draw of one instance per segment.
+// Points are uploaded as is and expanded into segments and joints in the vertex shader, so the
+// cpu only copies points each frame. The whole layer has to fit in one storage binding, which
+// is tens of millions of points on most devices.
+pub struct PolylineState {
+    points: GrowableBuffer<[f32; 2]>,
+    segments: GrowableBuffer<Segment>,
+    lines: GrowableBuffer<Line>,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    pipeline_layout: PipelineLayout,
+    pipeline: Option<RenderPipeline>,
+}
+
+impl Drawable for PolylineState {
+    fn new(Resources { device, .. }: &Resources) -> Self {
+        let points = GrowableBuffer::new(device, "Polyline point buffer", BufferUsages::STORAGE);
+        let segments =
+            GrowableBuffer::new(device, "Polyline segment buffer", BufferUsages::STORAGE);
+        let lines = GrowableBuffer::new(device, "Polyline line buffer", BufferUsages::STORAGE);
+
+        let entry = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::VERTEX,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Polyline bind group layout"),
+            entries: &[entry(0), entry(1), entry(2)],
+        });
+        let bind_group = create_bind_group(device, &bind_group_layout, &points, &segments, &lines);
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Polyline Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[PushConstantRange {
+                stages: ShaderStages::all(),
+                range: 0..std::mem::size_of::<ShaderConstants>() as u32,
+            }],
+        });
+
+        Self {
+            points,
+            segments,
+            lines,
+            bind_group_layout,
+            bind_group,
+            pipeline_layout,
+            pipeline: None,
+        }
+    }
+
+    fn surface_updated(
+        &mut self,
+        Resources {
+            device,
+            surface_resources_manager,
+            ..
+        }: &Resources,
+    ) {
+        let Some(format) = surface_resources_manager.format() else {
+            return;
+        };
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Polyline shader"),
+            source: ShaderSource::Wgsl(include_str!("polyline.wgsl").into()),
+        });
+        self.pipeline = Some(device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Polyline Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "vertex",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: "fragment",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: Some(surface_resources_manager.depth_stencil_state()),
+            multisample: MultisampleState {
+                count: surface_resources_manager.sample_count(),
+                ..Default::default()
+            },
+            multiview: None,
+        }));
+    }
+
+    fn needs_draw(&self, layer: &Layer) -> bool {
+        !layer.polylines.is_empty()
+    }
+
+    fn instance_count(&self) -> u64 {
+        self.segments.len()
+    }
+
+    fn draw<'b, 'a: 'b>(
+        &'a mut self,
+        resources: &'a Resources,
+        render_pass: &mut RenderPass<'b>,
+        constants: ShaderConstants,
+        layer: &Layer,
+    ) {
+        let Some(pipeline) = self.pipeline.as_ref() else {
+            return;
+        };
+
+        let visible = visible_rect(layer, constants.surface_size);
+        let mut points = Vec::new();
+        let mut segments = Vec::new();
+        let mut lines = Vec::new();
+        for polyline in layer.polylines.iter() {
+            if polyline.points.len() < 2
+                || !polyline
+                    .bounds()
+                    .is_some_and(|bounds| intersects(bounds, visible))
+            {
+                continue;
+            }
+
+            let first_point = points.len() as u32;
+            let line = lines.len() as u32;
+            points.extend(polyline.points.iter().map(|point| point.to_array()));
+            segments.extend(
+                (first_point..points.len() as u32 - 1).map(|point| Segment { point, line }),
+            );
+            lines.push(Line::new(polyline, points.len() as u32 - 1));
+        }
+        if segments.is_empty() {
+            return;
+        }
+
+        let device = &resources.device;
+        let queue = &resources.queue;
+        let points_recreated = self.points.upload(device, queue, &points);
+        let segments_recreated = self.segments.upload(device, queue, &segments);
+        let lines_recreated = self.lines.upload(device, queue, &lines);
+        if points_recreated || segments_recreated || lines_recreated {
+            self.bind_group = create_bind_group(
+                device,
+                &self.bind_group_layout,
+                &self.points,
+                &self.segments,
+                &self.lines,
+            );
+        }
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_push_constants(ShaderStages::all(), 0, bytemuck::cast_slice(&[constants]));
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..12, 0..segments.len() as u32);
+    }
+}
+
+fn create_bind_group(
+    device: &Device,
+    bind_group_layout: &BindGroupLayout,
+    points: &GrowableBuffer<[f32; 2]>,
+    segments: &GrowableBuffer<Segment>,
+    lines: &GrowableBuffer<Line>,
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Polyline bind group"),
+        layout: bind_group_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: points.binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: segments.binding(),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: lines.binding(),
+            },
+        ],
+    })
+}
@@ -0,0 +1,153 @@
+// Expands polylines into segments on the gpu. Each instance is one segment and draws twelve
+// vertices: a quad spanning the segment followed by the joint connecting it to the next
+// segment, which collapses to nothing at the end of the line.
+
+struct Segment {
+    // Index of the segment's first point
+    point: u32,
+    line: u32,
+}
+
+struct Line {
+    color: vec4<f32>,
+    width: f32,
+    // 0 round, 1 bevel, 2 miter
+    joint: u32,
+    depth: f32,
+    last_point: u32,
+}
+
+// Matches ShaderConstants in the shader crate
+struct Constants {
+    surface_size: vec2<f32>,
+    atlas_size: vec2<f32>,
+    clip: vec4<f32>,
+    backdrop: vec4<f32>,
+}
+
+// Matches MITER_LIMIT in scene/polyline.rs
+const MITER_LIMIT: f32 = 4.0;
+
+@group(0) @binding(0) var<storage, read> points: array<vec2<f32>>;
+@group(0) @binding(1) var<storage, read> segments: array<Segment>;
+@group(0) @binding(2) var<storage, read> lines: array<Line>;
+
+var<push_constant> constants: Constants;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // Offset from the center of round joints in pixels. Zero elsewhere so they are fully
+    // covered
+    @location(0) offset: vec2<f32>,
+    @location(1) radius: f32,
+    @location(2) color: vec4<f32>,
+}
+
+fn direction(start: vec2<f32>, end: vec2<f32>) -> vec2<f32> {
+    let delta = end - start;
+    let span = length(delta);
+    if span < 0.0001 {
+        return vec2<f32>(1.0, 0.0);
+    }
+    return delta / span;
+}
+
+fn normal(along: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(-along.y, along.x);
+}
+
+@vertex
+fn vertex(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    let segment = segments[instance_index];
+    let line = lines[segment.line];
+    let start = points[segment.point];
+    let end = points[segment.point + 1u];
+    // Lines thinner than a pixel are drawn a pixel wide and faded instead, so they don't
+    // shimmer as they move between samples
+    let half_width = max(line.width, 1.0) / 2.0;
+
+    let start_direction = direction(start, end);
+    let start_normal = normal(start_direction);
+
+    var pixel: vec2<f32>;
+    var offset = vec2<f32>(0.0, 0.0);
+    var radius = 1.0;
+
+    if vertex_index < 6u {
+        var corners = array<vec2<f32>, 6>(
+            vec2<f32>(0.0, -1.0),
+            vec2<f32>(1.0, -1.0),
+            vec2<f32>(1.0, 1.0),
+            vec2<f32>(0.0, -1.0),
+            vec2<f32>(1.0, 1.0),
+            vec2<f32>(0.0, 1.0),
+        );
+        let corner = corners[vertex_index];
+        pixel = mix(start, end, corner.x) + start_normal * corner.y * half_width;
+    } else if segment.point + 2u > line.last_point {
+        // Last segment of the line, so there is nothing to join
+        pixel = end;
+    } else {
+        let joint_vertex = vertex_index - 6u;
+        let next = points[segment.point + 2u];
+        let end_direction = direction(end, next);
+        let end_normal = normal(end_direction);
+        let turn = start_direction.x * end_direction.y - start_direction.y * end_direction.x;
+        // The joint fills the gap on the outside of the corner
+        let side = select(1.0, -1.0, turn > 0.0);
+        let outer_start = end + start_normal * side * half_width;
+        let outer_end = end + end_normal * side * half_width;
+
+        if line.joint == 0u {
+            var corners = array<vec2<f32>, 6>(
+                vec2<f32>(-1.0, -1.0),
+                vec2<f32>(1.0, -1.0),
+                vec2<f32>(1.0, 1.0),
+                vec2<f32>(-1.0, -1.0),
+                vec2<f32>(1.0, 1.0),
+                vec2<f32>(-1.0, 1.0),
+            );
+            offset = corners[joint_vertex] * (half_width + 1.0);
+            radius = half_width;
+            pixel = end + offset;
+        } else {
+            var tip = outer_start;
+            let bisector = start_normal + end_normal;
+            if line.joint == 2u && length(bisector) > 0.0001 {
+                let miter = normalize(bisector);
+                let miter_length = half_width / max(dot(miter, start_normal), 0.0001);
+                if miter_length / half_width <= MITER_LIMIT {
+                    tip = end + miter * side * miter_length;
+                }
+            }
+            // A bevel is the first triangle alone, and a miter adds a second reaching the tip
+            var corners = array<vec2<f32>, 6>(end, outer_start, tip, end, tip, outer_end);
+            if tip.x == outer_start.x && tip.y == outer_start.y {
+                corners = array<vec2<f32>, 6>(end, outer_start, outer_end, end, end, end);
+            }
+            pixel = corners[joint_vertex];
+        }
+    }
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(
+        pixel.x / constants.surface_size.x * 2.0 - 1.0,
+        1.0 - pixel.y / constants.surface_size.y * 2.0,
+        line.depth,
+        1.0,
+    );
+    out.offset = offset;
+    out.radius = radius;
+    out.color = vec4<f32>(line.color.rgb, line.color.a * min(line.width, 1.0));
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = clamp(in.radius - length(in.offset) + 0.5, 0.0, 1.0);
+    let alpha = in.color.a * coverage;
+    return vec4<f32>(in.color.rgb * alpha, alpha);
+}
@@ -0,0 +1,272 @@
+use wgpu::*;
+
+use crate::extension::ShaderExtension;
+
+// Uniform buffers are bound whole, so they're padded to the alignment wgsl structs use
+const UNIFORM_ALIGNMENT: usize = 16;
+
+struct PostEffect {
+    name: String,
+    extension: ShaderExtension,
+    uniforms: Buffer,
+    pipeline: Option<(TextureFormat, RenderPipeline)>,
+}
+
+// User supplied full screen passes run over the finished frame before it is presented, in the
+// order they were added. Each pass reads a copy of the frame and writes over it.
+pub(crate) struct PostEffects {
+    bind_group_layout: BindGroupLayout,
+    sampler: Sampler,
+    vertex_module: ShaderModule,
+    effects: Vec<PostEffect>,
+    // Copy of the frame the passes read from, recreated when the frame size changes
+    source: Option<Texture>,
+}
+
+impl PostEffects {
+    pub(crate) fn new(device: &Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Post effect bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Post effect sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let vertex_module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Post effect vertex shader"),
+            source: ShaderSource::Wgsl(include_str!("post_effect.wgsl").into()),
+        });
+
+        Self {
+            bind_group_layout,
+            sampler,
+            vertex_module,
+            effects: Vec::new(),
+            source: None,
+        }
+    }
+
+    // Appends the effect, or replaces the module of an existing effect with the same name while
+    // keeping its place and uniforms
+    pub(crate) fn add(&mut self, device: &Device, name: &str, extension: ShaderExtension) {
+        if let Some(effect) = self.effects.iter_mut().find(|effect| effect.name == name) {
+            effect.extension = extension;
+            effect.pipeline = None;
+            return;
+        }
+
+        self.effects.push(PostEffect {
+            name: name.to_string(),
+            extension,
+            uniforms: create_uniform_buffer(device, name, UNIFORM_ALIGNMENT),
+            pipeline: None,
+        });
+    }
+
+    pub(crate) fn remove(&mut self, name: &str) -> bool {
+        let count = self.effects.len();
+        self.effects.retain(|effect| effect.name != name);
+        self.effects.len() != count
+    }
+
+    // Replaces the contents of the effect's uniform block. Returns false if there is no effect
+    // with the given name
+    pub(crate) fn set_uniforms(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        name: &str,
+        data: &[u8],
+    ) -> bool {
+        let Some(effect) = self.effects.iter_mut().find(|effect| effect.name == name) else {
+            return false;
+        };
+
+        let size = data.len().max(1).next_multiple_of(UNIFORM_ALIGNMENT);
+        if effect.uniforms.size() as usize != size {
+            effect.uniforms = create_uniform_buffer(device, name, size);
+        }
+        let mut padded = data.to_vec();
+        padded.resize(size, 0);
+        queue.write_buffer(&effect.uniforms, 0, &padded);
+        true
+    }
+
+    fn pipeline<'a>(
+        bind_group_layout: &BindGroupLayout,
+        vertex_module: &ShaderModule,
+        effect: &'a mut PostEffect,
+        device: &Device,
+        format: TextureFormat,
+    ) -> &'a RenderPipeline {
+        if !matches!(&effect.pipeline, Some((built, _)) if *built == format) {
+            let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Post effect Pipeline Layout"),
+                bind_group_layouts: &[bind_group_layout],
+                push_constant_ranges: &[PushConstantRange {
+                    stages: ShaderStages::FRAGMENT,
+                    range: 0..std::mem::size_of::<[f32; 4]>() as u32,
+                }],
+            });
+            let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(&effect.name),
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    module: vertex_module,
+                    entry_point: "vertex",
+                    buffers: &[],
+                },
+                fragment: Some(FragmentState {
+                    module: &effect.extension.module,
+                    entry_point: "fragment",
+                    targets: &[Some(ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                multiview: None,
+            });
+            effect.pipeline = Some((format, pipeline));
+        }
+        &effect.pipeline.as_ref().unwrap().1
+    }
+
+    // Runs every effect over the target in order. `time` is passed through to the effects so
+    // they can animate
+    pub(crate) fn draw(&mut self, device: &Device, queue: &Queue, target: &Texture, time: f32) {
+        if self.effects.is_empty() {
+            return;
+        }
+
+        if self.source.as_ref().map_or(true, |source| {
+            source.size() != target.size() || source.format() != target.format()
+        }) {
+            self.source = Some(device.create_texture(&TextureDescriptor {
+                size: target.size(),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: target.format(),
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                label: Some("Post effect source"),
+                view_formats: &[],
+            }));
+        }
+        let source = self.source.as_ref().unwrap();
+        let source_view = source.create_view(&Default::default());
+        let view = target.create_view(&Default::default());
+
+        let size = target.size();
+        let constants = [size.width as f32, size.height as f32, time, 0.0];
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Post effect Encoder"),
+        });
+        for effect in self.effects.iter_mut() {
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Post effect bind group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(&source_view),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(&self.sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: effect.uniforms.as_entire_binding(),
+                    },
+                ],
+            });
+
+            // Each effect reads the output of the one before it
+            encoder.copy_texture_to_texture(
+                target.as_image_copy(),
+                source.as_image_copy(),
+                target.size(),
+            );
+
+            let pipeline = Self::pipeline(
+                &self.bind_group_layout,
+                &self.vertex_module,
+                effect,
+                device,
+                target.format(),
+            );
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Post effect Pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Load,
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.set_push_constants(
+                ShaderStages::FRAGMENT,
+                0,
+                bytemuck::cast_slice(&constants),
+            );
+            render_pass.draw(0..4, 0..1);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+fn create_uniform_buffer(device: &Device, name: &str, size: usize) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some(name),
+        size: size as BufferAddress,
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
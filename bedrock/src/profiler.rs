@@ -0,0 +1,216 @@
+use std::{fmt, time::Duration};
+
+use wgpu::*;
+
+// Maximum number of render passes timed per frame. Passes past this limit are still reported
+// but without a gpu time.
+const MAX_TIMED_PASSES: u32 = 512;
+
+// Cost of a single drawable's render pass within a layer
+#[derive(Debug, Clone)]
+pub struct ProfileEntry {
+    pub layer_index: usize,
+    pub layer_name: Option<String>,
+    pub drawable: &'static str,
+    pub instances: u64,
+    pub gpu_time: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProfileReport {
+    pub entries: Vec<ProfileEntry>,
+}
+
+impl ProfileReport {
+    pub fn total_gpu_time(&self) -> Duration {
+        self.entries.iter().filter_map(|entry| entry.gpu_time).sum()
+    }
+
+    // Most expensive entries first. Entries without timing information sort last.
+    pub fn sort_by_gpu_time(&mut self) {
+        self.entries.sort_by(|a, b| {
+            b.gpu_time
+                .unwrap_or_default()
+                .cmp(&a.gpu_time.unwrap_or_default())
+        });
+    }
+
+    pub fn sort_by_instances(&mut self) {
+        self.entries.sort_by(|a, b| b.instances.cmp(&a.instances));
+    }
+
+    // Collapses the per drawable entries into one entry per layer, keeping layer order
+    pub fn by_layer(&self) -> ProfileReport {
+        let mut entries: Vec<ProfileEntry> = Vec::new();
+        for entry in self.entries.iter() {
+            match entries
+                .iter_mut()
+                .find(|layer| layer.layer_index == entry.layer_index)
+            {
+                Some(layer) => {
+                    layer.instances += entry.instances;
+                    layer.gpu_time = match (layer.gpu_time, entry.gpu_time) {
+                        (Some(a), Some(b)) => Some(a + b),
+                        (a, b) => a.or(b),
+                    };
+                }
+                None => entries.push(ProfileEntry {
+                    drawable: "all",
+                    ..entry.clone()
+                }),
+            }
+        }
+        ProfileReport { entries }
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>6} {:<24} {:<32} {:>10} {:>12}",
+            "layer", "name", "drawable", "instances", "gpu time"
+        )?;
+        for entry in self.entries.iter() {
+            let gpu_time = entry
+                .gpu_time
+                .map(|time| format!("{:.3}ms", time.as_secs_f64() * 1000.0))
+                .unwrap_or_else(|| "-".to_string());
+            writeln!(
+                f,
+                "{:>6} {:<24} {:<32} {:>10} {:>12}",
+                entry.layer_index,
+                entry.layer_name.as_deref().unwrap_or("-"),
+                entry.drawable.rsplit("::").next().unwrap_or(entry.drawable),
+                entry.instances,
+                gpu_time
+            )?;
+        }
+        write!(f, "total gpu time: {:?}", self.total_gpu_time())
+    }
+}
+
+// Records timestamp queries around each render pass and reads them back at the end of the
+// frame. Reading back stalls until the gpu has finished the frame, so this should only be
+// enabled while investigating performance.
+pub struct Profiler {
+    query_set: Option<QuerySet>,
+    resolve_buffer: Buffer,
+    readback_buffer: Buffer,
+    timestamp_period: f32,
+    entries: Vec<ProfileEntry>,
+    report: ProfileReport,
+}
+
+impl Profiler {
+    pub fn new(device: &Device, queue: &Queue) -> Self {
+        // Timestamps are optional. Without them the report still contains instance counts
+        let query_set = device
+            .features()
+            .contains(Features::TIMESTAMP_QUERY)
+            .then(|| {
+                device.create_query_set(&QuerySetDescriptor {
+                    label: Some("Profiler query set"),
+                    ty: QueryType::Timestamp,
+                    count: MAX_TIMED_PASSES * 2,
+                })
+            });
+
+        let size = MAX_TIMED_PASSES as u64 * 2 * std::mem::size_of::<u64>() as u64;
+        let resolve_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Profiler resolve buffer"),
+            size,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Profiler readback buffer"),
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            timestamp_period: queue.get_timestamp_period(),
+            entries: Vec::new(),
+            report: ProfileReport::default(),
+        }
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.entries.clear();
+    }
+
+    // Timestamp writes for a render pass following `pending` passes which have been encoded but
+    // not recorded yet, if there is room left in the query set
+    pub fn pass_timestamps(&self, pending: u32) -> Option<RenderPassTimestampWrites> {
+        let pass_index = self.entries.len() as u32 + pending;
+        if pass_index >= MAX_TIMED_PASSES {
+            return None;
+        }
+
+        self.query_set
+            .as_ref()
+            .map(|query_set| RenderPassTimestampWrites {
+                query_set,
+                beginning_of_pass_write_index: Some(pass_index * 2),
+                end_of_pass_write_index: Some(pass_index * 2 + 1),
+            })
+    }
+
+    pub fn record_pass(
+        &mut self,
+        layer_index: usize,
+        layer_name: Option<&str>,
+        drawable: &'static str,
+        instances: u64,
+    ) {
+        self.entries.push(ProfileEntry {
+            layer_index,
+            layer_name: layer_name.map(str::to_string),
+            drawable,
+            instances,
+            gpu_time: None,
+        });
+    }
+
+    // Resolves the frame's timestamps and waits for them to be readable
+    pub fn end_frame(&mut self, device: &Device, queue: &Queue) {
+        let timed_passes = (self.entries.len() as u32).min(MAX_TIMED_PASSES);
+        if let (Some(query_set), true) = (self.query_set.as_ref(), timed_passes > 0) {
+            let size = timed_passes as u64 * 2 * std::mem::size_of::<u64>() as u64;
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Profiler Encoder"),
+            });
+            encoder.resolve_query_set(query_set, 0..timed_passes * 2, &self.resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, size);
+            queue.submit(std::iter::once(encoder.finish()));
+
+            let slice = self.readback_buffer.slice(..size);
+            slice.map_async(MapMode::Read, |_| ());
+            device.poll(Maintain::Wait);
+            {
+                let data = slice.get_mapped_range();
+                let timestamps: &[u64] = bytemuck::cast_slice(&data[..]);
+                for (entry, pass) in self.entries.iter_mut().zip(timestamps.chunks_exact(2)) {
+                    let ticks = pass[1].saturating_sub(pass[0]);
+                    entry.gpu_time = Some(Duration::from_nanos(
+                        (ticks as f64 * self.timestamp_period as f64) as u64,
+                    ));
+                }
+            }
+            self.readback_buffer.unmap();
+        }
+
+        self.report = ProfileReport {
+            entries: std::mem::take(&mut self.entries),
+        };
+    }
+
+    pub fn report(&self) -> &ProfileReport {
+        &self.report
+    }
+}
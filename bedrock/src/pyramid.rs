@@ -0,0 +1,328 @@
+use std::{
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+    ops::Range,
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+};
+
+use glam::{Vec2, Vec4};
+use rust_embed::RustEmbed;
+use shader::ShaderConstants;
+use wgpu::*;
+
+use crate::{
+    buffer::GrowableBuffer,
+    culling::{intersects, visible_rect},
+    mesh::{
+        create_bind_group, create_bind_group_layout, create_pipeline_layout,
+        create_render_pipeline, create_texture, MeshGpuVertex,
+    },
+    placeholder::MissingContent,
+    renderer::{Drawable, Resources},
+    scene::{ImagePyramid, Layer, PyramidTile},
+    sprite::{decode_image, DecodedImage},
+};
+
+// Tile textures kept around after they go offscreen, so panning back and forth doesn't reload
+// them. The least recently drawn are evicted first.
+const MAX_CACHED_TILES: usize = 256;
+
+struct LoadedTile {
+    _texture: Texture,
+    bind_group: BindGroup,
+    // Size of the tile's texture in pixels
+    size: Vec2,
+    // Frame the tile was last drawn in
+    last_used: u64,
+}
+
+struct TileDraw {
+    indices: Range<u32>,
+    tile: String,
+}
+
+// Decodes tiles on a background thread so large images don't stall frames while loading. The
+// thread exits once the state is dropped.
+struct TileLoader {
+    requests: Sender<String>,
+    results: Receiver<(String, Result<DecodedImage, String>)>,
+}
+
+impl TileLoader {
+    fn spawn<A: RustEmbed + 'static>() -> Self {
+        let (requests, request_receiver) = channel::<String>();
+        let (result_sender, results) = channel();
+        thread::spawn(move || {
+            for name in request_receiver {
+                let decoded = match A::get(&name) {
+                    Some(file) => decode_image(file.data.as_ref()),
+                    None => Err("no such asset".to_string()),
+                };
+                if result_sender.send((name, decoded)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { requests, results }
+    }
+}
+
+// Draws image pyramids from the tiles of the level matching each camera's zoom. Tiles which
+// haven't loaded yet are drawn from the closest coarser level that has, so the image sharpens
+// progressively instead of appearing in pieces.
+pub struct PyramidState<A: RustEmbed> {
+    vertices: GrowableBuffer<MeshGpuVertex>,
+    indices: GrowableBuffer<u32>,
+    bind_group_layout: BindGroupLayout,
+    pipeline_layout: PipelineLayout,
+    render_pipeline: Option<RenderPipeline>,
+    sampler: Sampler,
+    loader: TileLoader,
+    tiles: HashMap<String, LoadedTile>,
+    // Tiles requested from the loader which haven't arrived yet
+    pending: HashSet<String>,
+    // Tiles which are missing or couldn't be decoded. Kept so the error is only reported once
+    failed: HashSet<String>,
+    missing: Vec<MissingContent>,
+    frame: u64,
+    _assets: PhantomData<fn() -> A>,
+}
+
+impl<A: RustEmbed> PyramidState<A> {
+    fn receive_tiles(&mut self, device: &Device, queue: &Queue) {
+        while let Ok((name, decoded)) = self.loader.results.try_recv() {
+            self.pending.remove(&name);
+            let image = match decoded {
+                Ok(image) => image,
+                Err(error) => {
+                    eprintln!("Could not load pyramid tile {}: {}", name, error);
+                    self.failed.insert(name);
+                    continue;
+                }
+            };
+            let texture = create_texture(device, queue, image.width, image.height, &image.data);
+            let bind_group =
+                create_bind_group(device, &self.bind_group_layout, &texture, &self.sampler);
+            self.tiles.insert(
+                name,
+                LoadedTile {
+                    _texture: texture,
+                    bind_group,
+                    size: Vec2::new(image.width as f32, image.height as f32),
+                    last_used: self.frame,
+                },
+            );
+        }
+    }
+
+    fn request(&mut self, name: &str) {
+        if self.tiles.contains_key(name)
+            || self.pending.contains(name)
+            || self.failed.contains(name)
+        {
+            return;
+        }
+        if self.loader.requests.send(name.to_string()).is_ok() {
+            self.pending.insert(name.to_string());
+        }
+    }
+
+    // Finds the loaded tile closest to the requested level which covers the tile, starting with
+    // the tile itself
+    fn best_tile(
+        &self,
+        pyramid: &ImagePyramid,
+        tile: PyramidTile,
+    ) -> Option<(String, PyramidTile)> {
+        (tile.level..pyramid.levels()).find_map(|level| {
+            let shift = level - tile.level;
+            let name = pyramid.tile_name(level, tile.x >> shift, tile.y >> shift);
+            self.tiles
+                .contains_key(&name)
+                .then(|| (name, pyramid.tile(level, tile.x >> shift, tile.y >> shift)))
+        })
+    }
+
+    fn evict_tiles(&mut self) {
+        if self.tiles.len() <= MAX_CACHED_TILES {
+            return;
+        }
+        let mut by_age: Vec<_> = self
+            .tiles
+            .iter()
+            .filter(|(_, tile)| tile.last_used < self.frame)
+            .map(|(name, tile)| (tile.last_used, name.clone()))
+            .collect();
+        by_age.sort();
+        let excess = self.tiles.len() - MAX_CACHED_TILES;
+        for (_, name) in by_age.into_iter().take(excess) {
+            self.tiles.remove(&name);
+        }
+    }
+}
+
+impl<A: RustEmbed + 'static> Drawable for PyramidState<A> {
+    fn new(Resources { device, .. }: &Resources) -> Self {
+        let vertices = GrowableBuffer::new(device, "Pyramid vertex buffer", BufferUsages::VERTEX);
+        let indices = GrowableBuffer::new(device, "Pyramid index buffer", BufferUsages::INDEX);
+        let bind_group_layout = create_bind_group_layout(device);
+        let pipeline_layout = create_pipeline_layout(device, &bind_group_layout);
+
+        // Tiles are clamped so their edges don't bleed into each other
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Pyramid sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            vertices,
+            indices,
+            bind_group_layout,
+            pipeline_layout,
+            render_pipeline: None,
+            sampler,
+            loader: TileLoader::spawn::<A>(),
+            tiles: HashMap::new(),
+            pending: HashSet::new(),
+            failed: HashSet::new(),
+            missing: Vec::new(),
+            frame: 0,
+            _assets: PhantomData,
+        }
+    }
+
+    fn surface_updated(
+        &mut self,
+        Resources {
+            device,
+            surface_resources_manager,
+            ..
+        }: &Resources,
+    ) {
+        self.render_pipeline =
+            create_render_pipeline(device, &self.pipeline_layout, surface_resources_manager);
+    }
+
+    fn needs_draw(&self, layer: &Layer) -> bool {
+        !layer.pyramids.is_empty()
+    }
+
+    fn instance_count(&self) -> u64 {
+        self.indices.len() / 6
+    }
+
+    fn missing_content(&mut self) -> Vec<MissingContent> {
+        std::mem::take(&mut self.missing)
+    }
+
+    fn is_loading(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    fn draw<'b, 'a: 'b>(
+        &'a mut self,
+        Resources { device, queue, .. }: &'a Resources,
+        render_pass: &mut RenderPass<'b>,
+        constants: ShaderConstants,
+        layer: &Layer,
+    ) {
+        self.frame += 1;
+        self.receive_tiles(device, queue);
+
+        let visible = visible_rect(layer, constants.surface_size);
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut draws = Vec::new();
+        for pyramid in layer.pyramids.iter() {
+            if !intersects(pyramid.bounds(), visible) {
+                continue;
+            }
+            // The single tile of the coarsest level is always loaded so something can be shown
+            // at every zoom
+            let coarsest = pyramid.levels() - 1;
+            let top_name = pyramid.tile_name(coarsest, 0, 0);
+            if self.failed.contains(&top_name) {
+                self.missing
+                    .push(MissingContent::texture(pyramid.bounds(), top_name));
+                continue;
+            }
+            self.request(&top_name);
+
+            for tile in pyramid.visible_tiles(pyramid.level(), visible) {
+                self.request(&pyramid.tile_name(tile.level, tile.x, tile.y));
+                let Some((name, source)) = self.best_tile(pyramid, tile) else {
+                    continue;
+                };
+                let loaded = self.tiles.get_mut(&name).unwrap();
+                loaded.last_used = self.frame;
+
+                // Part of the source tile's texture covering this tile
+                let texel_size = source.region.z / loaded.size.x;
+                let uv_top_left = (Vec2::new(tile.region.x, tile.region.y)
+                    - Vec2::new(source.region.x, source.region.y))
+                    / (loaded.size * texel_size);
+                let uv_size = Vec2::new(tile.region.z, tile.region.w) / (loaded.size * texel_size);
+
+                let first_index = indices.len() as u32;
+                push_quad(
+                    &mut vertices,
+                    &mut indices,
+                    pyramid.screen_rect(tile.region),
+                    uv_top_left,
+                    uv_size,
+                    pyramid.depth,
+                );
+                draws.push(TileDraw {
+                    indices: first_index..indices.len() as u32,
+                    tile: name,
+                });
+            }
+        }
+        self.evict_tiles();
+        if draws.is_empty() {
+            return;
+        }
+
+        self.vertices.upload(device, queue, &vertices);
+        self.indices.upload(device, queue, &indices);
+
+        render_pass.set_pipeline(self.render_pipeline.as_ref().unwrap());
+        render_pass.set_push_constants(ShaderStages::all(), 0, bytemuck::cast_slice(&[constants]));
+        render_pass.set_vertex_buffer(0, self.vertices.buffer().slice(..));
+        render_pass.set_index_buffer(self.indices.buffer().slice(..), IndexFormat::Uint32);
+        for draw in draws {
+            render_pass.set_bind_group(0, &self.tiles[&draw.tile].bind_group, &[]);
+            render_pass.draw_indexed(self.indices.uploaded_range(draw.indices), 0, 0..1);
+        }
+    }
+}
+
+// Appends a white textured rectangle as two triangles
+fn push_quad(
+    vertices: &mut Vec<MeshGpuVertex>,
+    indices: &mut Vec<u32>,
+    rect: Vec4,
+    uv_top_left: Vec2,
+    uv_size: Vec2,
+    depth: f32,
+) {
+    let first = vertices.len() as u32;
+    for corner in [
+        Vec2::new(0.0, 0.0),
+        Vec2::new(1.0, 0.0),
+        Vec2::new(1.0, 1.0),
+        Vec2::new(0.0, 1.0),
+    ] {
+        let position = Vec2::new(rect.x, rect.y) + corner * Vec2::new(rect.z, rect.w);
+        vertices.push(MeshGpuVertex {
+            position: position.to_array(),
+            uv: (uv_top_left + corner * uv_size).to_array(),
+            color: [1.0; 4],
+            depth,
+        });
+    }
+    indices.extend([0, 1, 2, 0, 2, 3].map(|index| first + index));
+}
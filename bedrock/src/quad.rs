@@ -3,13 +3,15 @@ use shader::{InstancedQuad, ShaderConstants};
 use wgpu::*;
 
 use crate::{
+    buffer::GrowableBuffer,
+    culling::{intersects, visible_rect},
     renderer::{Drawable, Resources},
     scene::Layer,
     Quad,
 };
 
 pub struct QuadState {
-    buffer: Buffer,
+    buffer: GrowableBuffer<InstancedQuad>,
     bind_group_layout: BindGroupLayout,
     bind_group: BindGroup,
     render_pipeline: Option<RenderPipeline>,
@@ -17,12 +19,7 @@ pub struct QuadState {
 
 impl Drawable for QuadState {
     fn new(Resources { device, .. }: &Resources) -> Self {
-        let buffer = device.create_buffer(&BufferDescriptor {
-            label: Some("Quad buffer"),
-            size: std::mem::size_of::<InstancedQuad>() as u64 * 100000,
-            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        let buffer = GrowableBuffer::new(device, "Quad buffer", BufferUsages::STORAGE);
 
         let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: Some("Quad bind group layout"),
@@ -31,21 +28,14 @@ impl Drawable for QuadState {
                 visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
                 ty: BindingType::Buffer {
                     ty: BufferBindingType::Storage { read_only: true },
-                    has_dynamic_offset: false,
+                    has_dynamic_offset: true,
                     min_binding_size: None,
                 },
                 count: None,
             }],
         });
 
-        let bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: Some("Quad bind group"),
-            layout: &bind_group_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: buffer.as_entire_binding(),
-            }],
-        });
+        let bind_group = create_bind_group(device, &bind_group_layout, &buffer);
 
         Self {
             buffer,
@@ -65,6 +55,9 @@ impl Drawable for QuadState {
             ..
         }: &Resources,
     ) {
+        let Some(format) = surface_resources_manager.format() else {
+            return;
+        };
         let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("Quad Pipeline Layout"),
             bind_group_layouts: &[&self.bind_group_layout, &universal_bind_group_layout],
@@ -79,15 +72,15 @@ impl Drawable for QuadState {
             layout: Some(&render_pipeline_layout),
             vertex: VertexState {
                 module: shader,
-                entry_point: "quad::vertex",
+                entry_point: shader::QUAD_VERTEX,
                 buffers: &[],
             },
             fragment: Some(FragmentState {
                 module: shader,
-                entry_point: "quad::fragment",
+                entry_point: shader::QUAD_FRAGMENT,
                 targets: &[Some(ColorTargetState {
-                    format: surface_resources_manager.format(),
-                    blend: Some(BlendState::ALPHA_BLENDING),
+                    format,
+                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
                     write_mask: ColorWrites::ALL,
                 })],
             }),
@@ -100,25 +93,37 @@ impl Drawable for QuadState {
                 polygon_mode: PolygonMode::Fill,
                 conservative: false,
             },
-            depth_stencil: None,
+            depth_stencil: Some(surface_resources_manager.depth_stencil_state()),
             multisample: MultisampleState {
-                count: 4,
+                count: surface_resources_manager.sample_count(),
                 ..Default::default()
             },
             multiview: None,
         }));
     }
 
+    fn needs_draw(&self, layer: &Layer) -> bool {
+        layer.has_background() || !layer.material_quads.is_empty() || !layer.quads.is_empty()
+    }
+
+    fn instance_count(&self) -> u64 {
+        self.buffer.len()
+    }
+
     fn draw<'b, 'a: 'b>(
         &'a mut self,
-        queue: &Queue,
+        Resources {
+            device,
+            queue,
+            surface_resources_manager,
+            ..
+        }: &'a Resources,
         render_pass: &mut RenderPass<'b>,
         constants: ShaderConstants,
-        universal_bind_group: &'a BindGroup,
         layer: &Layer,
     ) {
         let mut quads = Vec::new();
-        if layer.background_color.is_some() || layer.background_blur_radius != 0.0 {
+        if layer.has_background() {
             quads.push(
                 Quad::new(
                     layer.clip.map(|clip| clip.xy()).unwrap_or(Vec2::ZERO),
@@ -133,15 +138,46 @@ impl Drawable for QuadState {
             );
         }
 
-        quads.extend(layer.quads.iter().map(|quad| quad.to_instanced()));
+        let visible = visible_rect(layer, constants.surface_size);
+        quads.extend(
+            layer
+                .material_quads
+                .iter()
+                .flat_map(|material_quad| material_quad.to_quads())
+                .filter(|quad| intersects(quad.bounds(), visible))
+                .map(|quad| quad.to_instanced()),
+        );
+        quads.extend(
+            layer
+                .quads
+                .iter()
+                .filter(|quad| intersects(quad.bounds(), visible))
+                .map(|quad| quad.to_instanced()),
+        );
 
         render_pass.set_pipeline(self.render_pipeline.as_ref().unwrap()); // 2.
         render_pass.set_push_constants(ShaderStages::all(), 0, bytemuck::cast_slice(&[constants]));
 
-        let quad_data: &[u8] = bytemuck::cast_slice(&quads[..]);
-        queue.write_buffer(&self.buffer, 0, quad_data);
-        render_pass.set_bind_group(0, &self.bind_group, &[]);
-        render_pass.set_bind_group(1, &universal_bind_group, &[]);
-        render_pass.draw(0..6, 0..quads.len() as u32);
+        if self.buffer.upload(device, queue, &quads) {
+            self.bind_group = create_bind_group(device, &self.bind_group_layout, &self.buffer);
+        }
+        render_pass.set_bind_group(1, surface_resources_manager.universal_bind_group(), &[]);
+        self.buffer
+            .draw_chunks(render_pass, 0, &self.bind_group, 0..6);
     }
 }
+
+fn create_bind_group(
+    device: &Device,
+    bind_group_layout: &BindGroupLayout,
+    buffer: &GrowableBuffer<InstancedQuad>,
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Quad bind group"),
+        layout: bind_group_layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: buffer.binding(),
+        }],
+    })
+}
@@ -0,0 +1,183 @@
+use glam::Vec2;
+use swash::{
+    scale::{Render, ScaleContext, Source, StrikeWith},
+    zeno::{Format, Placement, Vector},
+    GlyphId,
+};
+
+use crate::font::Font;
+
+// Coverage of a single glyph ready to be copied into the glyph atlas. Data is rgba with a
+// separate coverage for each subpixel in the color channels.
+pub(crate) struct RasterizedGlyph {
+    pub placement: Placement,
+    pub data: Vec<u8>,
+}
+
+// Turns glyph outlines into coverage images. Rendering fidelity and licensing differ between
+// rasterizers, so the backend is picked with the `system-raster` feature.
+pub(crate) trait GlyphRasterizer: Send {
+    // Rasterizes the glyph offset by a fraction of a pixel. Returns None for glyphs which
+    // couldn't be rendered
+    fn rasterize(
+        &mut self,
+        font: &Font,
+        glyph: GlyphId,
+        size: f32,
+        offset: Vec2,
+    ) -> Option<RasterizedGlyph>;
+}
+
+#[cfg(not(feature = "system-raster"))]
+pub(crate) fn default_rasterizer() -> Box<dyn GlyphRasterizer> {
+    Box::new(SwashRasterizer::new())
+}
+
+#[cfg(feature = "system-raster")]
+pub(crate) fn default_rasterizer() -> Box<dyn GlyphRasterizer> {
+    Box::new(system::SystemRasterizer::new())
+}
+
+// Renders glyphs with swash's own scaler, which handles hinting, color outlines, and bitmap
+// strikes the same way on every platform
+pub(crate) struct SwashRasterizer {
+    scale_context: ScaleContext,
+}
+
+impl SwashRasterizer {
+    pub fn new() -> Self {
+        Self {
+            scale_context: ScaleContext::new(),
+        }
+    }
+}
+
+impl GlyphRasterizer for SwashRasterizer {
+    fn rasterize(
+        &mut self,
+        font: &Font,
+        glyph: GlyphId,
+        size: f32,
+        offset: Vec2,
+    ) -> Option<RasterizedGlyph> {
+        let mut scaler = self
+            .scale_context
+            .builder(font.as_ref()?)
+            .size(size)
+            .hint(true)
+            .build();
+
+        let image = Render::new(&[
+            Source::ColorOutline(0),
+            Source::ColorBitmap(StrikeWith::BestFit),
+            Source::Outline,
+        ])
+        .format(Format::Subpixel)
+        .offset(Vector::new(offset.x, offset.y))
+        .render(&mut scaler, glyph)?;
+
+        Some(RasterizedGlyph {
+            placement: image.placement,
+            data: image.data,
+        })
+    }
+}
+
+// Renders glyphs with the platform's rasterizer through font-kit, so text matches the rest of
+// the system: Core Text on macOS, DirectWrite on Windows, and FreeType elsewhere. Color glyphs
+// and fonts the platform can't load fall back to swash.
+#[cfg(feature = "system-raster")]
+mod system {
+    use font_kit::{
+        canvas::{Canvas, Format, RasterizationOptions},
+        font::Font as NativeFont,
+        hinting::HintingOptions,
+    };
+    use glam::Vec2;
+    use pathfinder_geometry::{transform2d::Transform2F, vector::Vector2F};
+    use swash::{zeno::Placement, GlyphId};
+
+    use super::{GlyphRasterizer, RasterizedGlyph, SwashRasterizer};
+    use crate::font::Font;
+
+    pub(crate) struct SystemRasterizer {
+        fallback: SwashRasterizer,
+    }
+
+    impl SystemRasterizer {
+        pub fn new() -> Self {
+            Self {
+                fallback: SwashRasterizer::new(),
+            }
+        }
+    }
+
+    impl GlyphRasterizer for SystemRasterizer {
+        fn rasterize(
+            &mut self,
+            font: &Font,
+            glyph: GlyphId,
+            size: f32,
+            offset: Vec2,
+        ) -> Option<RasterizedGlyph> {
+            if font.has_color_glyphs() {
+                return self.fallback.rasterize(font, glyph, size, offset);
+            }
+
+            match font.with_native(|native| rasterize_native(native, glyph, size, offset)) {
+                Some(rasterized) => rasterized,
+                None => self.fallback.rasterize(font, glyph, size, offset),
+            }
+        }
+    }
+
+    fn rasterize_native(
+        native: &NativeFont,
+        glyph: GlyphId,
+        size: f32,
+        offset: Vec2,
+    ) -> Option<RasterizedGlyph> {
+        let glyph = glyph as u32;
+        let hinting = HintingOptions::Vertical(size);
+        let options = RasterizationOptions::SubpixelAa;
+        // Swash offsets point up while font-kit's raster space points down
+        let transform = Transform2F::from_translation(Vector2F::new(offset.x, -offset.y));
+        let bounds = native
+            .raster_bounds(glyph, size, transform, hinting, options)
+            .ok()?;
+        if bounds.width() <= 0 || bounds.height() <= 0 {
+            return None;
+        }
+
+        let mut canvas = Canvas::new(bounds.size(), Format::Rgb24);
+        native
+            .rasterize_glyph(
+                &mut canvas,
+                glyph,
+                size,
+                Transform2F::from_translation(-bounds.origin().to_f32()) * transform,
+                hinting,
+                options,
+            )
+            .ok()?;
+
+        let width = bounds.width() as usize;
+        let mut data = Vec::with_capacity(width * bounds.height() as usize * 4);
+        for row in canvas.pixels.chunks(canvas.stride) {
+            for pixel in row[..width * 3].chunks(3) {
+                data.extend_from_slice(pixel);
+                data.push(pixel[0].max(pixel[1]).max(pixel[2]));
+            }
+        }
+
+        Some(RasterizedGlyph {
+            placement: Placement {
+                left: bounds.origin_x(),
+                top: -bounds.origin_y(),
+                width: bounds.width() as u32,
+                height: bounds.height() as u32,
+            },
+            data,
+        })
+    }
+}
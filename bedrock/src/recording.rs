@@ -0,0 +1,181 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::scene::{Scene, SceneError, SCENE_SCHEMA_VERSION};
+
+const RECORDING_FORMAT: &str = "bedrock-recording";
+
+// Recordings are stored as json lines. The first line is a header and every following line
+// is a frame. Frames which draw the same scene as the frame before them omit the scene, so
+// static stretches of a recording cost a few bytes per frame.
+#[derive(Serialize, Deserialize)]
+struct Header {
+    format: String,
+    scene_version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FrameRecord {
+    time: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scene: Option<Scene>,
+}
+
+#[derive(Debug)]
+pub enum RecordingError {
+    Io(io::Error),
+    Scene(SceneError),
+    InvalidFormat(String),
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "could not access recording: {}", error),
+            Self::Scene(error) => write!(f, "invalid recorded scene: {}", error),
+            Self::InvalidFormat(reason) => write!(f, "invalid recording: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for RecordingError {}
+
+impl From<io::Error> for RecordingError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for RecordingError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Scene(SceneError::Json(error))
+    }
+}
+
+// Writes every scene passed to `Renderer::draw_scene` along with when it was drawn
+pub struct Recorder {
+    writer: BufWriter<File>,
+    start: Instant,
+    previous: Option<String>,
+}
+
+impl Recorder {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, RecordingError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(
+            &mut writer,
+            &Header {
+                format: RECORDING_FORMAT.to_string(),
+                scene_version: SCENE_SCHEMA_VERSION,
+            },
+        )?;
+        writeln!(writer)?;
+
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+            previous: None,
+        })
+    }
+
+    pub fn record(&mut self, scene: &Scene) -> Result<(), RecordingError> {
+        let time = self.start.elapsed().as_secs_f64();
+        let serialized = serde_json::to_string(scene)?;
+
+        if self.previous.as_ref() == Some(&serialized) {
+            serde_json::to_writer(&mut self.writer, &FrameRecord { time, scene: None })?;
+            writeln!(self.writer)?;
+        } else {
+            // The scene is already serialized, so splice it in rather than serializing twice
+            writeln!(
+                self.writer,
+                "{{\"time\":{},\"scene\":{}}}",
+                time, serialized
+            )?;
+            self.previous = Some(serialized);
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), RecordingError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub struct RecordedFrame {
+    // Time since the start of the recording when the frame was drawn
+    pub time: Duration,
+    // Shared with neighboring frames which drew the same scene
+    pub scene: Arc<Scene>,
+}
+
+pub struct Recording {
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl Recording {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RecordingError> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+
+        let header: Header = serde_json::from_str(
+            &lines
+                .next()
+                .ok_or_else(|| RecordingError::InvalidFormat("file is empty".to_string()))??,
+        )?;
+        if header.format != RECORDING_FORMAT {
+            return Err(RecordingError::InvalidFormat(format!(
+                "unknown format {}",
+                header.format
+            )));
+        }
+        if header.scene_version > SCENE_SCHEMA_VERSION {
+            return Err(RecordingError::Scene(SceneError::UnsupportedVersion {
+                found: header.scene_version,
+                supported: SCENE_SCHEMA_VERSION,
+            }));
+        }
+
+        let mut frames: Vec<RecordedFrame> = Vec::new();
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let record: FrameRecord = serde_json::from_str(&line)?;
+            let scene = match record.scene {
+                Some(scene) => Arc::new(scene),
+                None => frames
+                    .last()
+                    .map(|previous| previous.scene.clone())
+                    .ok_or_else(|| {
+                        RecordingError::InvalidFormat(
+                            "first frame does not contain a scene".to_string(),
+                        )
+                    })?,
+            };
+            frames.push(RecordedFrame {
+                time: Duration::from_secs_f64(record.time.max(0.0)),
+                scene,
+            });
+        }
+
+        Ok(Self { frames })
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.frames
+            .last()
+            .map(|frame| frame.time)
+            .unwrap_or_default()
+    }
+}
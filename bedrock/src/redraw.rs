@@ -0,0 +1,147 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use glam::Vec4;
+use winit::{
+    event::{Event, WindowEvent},
+    window::WindowId,
+};
+
+use crate::scene::{Guide, Layer, Scene, ThemeColor};
+
+// Skips frames whose scene matches the one last drawn into the window, so idle apps leave the
+// gpu alone. Windows are redrawn regardless once damaged, since their previous frame may no
+// longer be on screen.
+#[derive(Default)]
+pub(crate) struct RedrawTracker {
+    drawn: HashMap<WindowId, DrawnScene>,
+    damaged: HashSet<WindowId>,
+    // Set by `Renderer::request_redraw` to force the next frame of every window
+    forced: bool,
+}
+
+struct DrawnScene {
+    clear_color: Vec4,
+    guides: Vec<Guide>,
+    theme_colors: Vec<ThemeColor>,
+    // Layers are kept so unchanged layers shared through copy on write are compared by pointer
+    // rather than by content
+    layers: Vec<Arc<Layer>>,
+    hashes: Vec<u64>,
+}
+
+impl RedrawTracker {
+    pub fn needs_redraw(&self, window_id: WindowId, scene: &Scene) -> bool {
+        if self.forced || self.damaged.contains(&window_id) {
+            return true;
+        }
+        let Some(drawn) = self.drawn.get(&window_id) else {
+            return true;
+        };
+        drawn.clear_color != scene.clear_color
+            || drawn.guides != scene.guides
+            || drawn.theme_colors != scene.theme_colors
+            || drawn.layers.len() != scene.layers.len()
+            || scene
+                .layers
+                .iter()
+                .zip(drawn.layers.iter().zip(drawn.hashes.iter()))
+                .any(|(layer, (drawn_layer, drawn_hash))| {
+                    !Arc::ptr_eq(layer, drawn_layer)
+                        && (!layer.custom.same_items(&drawn_layer.custom)
+                            || hash_layer(layer) != *drawn_hash)
+                })
+    }
+
+    pub fn drawn(&mut self, window_id: WindowId, scene: &Scene) {
+        self.damaged.remove(&window_id);
+        if self.damaged.is_empty() {
+            self.forced = false;
+        }
+        self.drawn.insert(
+            window_id,
+            DrawnScene {
+                clear_color: scene.clear_color,
+                guides: scene.guides.clone(),
+                theme_colors: scene.theme_colors.clone(),
+                layers: scene.layers.iter().cloned().collect(),
+                hashes: scene.layers.iter().map(|layer| hash_layer(layer)).collect(),
+            },
+        );
+    }
+
+    pub fn damage(&mut self, window_id: WindowId) {
+        self.damaged.insert(window_id);
+    }
+
+    pub fn damage_all(&mut self) {
+        self.forced = true;
+        self.damaged.extend(self.drawn.keys().copied());
+    }
+
+    pub fn remove_window(&mut self, window_id: WindowId) {
+        self.drawn.remove(&window_id);
+        self.damaged.remove(&window_id);
+    }
+
+    // Notices events after which the window's previous frame may be gone
+    pub fn handle_event(&mut self, event: &Event<()>) {
+        match event {
+            Event::Resumed => self.damage_all(),
+            Event::WindowEvent { event, window_id } => match event {
+                WindowEvent::Resized(_)
+                | WindowEvent::ScaleFactorChanged { .. }
+                | WindowEvent::Occluded(false)
+                | WindowEvent::ThemeChanged(_) => self.damage(*window_id),
+                WindowEvent::Destroyed => self.remove_window(*window_id),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+// Layers contain floats so they can't derive Hash, so their serialized form is hashed like the
+// redundancy detector does. Only happens for layers which aren't shared with the last frame.
+pub(crate) fn hash_layer(layer: &Layer) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_vec(layer)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+    use crate::scene::Quad;
+
+    #[test]
+    fn test_only_changed_or_damaged_windows_redraw() {
+        let window_id = WindowId::from(0);
+        let scene = Scene::new().with_quad(Quad::new(vec2(0.0, 0.0), vec2(10.0, 10.0), Vec4::ONE));
+        let mut tracker = RedrawTracker::default();
+        assert!(tracker.needs_redraw(window_id, &scene));
+        tracker.drawn(window_id, &scene);
+
+        // Rebuilt but identical scenes are skipped too
+        let rebuilt =
+            Scene::new().with_quad(Quad::new(vec2(0.0, 0.0), vec2(10.0, 10.0), Vec4::ONE));
+        assert!(!tracker.needs_redraw(window_id, &scene));
+        assert!(!tracker.needs_redraw(window_id, &rebuilt));
+
+        let changed =
+            Scene::new().with_quad(Quad::new(vec2(5.0, 0.0), vec2(10.0, 10.0), Vec4::ONE));
+        assert!(tracker.needs_redraw(window_id, &changed));
+
+        tracker.damage(window_id);
+        assert!(tracker.needs_redraw(window_id, &scene));
+        tracker.drawn(window_id, &scene);
+        assert!(!tracker.needs_redraw(window_id, &scene));
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use serde::Serialize;
+
+use crate::scene::Scene;
+
+// Debug helper which notices primitives that are rebuilt identically frame after frame. Scenes
+// are immutable, so redrawing unchanged content costs a full rebuild and upload each frame.
+// Warnings point at the primitives which would benefit from being cached.
+pub(crate) struct RedundancyDetector {
+    threshold: u64,
+    frame: u64,
+    primitives: HashMap<PrimitiveKey, PrimitiveHistory>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct PrimitiveKey {
+    layer: usize,
+    kind: &'static str,
+    index: usize,
+}
+
+struct PrimitiveHistory {
+    hash: u64,
+    identical_frames: u64,
+    last_seen: u64,
+    warned: bool,
+}
+
+impl RedundancyDetector {
+    pub fn new(threshold: u64) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            frame: 0,
+            primitives: HashMap::new(),
+        }
+    }
+
+    // Records the scene's primitives and returns warnings for those which just crossed the
+    // threshold. Each primitive is only reported once until it changes.
+    pub fn observe(&mut self, scene: &Scene) -> Vec<String> {
+        self.frame += 1;
+        let mut warnings = Vec::new();
+
+        for (layer_index, layer) in scene.layers.iter().enumerate() {
+            self.observe_all(layer_index, "Quad", &layer.quads, &mut warnings);
+            self.observe_all(
+                layer_index,
+                "MaterialQuad",
+                &layer.material_quads,
+                &mut warnings,
+            );
+            self.observe_all(layer_index, "Text", &layer.texts, &mut warnings);
+            self.observe_all(layer_index, "Path", &layer.paths, &mut warnings);
+            self.observe_all(layer_index, "Sprite", &layer.sprites, &mut warnings);
+            self.observe_all(
+                layer_index,
+                "ShaderQuad",
+                &layer.shader_quads,
+                &mut warnings,
+            );
+            self.observe_all(layer_index, "Mirror", &layer.mirrors, &mut warnings);
+        }
+
+        // Forget primitives which are no longer in the scene
+        let frame = self.frame;
+        self.primitives
+            .retain(|_, history| history.last_seen == frame);
+
+        warnings
+    }
+
+    fn observe_all<T: Serialize>(
+        &mut self,
+        layer: usize,
+        kind: &'static str,
+        items: &[T],
+        warnings: &mut Vec<String>,
+    ) {
+        for (index, item) in items.iter().enumerate() {
+            let key = PrimitiveKey { layer, kind, index };
+            let hash = hash_primitive(item);
+            let history = self
+                .primitives
+                .entry(key)
+                .or_insert_with(|| PrimitiveHistory {
+                    hash,
+                    identical_frames: 0,
+                    last_seen: 0,
+                    warned: false,
+                });
+
+            if history.hash == hash && history.last_seen + 1 == self.frame {
+                history.identical_frames += 1;
+            } else {
+                history.hash = hash;
+                history.identical_frames = 1;
+                history.warned = false;
+            }
+            history.last_seen = self.frame;
+
+            if !history.warned && history.identical_frames >= self.threshold {
+                history.warned = true;
+                warnings.push(format!(
+                    "{} {} in layer {} has been identical for {} frames. Consider caching it \
+                     instead of rebuilding it every frame",
+                    kind, index, layer, history.identical_frames
+                ));
+            }
+        }
+    }
+}
+
+// Primitives contain floats so they can't derive Hash. Their serialized form is hashed instead,
+// which is slow but only happens when the detector is enabled.
+fn hash_primitive<T: Serialize>(item: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_vec(item)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{vec2, vec4};
+
+    use super::*;
+    use crate::scene::Quad;
+
+    #[test]
+    fn test_warns_once_per_change() {
+        let mut detector = RedundancyDetector::new(3);
+        let quad = Quad::new(vec2(0.0, 0.0), vec2(1.0, 1.0), vec4(1.0, 1.0, 1.0, 1.0));
+        let scene = Scene::new().with_quad(quad.clone());
+
+        assert!(detector.observe(&scene).is_empty());
+        assert!(detector.observe(&scene).is_empty());
+        assert_eq!(detector.observe(&scene).len(), 1);
+        assert!(detector.observe(&scene).is_empty());
+
+        let changed = Scene::new().with_quad(quad.with_corner_radius(2.0));
+        assert!(detector.observe(&changed).is_empty());
+    }
+}
@@ -0,0 +1,216 @@
+use std::{any::Any, collections::HashMap, fmt, sync::Arc};
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    renderer::{Drawable, Resources},
+    scene::{CustomItems, Layer, Scene},
+};
+
+type PrimitiveLoader =
+    Box<dyn Fn(serde_json::Value, &mut CustomItems) -> Result<(), serde_json::Error> + Send + Sync>;
+type DrawableFactory = Box<dyn Fn(&Resources) -> Box<dyn Drawable> + Send + Sync>;
+
+#[derive(Debug)]
+pub enum RegistryError {
+    UnknownPrimitive {
+        layer: usize,
+        kind: String,
+    },
+    InvalidPrimitive {
+        layer: usize,
+        kind: String,
+        error: serde_json::Error,
+    },
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownPrimitive { layer, kind } => {
+                write!(f, "layer {} uses unregistered primitive {}", layer, kind)
+            }
+            Self::InvalidPrimitive { layer, kind, error } => {
+                write!(
+                    f,
+                    "invalid {} primitive in layer {}: {}",
+                    kind, layer, error
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+// Drawables and custom primitives registered by name. Extension crates usually expose a
+// function which registers everything they provide, so hosts only have to call it before
+// creating their renderer and loading scenes.
+#[derive(Default)]
+pub struct Registry {
+    primitives: HashMap<String, PrimitiveLoader>,
+    // Kept in registration order, which is the order the drawables draw in
+    drawables: Vec<(String, DrawableFactory)>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    // Named items of this kind are deserialized into T and attached as custom items, where a
+    // drawable can pick them up with `layer.custom.get::<T>()`
+    pub fn register_primitive<T: DeserializeOwned + Any + Send + Sync>(
+        &mut self,
+        kind: impl Into<String>,
+    ) {
+        self.primitives.insert(
+            kind.into(),
+            Box::new(
+                |data: serde_json::Value,
+                 custom: &mut CustomItems|
+                 -> Result<(), serde_json::Error> {
+                    custom.add(serde_json::from_value::<T>(data)?);
+                    Ok(())
+                },
+            ),
+        );
+    }
+
+    pub fn with_primitive<T: DeserializeOwned + Any + Send + Sync>(
+        mut self,
+        kind: impl Into<String>,
+    ) -> Self {
+        self.register_primitive::<T>(kind);
+        self
+    }
+
+    // Registering a drawable under a name which is already taken replaces the earlier one in
+    // place
+    pub fn register_drawable<T: Drawable + 'static>(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        let factory: DrawableFactory =
+            Box::new(|resources: &Resources| -> Box<dyn Drawable> { Box::new(T::new(resources)) });
+        match self
+            .drawables
+            .iter_mut()
+            .find(|(existing, _)| *existing == name)
+        {
+            Some((_, existing)) => *existing = factory,
+            None => self.drawables.push((name, factory)),
+        }
+    }
+
+    pub fn with_drawable<T: Drawable + 'static>(mut self, name: impl Into<String>) -> Self {
+        self.register_drawable::<T>(name);
+        self
+    }
+
+    pub fn has_primitive(&self, kind: &str) -> bool {
+        self.primitives.contains_key(kind)
+    }
+
+    pub fn drawable_names(&self) -> impl Iterator<Item = &str> {
+        self.drawables.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub(crate) fn create_drawable(
+        &self,
+        name: &str,
+        resources: &Resources,
+    ) -> Option<Box<dyn Drawable>> {
+        self.drawables
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, factory)| factory(resources))
+    }
+
+    pub(crate) fn create_drawables(&self, resources: &Resources) -> Vec<Box<dyn Drawable>> {
+        self.drawables
+            .iter()
+            .map(|(_, factory)| factory(resources))
+            .collect()
+    }
+
+    // Turns the named items of every layer into custom items. Resolved items are removed from
+    // the named list, so like other custom items they aren't saved with the scene afterwards.
+    // Stops at the first item which isn't registered or doesn't match its registered type
+    pub fn resolve(&self, scene: &mut Scene) -> Result<(), RegistryError> {
+        for (index, layer) in scene.layers.iter_mut().enumerate() {
+            if !layer.named.is_empty() {
+                self.resolve_layer(Arc::make_mut(layer), index)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve_layer(&self, layer: &mut Layer, index: usize) -> Result<(), RegistryError> {
+        for item in std::mem::take(&mut layer.named) {
+            let Some(loader) = self.primitives.get(&item.kind) else {
+                return Err(RegistryError::UnknownPrimitive {
+                    layer: index,
+                    kind: item.kind,
+                });
+            };
+            loader(item.data, &mut layer.custom).map_err(|error| {
+                RegistryError::InvalidPrimitive {
+                    layer: index,
+                    kind: item.kind,
+                    error,
+                }
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registry")
+            .field("primitives", &self.primitives.keys().collect::<Vec<_>>())
+            .field("drawables", &self.drawable_names().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+    use crate::scene::NamedItem;
+
+    #[derive(Deserialize)]
+    struct Gauge {
+        value: f32,
+    }
+
+    #[test]
+    fn test_named_items_resolve_to_custom_items() {
+        let registry = Registry::new().with_primitive::<Gauge>("gauge");
+        let mut scene = Scene::new().with_named(NamedItem::new("gauge", json!({ "value": 0.5 })));
+        registry.resolve(&mut scene).unwrap();
+
+        let values: Vec<f32> = scene
+            .layer()
+            .custom
+            .get::<Gauge>()
+            .map(|gauge| gauge.value)
+            .collect();
+        assert_eq!(values, vec![0.5]);
+        assert!(scene.layer().named.is_empty());
+
+        let mut scene = Scene::new().with_named(NamedItem::new("chart", json!({})));
+        assert!(matches!(
+            registry.resolve(&mut scene),
+            Err(RegistryError::UnknownPrimitive { layer: 0, .. })
+        ));
+        let mut scene =
+            Scene::new().with_named(NamedItem::new("gauge", json!({ "value": "full" })));
+        assert!(matches!(
+            registry.resolve(&mut scene),
+            Err(RegistryError::InvalidPrimitive { .. })
+        ));
+    }
+}
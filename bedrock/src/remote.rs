@@ -0,0 +1,211 @@
+use std::{
+    fmt,
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use crate::scene::{Scene, SceneError};
+
+// Scenes larger than this are rejected rather than allocated, so a corrupt length prefix
+// can't exhaust the receiver's memory
+pub const MAX_MESSAGE_SIZE: u32 = 64 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum RemoteError {
+    Io(io::Error),
+    Scene(SceneError),
+    MessageTooLarge(u64),
+}
+
+impl fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "remote connection failed: {}", error),
+            Self::Scene(error) => write!(f, "invalid remote scene: {}", error),
+            Self::MessageTooLarge(size) => write!(
+                f,
+                "scene message of {} bytes is larger than the {} byte limit",
+                size, MAX_MESSAGE_SIZE
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RemoteError {}
+
+impl From<io::Error> for RemoteError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<SceneError> for RemoteError {
+    fn from(error: SceneError) -> Self {
+        Self::Scene(error)
+    }
+}
+
+// Each message is a scene's versioned json prefixed with its length in bytes as a big endian
+// u32. Simple enough to write from any language with a socket and a json library.
+pub fn write_scene(writer: &mut impl Write, scene: &Scene) -> Result<(), RemoteError> {
+    let mut json = Vec::new();
+    scene.to_compact_writer(&mut json)?;
+    if json.len() as u64 > MAX_MESSAGE_SIZE as u64 {
+        return Err(RemoteError::MessageTooLarge(json.len() as u64));
+    }
+    writer.write_all(&(json.len() as u32).to_be_bytes())?;
+    writer.write_all(&json)?;
+    writer.flush()?;
+    Ok(())
+}
+
+// Reads the next scene, or None if the stream closed cleanly between messages
+pub fn read_scene(reader: &mut impl Read) -> Result<Option<Scene>, RemoteError> {
+    let mut length = [0; 4];
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error.into()),
+    }
+    let length = u32::from_be_bytes(length);
+    if length > MAX_MESSAGE_SIZE {
+        return Err(RemoteError::MessageTooLarge(length as u64));
+    }
+
+    let mut json = vec![0; length as usize];
+    reader.read_exact(&mut json)?;
+    Ok(Some(Scene::from_reader(json.as_slice())?))
+}
+
+// Sends scenes to a `RemoteServer`, such as one running on an embedded target
+pub struct RemoteClient {
+    writer: BufWriter<TcpStream>,
+}
+
+impl RemoteClient {
+    pub fn connect(address: impl ToSocketAddrs) -> Result<Self, RemoteError> {
+        let stream = TcpStream::connect(address)?;
+        // Scenes are sent one at a time as frames are built, so don't wait to batch them
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            writer: BufWriter::new(stream),
+        })
+    }
+
+    pub fn send(&mut self, scene: &Scene) -> Result<(), RemoteError> {
+        write_scene(&mut self.writer, scene)
+    }
+}
+
+// Accepts connections on a background thread and keeps the most recent scene received from
+// any of them. Scenes arriving faster than they are taken replace each other, so a slow
+// renderer always shows the latest frame instead of falling behind. Connections which send
+// invalid messages are dropped. The threads live until the process exits.
+pub struct RemoteServer {
+    address: SocketAddr,
+    latest: Arc<Mutex<Option<Scene>>>,
+}
+
+impl RemoteServer {
+    // `on_scene` is called from the connection's thread after each scene arrives, so event
+    // loops can be woken to draw it
+    pub fn bind(
+        address: impl ToSocketAddrs,
+        on_scene: impl Fn() + Send + Sync + 'static,
+    ) -> Result<Self, RemoteError> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let latest = Arc::new(Mutex::new(None));
+        let on_scene = Arc::new(on_scene);
+
+        thread::spawn({
+            let latest = latest.clone();
+            move || {
+                for stream in listener.incoming() {
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(error) => {
+                            tracing::warn!("Could not accept remote connection: {}", error);
+                            continue;
+                        }
+                    };
+                    let latest = latest.clone();
+                    let on_scene = on_scene.clone();
+                    thread::spawn(move || {
+                        let peer = stream.peer_addr().ok();
+                        let mut reader = BufReader::new(stream);
+                        loop {
+                            match read_scene(&mut reader) {
+                                Ok(Some(scene)) => {
+                                    *latest.lock().unwrap() = Some(scene);
+                                    on_scene();
+                                }
+                                Ok(None) => break,
+                                Err(error) => {
+                                    tracing::warn!(
+                                        "Dropping remote connection from {:?}: {}",
+                                        peer,
+                                        error
+                                    );
+                                    break;
+                                }
+                            }
+                        }
+                    });
+                }
+            }
+        });
+
+        Ok(Self { address, latest })
+    }
+
+    // Useful when bound to port 0
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    // The newest scene received since the last call
+    pub fn take_scene(&self) -> Option<Scene> {
+        self.latest.lock().unwrap().take()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::mpsc, time::Duration};
+
+    use glam::{vec2, Vec4};
+
+    use super::*;
+    use crate::scene::Quad;
+
+    #[test]
+    fn test_scenes_round_trip_through_server() {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let server = RemoteServer::bind("127.0.0.1:0", move || {
+            sender.lock().unwrap().send(()).unwrap();
+        })
+        .unwrap();
+
+        let scene = Scene::new().with_quad(Quad::new(vec2(1.0, 2.0), vec2(3.0, 4.0), Vec4::ONE));
+        let mut client = RemoteClient::connect(server.local_addr()).unwrap();
+        client.send(&scene).unwrap();
+
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        let received = server.take_scene().unwrap();
+        assert_eq!(received.layers[0].quads[0].top_left(), vec2(1.0, 2.0));
+        assert!(server.take_scene().is_none());
+    }
+
+    #[test]
+    fn test_oversized_messages_are_rejected() {
+        let message = (MAX_MESSAGE_SIZE + 1).to_be_bytes();
+        assert!(matches!(
+            read_scene(&mut message.as_slice()),
+            Err(RemoteError::MessageTooLarge(_))
+        ));
+    }
+}
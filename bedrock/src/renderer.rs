@@ -1,74 +1,1154 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    fmt,
+    path::Path,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
 
 use rust_embed::RustEmbed;
 use wgpu::*;
 
 use glam::*;
-use shader::ShaderConstants;
-use winit::{event::Event, window::Window};
+use shader::{ShaderConstants, ShaderFeatures};
+use winit::{
+    dpi::PhysicalSize,
+    event::{Event, WindowEvent},
+    raw_window_handle::{RawDisplayHandle, RawWindowHandle},
+    window::{Icon, Window, WindowId},
+};
 
 pub use crate::resources::Resources;
 use crate::{
-    glyph::GlyphState, path::PathState, quad::QuadState, scene::Layer, sprite::SpriteState, Scene,
+    color_space::{ColorConversion, ColorSpace},
+    contrast::{read_backdrop, BackdropContrast},
+    dither::Dither,
+    easing::Easing,
+    ellipse::EllipseState,
+    extension::{ExtensionKind, ShaderExtension, ShaderExtensionError},
+    frame_hook::FrameHookContext,
+    frame_limiter::{FrameLimitStrategy, FrameLimiter, FrameStats},
+    glyph::{GlyphState, SubpixelOrder, TextRendering},
+    gpu_path::GpuPathState,
+    guide::GuideState,
+    histogram::{Histogram, HistogramPass},
+    mesh::MeshState,
+    mirror::MirrorState,
+    particle::ParticleState,
+    path::PathState,
+    pixel_probe::PixelProbe,
+    placeholder::{MissingContent, Placeholder},
+    polyline::PolylineState,
+    profiler::{ProfileReport, Profiler},
+    pyramid::PyramidState,
+    quad::QuadState,
+    recording::{Recorder, Recording, RecordingError},
+    redraw::RedrawTracker,
+    redundancy::RedundancyDetector,
+    registry::Registry,
+    scene::{ColorScheme, Layer, Path as ScenePath, SafeAreaInsets, ValidationWarning},
+    shader_abi::ShaderAbiError,
+    shader_quad::ShaderQuadState,
+    shaper::{character_outline, glyph_outline},
+    sprite::SpriteState,
+    surface_wrapper::SurfaceSource,
+    transition::TransitionKind,
+    visual_diff::{diff_images, diff_scenes, VisualDiff},
+    watchdog::{degrade, Watchdog},
+    Scene,
 };
 
-pub trait Drawable {
+// Drawables are sent to rayon's thread pool when parallel encoding is enabled, so they must be
+// Send
+pub trait Drawable: Send {
     fn new(resources: &Resources) -> Self
     where
         Self: Sized;
 
+    // Called whenever the surface is created or changes in a way pipelines depend on. Render
+    // pipelines must match the surface manager's format, depth stencil state, and sample count
     fn surface_updated(&mut self, resources: &Resources);
 
+    // Whether this drawable has anything to contribute for the given layer. Drawables which
+    // return false are skipped entirely, so no pipeline is bound and no render pass is begun.
+    // Drawables which don't say are drawn for every layer
+    fn needs_draw(&self, _layer: &Layer) -> bool {
+        true
+    }
+
+    // Name used to attribute costs to this drawable in profile reports
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    // Number of instances (or triangles for non instanced drawables) submitted by the most
+    // recent call to draw
+    fn instance_count(&self) -> u64 {
+        0
+    }
+
+    // Bounds of items from the most recent call to draw which couldn't be drawn because their
+    // assets weren't available. The renderer's placeholder policy is drawn over each of them.
+    fn missing_content(&mut self) -> Vec<MissingContent> {
+        Vec::new()
+    }
+
+    // Whether assets are still loading in the background. Frames keep being drawn until they
+    // arrive, even when redrawing on demand and the scene hasn't changed.
+    fn is_loading(&self) -> bool {
+        false
+    }
+
+    // Called once per frame before any layer is drawn. Work which should happen once a frame
+    // however many layers or render targets show the drawable's items, such as stepping a
+    // simulation, is recorded into the frame's encoder, which is submitted ahead of the layers
+    fn prepare_frame(
+        &mut self,
+        _resources: &Resources,
+        _encoder: &mut CommandEncoder,
+        _scene: &Scene,
+    ) {
+    }
+
+    // Resources are borrowed for as long as the render pass, so drawables can create buffers
+    // lazily and bind gpu objects owned by the resources, such as the universal bind group
     fn draw<'b, 'a: 'b>(
         &'a mut self,
-        queue: &Queue,
+        resources: &'a Resources,
         render_pass: &mut RenderPass<'b>,
         constants: ShaderConstants,
-        universal_bind_group: &'a BindGroup,
         layer: &Layer,
     );
 }
 
+#[derive(Debug)]
+pub enum RenderError {
+    Surface(SurfaceError),
+    // Sprite textures which couldn't be loaded. Only reported in strict texture mode
+    MissingTextures(Vec<String>),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Surface(error) => write!(f, "could not draw to surface: {}", error),
+            Self::MissingTextures(textures) => {
+                write!(f, "missing sprite textures: {}", textures.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
 pub struct Renderer {
     pub(crate) resources: Resources,
     pub(crate) drawables: Vec<Box<dyn Drawable>>,
+    recorder: Option<Recorder>,
+    redundancy_detector: Option<RedundancyDetector>,
+    redraw_tracker: Option<RedrawTracker>,
+    frame_limiter: Option<FrameLimiter>,
+    frame_stats: FrameStats,
+    watchdog: Option<Watchdog>,
+    strict_textures: bool,
+    // Appearance each window's platform prefers, updated as the preference changes
+    color_schemes: HashMap<WindowId, ColorScheme>,
+    color_scheme_callback: Option<Box<dyn FnMut(WindowId, ColorScheme)>>,
+    // Checks drawn scenes for invalid data, on by default in debug builds. Holds the warnings
+    // of the last scene so each problem is only printed when it appears
+    scene_validation: Option<Vec<ValidationWarning>>,
 }
 
 impl Renderer {
-    // Creating some of the wgpu types requires async code
+    // Creating some of the wgpu types requires async code. Panics if the embedded shader
+    // doesn't match this version of the host
     pub async fn new(window: Arc<Window>) -> Self {
-        let resources = Resources::new(window).await;
+        Self::try_new(window)
+            .await
+            .unwrap_or_else(|error| panic!("Shader and host are out of sync: {}", error))
+    }
+
+    // Same as `new`, but returns an error when the embedded shader is missing or was built
+    // from a different version of the shader crate
+    pub async fn try_new(window: Arc<Window>) -> Result<Self, ShaderAbiError> {
+        let color_scheme = window.theme().map(ColorScheme::from);
+        let mut renderer = Self::from_resources(Resources::try_new(window).await?);
+        if let Some(color_scheme) = color_scheme {
+            let window_id = renderer.resources.primary_window;
+            renderer.color_schemes.insert(window_id, color_scheme);
+        }
+        Ok(renderer)
+    }
 
+    // Renders into a native window owned by a host outside of winit, such as a C++ or Swift
+    // app embedding the renderer. The surface is created right away, and since no winit events
+    // arrive for the window the host has to call `resize_surface` when it changes size.
+    //
+    // Safety: the handles must stay valid until the renderer is dropped
+    pub async unsafe fn from_raw_handle(
+        display: RawDisplayHandle,
+        window: RawWindowHandle,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        // Raw windows have no winit id, but the renderer only ever draws into this one
+        let window_id = WindowId::from(u64::MAX);
+        let mut resources = Resources::with_source(
+            window_id,
+            SurfaceSource::RawHandle {
+                display,
+                window,
+                size: PhysicalSize::new(width, height),
+            },
+        )
+        .await
+        .unwrap_or_else(|error| panic!("Shader and host are out of sync: {}", error));
+        resources.start_surfaces();
+        Self::from_resources(resources)
+    }
+
+    // Renders without a window, for tools and scripts which only want images from
+    // `render_rgba`. Drawing scenes to the window does nothing since there is nothing to present
+    // them on. Panics if the embedded shader doesn't match this version of the host
+    pub async fn headless(width: u32, height: u32) -> Self {
+        Self::try_headless(width, height)
+            .await
+            .unwrap_or_else(|error| panic!("Shader and host are out of sync: {}", error))
+    }
+
+    // Same as `headless`, but returns an error when the embedded shader is missing or was built
+    // from a different version of the shader crate
+    pub async fn try_headless(width: u32, height: u32) -> Result<Self, ShaderAbiError> {
+        // Headless renderers have no winit window, but only ever draw into this one
+        let window_id = WindowId::from(u64::MAX);
+        let mut resources = Resources::with_source(
+            window_id,
+            SurfaceSource::Headless {
+                size: PhysicalSize::new(width, height),
+            },
+        )
+        .await?;
+        resources.start_surfaces();
+        Ok(Self::from_resources(resources))
+    }
+
+    fn from_resources(resources: Resources) -> Self {
         Self {
             resources,
             drawables: Vec::new(),
+            recorder: None,
+            redundancy_detector: None,
+            redraw_tracker: None,
+            frame_limiter: None,
+            frame_stats: FrameStats::default(),
+            watchdog: None,
+            strict_textures: false,
+            color_schemes: HashMap::new(),
+            color_scheme_callback: None,
+            scene_validation: cfg!(debug_assertions).then(Vec::new),
         }
     }
 
+    // Drawables are drawn in the order they are added. Custom drawables usually draw items of
+    // their own type attached with `Layer::add_custom`, checking `layer.custom.contains::<T>()`
+    // in `needs_draw`
     pub fn with_drawable<T: Drawable + 'static>(mut self) -> Self {
         let drawable = T::new(&self.resources);
-        self.drawables.push(Box::new(drawable));
+        self.push_drawable(Box::new(drawable));
         self
     }
 
+    // Adds every drawable in the registry in the order they were registered
+    pub fn with_registered_drawables(mut self, registry: &Registry) -> Self {
+        for drawable in registry.create_drawables(&self.resources) {
+            self.push_drawable(drawable);
+        }
+        self
+    }
+
+    // Adds a single drawable from the registry. Unknown names are ignored and return false
+    pub fn add_registered_drawable(&mut self, registry: &Registry, name: &str) -> bool {
+        let Some(drawable) = registry.create_drawable(name, &self.resources) else {
+            return false;
+        };
+        self.push_drawable(drawable);
+        true
+    }
+
+    fn push_drawable(&mut self, mut drawable: Box<dyn Drawable>) {
+        // Surfaces created eagerly already exist, so build the pipelines now
+        if self.resources.surface_resources_manager.ready() {
+            drawable.surface_updated(&self.resources);
+        }
+        self.drawables.push(drawable);
+    }
+
     pub fn with_default_drawables<A: RustEmbed + 'static>(self) -> Self {
         self.with_drawable::<QuadState>()
+            .with_drawable::<EllipseState>()
             .with_drawable::<GlyphState>()
             .with_drawable::<PathState>()
+            .with_drawable::<GpuPathState>()
+            .with_drawable::<PolylineState>()
+            .with_drawable::<MeshState<A>>()
+            .with_drawable::<PyramidState<A>>()
             .with_drawable::<SpriteState<A>>()
+            .with_drawable::<ShaderQuadState>()
+            .with_drawable::<ParticleState>()
+            .with_drawable::<MirrorState>()
+            .with_drawable::<GuideState>()
     }
 
+    // Draws the scene into the window the renderer was created with
     pub fn draw_scene(&mut self, scene: &Scene) -> bool {
-        if let Err(render_error) = self.resources.render(scene, self.drawables.as_mut_slice()) {
-            eprintln!("Render error: {:?}", render_error);
+        self.draw_window_scene(self.resources.primary_window, scene)
+    }
+
+    // Draws the scene into any window added with `add_window`. Windows share drawables, so
+    // atlases and pipelines are only built once
+    pub fn draw_window_scene(&mut self, window_id: WindowId, scene: &Scene) -> bool {
+        if let Err(render_error) = self.try_draw_window_scene(window_id, scene) {
+            eprintln!("Render error: {}", render_error);
             false
         } else {
             true
         }
     }
 
+    // Same as `draw_scene`, but returns why the frame couldn't be drawn
+    pub fn try_draw_scene(&mut self, scene: &Scene) -> Result<(), RenderError> {
+        self.try_draw_window_scene(self.resources.primary_window, scene)
+    }
+
+    // Same as `draw_window_scene`, but returns why the frame couldn't be drawn
+    pub fn try_draw_window_scene(
+        &mut self,
+        window_id: WindowId,
+        scene: &Scene,
+    ) -> Result<(), RenderError> {
+        if !self.needs_window_redraw(window_id, scene) {
+            return Ok(());
+        }
+
+        // Other windows are drawn in the same frame, so only the primary window is paced
+        if window_id == self.resources.primary_window {
+            if let Some(limiter) = self.frame_limiter.as_mut() {
+                limiter.wait();
+            }
+            self.frame_stats.frame_started(Instant::now());
+        }
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(error) = recorder.record(scene) {
+                eprintln!("Stopped recording: {}", error);
+                self.recorder = None;
+            }
+        }
+
+        if let Some(detector) = self.redundancy_detector.as_mut() {
+            for warning in detector.observe(scene) {
+                eprintln!("{}", warning);
+            }
+        }
+
+        if let Some(reported) = self.scene_validation.as_mut() {
+            let warnings = scene.validate();
+            for warning in warnings
+                .iter()
+                .filter(|warning| !reported.contains(warning))
+            {
+                eprintln!("Invalid scene data: {}", warning);
+            }
+            *reported = warnings;
+        }
+
+        let original = scene;
+        let themed = scene.themed(self.color_scheme(window_id));
+        let scene = themed.as_ref();
+        let degraded = self
+            .watchdog
+            .as_ref()
+            .filter(|watchdog| watchdog.degraded())
+            .map(|_| degrade(scene));
+        let scene = degraded.as_ref().unwrap_or(scene);
+        // Degraded frames are drawn without multisampling, which needs pipelines built for a
+        // single sample
+        if self.resources.set_multisampling(degraded.is_none()) {
+            for drawable in self.drawables.iter_mut() {
+                drawable.surface_updated(&self.resources);
+            }
+        }
+
+        let start = Instant::now();
+        let result = self
+            .resources
+            .render(window_id, scene, self.drawables.as_mut_slice());
+        if let Some(watchdog) = self.watchdog.as_mut() {
+            watchdog.frame_finished(
+                &self.resources.device,
+                &self.resources.queue,
+                start.elapsed(),
+            );
+        }
+
+        result.map_err(RenderError::Surface)?;
+        if let Some(tracker) = self.redraw_tracker.as_mut() {
+            tracker.drawn(window_id, original);
+        }
+        if self.strict_textures && !self.resources.missing_textures.is_empty() {
+            return Err(RenderError::MissingTextures(
+                self.resources.missing_textures.clone(),
+            ));
+        }
+        Ok(())
+    }
+
+    // Caps how often frames are drawn into the primary window, on top of any vsync. Drawing
+    // calls block until the next frame is due
+    pub fn with_frame_limit(mut self, fps: f32, strategy: FrameLimitStrategy) -> Self {
+        self.set_frame_limit(fps, strategy);
+        self
+    }
+
+    pub fn set_frame_limit(&mut self, fps: f32, strategy: FrameLimitStrategy) {
+        self.frame_limiter = Some(FrameLimiter::new(fps, strategy));
+    }
+
+    pub fn clear_frame_limit(&mut self) {
+        self.frame_limiter = None;
+    }
+
+    // Recent frame times of the primary window
+    pub fn frame_stats(&self) -> &FrameStats {
+        &self.frame_stats
+    }
+
+    // Skips drawing scenes identical to the one last drawn into the window, so apps can call
+    // `draw_scene` on every event without keeping the gpu busy while idle. Windows are still
+    // redrawn after they are resized or uncovered, and while transitions or placeholders animate
+    pub fn with_redraw_on_demand(mut self) -> Self {
+        self.redraw_tracker = Some(RedrawTracker::default());
+        self
+    }
+
+    // Forces the next frame of every window to be drawn even if its scene hasn't changed, and
+    // asks winit to send them redraw events
+    pub fn request_redraw(&mut self) {
+        if let Some(tracker) = self.redraw_tracker.as_mut() {
+            tracker.damage_all();
+        }
+        self.resources.surface_resources_manager.request_redraw();
+    }
+
+    // Whether drawing the scene would do any work. Always true unless redraw on demand is on
+    pub fn needs_redraw(&self, scene: &Scene) -> bool {
+        self.needs_window_redraw(self.resources.primary_window, scene)
+    }
+
+    pub fn needs_window_redraw(&self, window_id: WindowId, scene: &Scene) -> bool {
+        self.redraw_tracker.as_ref().map_or(true, |tracker| {
+            // Particles move every frame, so scenes with emitters are never idle
+            self.resources.transition.is_some()
+                || self.resources.placeholders_drawn
+                || self.drawables.iter().any(|drawable| drawable.is_loading())
+                || scene
+                    .layers
+                    .iter()
+                    .any(|layer| !layer.particle_emitters.is_empty())
+                || tracker.needs_redraw(window_id, scene)
+        })
+    }
+
+    // Draws the new scene with the old one animating away over it. Later calls to `draw_scene`
+    // keep compositing the old scene until the duration has passed, so apps should keep
+    // redrawing while `is_transitioning` returns true. The old scene is rendered once up front,
+    // so changes to it after this call aren't shown.
+    pub fn transition(
+        &mut self,
+        old_scene: &Scene,
+        new_scene: &Scene,
+        duration: Duration,
+        easing: Easing,
+        kind: TransitionKind,
+    ) -> bool {
+        self.resources.start_transition(
+            old_scene,
+            self.drawables.as_mut_slice(),
+            duration,
+            easing,
+            kind,
+        );
+        self.draw_scene(new_scene)
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        self.resources.transition.is_some()
+    }
+
+    // Rasterizes the scene with the same drawables as the window and returns the top left
+    // `width` by `height` pixels as straight alpha rgba8 rows. Useful for generating cursor
+    // images and badges on the fly. The size is limited to the window's, and the gpu is waited
+    // on, so this is meant for occasional small images rather than every frame.
+    pub fn render_rgba(&mut self, scene: &Scene, width: u32, height: u32) -> Option<Vec<u8>> {
+        self.resources
+            .render_image(scene, self.drawables.as_mut_slice(), width, height)
+    }
+
+    // Renders both scenes and highlights the pixels which differ, along with a list of the
+    // primitives which changed. Same size limits as `render_rgba`.
+    pub fn visual_diff(
+        &mut self,
+        before: &Scene,
+        after: &Scene,
+        width: u32,
+        height: u32,
+    ) -> Option<VisualDiff> {
+        let before_image = self.render_rgba(before, width, height)?;
+        let after_image = self.render_rgba(after, width, height)?;
+        let (image, changed_pixels) = diff_images(&before_image, &after_image);
+        Some(VisualDiff {
+            width,
+            height,
+            image,
+            changed_pixels,
+            changes: diff_scenes(before, after),
+        })
+    }
+
+    // Renders the scene into a square window icon, such as one with an unread count badge
+    pub fn render_icon(&mut self, scene: &Scene, size: u32) -> Option<Icon> {
+        let rgba = self.render_rgba(scene, size, size)?;
+        Icon::from_rgba(rgba, size, size).ok()
+    }
+
+    // Outline of the character in the font as a path with no fill or stroke, for letterforms
+    // which are animated, stroked, or used as masks. The origin is on the baseline at the
+    // start of the glyph, so translate the path to where the text would be drawn. The path uses
+    // the nonzero fill rule so overlapping contours fill solid. Returns None if the font can't
+    // be found or doesn't have the character
+    pub fn glyph_outline(&self, font: &str, glyph: char, size: f32) -> Option<ScenePath> {
+        character_outline(font, glyph, size)
+    }
+
+    // Same as `glyph_outline`, but takes the font's id for the glyph so shaped glyphs such as
+    // ligatures and alternates can be outlined. Returns None if the font can't be found or the
+    // glyph has no outline
+    pub fn glyph_id_outline(&self, font: &str, glyph: u16, size: f32) -> Option<ScenePath> {
+        glyph_outline(font, glyph, size)
+    }
+
+    // Reads back the color of the pixel at the given position after each frame is drawn, for
+    // use with `PixelInspector`. Stalls every frame until the gpu finishes, so pass None once
+    // inspection is done.
+    pub fn inspect_pixel(&mut self, position: Option<Vec2>) {
+        match (position, self.resources.pixel_probe.as_mut()) {
+            (Some(position), Some(probe)) => probe.position = position,
+            (Some(position), None) => {
+                self.resources.pixel_probe = Some(PixelProbe::new(&self.resources.device, position))
+            }
+            (None, _) => self.resources.pixel_probe = None,
+        }
+    }
+
+    // Straight alpha color of the inspected pixel in the last drawn frame. None if no pixel is
+    // being inspected, it was off screen, or the surface format can't be read back
+    pub fn inspected_color(&self) -> Option<Vec4> {
+        self.resources
+            .pixel_probe
+            .as_ref()
+            .and_then(|probe| probe.color)
+    }
+
+    // Records every scene drawn from now on to the given file until `stop_recording` is called.
+    // Replaces any recording already in progress.
+    pub fn start_recording(&mut self, path: impl AsRef<Path>) -> Result<(), RecordingError> {
+        self.stop_recording()?;
+        self.recorder = Some(Recorder::create(path)?);
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> Result<(), RecordingError> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    // Draws every frame of a recording, waiting between frames to match the recorded timing.
+    // Blocks until the recording has finished playing.
+    pub fn replay(&mut self, path: impl AsRef<Path>) -> Result<(), RecordingError> {
+        let recording = Recording::load(path)?;
+        let start = Instant::now();
+        for frame in recording.frames.iter() {
+            if let Some(wait) = frame.time.checked_sub(start.elapsed()) {
+                thread::sleep(wait);
+            }
+            self.draw_scene(&frame.scene);
+        }
+        Ok(())
+    }
+
+    // Falls back to drawing only plain quads and text without multisampling after several frames
+    // in a row take longer than `hang_threshold` or the gpu stops finishing submitted work.
+    // Pipelines are rebuilt for a single sample when it happens and again when the watchdog is
+    // reset. Poll `take_degraded` to find out when it happens
+    pub fn with_watchdog(mut self, hang_threshold: Duration) -> Self {
+        self.watchdog = Some(Watchdog::new(hang_threshold));
+        self
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.watchdog
+            .as_ref()
+            .map_or(false, |watchdog| watchdog.degraded())
+    }
+
+    // Returns true once after the watchdog switches to degraded mode, so the app can warn the
+    // user or lower its own settings
+    pub fn take_degraded(&mut self) -> bool {
+        self.watchdog
+            .as_mut()
+            .map_or(false, |watchdog| watchdog.take_newly_degraded())
+    }
+
+    // Returns to full rendering, such as after the user changes settings or the gpu recovers
+    pub fn reset_watchdog(&mut self) {
+        if let Some(watchdog) = self.watchdog.as_mut() {
+            watchdog.reset();
+        }
+    }
+
+    // Warns when a primitive is drawn unchanged for `threshold` consecutive frames. Hashes every
+    // primitive each frame, so it is only enabled in debug builds and does nothing in release.
+    pub fn with_redundancy_warnings(mut self, threshold: u64) -> Self {
+        if cfg!(debug_assertions) {
+            self.redundancy_detector = Some(RedundancyDetector::new(threshold));
+        }
+        self
+    }
+
+    // Turns the check of every drawn scene done by `Scene::validate` on or off. On by default in
+    // debug builds, and worth turning off for hdr content or scenes too large to check each frame
+    pub fn with_scene_validation(mut self, enabled: bool) -> Self {
+        self.scene_validation = enabled.then(Vec::new);
+        self
+    }
+
+    // Times every render pass on the gpu and records per layer instance counts. Reading back
+    // the timings stalls each frame, so this is meant for diagnosing slow scenes.
+    pub fn with_profiling(mut self) -> Self {
+        self.resources.profiler =
+            Some(Profiler::new(&self.resources.device, &self.resources.queue));
+        self
+    }
+
+    // Encodes each drawable's pass for a layer into its own command buffer on rayon's thread
+    // pool, and tessellates layers with many uncached paths across it too. Command buffers are
+    // submitted in draw order so output is identical. Layers are still submitted one after
+    // another since drawables reuse their buffers between layers.
+    pub fn with_parallel_encoding(mut self) -> Self {
+        self.resources.parallel_encoding = true;
+        self
+    }
+
+    pub fn with_shader_features(mut self, features: ShaderFeatures) -> Self {
+        self.set_shader_features(features);
+        self
+    }
+
+    // Switches to the shader permutation compiled with the given feature toggles and rebuilds
+    // every pipeline against it
+    pub fn set_shader_features(&mut self, features: ShaderFeatures) {
+        if self.resources.set_shader_features(features)
+            && self.resources.surface_resources_manager.ready()
+        {
+            for drawable in self.drawables.iter_mut() {
+                drawable.surface_updated(&self.resources);
+            }
+        }
+    }
+
+    // Renders paths by evaluating them in the fragment shader rather than tessellating them on
+    // the cpu. Cheaper for paths which change every frame, more expensive to shade for large
+    // static paths with many segments.
+    pub fn with_gpu_paths(mut self) -> Self {
+        self.resources.gpu_paths = true;
+        if self.resources.surface_resources_manager.ready() {
+            for drawable in self.drawables.iter_mut() {
+                drawable.surface_updated(&self.resources);
+            }
+        }
+        self
+    }
+
+    // Sets the order of the display's subpixels. Texts drawn without subpixel antialiasing and
+    // every text when set to Off use grayscale coverage.
+    pub fn with_subpixel_order(mut self, subpixel_order: SubpixelOrder) -> Self {
+        self.resources.subpixel_order = subpixel_order;
+        if self.resources.surface_resources_manager.ready() {
+            for drawable in self.drawables.iter_mut() {
+                drawable.surface_updated(&self.resources);
+            }
+        }
+        self
+    }
+
+    // Sets the contrast and gamma corrections applied to text coverage
+    pub fn with_text_rendering(mut self, text_rendering: TextRendering) -> Self {
+        self.resources.text_rendering = text_rendering;
+        if self.resources.surface_resources_manager.ready() {
+            for drawable in self.drawables.iter_mut() {
+                drawable.surface_updated(&self.resources);
+            }
+        }
+        self
+    }
+
+    // Insets layers marked with `Layer::with_safe_area` are clipped to. Apps should update
+    // them whenever the platform reports a change, such as on rotation
+    pub fn with_safe_area_insets(mut self, insets: SafeAreaInsets) -> Self {
+        self.set_safe_area_insets(insets);
+        self
+    }
+
+    pub fn set_safe_area_insets(&mut self, insets: SafeAreaInsets) {
+        self.resources.safe_area_insets = insets;
+    }
+
+    // Unobstructed part of the primary window as (x, y, width, height), for laying out
+    // content. None until the window's surface is created
+    pub fn safe_area(&mut self) -> Option<Vec4> {
+        let window_id = self.resources.primary_window;
+        if !self
+            .resources
+            .surface_resources_manager
+            .set_current(window_id)
+        {
+            return None;
+        }
+        Some(
+            self.resources
+                .safe_area_insets
+                .rect(self.resources.surface_size()?),
+        )
+    }
+
+    // Reports the gamut of the monitor a window is on. Neither winit nor wgpu expose monitor
+    // color profiles, so apps should forward what the platform reports (NSScreen's color
+    // space on macOS for example) and update it when the window moves between monitors
+    pub fn set_monitor_color_space(&mut self, window_id: WindowId, color_space: ColorSpace) {
+        self.resources
+            .monitor_color_spaces
+            .insert(window_id, color_space);
+    }
+
+    // Last color space reported for the window's monitor, or None if the app hasn't set one
+    pub fn monitor_color_space(&self, window_id: WindowId) -> Option<ColorSpace> {
+        self.resources.monitor_color_spaces.get(&window_id).copied()
+    }
+
+    // Overrides the appearance a window's scenes are drawn in, for apps with their own theme
+    // setting or hosts of raw handle windows forwarding the platform's preference. Calls the
+    // color scheme callback when it changes
+    pub fn set_color_scheme(&mut self, window_id: WindowId, color_scheme: ColorScheme) {
+        if self.color_scheme(window_id) == color_scheme {
+            return;
+        }
+        self.color_schemes.insert(window_id, color_scheme);
+        if let Some(tracker) = self.redraw_tracker.as_mut() {
+            tracker.damage(window_id);
+        }
+        if let Some(callback) = self.color_scheme_callback.as_mut() {
+            callback(window_id, color_scheme);
+        }
+    }
+
+    // Light until the platform reports a preference for the window
+    pub fn color_scheme(&self, window_id: WindowId) -> ColorScheme {
+        self.color_schemes
+            .get(&window_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    // Called with the window and its new scheme whenever the platform's light or dark
+    // preference flips, so apps building scenes in code can restyle them. Scenes with theme
+    // colors restyle on their own
+    pub fn with_color_scheme_callback(
+        mut self,
+        callback: impl FnMut(WindowId, ColorScheme) + 'static,
+    ) -> Self {
+        self.set_color_scheme_callback(callback);
+        self
+    }
+
+    pub fn set_color_scheme_callback(
+        &mut self,
+        callback: impl FnMut(WindowId, ColorScheme) + 'static,
+    ) {
+        self.color_scheme_callback = Some(Box::new(callback));
+    }
+
+    pub fn clear_color_scheme_callback(&mut self) {
+        self.color_scheme_callback = None;
+    }
+
+    // Converts finished frames from srgb to the monitor's color space so content doesn't
+    // appear oversaturated on wide gamut displays. Windows on srgb monitors are untouched
+    pub fn with_color_conversion(mut self) -> Self {
+        self.resources.color_conversion = Some(ColorConversion::new(&self.resources.device));
+        self
+    }
+
+    // Adds ordered noise to finished frames on 8 bit surfaces so large gradients and blurs
+    // don't band
+    pub fn with_dithering(mut self) -> Self {
+        self.set_dithering(true);
+        self
+    }
+
+    pub fn set_dithering(&mut self, dithering: bool) {
+        if !dithering {
+            self.resources.dither = None;
+        } else if self.resources.dither.is_none() {
+            self.resources.dither = Some(Dither::new(&self.resources.device));
+        }
+    }
+
+    // Counts the pixels of each frame drawn into the primary window by luminance and color in a
+    // compute pass. Counts are read back without stalling, so `histogram` returns those of a
+    // frame or two earlier.
+    pub fn with_histogram(mut self) -> Self {
+        self.set_histogram(true);
+        self
+    }
+
+    pub fn set_histogram(&mut self, histogram: bool) {
+        if !histogram {
+            self.resources.histogram = None;
+        } else if self.resources.histogram.is_none() {
+            self.resources.histogram = Some(HistogramPass::new(&self.resources.device));
+        }
+    }
+
+    // Summarizes what the last frame of the primary window drew under the region, so overlay
+    // labels can pick a light or dark color which stands out against arbitrary imagery. Reads
+    // the offscreen copy of the frame made before the last layer's final pass, so labels should
+    // be in the scene's top layer. Waits for the gpu, so call it when labels or their backdrop
+    // change rather than every frame.
+    pub fn backdrop_contrast(&mut self, rect: Vec4) -> Option<BackdropContrast> {
+        let resources = &mut self.resources;
+        if !resources
+            .surface_resources_manager
+            .set_current(resources.primary_window)
+        {
+            return None;
+        }
+        read_backdrop(
+            &resources.device,
+            &resources.queue,
+            resources.surface_resources_manager.offscreen_texture()?,
+            rect,
+        )
+    }
+
+    // Most recent histogram read back from the gpu. None until the first one arrives or if the
+    // surface format can't be counted
+    pub fn histogram(&self) -> Option<&Histogram> {
+        self.resources
+            .histogram
+            .as_ref()
+            .and_then(|histogram| histogram.latest.as_ref())
+    }
+
+    // Hands out the frame between layers and at the end of each frame so other wgpu renderers
+    // can draw into the same swapchain. Their command buffers are submitted right after the
+    // layer they follow. With redraw on demand, call `request_redraw` when only their content
+    // changes.
+    pub fn with_frame_hook(
+        mut self,
+        hook: impl FnMut(&FrameHookContext) -> Vec<CommandBuffer> + Send + Sync + 'static,
+    ) -> Self {
+        self.set_frame_hook(hook);
+        self
+    }
+
+    pub fn set_frame_hook(
+        &mut self,
+        hook: impl FnMut(&FrameHookContext) -> Vec<CommandBuffer> + Send + Sync + 'static,
+    ) {
+        self.resources.frame_hook = Some(Box::new(hook));
+    }
+
+    pub fn clear_frame_hook(&mut self) {
+        self.resources.frame_hook = None;
+    }
+
+    // Raw wgpu handles for creating resources shared with other renderers
+    pub fn device(&self) -> &Device {
+        &self.resources.device
+    }
+
+    pub fn queue(&self) -> &Queue {
+        &self.resources.queue
+    }
+
+    pub fn adapter(&self) -> &Adapter {
+        &self.resources.adapter
+    }
+
+    pub fn instance(&self) -> &Instance {
+        &self.resources.instance
+    }
+
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
+
+    // Format every window's surface is configured with. None until a surface has been created
+    pub fn surface_format(&self) -> Option<TextureFormat> {
+        self.resources.surface_resources_manager.format()
+    }
+
+    // Tests items against a depth buffer so those with a higher `depth` are drawn over lower
+    // ones regardless of order, which lets heavily overlapping scenes skip shading hidden
+    // pixels. Depth is cleared for each layer. Must be called before the event loop starts
+    // since pipelines are built along with the surface.
+    pub fn with_depth_testing(mut self) -> Self {
+        self.resources
+            .surface_resources_manager
+            .set_depth_testing(true);
+        self
+    }
+
+    // Creates the window's surface and builds every pipeline right away instead of once the
+    // event loop starts, so the first frame isn't held up compiling them. Call after the other
+    // builder methods which affect pipelines, such as `with_depth_testing` and
+    // `with_transparency`. Drawables added afterwards build their pipelines as they're added.
+    // Not supported on android, where windows have no surface until the app resumes.
+    pub fn with_eager_pipelines(mut self) -> Self {
+        if self.resources.create_surfaces_eagerly() {
+            for drawable in self.drawables.iter_mut() {
+                drawable.surface_updated(&self.resources);
+            }
+        }
+        self
+    }
+
+    // Matches fonts, measures text, and rasterizes glyphs with the platform's text stack
+    // (DirectWrite, Core Text, or fontconfig and FreeType) so text lines up with native apps.
+    // Applies to every renderer in the process and must be called before any text is drawn or
+    // measured, since shaped text is cached.
+    #[cfg(feature = "system-raster")]
+    pub fn with_system_text(self) -> Self {
+        crate::font::set_system_text(true);
+        self
+    }
+
+    // Lets the desktop show through wherever the frame is transparent, for overlays and
+    // tooltips. The window must be built with `with_transparent(true)`, and scenes should be
+    // built with `Scene::transparent` or otherwise avoid opaque clear colors and backgrounds.
+    // Must be called before the event loop starts since the surface's alpha mode is picked
+    // when it is created.
+    pub fn with_transparency(mut self) -> Self {
+        self.resources
+            .surface_resources_manager
+            .set_transparent(true);
+        self
+    }
+
+    // Sets what is drawn in place of sprites and texts whose assets couldn't be loaded
+    pub fn with_placeholder(mut self, placeholder: Placeholder) -> Self {
+        self.set_placeholder(placeholder);
+        self
+    }
+
+    pub fn set_placeholder(&mut self, placeholder: Placeholder) {
+        self.resources.placeholder = placeholder;
+    }
+
+    // Limits the size of textures created for sprite images too large for the atlas, which
+    // are split into tiles of at most this many pixels on each side. Defaults to the device's
+    // limit, and is clamped to it.
+    pub fn with_max_texture_size(mut self, max_texture_size: u32) -> Self {
+        self.resources.max_texture_size =
+            max_texture_size.clamp(64, self.resources.device.limits().max_texture_dimension_2d);
+        self
+    }
+
+    // Makes `try_draw_scene` return an error whenever a sprite's texture can't be loaded
+    // rather than only drawing the checkerboard placeholder over it, so tests and CI catch
+    // broken asset paths
+    pub fn with_strict_textures(mut self) -> Self {
+        self.strict_textures = true;
+        self
+    }
+
+    // Textures of sprites which couldn't be loaded during the last draw
+    pub fn missing_textures(&self) -> &[String] {
+        &self.resources.missing_textures
+    }
+
+    // Frees the texture of a layer render target. Sprites using it show placeholders until a
+    // layer draws into it again. Returns false if no layer has drawn into it
+    pub fn remove_render_target(&mut self, name: &str) -> bool {
+        self.resources.render_targets.remove(name).is_some()
+    }
+
+    // Loads a wgsl module which drawables and passes can look up by name in
+    // `Resources::extensions`. The module is validated up front so mistakes are reported here
+    // rather than when a pipeline is created.
+    pub fn load_wgsl_extension(
+        &mut self,
+        name: &str,
+        kind: ExtensionKind,
+        source: &str,
+    ) -> Result<(), ShaderExtensionError> {
+        let extension = ShaderExtension::load(&self.resources.device, name, kind, source)?;
+        self.resources
+            .extensions
+            .insert(name.to_string(), extension);
+        Ok(())
+    }
+
+    // Registers a fragment shader for `ShaderQuad`s with the given name to be drawn with. The
+    // declarations in shader_quad.wgsl, including the vertex shader, are prepended to the
+    // source, so it only needs a `fragment` entry point taking a `ShaderQuadInput`. Registering
+    // an existing name replaces its shader.
+    pub fn register_quad_shader(
+        &mut self,
+        name: &str,
+        source: &str,
+    ) -> Result<(), ShaderExtensionError> {
+        let source = format!("{}\n{}", include_str!("shader_quad.wgsl"), source);
+        self.load_wgsl_extension(name, ExtensionKind::Quad, &source)
+    }
+
+    // Adds a full screen pass which runs over every finished frame before it is presented,
+    // after any previously added effects. Adding an effect with an existing name replaces its
+    // module in place. The module needs a `fragment` entry point, which receives the pixel
+    // position and `@location(0) uv: vec2<f32>`, and may use these bindings:
+    //   @group(0) @binding(0) the frame as a `texture_2d<f32>`
+    //   @group(0) @binding(1) a linear `sampler`
+    //   @group(0) @binding(2) a `var<uniform>` block set with `set_post_effect_uniforms`
+    //   a `var<push_constant>` vec4 holding the frame width, height, and seconds since the
+    //   renderer was created
+    // Colors are premultiplied and in the surface's encoding.
+    pub fn add_post_effect(
+        &mut self,
+        name: &str,
+        source: &str,
+    ) -> Result<(), ShaderExtensionError> {
+        let extension = ShaderExtension::load(
+            &self.resources.device,
+            name,
+            ExtensionKind::PostEffect,
+            source,
+        )?;
+        self.resources
+            .post_effects
+            .add(&self.resources.device, name, extension);
+        Ok(())
+    }
+
+    // Same as `add_post_effect` for effects compiled to spirv ahead of time
+    pub fn add_spirv_post_effect(
+        &mut self,
+        name: &str,
+        data: &[u8],
+    ) -> Result<(), ShaderExtensionError> {
+        let extension = ShaderExtension::load_spirv(
+            &self.resources.device,
+            name,
+            ExtensionKind::PostEffect,
+            data,
+        )?;
+        self.resources
+            .post_effects
+            .add(&self.resources.device, name, extension);
+        Ok(())
+    }
+
+    pub fn remove_post_effect(&mut self, name: &str) -> bool {
+        self.resources.post_effects.remove(name)
+    }
+
+    // Replaces the effect's uniform block. The value must match the layout of the block the
+    // effect declares. Returns false if no effect has the given name
+    pub fn set_post_effect_uniforms<T: bytemuck::Pod>(&mut self, name: &str, uniforms: &T) -> bool {
+        self.resources.post_effects.set_uniforms(
+            &self.resources.device,
+            &self.resources.queue,
+            name,
+            bytemuck::bytes_of(uniforms),
+        )
+    }
+
+    pub fn profile_report(&self) -> Option<&ProfileReport> {
+        self.resources
+            .profiler
+            .as_ref()
+            .map(|profiler| profiler.report())
+    }
+
+    // Renders into another window using the same device and drawables. The window's events
+    // must be passed to `handle_event` along with the rest
+    pub fn add_window(&mut self, window: Arc<Window>) {
+        if let Some(theme) = window.theme() {
+            self.color_schemes.insert(window.id(), theme.into());
+        }
+        if self.resources.add_window(window) {
+            for drawable in self.drawables.iter_mut() {
+                drawable.surface_updated(&self.resources);
+            }
+        }
+    }
+
+    // Resizes the primary window's surface. Only needed when the size change isn't passed to
+    // `handle_event`, such as for windows created from raw handles or hidden windows rendered
+    // into without running the event loop
+    pub fn resize_surface(&mut self, width: u32, height: u32) {
+        if let Some(tracker) = self.redraw_tracker.as_mut() {
+            tracker.damage(self.resources.primary_window);
+        }
+        if self
+            .resources
+            .resize_window(self.resources.primary_window, width, height)
+        {
+            for drawable in self.drawables.iter_mut() {
+                drawable.surface_updated(&self.resources);
+            }
+        }
+    }
+
+    pub fn remove_window(&mut self, window_id: WindowId) {
+        self.resources
+            .surface_resources_manager
+            .remove_window(window_id);
+        self.resources.monitor_color_spaces.remove(&window_id);
+        self.color_schemes.remove(&window_id);
+        if let Some(tracker) = self.redraw_tracker.as_mut() {
+            tracker.remove_window(window_id);
+        }
+    }
+
     pub fn handle_event(&mut self, event: &Event<()>) {
+        if let Some(tracker) = self.redraw_tracker.as_mut() {
+            tracker.handle_event(event);
+        }
+        if let Event::WindowEvent {
+            window_id,
+            event: WindowEvent::ThemeChanged(theme),
+        } = event
+        {
+            self.set_color_scheme(*window_id, (*theme).into());
+        }
         if self.resources.handle_event(event) {
+            // Recreated surfaces start out blank
+            if let Some(tracker) = self.redraw_tracker.as_mut() {
+                tracker.damage_all();
+            }
             for drawable in self.drawables.iter_mut() {
                 drawable.surface_updated(&self.resources);
             }
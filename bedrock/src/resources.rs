@@ -1,28 +1,119 @@
-use std::sync::Arc;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use glam::{vec2, Vec4};
-use shader::ShaderConstants;
+use glam::{vec2, Vec2, Vec4};
+use rayon::prelude::*;
+use shader::{ShaderConstants, ShaderFeatures};
 use wgpu::*;
-use winit::{event::Event, window::Window};
+use winit::{
+    dpi::PhysicalSize,
+    event::Event,
+    window::{Window, WindowId},
+};
 
 use crate::{
-    renderer::Drawable, surface_wrapper::SurfaceResourcesManager, Asset, Scene, ATLAS_SIZE,
+    blur::{BackdropBlur, Beneath},
+    clip::ClipStencil,
+    color_space::{ColorConversion, ColorSpace},
+    composite::LayerCompositor,
+    culling::{intersection, scissor_rect},
+    dither::Dither,
+    easing::Easing,
+    extension::ShaderExtension,
+    frame_hook::{FrameHook, FrameHookContext},
+    glyph::{SubpixelOrder, TextRendering},
+    guide::with_guide_layer,
+    histogram::HistogramPass,
+    layer_cache::LayerCache,
+    pixel_probe::{decode_pixel, PixelProbe},
+    placeholder::{MissingContent, Placeholder},
+    post_effect::PostEffects,
+    profiler::Profiler,
+    renderer::Drawable,
+    scene::{Layer, SafeAreaInsets},
+    shader_abi::{self, ShaderAbiError},
+    surface_wrapper::{SurfaceResourcesManager, SurfaceSource, MULTISAMPLE_COUNT},
+    transition::{ActiveTransition, TransitionKind},
+    Asset, Scene, ATLAS_SIZE,
 };
 
 pub struct Resources {
-    pub window: Arc<Window>,
+    // Window drawn into by `Renderer::draw_scene`. Others are added with `add_window`
+    pub primary_window: WindowId,
     pub instance: Instance,
     pub surface_resources_manager: SurfaceResourcesManager,
     pub adapter: Adapter,
     pub device: Device,
     pub queue: Queue,
     pub shader: ShaderModule,
+    pub shader_features: ShaderFeatures,
+    // Previously loaded shader permutations so toggling features back and forth doesn't
+    // recreate modules
+    shader_variants: HashMap<ShaderFeatures, ShaderModule>,
     pub sampler: Sampler,
     pub universal_bind_group_layout: BindGroupLayout,
+    pub backdrop_blur: BackdropBlur,
+    pub clip_stencil: ClipStencil,
+    pub profiler: Option<Profiler>,
+    // Whether drawables are encoded across threads, see `with_parallel_encoding`
+    pub parallel_encoding: bool,
+    pub gpu_paths: bool,
+    pub placeholder: Placeholder,
+    // Textures of sprites which couldn't be drawn during the last render
+    pub(crate) missing_textures: Vec<String>,
+    // Whether the last render drew any placeholders, which animate and so keep needing frames
+    pub(crate) placeholders_drawn: bool,
+    pub subpixel_order: SubpixelOrder,
+    pub text_rendering: TextRendering,
+    // Used to animate placeholders
+    pub created: Instant,
+    // Snapshot of the previous scene composited over new frames while a transition runs
+    pub(crate) transition: Option<ActiveTransition>,
+    pub(crate) pixel_probe: Option<PixelProbe>,
+    pub safe_area_insets: SafeAreaInsets,
+    // Gamut of each window's monitor as reported by the app
+    pub monitor_color_spaces: HashMap<WindowId, ColorSpace>,
+    // Converts frames for wide gamut monitors when enabled
+    pub(crate) color_conversion: Option<ColorConversion>,
+    // Hides gradient banding on 8 bit surfaces when enabled
+    pub(crate) dither: Option<Dither>,
+    pub(crate) histogram: Option<HistogramPass>,
+    // Textures drawn into by layers with a render target, kept between frames so sprites can
+    // keep showing them after the layers are gone
+    pub(crate) render_targets: HashMap<String, Texture>,
+    pub(crate) layer_cache: LayerCache,
+    pub(crate) compositor: LayerCompositor,
+    pub(crate) post_effects: PostEffects,
+    pub(crate) frame_hook: Option<FrameHook>,
+    // Build pipelines which are otherwise created the first time they're needed whenever the
+    // surface changes
+    pub eager_pipelines: bool,
+    // Largest texture drawables create. Sprite images which don't fit in the atlas are split
+    // into tiles of at most this size
+    pub max_texture_size: u32,
+    pub extensions: HashMap<String, ShaderExtension>,
 }
 
 impl Resources {
+    // Panics if the embedded shader doesn't match this version of the host
     pub async fn new(window: Arc<Window>) -> Self {
+        Self::try_new(window)
+            .await
+            .unwrap_or_else(|error| panic!("Shader and host are out of sync: {}", error))
+    }
+
+    pub async fn try_new(window: Arc<Window>) -> Result<Self, ShaderAbiError> {
+        Self::with_source(window.id(), SurfaceSource::Window(window)).await
+    }
+
+    pub(crate) async fn with_source(
+        primary_window: WindowId,
+        source: SurfaceSource,
+    ) -> Result<Self, ShaderAbiError> {
         // The instance is a handle to our GPU
         let instance = Instance::new(InstanceDescriptor {
             backends: Backends::VULKAN,
@@ -43,7 +134,11 @@ impl Resources {
                     required_features: Features::PUSH_CONSTANTS
                         | Features::SPIRV_SHADER_PASSTHROUGH
                         | Features::VERTEX_WRITABLE_STORAGE
-                        | Features::CLEAR_TEXTURE,
+                        | Features::CLEAR_TEXTURE
+                        // Lets subpixel text blend each channel separately when available
+                        | (adapter.features() & Features::DUAL_SOURCE_BLENDING)
+                        // Only used for profiling, so request it when available
+                        | (adapter.features() & Features::TIMESTAMP_QUERY),
                     required_limits: Limits {
                         max_push_constant_size: 256,
                         ..Default::default()
@@ -55,14 +150,8 @@ impl Resources {
             .await
             .unwrap();
 
-        let shader = device.create_shader_module(ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: util::make_spirv(
-                &Asset::get("shader.spv")
-                    .expect("Could not load shader")
-                    .data,
-            ),
-        });
+        let shader_features = ShaderFeatures::default();
+        let shader = load_shader(&device, shader_features)?;
 
         let sampler = device.create_sampler(&SamplerDescriptor {
             address_mode_u: AddressMode::ClampToEdge,
@@ -94,149 +183,1005 @@ impl Resources {
                         ty: BindingType::Sampler(SamplerBindingType::Filtering),
                         count: None,
                     },
+                    BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
                 ],
             });
 
-        Self {
-            window,
+        let backdrop_blur = BackdropBlur::new(&device);
+        let clip_stencil = ClipStencil::new(&device);
+        let compositor = LayerCompositor::new(&device);
+        let post_effects = PostEffects::new(&device);
+
+        let max_texture_size = device.limits().max_texture_dimension_2d;
+        let mut resources = Self {
+            primary_window,
             instance,
             surface_resources_manager: SurfaceResourcesManager::new(),
             adapter,
             device,
             queue,
             shader,
+            shader_features,
+            shader_variants: HashMap::new(),
             sampler,
             universal_bind_group_layout,
+            backdrop_blur,
+            clip_stencil,
+            profiler: None,
+            parallel_encoding: false,
+            gpu_paths: false,
+            placeholder: Placeholder::default(),
+            missing_textures: Vec::new(),
+            placeholders_drawn: false,
+            subpixel_order: SubpixelOrder::default(),
+            text_rendering: TextRendering::default(),
+            created: Instant::now(),
+            transition: None,
+            pixel_probe: None,
+            safe_area_insets: SafeAreaInsets::default(),
+            monitor_color_spaces: HashMap::new(),
+            color_conversion: None,
+            dither: None,
+            histogram: None,
+            render_targets: HashMap::new(),
+            layer_cache: LayerCache::default(),
+            compositor,
+            post_effects,
+            frame_hook: None,
+            eager_pipelines: false,
+            max_texture_size,
+            extensions: HashMap::new(),
+        };
+        // The surface is created once the event loop starts
+        resources.add_source(primary_window, source);
+        Ok(resources)
+    }
+
+    // Swaps the shader module for the permutation built with the given features. Falls back to
+    // the current module if that permutation wasn't embedded. Pipelines must be rebuilt after
+    // a successful swap.
+    pub fn set_shader_features(&mut self, features: ShaderFeatures) -> bool {
+        if features == self.shader_features {
+            return false;
+        }
+
+        let shader = match self.shader_variants.remove(&features) {
+            Some(shader) => shader,
+            None => match load_shader(&self.device, features) {
+                Ok(shader) => shader,
+                Err(error) => {
+                    eprintln!(
+                        "Could not load shader permutation: {}. Keeping {:?}",
+                        error, self.shader_features
+                    );
+                    return false;
+                }
+            },
+        };
+
+        let previous = std::mem::replace(&mut self.shader, shader);
+        self.shader_variants.insert(self.shader_features, previous);
+        self.shader_features = features;
+        if self.surface_resources_manager.ready() {
+            self.update_internal_pipelines();
         }
+        true
     }
 
     pub fn handle_event(&mut self, event: &Event<()>) -> bool {
-        self.surface_resources_manager.handle_event(
+        let surface_updated = self.surface_resources_manager.handle_event(
             event,
-            self.window.clone(),
             &self.instance,
             &self.adapter,
             &self.device,
             &self.sampler,
             &self.universal_bind_group_layout,
             false,
-        )
+        );
+        if surface_updated {
+            self.update_internal_pipelines();
+        }
+        surface_updated
+    }
+
+    // Renders into another window with the same device, atlases, and pipelines. Returns true
+    // if a surface was created, in which case drawables need to be told the surface changed
+    pub fn add_window(&mut self, window: Arc<Window>) -> bool {
+        self.add_source(window.id(), SurfaceSource::Window(window))
+    }
+
+    fn add_source(&mut self, window_id: WindowId, source: SurfaceSource) -> bool {
+        let surface_updated = self.surface_resources_manager.add_window(
+            window_id,
+            source,
+            &self.instance,
+            &self.adapter,
+            &self.device,
+            &self.sampler,
+            &self.universal_bind_group_layout,
+            false,
+        );
+        if surface_updated {
+            self.update_internal_pipelines();
+        }
+        surface_updated
+    }
+
+    // Creates every window's surface without waiting for the event loop to start and builds
+    // all internal pipelines up front. Returns true if a surface was created, in which case
+    // drawables need to be told the surface changed
+    pub fn create_surfaces_eagerly(&mut self) -> bool {
+        self.eager_pipelines = true;
+        let _span = tracing::info_span!("surface_reconfigure", reason = "eager").entered();
+        self.start_surfaces()
+    }
+
+    // Creates every window's surface for hosts which don't run a winit event loop
+    pub(crate) fn start_surfaces(&mut self) -> bool {
+        let surface_updated = self.surface_resources_manager.start(
+            &self.instance,
+            &self.adapter,
+            &self.device,
+            &self.sampler,
+            &self.universal_bind_group_layout,
+            false,
+        );
+        if surface_updated {
+            self.update_internal_pipelines();
+        }
+        surface_updated
+    }
+
+    // Resizes a window's surface when no winit resize event will arrive for it. Returns true
+    // if the surface was reconfigured, in which case drawables need to be told
+    pub fn resize_window(&mut self, window_id: WindowId, width: u32, height: u32) -> bool {
+        let surface_updated = self.surface_resources_manager.resize(
+            window_id,
+            PhysicalSize::new(width, height),
+            &self.device,
+            &self.sampler,
+            &self.universal_bind_group_layout,
+        );
+        if surface_updated {
+            self.update_internal_pipelines();
+        }
+        surface_updated
+    }
+
+    // Turns multisampling on or off for every surface. Returns true if the surfaces changed, in
+    // which case drawables need to be told so they rebuild their pipelines
+    pub(crate) fn set_multisampling(&mut self, multisampling: bool) -> bool {
+        let surface_updated = self.surface_resources_manager.set_sample_count(
+            if multisampling { MULTISAMPLE_COUNT } else { 1 },
+            &self.device,
+            &self.sampler,
+            &self.universal_bind_group_layout,
+        );
+        if surface_updated {
+            self.update_internal_pipelines();
+        }
+        surface_updated
+    }
+
+    fn update_internal_pipelines(&mut self) {
+        let Some(format) = self.surface_resources_manager.format() else {
+            return;
+        };
+        self.backdrop_blur
+            .surface_updated(&self.device, &self.shader, format);
+        let sample_count = self.surface_resources_manager.sample_count();
+        self.clip_stencil.surface_updated(
+            &self.device,
+            &self.shader,
+            format,
+            self.surface_resources_manager.depth_format(),
+            sample_count,
+        );
+
+        if self.eager_pipelines {
+            self.compositor.pipeline(&self.device, format, sample_count);
+            if let Some(color_conversion) = self.color_conversion.as_mut() {
+                color_conversion.pipeline(&self.device, format);
+            }
+            if let Some(dither) = self.dither.as_mut() {
+                dither.pipeline(&self.device, format);
+            }
+        }
     }
 
+    // Draws the scene into the window's surface. Windows whose surface hasn't been created yet
+    // are skipped
     pub fn render(
         &mut self,
+        window_id: WindowId,
         scene: &Scene,
         drawables: &mut [Box<dyn Drawable>],
     ) -> Result<(), SurfaceError> {
-        let frame = self.surface_resources_manager.surface_texture(
+        if !self.surface_resources_manager.set_current(window_id) {
+            return Ok(());
+        }
+        let _span =
+            tracing::debug_span!("render", ?window_id, layers = scene.layers.len()).entered();
+
+        // Headless windows are only rendered into images
+        let Some(frame) = self.surface_resources_manager.surface_texture(
             &self.device,
             &self.sampler,
             &self.universal_bind_group_layout,
+        ) else {
+            return Ok(());
+        };
+
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.begin_frame();
+        }
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Frame Prepare Encoder"),
+            });
+        for drawable in drawables.iter_mut() {
+            drawable.prepare_frame(self, &mut encoder, scene);
+        }
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        self.render_to(scene, drawables, &frame.texture);
+
+        // Transitions and pixel inspection only apply to the primary window
+        if window_id == self.primary_window {
+            if let Some(transition) = self.transition.as_ref() {
+                transition.draw(&self.device, &self.queue, &frame.texture);
+                if transition.finished() {
+                    self.transition = None;
+                }
+            }
+
+            if let Some(probe) = self.pixel_probe.as_mut() {
+                probe.read(&self.device, &self.queue, &frame.texture);
+            }
+        }
+
+        // User effects run on every window before the output is adjusted for the monitor
+        self.post_effects.draw(
+            &self.device,
+            &self.queue,
+            &frame.texture,
+            self.created.elapsed().as_secs_f32(),
+        );
+
+        if let Some(hook) = self.frame_hook.as_mut() {
+            let view = frame.texture.create_view(&Default::default());
+            let buffers = hook(&FrameHookContext {
+                device: &self.device,
+                queue: &self.queue,
+                layer: None,
+                view: &view,
+                resolve_target: None,
+                format: frame.texture.format(),
+                size: frame.texture.size(),
+            });
+            self.queue.submit(buffers);
+        }
+
+        // Counted after effects so the histogram matches what is shown, but before conversion for
+        // the monitor so values are comparable across displays
+        if window_id == self.primary_window {
+            if let Some(histogram) = self.histogram.as_mut() {
+                histogram.count(&self.device, &self.queue, &frame.texture);
+            }
+        }
+
+        // Converted last so the probe still reports scene colors
+        if let Some(conversion) = self.color_conversion.as_mut() {
+            let color_space = self.monitor_color_spaces.get(&window_id).copied();
+            conversion.draw(
+                &self.device,
+                &self.queue,
+                &frame.texture,
+                color_space.unwrap_or_default(),
+            );
+        }
+
+        // Dithering comes after every other pass so nothing requantizes the noise away
+        if let Some(dither) = self.dither.as_mut() {
+            dither.draw(&self.device, &self.queue, &frame.texture);
+        }
+
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.end_frame(&self.device, &self.queue);
+        }
+
+        frame.present();
+
+        Ok(())
+    }
+
+    // Converts a straight alpha scene color to the premultiplied color primitives blend with,
+    // matching the conversion the shaders do for srgb surfaces
+    fn clear_color(&self, color: Vec4) -> Color {
+        let color = if self.shader_features.srgb {
+            color * color
+        } else {
+            color
+        };
+        Color {
+            r: (color.x * color.w) as f64,
+            g: (color.y * color.w) as f64,
+            b: (color.z * color.w) as f64,
+            a: color.w as f64,
+        }
+    }
+
+    // Renders the scene into a texture the size of the primary window and reads back the top
+    // left `width` by `height` pixels as straight alpha rgba8. Returns None if the window has
+    // no surface yet, the image doesn't fit inside it, or the surface format can't be read.
+    pub(crate) fn render_image(
+        &mut self,
+        scene: &Scene,
+        drawables: &mut [Box<dyn Drawable>],
+        width: u32,
+        height: u32,
+    ) -> Option<Vec<u8>> {
+        if !self
+            .surface_resources_manager
+            .set_current(self.primary_window)
+        {
+            return None;
+        }
+
+        let size = self.surface_resources_manager.offscreen_texture()?.size();
+        if width == 0 || height == 0 || width > size.width || height > size.height {
+            return None;
+        }
+        let target = self.device.create_texture(&TextureDescriptor {
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: self.surface_resources_manager.format()?,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            label: Some("Image Texture"),
+            view_formats: &[],
+        });
+
+        // The image isn't a frame, so keep its passes out of the profile
+        let profiler = self.profiler.take();
+        self.render_to(scene, drawables, &target);
+        self.profiler = profiler;
+
+        let padded_row = (width * 4).next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = self.device.create_buffer(&BufferDescriptor {
+            label: Some("Image readback buffer"),
+            size: (padded_row * height) as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Image Readback Encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            target.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
         );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        slice.map_async(MapMode::Read, |_| ());
+        self.device.poll(Maintain::Wait);
+        let data = slice.get_mapped_range();
+        let mut image = Vec::with_capacity((width * height * 4) as usize);
+        for row in data.chunks_exact(padded_row as usize) {
+            for pixel in row[..(width * 4) as usize].chunks_exact(4) {
+                let color =
+                    decode_pixel(target.format(), [pixel[0], pixel[1], pixel[2], pixel[3]])?;
+                image.extend(
+                    (color * 255.0)
+                        .round()
+                        .to_array()
+                        .map(|channel| channel as u8),
+                );
+            }
+        }
+        Some(image)
+    }
+
+    // Renders the old scene into a snapshot which is composited over the following frames until
+    // the transition finishes. Returns false if there is no surface to size the snapshot to yet
+    pub(crate) fn start_transition(
+        &mut self,
+        old_scene: &Scene,
+        drawables: &mut [Box<dyn Drawable>],
+        duration: Duration,
+        easing: Easing,
+        kind: TransitionKind,
+    ) -> bool {
+        if !self
+            .surface_resources_manager
+            .set_current(self.primary_window)
+        {
+            return false;
+        }
+        let (Some(format), Some(size)) =
+            (self.surface_resources_manager.format(), self.surface_size())
+        else {
+            return false;
+        };
+
+        let snapshot = ActiveTransition::create_snapshot(&self.device, format, size);
+
+        // The snapshot isn't a frame, so keep its passes out of the profile
+        let profiler = self.profiler.take();
+        self.render_to(old_scene, drawables, &snapshot);
+        self.profiler = profiler;
+
+        self.transition = Some(ActiveTransition::new(
+            &self.device,
+            snapshot,
+            duration,
+            easing,
+            kind,
+        ));
+        true
+    }
+
+    // Size of the current surface. None while suspended or before the surface is created
+    pub fn surface_size(&self) -> Option<Vec2> {
+        let size = self.surface_resources_manager.offscreen_texture()?.size();
+        Some(vec2(size.width as f32, size.height as f32))
+    }
+
+    // Clips layers marked to stay within the safe area. Scenes without any are used as is
+    fn apply_safe_area<'a>(&self, scene: &'a Scene) -> Cow<'a, Scene> {
+        if self.safe_area_insets.is_empty()
+            || !scene.layers.iter().any(|layer| layer.within_safe_area)
+        {
+            return Cow::Borrowed(scene);
+        }
+
+        let Some(surface_size) = self.surface_size() else {
+            return Cow::Borrowed(scene);
+        };
+        let safe_area = self.safe_area_insets.rect(surface_size);
+        let mut scene = scene.clone();
+        for layer in scene
+            .layers
+            .iter_mut()
+            .filter(|layer| layer.within_safe_area)
+        {
+            let layer = Arc::make_mut(layer);
+            layer.clip = Some(match layer.clip {
+                Some(clip) => intersection(clip, safe_area),
+                None => safe_area,
+            });
+        }
+        Cow::Owned(scene)
+    }
+
+    // Renders the scene into the target, which must match the surface's size and format.
+    // Layers with a render target are drawn into their own textures first instead, and static
+    // layers are drawn from their caches
+    pub(crate) fn render_to(
+        &mut self,
+        scene: &Scene,
+        drawables: &mut [Box<dyn Drawable>],
+        target: &Texture,
+    ) {
+        let cached = self
+            .layer_cache
+            .apply(scene, &mut self.render_targets, target.size());
+        let scene = with_guide_layer(&cached);
+        let scene = &*scene;
+        if !scene
+            .layers
+            .iter()
+            .any(|layer| layer.render_target.is_some())
+        {
+            self.render_layers(scene, drawables, target);
+            return;
+        }
+
+        let mut names: Vec<&String> = Vec::new();
+        for name in scene
+            .layers
+            .iter()
+            .filter_map(|layer| layer.render_target.as_ref())
+        {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        for name in names {
+            let mut target_scene = Scene::transparent();
+            target_scene.layers = scene
+                .layers
+                .iter()
+                .filter(|layer| layer.render_target.as_ref() == Some(name))
+                .cloned()
+                .collect();
+
+            // Taken out while it is drawn so sprites in its own layers can't sample it
+            let texture = match self.render_targets.remove(name) {
+                Some(texture) if texture.size() == target.size() => texture,
+                _ => self.device.create_texture(&TextureDescriptor {
+                    size: target.size(),
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: target.format(),
+                    usage: TextureUsages::RENDER_ATTACHMENT
+                        | TextureUsages::TEXTURE_BINDING
+                        | TextureUsages::COPY_SRC
+                        | TextureUsages::COPY_DST,
+                    label: Some("Render target texture"),
+                    // Sprites sample the stored values directly like they do the atlas
+                    view_formats: &[target.format().remove_srgb_suffix()],
+                }),
+            };
+            self.render_layers(&target_scene, drawables, &texture);
+            self.render_targets.insert(name.clone(), texture);
+        }
+
+        let mut frame_scene = scene.clone();
+        frame_scene
+            .layers
+            .retain(|layer| layer.render_target.is_none());
+        self.render_layers(&frame_scene, drawables, target);
+    }
+
+    // Clears the offscreen texture before anything is drawn into the frame, otherwise copies
+    // the frame drawn so far into it so the next pass can sample its backdrop
+    fn refresh_backdrop(&self, encoder: &mut CommandEncoder, target: &Texture, clear: bool) {
+        let Some(offscreen_texture) = self.surface_resources_manager.offscreen_texture() else {
+            return;
+        };
+        if clear {
+            encoder.clear_texture(
+                offscreen_texture,
+                &ImageSubresourceRange {
+                    aspect: TextureAspect::All,
+                    base_mip_level: 0,
+                    mip_level_count: None,
+                    base_array_layer: 0,
+                    array_layer_count: None,
+                },
+            );
+        } else {
+            encoder.copy_texture_to_texture(
+                ImageCopyTexture {
+                    texture: target,
+                    mip_level: 0,
+                    origin: Origin3d::ZERO,
+                    aspect: Default::default(),
+                },
+                ImageCopyTexture {
+                    texture: offscreen_texture,
+                    mip_level: 0,
+                    origin: Origin3d::ZERO,
+                    aspect: Default::default(),
+                },
+                Extent3d {
+                    width: target.width(),
+                    height: target.height(),
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+
+    // Records the render pass for the drawable at `index` among those drawn in the layer. Only
+    // reads the resources so passes can be encoded on separate threads
+    fn encode_pass(
+        &self,
+        encoder: &mut CommandEncoder,
+        pass: &LayerPass,
+        index: usize,
+        drawable: &mut dyn Drawable,
+    ) {
+        let _drawable_span = tracing::trace_span!("drawable", name = drawable.name()).entered();
+        // The first drawable should clear the output texture
+        let load = match (pass.content_views, index) {
+            (Some(_), 0) => LoadOp::Clear(Color::TRANSPARENT),
+            (None, 0) if pass.first => LoadOp::Clear(pass.clear_color),
+            _ => LoadOp::Load,
+        };
+        // Depth only orders items within a layer and clips only apply to their own layer, so
+        // each layer starts from a cleared depth and stencil buffer
+        let depth_cleared = index != 0;
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(match pass.content_views {
+                Some((content_color_view, content_resolve_target)) => RenderPassColorAttachment {
+                    view: content_color_view,
+                    resolve_target: content_resolve_target.as_ref(),
+                    ops: Operations {
+                        load,
+                        store: StoreOp::Store,
+                    },
+                },
+                None => RenderPassColorAttachment {
+                    view: pass.color_view,
+                    resolve_target: pass.resolve_target,
+                    ops: Operations {
+                        load,
+                        store: StoreOp::Store,
+                    },
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: pass.depth_view,
+                depth_ops: Some(Operations {
+                    load: if depth_cleared {
+                        LoadOp::Load
+                    } else {
+                        LoadOp::Clear(0.0)
+                    },
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: Some(Operations {
+                    load: if depth_cleared {
+                        LoadOp::Load
+                    } else {
+                        LoadOp::Clear(0)
+                    },
+                    store: StoreOp::Store,
+                }),
+            }),
+            timestamp_writes: self
+                .profiler
+                .as_ref()
+                .and_then(|profiler| profiler.pass_timestamps(index as u32)),
+            occlusion_query_set: None,
+        });
+
+        let (width, height) = (pass.target.width(), pass.target.height());
+        if let Some([x, y, width, height]) = pass
+            .layer
+            .clip
+            .and_then(|clip| scissor_rect(clip, width, height))
+        {
+            render_pass.set_scissor_rect(x, y, width, height);
+        }
+
+        if !depth_cleared {
+            self.clip_stencil.draw(&mut render_pass, pass.constants);
+        }
+        render_pass.set_stencil_reference(pass.layer.clip_paths.len() as u32);
+
+        drawable.draw(self, &mut render_pass, pass.constants, pass.layer);
+        drop(render_pass);
+        tracing::trace!(instances = drawable.instance_count(), "Drew layer content");
+    }
 
-        let frame_view = frame.texture.create_view(&Default::default());
+    fn render_layers(
+        &mut self,
+        scene: &Scene,
+        drawables: &mut [Box<dyn Drawable>],
+        target: &Texture,
+    ) {
+        let scene = self.apply_safe_area(scene);
+        let scene = &*scene;
+        self.missing_textures.clear();
+        self.placeholders_drawn = false;
+        let frame_view = target.create_view(&Default::default());
         let multisampled_view = self
             .surface_resources_manager
             .multisampled_texture()
+            .map(|texture| texture.create_view(&Default::default()));
+        // Passes draw into the multisampled texture and resolve into the frame, or straight into
+        // the frame when multisampling is off
+        let (color_view, resolve_target) = match &multisampled_view {
+            Some(multisampled_view) => (multisampled_view, Some(&frame_view)),
+            None => (&frame_view, None),
+        };
+        let depth_view = self
+            .surface_resources_manager
+            .depth_texture()
             .create_view(&Default::default());
 
         let constants = ShaderConstants {
-            surface_size: vec2(frame.texture.width() as f32, frame.texture.height() as f32),
+            surface_size: vec2(target.width() as f32, target.height() as f32),
             atlas_size: ATLAS_SIZE,
             clip: Vec4::ZERO,
+            backdrop: Vec4::ZERO,
         };
+        let clear_color = self.clear_color(scene.clear_color);
 
         let mut first = true;
-        for layer in scene.layers.iter() {
+        let mut layers = scene
+            .layers
+            .iter()
+            .map(|layer| &**layer)
+            .enumerate()
+            // Layers clipped to nothing on the surface have no pixels to draw
+            .filter(|(_, layer)| {
+                !layer.is_empty()
+                    && layer.clip.map_or(true, |clip| {
+                        scissor_rect(clip, target.width(), target.height()).is_some()
+                    })
+            });
+        let mut placeholders: Option<(usize, Layer)> = None;
+        loop {
+            // Placeholders for a layer's missing content are drawn as their own layer directly
+            // after it
+            let generated = placeholders.take();
+            let (layer_index, layer) = match &generated {
+                Some((layer_index, layer)) => (*layer_index, layer),
+                None => match layers.next() {
+                    Some(next) => next,
+                    None => break,
+                },
+            };
+            let _layer_span = tracing::debug_span!(
+                "layer",
+                index = layer_index,
+                name = layer.name.as_deref(),
+                placeholders = generated.is_some()
+            )
+            .entered();
+
             let mut encoder = self
                 .device
                 .create_command_encoder(&CommandEncoderDescriptor {
                     label: Some("Render Encoder"),
                 });
-            for drawable in drawables.iter_mut() {
-                // Either clear the offscreen texture or copy the previous layer to it
-                if first {
-                    encoder.clear_texture(
-                        self.surface_resources_manager.offscreen_texture(),
-                        &ImageSubresourceRange {
-                            aspect: TextureAspect::All,
-                            base_mip_level: 0,
-                            mip_level_count: None,
-                            base_array_layer: 0,
-                            array_layer_count: None,
+            let mut layer_constants = constants;
+            self.clip_stencil.prepare(&self.device, &self.queue, layer);
+            // Content blurred and color filtered layers are drawn on their own and composited once
+            // they're done
+            let content_views = layer.needs_composite().then(|| {
+                self.compositor.views(
+                    &self.device,
+                    target,
+                    self.surface_resources_manager.sample_count(),
+                )
+            });
+            let mut layer_drawables: Vec<&mut Box<dyn Drawable>> = drawables
+                .iter_mut()
+                .filter(|drawable| drawable.needs_draw(layer))
+                .collect();
+            let drawn = !layer_drawables.is_empty();
+            let mut command_buffers = Vec::new();
+            if let Some((first_drawable, rest)) = layer_drawables.split_first_mut() {
+                self.refresh_backdrop(&mut encoder, target, first);
+                // Blur the backdrop once per layer before anything samples it
+                let blur_source = self
+                    .surface_resources_manager
+                    .offscreen_texture()
+                    .filter(|_| layer.background_blur_radius != 0.0);
+                if let Some(source) = blur_source {
+                    if let Some(level) = self.backdrop_blur.blur(
+                        &self.device,
+                        &mut encoder,
+                        source,
+                        self.surface_resources_manager.backdrop_texture(),
+                        layer,
+                        Beneath {
+                            clear_color: scene.clear_color,
+                            layers: &scene.layers[..layer_index],
                         },
+                    ) {
+                        layer_constants.backdrop.x = level as f32 + 1.0;
+                    }
+                }
+
+                let pass = LayerPass {
+                    layer,
+                    target,
+                    color_view,
+                    resolve_target,
+                    content_views: content_views.as_ref(),
+                    depth_view: &depth_view,
+                    constants: layer_constants,
+                    clear_color,
+                    first,
+                };
+                self.encode_pass(&mut encoder, &pass, 0, &mut ***first_drawable);
+
+                if self.parallel_encoding && !rest.is_empty() {
+                    // Each remaining pass refreshes its own backdrop copy, so the command
+                    // buffers only need submitting in order to match the serial output
+                    let resources = &*self;
+                    let encoded: Vec<CommandBuffer> = rest
+                        .par_iter_mut()
+                        .enumerate()
+                        .map(|(index, drawable)| {
+                            let mut encoder = resources.device.create_command_encoder(
+                                &CommandEncoderDescriptor {
+                                    label: Some("Render Encoder"),
+                                },
+                            );
+                            resources.refresh_backdrop(
+                                &mut encoder,
+                                target,
+                                pass.clears_backdrop(index + 1),
+                            );
+                            resources.encode_pass(&mut encoder, &pass, index + 1, &mut ***drawable);
+                            encoder.finish()
+                        })
+                        .collect();
+                    let layer_encoder = std::mem::replace(
+                        &mut encoder,
+                        self.device
+                            .create_command_encoder(&CommandEncoderDescriptor {
+                                label: Some("Render Encoder"),
+                            }),
                     );
+                    command_buffers.push(layer_encoder.finish());
+                    command_buffers.extend(encoded);
                 } else {
-                    encoder.copy_texture_to_texture(
-                        ImageCopyTexture {
-                            texture: &frame.texture,
-                            mip_level: 0,
-                            origin: Origin3d::ZERO,
-                            aspect: Default::default(),
-                        },
-                        ImageCopyTexture {
-                            texture: self.surface_resources_manager.offscreen_texture(),
-                            mip_level: 0,
-                            origin: Origin3d::ZERO,
-                            aspect: Default::default(),
-                        },
-                        Extent3d {
-                            width: frame.texture.width(),
-                            height: frame.texture.height(),
-                            depth_or_array_layers: 1,
-                        },
-                    );
+                    for (index, drawable) in rest.iter_mut().enumerate() {
+                        self.refresh_backdrop(
+                            &mut encoder,
+                            target,
+                            pass.clears_backdrop(index + 1),
+                        );
+                        self.encode_pass(&mut encoder, &pass, index + 1, &mut ***drawable);
+                    }
                 }
 
-                // The first drawable should clear the output texture
-                let attachment_op = if first {
-                    Operations::<Color> {
-                        load: LoadOp::<_>::Clear(Color::WHITE),
-                        store: StoreOp::Store,
+                if let Some(profiler) = self.profiler.as_mut() {
+                    for drawable in layer_drawables.iter() {
+                        profiler.record_pass(
+                            layer_index,
+                            layer.name.as_deref(),
+                            drawable.name(),
+                            drawable.instance_count(),
+                        );
                     }
+                }
+                if content_views.is_none() {
+                    first = false;
+                }
+            }
+
+            if drawn && content_views.is_some() {
+                let load = if first {
+                    LoadOp::Clear(clear_color)
                 } else {
-                    Operations::<Color> {
-                        load: LoadOp::<_>::Load,
-                        store: StoreOp::Store,
-                    }
+                    LoadOp::Load
                 };
+                self.compositor.composite(
+                    &self.device,
+                    &mut self.backdrop_blur,
+                    &mut encoder,
+                    layer,
+                    (color_view, resolve_target),
+                    load,
+                );
+                first = false;
+            }
+            command_buffers.push(encoder.finish());
+            self.queue.submit(command_buffers);
 
-                let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                    label: Some("Render Pass"),
-                    color_attachments: &[Some(RenderPassColorAttachment {
-                        view: &multisampled_view,
-                        resolve_target: Some(&frame_view),
-                        ops: attachment_op,
-                    })],
-                    depth_stencil_attachment: None,
-                    timestamp_writes: None,
-                    occlusion_query_set: None,
+            // Skipped until something is drawn, since the first layer to draw clears the frame
+            if let (Some(hook), None, false) = (self.frame_hook.as_mut(), &generated, first) {
+                let buffers = hook(&FrameHookContext {
+                    device: &self.device,
+                    queue: &self.queue,
+                    layer: Some(layer),
+                    view: color_view,
+                    resolve_target,
+                    format: target.format(),
+                    size: target.size(),
                 });
+                self.queue.submit(buffers);
+            }
 
-                if let Some(clip) = layer.clip {
-                    render_pass.set_scissor_rect(
-                        clip.x.max(0.0) as u32,
-                        clip.y.max(0.0) as u32,
-                        (clip.z as u32).min(frame.texture.width()),
-                        (clip.w as u32).min(frame.texture.height()),
-                    );
+            if generated.is_none() {
+                let missing: Vec<MissingContent> = drawables
+                    .iter_mut()
+                    .flat_map(|drawable| drawable.missing_content())
+                    .collect();
+                for texture in missing
+                    .iter()
+                    .filter_map(|content| content.texture.as_ref())
+                {
+                    if !self.missing_textures.contains(texture) {
+                        self.missing_textures.push(texture.clone());
+                    }
+                }
+                if !missing.is_empty() {
+                    self.placeholders_drawn = true;
+                    let seconds = self.created.elapsed().as_secs_f32();
+                    placeholders = self
+                        .placeholder
+                        .layer(layer, &missing, seconds)
+                        .map(|placeholder_layer| (layer_index, placeholder_layer));
                 }
-
-                drawable.draw(
-                    &self.queue,
-                    &mut render_pass,
-                    constants,
-                    self.surface_resources_manager.universal_bind_group(),
-                    &layer,
-                );
-
-                first = false;
             }
+        }
+
+        // Nothing was drawn, but the frame still needs to be cleared
+        if first {
+            let mut encoder = self
+                .device
+                .create_command_encoder(&CommandEncoderDescriptor {
+                    label: Some("Clear Encoder"),
+                });
+            encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Clear Pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: color_view,
+                    resolve_target,
+                    ops: Operations {
+                        load: LoadOp::Clear(clear_color),
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
             self.queue.submit(std::iter::once(encoder.finish()));
         }
+    }
+}
 
-        frame.present();
+// Targets shared by the passes of every drawable drawn in a layer
+struct LayerPass<'a> {
+    layer: &'a Layer,
+    target: &'a Texture,
+    color_view: &'a TextureView,
+    resolve_target: Option<&'a TextureView>,
+    // Set when the layer is drawn on its own and composited afterwards
+    content_views: Option<&'a (TextureView, Option<TextureView>)>,
+    depth_view: &'a TextureView,
+    constants: ShaderConstants,
+    clear_color: Color,
+    // Whether nothing had been drawn into the frame before this layer
+    first: bool,
+}
 
-        Ok(())
+impl LayerPass<'_> {
+    // The offscreen texture starts out cleared until something is drawn into the frame. Layers
+    // drawn on their own don't add to the frame until they're composited
+    fn clears_backdrop(&self, index: usize) -> bool {
+        self.first && (index == 0 || self.content_views.is_some())
     }
 }
+
+fn load_shader(device: &Device, features: ShaderFeatures) -> Result<ShaderModule, ShaderAbiError> {
+    let file_name = features.spirv_file_name();
+    let shader_data = Asset::get(&file_name)
+        .ok_or(ShaderAbiError::MissingModule(file_name))?
+        .data;
+    // Fail fast if the shader was built against a different version of the shader crate
+    // rather than rendering garbage
+    shader_abi::validate(&shader_data, device.limits().max_push_constant_size)?;
+
+    Ok(device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Shader"),
+        source: util::make_spirv(&shader_data),
+    }))
+}
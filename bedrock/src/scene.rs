@@ -1,24 +1,166 @@
+mod badge;
+mod bounds;
+mod camera;
+mod caret;
+mod custom;
+mod ellipse;
+mod focus_ring;
+mod format;
+mod guide;
+mod hit_test;
+mod keyframes;
+mod material;
+mod merge;
+mod mesh;
+mod mirror;
+mod particles;
+mod pixel_inspector;
+mod polyline;
+mod pyramid;
 mod quad;
+mod safe_area;
+mod shader_quad;
+mod text_log;
+mod text_style;
+mod theme;
+mod validate;
+mod wrap;
 
-use glam::{Vec2, Vec4};
-use serde::Deserialize;
+use std::{any::Any, sync::Arc};
 
+use glam::{vec2, Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+use smallvec::{smallvec, SmallVec};
+
+pub use badge::*;
+pub use camera::*;
+pub use custom::*;
+pub use ellipse::*;
+pub use focus_ring::*;
+pub use format::*;
+pub use guide::*;
+pub use hit_test::*;
+pub use keyframes::*;
+pub use material::*;
+pub use mesh::*;
+pub use mirror::*;
+pub use particles::*;
+pub use pixel_inspector::*;
+pub use polyline::*;
+pub use pyramid::*;
 pub use quad::*;
+pub use safe_area::*;
+pub use shader_quad::*;
+pub use text_log::*;
+pub use text_style::*;
+pub use theme::*;
+pub use validate::*;
+pub use wrap::*;
+
+// Straight (not premultiplied) rgba in the 0 to 1 range, which is how every color in a scene is
+// given. Every primitive's shader premultiplies its output and the pipelines blend with
+// premultiplied alpha, which avoids dark fringes where antialiased edges meet bright
+// backgrounds. Sprite textures are premultiplied when they are decoded. Glyphs on adapters
+// without dual source blending composite themselves over the frame in the shader instead.
+pub type Color = Vec4;
 
-#[derive(Deserialize, Debug, Clone)]
+// Lists of a layer's items. Most layers only hold a couple of each kind of item, which are kept
+// inline so cloning a layer to change it doesn't allocate for every list
+pub type LayerItems<T> = SmallVec<[T; 2]>;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Scene {
-    pub layers: Vec<Layer>,
+    // Color the frame is cleared to before the first layer is drawn. Transparent colors only
+    // show through on windows created with transparency
+    #[serde(default = "default_clear_color")]
+    pub clear_color: Color,
+    // Layers are shared between clones of the scene and copied on first write, so cloning a
+    // mostly static scene only copies the layers that then change
+    pub layers: SmallVec<[Arc<Layer>; 4]>,
+    // Drawn above every layer, unclipped and unaffected by layer effects
+    #[serde(default)]
+    pub guides: Vec<Guide>,
+    // Keyframed animation of the scene's layers and items, played by `ScenePlayer`
+    #[serde(default)]
+    pub tracks: Vec<KeyframeTrack>,
+    // Light colors and what they become when the window is in dark mode
+    #[serde(default)]
+    pub theme_colors: Vec<ThemeColor>,
 }
 
 impl Scene {
     pub fn new() -> Self {
         Self {
-            layers: vec![Default::default()],
+            clear_color: default_clear_color(),
+            layers: smallvec![Default::default()],
+            guides: Vec::new(),
+            tracks: Vec::new(),
+            theme_colors: Vec::new(),
+        }
+    }
+
+    // Scene which leaves the frame fully transparent until something is drawn, for use with
+    // transparent windows
+    pub fn transparent() -> Self {
+        Self {
+            clear_color: Vec4::ZERO,
+            layers: smallvec![Arc::new(Layer {
+                background_color: None,
+                ..Default::default()
+            })],
+            guides: Vec::new(),
+            tracks: Vec::new(),
+            theme_colors: Vec::new(),
         }
     }
 
+    pub fn with_clear_color(mut self, color: Vec4) -> Self {
+        self.set_clear_color(color);
+        self
+    }
+
+    pub fn set_clear_color(&mut self, color: Vec4) {
+        self.clear_color = color;
+    }
+
+    pub fn add_guide(&mut self, guide: Guide) {
+        self.guides.push(guide);
+    }
+
+    pub fn with_guide(mut self, guide: Guide) -> Self {
+        self.add_guide(guide);
+        self
+    }
+
+    pub fn add_crosshair(&mut self, position: Vec2, color: Vec4) {
+        self.guides.extend(Guide::crosshair(position, color));
+    }
+
+    pub fn with_crosshair(mut self, position: Vec2, color: Vec4) -> Self {
+        self.add_crosshair(position, color);
+        self
+    }
+
+    pub fn add_theme_color(&mut self, theme_color: ThemeColor) {
+        self.theme_colors.push(theme_color);
+    }
+
+    pub fn with_theme_color(mut self, theme_color: ThemeColor) -> Self {
+        self.add_theme_color(theme_color);
+        self
+    }
+
+    pub fn add_track(&mut self, track: KeyframeTrack) {
+        self.tracks.push(track);
+    }
+
+    pub fn with_track(mut self, track: KeyframeTrack) -> Self {
+        self.add_track(track);
+        self
+    }
+
     pub fn add_layer(&mut self, layer: Layer) {
-        self.layers.push(layer);
+        self.layers.push(Arc::new(layer));
     }
 
     pub fn with_layer(mut self, layer: Layer) -> Self {
@@ -30,8 +172,9 @@ impl Scene {
         self.layers.last().unwrap()
     }
 
+    // Copies the last layer first if it's shared with another clone of the scene
     pub fn layer_mut(&mut self) -> &mut Layer {
-        self.layers.last_mut().unwrap()
+        Arc::make_mut(self.layers.last_mut().unwrap())
     }
 
     pub fn with_clip(mut self, clip: Vec4) -> Self {
@@ -39,11 +182,36 @@ impl Scene {
         self
     }
 
+    pub fn with_safe_area(mut self) -> Self {
+        self.layer_mut().within_safe_area = true;
+        self
+    }
+
     pub fn with_blur(mut self, radius: f32) -> Self {
         self.layer_mut().background_blur_radius = radius;
         self
     }
 
+    pub fn with_blur_resolution(mut self, resolution: BlurResolution) -> Self {
+        self.layer_mut().background_blur_resolution = resolution;
+        self
+    }
+
+    pub fn with_blur_quality(mut self, quality: BlurQuality) -> Self {
+        self.layer_mut().background_blur_quality = quality;
+        self
+    }
+
+    pub fn with_content_blur(mut self, radius: f32) -> Self {
+        self.layer_mut().content_blur_radius = radius;
+        self
+    }
+
+    pub fn with_color_filter(mut self, filter: ColorFilter) -> Self {
+        self.layer_mut().color_filter = Some(filter);
+        self
+    }
+
     pub fn with_background(mut self, color: Vec4) -> Self {
         self.layer_mut().background_color = Some(color);
         self
@@ -67,6 +235,11 @@ impl Scene {
         self.layer().font_size
     }
 
+    pub fn with_text_style(mut self, text_style: TextStyle) -> Self {
+        self.layer_mut().text_style = text_style;
+        self
+    }
+
     pub fn add_quad(&mut self, quad: Quad) {
         self.layer_mut().add_quad(quad);
     }
@@ -76,6 +249,15 @@ impl Scene {
         self
     }
 
+    pub fn add_material_quad(&mut self, material_quad: MaterialQuad) {
+        self.layer_mut().add_material_quad(material_quad);
+    }
+
+    pub fn with_material_quad(mut self, material_quad: MaterialQuad) -> Self {
+        self.add_material_quad(material_quad);
+        self
+    }
+
     pub fn add_text(&mut self, text: Text) {
         self.layer_mut().add_text(text);
     }
@@ -85,6 +267,15 @@ impl Scene {
         self
     }
 
+    pub fn add_text_log(&mut self, text_log: TextLog) {
+        self.layer_mut().add_text_log(text_log);
+    }
+
+    pub fn with_text_log(mut self, text_log: TextLog) -> Self {
+        self.add_text_log(text_log);
+        self
+    }
+
     pub fn add_path(&mut self, path: Path) {
         self.layer_mut().add_path(path);
     }
@@ -94,6 +285,24 @@ impl Scene {
         self
     }
 
+    pub fn add_focus_ring(&mut self, focus_ring: &FocusRing) {
+        self.layer_mut().add_focus_ring(focus_ring);
+    }
+
+    pub fn add_badge(&mut self, badge: &Badge) {
+        self.layer_mut().add_badge(badge);
+    }
+
+    pub fn with_badge(mut self, badge: &Badge) -> Self {
+        self.add_badge(badge);
+        self
+    }
+
+    pub fn with_focus_ring(mut self, focus_ring: &FocusRing) -> Self {
+        self.add_focus_ring(focus_ring);
+        self
+    }
+
     pub fn add_sprite(&mut self, sprite: Sprite) {
         self.layer_mut().add_sprite(sprite);
     }
@@ -102,46 +311,301 @@ impl Scene {
         self.add_sprite(sprite);
         self
     }
+
+    pub fn add_mirror(&mut self, mirror: Mirror) {
+        self.layer_mut().add_mirror(mirror);
+    }
+
+    pub fn add_ellipse(&mut self, ellipse: Ellipse) {
+        self.layer_mut().add_ellipse(ellipse);
+    }
+
+    pub fn with_ellipse(mut self, ellipse: Ellipse) -> Self {
+        self.add_ellipse(ellipse);
+        self
+    }
+
+    pub fn add_polyline(&mut self, polyline: Polyline) {
+        self.layer_mut().add_polyline(polyline);
+    }
+
+    pub fn with_polyline(mut self, polyline: Polyline) -> Self {
+        self.add_polyline(polyline);
+        self
+    }
+
+    pub fn add_mesh(&mut self, mesh: Mesh) {
+        self.layer_mut().add_mesh(mesh);
+    }
+
+    pub fn with_mesh(mut self, mesh: Mesh) -> Self {
+        self.add_mesh(mesh);
+        self
+    }
+
+    pub fn add_pyramid(&mut self, pyramid: ImagePyramid) {
+        self.layer_mut().add_pyramid(pyramid);
+    }
+
+    pub fn with_pyramid(mut self, pyramid: ImagePyramid) -> Self {
+        self.add_pyramid(pyramid);
+        self
+    }
+
+    pub fn add_particle_emitter(&mut self, emitter: ParticleEmitter) {
+        self.layer_mut().add_particle_emitter(emitter);
+    }
+
+    pub fn with_particle_emitter(mut self, emitter: ParticleEmitter) -> Self {
+        self.add_particle_emitter(emitter);
+        self
+    }
+
+    pub fn add_shader_quad(&mut self, shader_quad: ShaderQuad) {
+        self.layer_mut().add_shader_quad(shader_quad);
+    }
+
+    pub fn with_shader_quad(mut self, shader_quad: ShaderQuad) -> Self {
+        self.add_shader_quad(shader_quad);
+        self
+    }
+
+    pub fn add_custom<T: Any + Send + Sync>(&mut self, item: T) {
+        self.layer_mut().add_custom(item);
+    }
+
+    pub fn with_custom<T: Any + Send + Sync>(mut self, item: T) -> Self {
+        self.add_custom(item);
+        self
+    }
+
+    pub fn add_named(&mut self, item: NamedItem) {
+        self.layer_mut().add_named(item);
+    }
+
+    pub fn with_named(mut self, item: NamedItem) -> Self {
+        self.add_named(item);
+        self
+    }
+
+    pub fn with_mirror(mut self, mirror: Mirror) -> Self {
+        self.add_mirror(mirror);
+        self
+    }
+
+    // Mirrors are drawn after every other item in their layer, so the loupe goes in the current
+    // layer and its outline and label in a new transparent layer above it
+    pub fn add_pixel_inspector(&mut self, inspector: &PixelInspector) {
+        let (mirror, outline, label) = inspector.layout();
+        self.add_mirror(mirror);
+
+        let mut overlay = Layer {
+            name: Some("Pixel inspector".to_string()),
+            clip: self.layer().clip,
+            background_color: None,
+            font_name: self.font().to_string(),
+            ..Default::default()
+        };
+        overlay.add_path(outline);
+        if let Some(label) = label {
+            overlay.add_badge(&label);
+        }
+        self.add_layer(overlay);
+    }
+
+    pub fn with_pixel_inspector(mut self, inspector: &PixelInspector) -> Self {
+        self.add_pixel_inspector(inspector);
+        self
+    }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Layer {
+    #[serde(default)]
+    pub name: Option<String>,
     #[serde(default)]
     pub clip: Option<Vec4>,
+    // Stack of fill areas the layer's items are clipped to on top of `clip`. Nested paths
+    // intersect, so items only show where every path on the stack covers
+    #[serde(default)]
+    pub clip_paths: Vec<Path>,
+    // Clip the layer to the renderer's safe area as well, so content stays clear of notches
+    // and menu bars. Usually set on a root layer holding the app's main content
+    #[serde(default)]
+    pub within_safe_area: bool,
+    // Draws the layer into the named offscreen texture instead of the frame. Sprites using the
+    // name as their texture show it in this and later frames, so expensive static content can
+    // be drawn once and reused. Layers sharing a target are drawn into it in order
+    #[serde(default)]
+    pub render_target: Option<String>,
+    #[serde(default)]
+    pub cache_hint: CacheHint,
+    // Safe to animate every frame. Changing the radius never reallocates any gpu resources
     #[serde(default)]
     pub background_blur_radius: f32,
     #[serde(default)]
-    pub background_color: Option<Vec4>,
+    pub background_blur_resolution: BlurResolution,
+    #[serde(default)]
+    pub background_blur_quality: BlurQuality,
+    // Only update a reduced resolution backdrop blur every other frame, reusing the previous
+    // result in between. Halves the cost of huge blurs over animated content at the price of
+    // a frame of latency. Changing the blur's settings still updates it immediately
+    #[serde(default)]
+    pub background_blur_half_rate: bool,
+    #[serde(default)]
+    pub background_color: Option<Color>,
+    // Blurs everything the layer draws, background included, for frosted glass or depth of
+    // field effects. The layer is drawn into its own texture first, which costs a full
+    // screen of memory and a composite pass
+    #[serde(default)]
+    pub content_blur_radius: f32,
+    #[serde(default)]
+    pub color_filter: Option<ColorFilter>,
     #[serde(default = "default_font")]
     pub font_name: String,
     #[serde(default = "default_size")]
     pub font_size: f32,
+    // Defaults for properties the layer's texts leave unset
+    #[serde(default)]
+    pub text_style: TextStyle,
+    // Drawn beneath the layer's plain quads
+    #[serde(default)]
+    pub material_quads: LayerItems<MaterialQuad>,
+    #[serde(default)]
+    pub quads: LayerItems<Quad>,
+    // Drawn after the layer's quads and beneath its text
+    #[serde(default)]
+    pub ellipses: LayerItems<Ellipse>,
+    #[serde(default)]
+    pub texts: LayerItems<Text>,
+    // Drawn after the layer's texts
+    #[serde(default)]
+    pub text_logs: LayerItems<TextLog>,
+    #[serde(default)]
+    pub paths: LayerItems<Path>,
+    // Drawn after the layer's paths
+    #[serde(default)]
+    pub polylines: LayerItems<Polyline>,
+    // Drawn after the layer's polylines
+    #[serde(default)]
+    pub meshes: LayerItems<Mesh>,
+    // Drawn after the layer's meshes
+    #[serde(default)]
+    pub pyramids: LayerItems<ImagePyramid>,
+    #[serde(default)]
+    pub sprites: LayerItems<Sprite>,
+    // Drawn after the layer's sprites
     #[serde(default)]
-    pub quads: Vec<Quad>,
+    pub shader_quads: LayerItems<ShaderQuad>,
+    // Drawn after the layer's shader quads
     #[serde(default)]
-    pub texts: Vec<Text>,
+    pub particle_emitters: LayerItems<ParticleEmitter>,
+    // Drawn above the layer's other items
     #[serde(default)]
-    pub paths: Vec<Path>,
+    pub mirrors: LayerItems<Mirror>,
+    // Drawn after everything else in the layer
     #[serde(default)]
-    pub sprites: Vec<Sprite>,
+    pub guides: LayerItems<Guide>,
+    // Items for custom drawables, which are drawn in the order the drawables were added
+    #[serde(skip)]
+    pub custom: CustomItems,
+    // Custom items saved by name, waiting for a registry to resolve them
+    #[serde(default)]
+    pub named: Vec<NamedItem>,
 }
 
 impl Default for Layer {
     fn default() -> Self {
         Self {
+            name: None,
             clip: None,
+            clip_paths: Vec::new(),
+            within_safe_area: false,
+            render_target: None,
+            cache_hint: CacheHint::Dynamic,
             background_blur_radius: 0.0,
+            background_blur_resolution: BlurResolution::Full,
+            background_blur_quality: BlurQuality::Low,
+            background_blur_half_rate: false,
             background_color: Some(Vec4::new(1.0, 1.0, 1.0, 1.0)),
+            content_blur_radius: 0.0,
+            color_filter: None,
             font_name: "Courier New".to_string(),
             font_size: 16.0,
-            quads: Vec::new(),
-            texts: Vec::new(),
-            paths: Vec::new(),
-            sprites: Vec::new(),
+            text_style: TextStyle::default(),
+            material_quads: SmallVec::new(),
+            quads: SmallVec::new(),
+            ellipses: SmallVec::new(),
+            texts: SmallVec::new(),
+            text_logs: SmallVec::new(),
+            paths: SmallVec::new(),
+            polylines: SmallVec::new(),
+            meshes: SmallVec::new(),
+            pyramids: SmallVec::new(),
+            sprites: SmallVec::new(),
+            shader_quads: SmallVec::new(),
+            particle_emitters: SmallVec::new(),
+            mirrors: SmallVec::new(),
+            guides: SmallVec::new(),
+            custom: CustomItems::default(),
+            named: Vec::new(),
         }
     }
 }
 
+// Resolution the backdrop of a layer is blurred at. Reduced resolutions blur the backdrop once
+// per layer with a dual filter and are much cheaper for large radii. Every quad in the layer
+// with a background blur samples the same blurred backdrop.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlurResolution {
+    #[default]
+    Full,
+    Half,
+    Quarter,
+}
+
+// How a reduced resolution backdrop blur is computed. Full resolution blurs are always done
+// inline, so they aren't affected.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlurQuality {
+    // Dual filter passes only. The cheapest option, but radii beyond what the smallest level
+    // can reach spread the samples apart and start to band
+    #[default]
+    Low,
+    // Dual filter downsampling followed by a separable gaussian
+    Medium,
+    // Gaussian with twice the samples, which also runs at a higher resolution level
+    High,
+}
+
+impl BlurQuality {
+    // Samples on each side of the gaussian kernel's center, or None for dual filtering only
+    pub(crate) fn gaussian_taps(self) -> Option<u32> {
+        match self {
+            BlurQuality::Low => None,
+            BlurQuality::Medium => Some(8),
+            BlurQuality::High => Some(16),
+        }
+    }
+}
+
+// Whether the renderer keeps a layer's pixels between frames
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CacheHint {
+    // Drawn from scratch every frame
+    #[default]
+    Dynamic,
+    // Drawn once into a texture the size of the window, which is composited on later frames
+    // until the layer changes. Layers shared with the previous scene are compared by pointer
+    // and others are hashed. Layers blurring their backdrop depend on what's drawn below them,
+    // so they aren't cached
+    Static,
+}
+
+pub(crate) fn default_clear_color() -> Vec4 {
+    Vec4::ONE
+}
+
 fn default_font() -> String {
     "Courier New".to_string()
 }
@@ -151,15 +615,83 @@ fn default_size() -> f32 {
 }
 
 impl Layer {
+    pub fn has_background(&self) -> bool {
+        self.background_color.is_some() || self.background_blur_radius != 0.0
+    }
+
+    // A layer with no background and no items draws nothing, so the renderer skips it
+    pub fn is_empty(&self) -> bool {
+        !self.has_background()
+            && self.material_quads.is_empty()
+            && self.quads.is_empty()
+            && self.texts.is_empty()
+            && self.text_logs.is_empty()
+            && self.paths.is_empty()
+            && self.sprites.is_empty()
+            && self.ellipses.is_empty()
+            && self.polylines.is_empty()
+            && self.meshes.is_empty()
+            && self.pyramids.is_empty()
+            && self.shader_quads.is_empty()
+            && self.particle_emitters.is_empty()
+            && self.mirrors.is_empty()
+            && self.guides.is_empty()
+            && self.custom.is_empty()
+            && self.named.is_empty()
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
     pub fn with_clip(mut self, clip: Vec4) -> Self {
         self.clip = Some(clip);
         self
     }
 
+    pub fn with_safe_area(mut self) -> Self {
+        self.within_safe_area = true;
+        self
+    }
+
+    pub fn with_render_target(mut self, name: impl Into<String>) -> Self {
+        self.set_render_target(Some(name.into()));
+        self
+    }
+
+    pub fn set_render_target(&mut self, name: Option<String>) {
+        self.render_target = name;
+    }
+
+    pub fn with_cache_hint(mut self, cache_hint: CacheHint) -> Self {
+        self.cache_hint = cache_hint;
+        self
+    }
+
+    pub fn set_cache_hint(&mut self, cache_hint: CacheHint) {
+        self.cache_hint = cache_hint;
+    }
+
     pub fn set_clip(&mut self, clip: Vec4) {
         self.clip = Some(clip);
     }
 
+    // Clips the layer's items to the path's fill area, whether or not the path is filled
+    pub fn push_clip_path(&mut self, path: Path) {
+        self.clip_paths.push(path);
+    }
+
+    pub fn with_clip_path(mut self, path: Path) -> Self {
+        self.push_clip_path(path);
+        self
+    }
+
+    // Removes the most recently pushed clip path
+    pub fn pop_clip(&mut self) -> Option<Path> {
+        self.clip_paths.pop()
+    }
+
     pub fn with_blur(mut self, radius: f32) -> Self {
         self.background_blur_radius = radius;
         self
@@ -169,11 +701,61 @@ impl Layer {
         self.background_blur_radius = radius;
     }
 
+    pub fn with_blur_resolution(mut self, resolution: BlurResolution) -> Self {
+        self.background_blur_resolution = resolution;
+        self
+    }
+
+    pub fn set_blur_resolution(&mut self, resolution: BlurResolution) {
+        self.background_blur_resolution = resolution;
+    }
+
+    pub fn with_blur_quality(mut self, quality: BlurQuality) -> Self {
+        self.background_blur_quality = quality;
+        self
+    }
+
+    pub fn set_blur_quality(&mut self, quality: BlurQuality) {
+        self.background_blur_quality = quality;
+    }
+
+    pub fn with_half_rate_blur(mut self) -> Self {
+        self.background_blur_half_rate = true;
+        self
+    }
+
+    pub fn set_half_rate_blur(&mut self, half_rate: bool) {
+        self.background_blur_half_rate = half_rate;
+    }
+
     pub fn with_background(mut self, color: Vec4) -> Self {
         self.background_color = Some(color);
         self
     }
 
+    pub fn with_content_blur(mut self, radius: f32) -> Self {
+        self.content_blur_radius = radius;
+        self
+    }
+
+    pub fn set_content_blur(&mut self, radius: f32) {
+        self.content_blur_radius = radius;
+    }
+
+    pub fn with_color_filter(mut self, filter: ColorFilter) -> Self {
+        self.color_filter = Some(filter);
+        self
+    }
+
+    pub fn set_color_filter(&mut self, filter: Option<ColorFilter>) {
+        self.color_filter = filter;
+    }
+
+    // Whether the layer is drawn into its own texture and composited over the frame
+    pub(crate) fn needs_composite(&self) -> bool {
+        self.content_blur_radius != 0.0 || self.color_filter.is_some()
+    }
+
     pub fn set_background(&mut self, color: Vec4) {
         self.background_color = Some(color);
     }
@@ -187,6 +769,15 @@ impl Layer {
         self.font_name = font_name;
     }
 
+    pub fn with_text_style(mut self, text_style: TextStyle) -> Self {
+        self.text_style = text_style;
+        self
+    }
+
+    pub fn set_text_style(&mut self, text_style: TextStyle) {
+        self.text_style = text_style;
+    }
+
     pub fn add_quad(&mut self, quad: Quad) {
         self.quads.push(quad);
     }
@@ -196,6 +787,15 @@ impl Layer {
         self
     }
 
+    pub fn add_material_quad(&mut self, material_quad: MaterialQuad) {
+        self.material_quads.push(material_quad);
+    }
+
+    pub fn with_material_quad(mut self, material_quad: MaterialQuad) -> Self {
+        self.add_material_quad(material_quad);
+        self
+    }
+
     pub fn add_text(&mut self, text: Text) {
         self.texts.push(text);
     }
@@ -205,6 +805,15 @@ impl Layer {
         self
     }
 
+    pub fn add_text_log(&mut self, text_log: TextLog) {
+        self.text_logs.push(text_log);
+    }
+
+    pub fn with_text_log(mut self, text_log: TextLog) -> Self {
+        self.add_text_log(text_log);
+        self
+    }
+
     pub fn add_path(&mut self, path: Path) {
         self.paths.push(path);
     }
@@ -214,6 +823,28 @@ impl Layer {
         self
     }
 
+    // Focus rings are drawn as paths, so they stack with the layer's other paths
+    pub fn add_focus_ring(&mut self, focus_ring: &FocusRing) {
+        self.add_path(focus_ring.to_path());
+    }
+
+    pub fn with_focus_ring(mut self, focus_ring: &FocusRing) -> Self {
+        self.add_focus_ring(focus_ring);
+        self
+    }
+
+    // Measures the badge with the layer's font and adds its background and text
+    pub fn add_badge(&mut self, badge: &Badge) {
+        let (background, text) = badge.layout(&self.font_name);
+        self.add_quad(background);
+        self.add_text(text);
+    }
+
+    pub fn with_badge(mut self, badge: &Badge) -> Self {
+        self.add_badge(badge);
+        self
+    }
+
     pub fn add_sprite(&mut self, sprite: Sprite) {
         self.sprites.push(sprite);
     }
@@ -222,56 +853,274 @@ impl Layer {
         self.add_sprite(sprite);
         self
     }
+
+    pub fn add_mirror(&mut self, mirror: Mirror) {
+        self.mirrors.push(mirror);
+    }
+
+    pub fn add_guide(&mut self, guide: Guide) {
+        self.guides.push(guide);
+    }
+
+    pub fn add_ellipse(&mut self, ellipse: Ellipse) {
+        self.ellipses.push(ellipse);
+    }
+
+    pub fn with_ellipse(mut self, ellipse: Ellipse) -> Self {
+        self.add_ellipse(ellipse);
+        self
+    }
+
+    pub fn add_polyline(&mut self, polyline: Polyline) {
+        self.polylines.push(polyline);
+    }
+
+    pub fn with_polyline(mut self, polyline: Polyline) -> Self {
+        self.add_polyline(polyline);
+        self
+    }
+
+    pub fn add_mesh(&mut self, mesh: Mesh) {
+        self.meshes.push(mesh);
+    }
+
+    pub fn with_mesh(mut self, mesh: Mesh) -> Self {
+        self.add_mesh(mesh);
+        self
+    }
+
+    pub fn add_pyramid(&mut self, pyramid: ImagePyramid) {
+        self.pyramids.push(pyramid);
+    }
+
+    pub fn with_pyramid(mut self, pyramid: ImagePyramid) -> Self {
+        self.add_pyramid(pyramid);
+        self
+    }
+
+    pub fn add_particle_emitter(&mut self, emitter: ParticleEmitter) {
+        self.particle_emitters.push(emitter);
+    }
+
+    pub fn with_particle_emitter(mut self, emitter: ParticleEmitter) -> Self {
+        self.add_particle_emitter(emitter);
+        self
+    }
+
+    pub fn add_shader_quad(&mut self, shader_quad: ShaderQuad) {
+        self.shader_quads.push(shader_quad);
+    }
+
+    pub fn with_shader_quad(mut self, shader_quad: ShaderQuad) -> Self {
+        self.add_shader_quad(shader_quad);
+        self
+    }
+
+    // Attaches an item of a user defined type for a custom drawable to pick up with
+    // `layer.custom.get::<T>()`
+    pub fn add_custom<T: Any + Send + Sync>(&mut self, item: T) {
+        self.custom.add(item);
+    }
+
+    pub fn with_custom<T: Any + Send + Sync>(mut self, item: T) -> Self {
+        self.add_custom(item);
+        self
+    }
+
+    pub fn add_named(&mut self, item: NamedItem) {
+        self.named.push(item);
+    }
+
+    pub fn with_named(mut self, item: NamedItem) -> Self {
+        self.add_named(item);
+        self
+    }
+
+    pub fn with_mirror(mut self, mirror: Mirror) -> Self {
+        self.add_mirror(mirror);
+        self
+    }
+
+    pub fn with_guide(mut self, guide: Guide) -> Self {
+        self.add_guide(guide);
+        self
+    }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Text {
     pub text: String,
     pub bottom_left: Vec2,
     pub size: f32,
-    pub color: Vec4,
+    // Unset properties are taken from the layer's text style
+    #[serde(default)]
+    pub color: Option<Color>,
     #[serde(default)]
-    pub bold: bool,
+    pub bold: Option<bool>,
     #[serde(default)]
-    pub italic: bool,
-    #[serde(default = "default_subpixel")]
-    pub subpixel: bool,
+    pub italic: Option<bool>,
+    #[serde(default)]
+    pub subpixel: Option<bool>,
+    // Only used when depth testing is enabled. Items with a higher depth are drawn over lower
+    // ones regardless of order, and equal depths fall back to painter's order
+    #[serde(default)]
+    pub depth: f32,
+    // Base direction for the bidi algorithm. Mixed direction text is always shown in visual
+    // order, and this only decides how runs are ordered relative to each other
+    #[serde(default)]
+    pub direction: TextDirection,
+    // Stacks glyphs top to bottom for CJK text. The first glyph sits on `bottom_left` and later
+    // ones continue downwards, each centered in a column the font size wide
+    #[serde(default)]
+    pub vertical: bool,
+    // OpenType features applied on top of the font's defaults and the layer's features, in
+    // order
+    #[serde(default)]
+    pub features: Vec<FontFeature>,
+    // Extra space in pixels added after each glyph. Negative values tighten the text
+    #[serde(default)]
+    pub letter_spacing: Option<f32>,
+    // Distance between the baselines of lines separated by `\n` as a multiple of the font
+    // size. Unset everywhere uses the font's own line spacing
+    #[serde(default)]
+    pub line_height: Option<f32>,
 }
 
-fn default_subpixel() -> bool {
-    true
+// Paragraph direction of a text
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TextDirection {
+    // Picked from the first strongly directional character
+    #[default]
+    Auto,
+    LeftToRight,
+    RightToLeft,
+}
+
+// OpenType feature setting passed to the shaper, such as `liga` for ligatures or `tnum` for
+// tabular numbers. Most features are toggles where 0 disables and 1 enables them, while
+// features like `salt` use the value to pick an alternate.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FontFeature {
+    // Four character feature tag
+    pub tag: String,
+    pub value: u16,
+}
+
+impl FontFeature {
+    pub fn new(tag: impl Into<String>, value: u16) -> Self {
+        Self {
+            tag: tag.into(),
+            value,
+        }
+    }
+
+    pub fn enable(tag: impl Into<String>) -> Self {
+        Self::new(tag, 1)
+    }
+
+    pub fn disable(tag: impl Into<String>) -> Self {
+        Self::new(tag, 0)
+    }
 }
 
 impl Text {
     pub fn new(text: String, bottom_left: Vec2, size: f32, color: Vec4) -> Self {
+        Self::unstyled(text, bottom_left, size).with_color(color)
+    }
+
+    // Text which takes its color and other properties from its layer's text style
+    pub fn unstyled(text: String, bottom_left: Vec2, size: f32) -> Self {
         Self {
             text,
             bottom_left,
             size,
-            color,
-            bold: false,
-            italic: false,
-            subpixel: true,
+            color: None,
+            bold: None,
+            italic: None,
+            subpixel: None,
+            depth: 0.0,
+            direction: TextDirection::Auto,
+            vertical: false,
+            features: Vec::new(),
+            letter_spacing: None,
+            line_height: None,
         }
     }
 
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = Some(color);
+        self
+    }
+
     pub fn with_bold(mut self) -> Self {
-        self.bold = true;
+        self.bold = Some(true);
         self
     }
 
     pub fn with_italic(mut self) -> Self {
-        self.italic = true;
+        self.italic = Some(true);
         self
     }
 
     pub fn without_subpixel(mut self) -> Self {
-        self.subpixel = false;
+        self.subpixel = Some(false);
+        self
+    }
+
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn with_direction(mut self, direction: TextDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn with_vertical(mut self) -> Self {
+        self.vertical = true;
+        self
+    }
+
+    pub fn with_feature(mut self, feature: FontFeature) -> Self {
+        self.features.push(feature);
+        self
+    }
+
+    // Toggles standard, contextual, and discretionary ligatures together. Programming fonts
+    // build most of their ligatures from contextual alternates, so `calt` is included
+    pub fn with_ligatures(self, enabled: bool) -> Self {
+        let value = enabled as u16;
+        ["liga", "clig", "calt", "dlig"]
+            .into_iter()
+            .fold(self, |text, tag| {
+                text.with_feature(FontFeature::new(tag, value))
+            })
+    }
+
+    // Enables stylistic set `ss01` through `ss20`
+    pub fn with_stylistic_set(self, set: u8) -> Self {
+        debug_assert!((1..=20).contains(&set), "stylistic sets range from 1 to 20");
+        self.with_feature(FontFeature::enable(format!("ss{:02}", set)))
+    }
+
+    // Gives every digit the same advance so columns of numbers line up
+    pub fn with_tabular_numbers(self) -> Self {
+        self.with_feature(FontFeature::enable("tnum"))
+    }
+
+    pub fn with_letter_spacing(mut self, letter_spacing: f32) -> Self {
+        self.letter_spacing = Some(letter_spacing);
+        self
+    }
+
+    pub fn with_line_height(mut self, line_height: f32) -> Self {
+        self.line_height = Some(line_height);
         self
     }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum PathCommand {
     CubicBezierTo {
@@ -286,16 +1135,41 @@ pub enum PathCommand {
     LineTo {
         to: Vec2,
     },
+    // Closes the current subpath and starts a new one. Lets a single path contain holes
+    MoveTo {
+        start: Vec2,
+    },
+}
+
+// Which areas enclosed by a path's contours are filled
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillRule {
+    // Areas enclosed by an odd number of contours, so overlapping contours cut holes
+    #[default]
+    EvenOdd,
+    // Areas the contours wind around at all, like font outlines. Holes have to wind the other
+    // way
+    NonZero,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Path {
     #[serde(default)]
-    pub fill: Option<Vec4>,
+    pub fill: Option<Color>,
     #[serde(default)]
-    pub stroke: Option<(f32, Vec4)>,
+    pub stroke: Option<(f32, Color)>,
     pub start: Vec2,
     pub commands: Vec<PathCommand>,
+    // Leaves each subpath unclosed so strokes don't draw the closing edge. Fills are always
+    // closed
+    #[serde(default)]
+    pub open: bool,
+    #[serde(default)]
+    pub fill_rule: FillRule,
+    // Only used when depth testing is enabled. Items with a higher depth are drawn over lower
+    // ones regardless of order, and equal depths fall back to painter's order
+    #[serde(default)]
+    pub depth: f32,
 }
 
 impl Path {
@@ -305,6 +1179,9 @@ impl Path {
             stroke: None,
             start,
             commands: Vec::new(),
+            open: false,
+            fill_rule: FillRule::EvenOdd,
+            depth: 0.0,
         }
     }
 
@@ -314,6 +1191,9 @@ impl Path {
             stroke: Some(stroke),
             start,
             commands: Vec::new(),
+            open: false,
+            fill_rule: FillRule::EvenOdd,
+            depth: 0.0,
         }
     }
 
@@ -323,9 +1203,49 @@ impl Path {
             stroke: None,
             start,
             commands: Vec::new(),
+            open: false,
+            fill_rule: FillRule::EvenOdd,
+            depth: 0.0,
         }
     }
 
+    // Closed rounded rectangle outline. Corners are approximated with cubic beziers
+    pub fn rounded_rect(top_left: Vec2, size: Vec2, corner_radius: f32) -> Self {
+        // Distance along the tangent to place control points for a quarter circle
+        const KAPPA: f32 = 0.552_284_8;
+
+        let radius = corner_radius.max(0.0).min(size.min_element() / 2.0);
+        let control = radius * (1.0 - KAPPA);
+        let (left, top) = (top_left.x, top_left.y);
+        let (right, bottom) = (top_left.x + size.x, top_left.y + size.y);
+
+        Self::new(vec2(left + radius, top))
+            .line_to(vec2(right - radius, top))
+            .cubic_bezier_to(
+                vec2(right - control, top),
+                vec2(right, top + control),
+                vec2(right, top + radius),
+            )
+            .line_to(vec2(right, bottom - radius))
+            .cubic_bezier_to(
+                vec2(right, bottom - control),
+                vec2(right - control, bottom),
+                vec2(right - radius, bottom),
+            )
+            .line_to(vec2(left + radius, bottom))
+            .cubic_bezier_to(
+                vec2(left + control, bottom),
+                vec2(left, bottom - control),
+                vec2(left, bottom - radius),
+            )
+            .line_to(vec2(left, top + radius))
+            .cubic_bezier_to(
+                vec2(left, top + control),
+                vec2(left + control, top),
+                vec2(left + radius, top),
+            )
+    }
+
     pub fn with_fill(mut self, fill: Vec4) -> Self {
         self.fill = Some(fill);
         self
@@ -336,6 +1256,16 @@ impl Path {
         self
     }
 
+    pub fn with_open(mut self, open: bool) -> Self {
+        self.open = open;
+        self
+    }
+
+    pub fn with_fill_rule(mut self, fill_rule: FillRule) -> Self {
+        self.fill_rule = fill_rule;
+        self
+    }
+
     pub fn cubic_bezier_to(mut self, control1: Vec2, control2: Vec2, to: Vec2) -> Self {
         self.commands.push(PathCommand::CubicBezierTo {
             control1,
@@ -355,12 +1285,247 @@ impl Path {
         self.commands.push(PathCommand::LineTo { to });
         self
     }
+
+    pub fn move_to(mut self, start: Vec2) -> Self {
+        self.commands.push(PathCommand::MoveTo { start });
+        self
+    }
+
+    // Moves every point of the path by the offset
+    pub fn translate(&mut self, offset: Vec2) {
+        self.start += offset;
+        for command in self.commands.iter_mut() {
+            match command {
+                PathCommand::LineTo { to } => *to += offset,
+                PathCommand::QuadraticBezierTo { control, to } => {
+                    *control += offset;
+                    *to += offset;
+                }
+                PathCommand::CubicBezierTo {
+                    control1,
+                    control2,
+                    to,
+                } => {
+                    *control1 += offset;
+                    *control2 += offset;
+                    *to += offset;
+                }
+                PathCommand::MoveTo { start } => *start += offset,
+            }
+        }
+    }
+
+    // Conservative area covered by the path and its stroke, as (x, y, width, height). Control
+    // points are included so curves are always contained.
+    pub fn bounds(&self) -> Vec4 {
+        let mut min = self.start;
+        let mut max = self.start;
+        let mut include = |point: Vec2| {
+            min = min.min(point);
+            max = max.max(point);
+        };
+        for command in self.commands.iter() {
+            match command {
+                PathCommand::CubicBezierTo {
+                    control1,
+                    control2,
+                    to,
+                } => {
+                    include(*control1);
+                    include(*control2);
+                    include(*to);
+                }
+                PathCommand::QuadraticBezierTo { control, to } => {
+                    include(*control);
+                    include(*to);
+                }
+                PathCommand::LineTo { to } => include(*to),
+                PathCommand::MoveTo { start } => include(*start),
+            }
+        }
+
+        let extension = self.stroke.map(|(width, _)| width / 2.0).unwrap_or(0.0);
+        Vec4::new(
+            min.x - extension,
+            min.y - extension,
+            max.x - min.x + extension * 2.0,
+            max.y - min.y + extension * 2.0,
+        )
+    }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Sprite {
     pub top_left: Vec2,
     pub size: Vec2,
-    pub color: Vec4,
+    pub color: Color,
     pub texture: String,
+    #[serde(default)]
+    pub adjustments: ColorAdjustments,
+    // Texture alpha below which fragments are discarded instead of blended. Keeps hard edged
+    // icon art free of blending fringes
+    #[serde(default)]
+    pub alpha_cutoff: Option<f32>,
+    #[serde(default)]
+    pub filter: SpriteFilter,
+    // Added to the mip level picked by the linear and anisotropic filters. Negative values
+    // sharpen and positive values blur
+    #[serde(default)]
+    pub lod_bias: f32,
+    // Only used when depth testing is enabled. Items with a higher depth are drawn over lower
+    // ones regardless of order, and equal depths fall back to painter's order
+    #[serde(default)]
+    pub depth: f32,
+}
+
+// How a sprite's texture is sampled when it is drawn at a different size than the image
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpriteFilter {
+    // Nearest texel of the full resolution image. Best for pixel art drawn at integer scales
+    #[default]
+    Nearest,
+    // Trilinear filtering between mip levels
+    Linear,
+    // Trilinear filtering with up to 16x anisotropy for sprites which are rotated or squashed.
+    // Behaves like Linear on adapters without anisotropic filtering
+    Anisotropic,
+}
+
+impl Sprite {
+    pub fn with_adjustments(mut self, adjustments: ColorAdjustments) -> Self {
+        self.adjustments = adjustments;
+        self
+    }
+
+    pub fn set_adjustments(&mut self, adjustments: ColorAdjustments) {
+        self.adjustments = adjustments;
+    }
+
+    pub fn with_alpha_cutoff(mut self, threshold: f32) -> Self {
+        self.alpha_cutoff = Some(threshold);
+        self
+    }
+
+    pub fn with_filter(mut self, filter: SpriteFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_lod_bias(mut self, lod_bias: f32) -> Self {
+        self.lod_bias = lod_bias;
+        self
+    }
+
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn bounds(&self) -> Vec4 {
+        Vec4::new(self.top_left.x, self.top_left.y, self.size.x, self.size.y)
+    }
+}
+
+// Color adjustments applied to a sprite in its fragment shader so that hover or disabled states
+// can reuse the same texture. Hue is applied first, then saturation, contrast, and brightness.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct ColorAdjustments {
+    // Added to each channel. 0 is unchanged
+    pub brightness: f32,
+    // Scales each channel about mid gray. 1 is unchanged
+    pub contrast: f32,
+    // 0 is grayscale, 1 is unchanged, and larger values oversaturate
+    pub saturation: f32,
+    // Rotation of the hue in radians
+    pub hue: f32,
+    // Blends toward grayscale on top of the saturation. 0 is unchanged, 1 is fully gray
+    pub grayscale: f32,
+}
+
+impl Default for ColorAdjustments {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            hue: 0.0,
+            grayscale: 0.0,
+        }
+    }
+}
+
+impl ColorAdjustments {
+    pub fn with_brightness(mut self, brightness: f32) -> Self {
+        self.brightness = brightness;
+        self
+    }
+
+    pub fn with_contrast(mut self, contrast: f32) -> Self {
+        self.contrast = contrast;
+        self
+    }
+
+    pub fn with_saturation(mut self, saturation: f32) -> Self {
+        self.saturation = saturation;
+        self
+    }
+
+    pub fn with_hue(mut self, hue: f32) -> Self {
+        self.hue = hue;
+        self
+    }
+
+    pub fn with_grayscale(mut self, grayscale: f32) -> Self {
+        self.grayscale = grayscale;
+        self
+    }
+
+    pub(crate) fn to_vec4(&self) -> Vec4 {
+        Vec4::new(
+            self.brightness,
+            self.contrast,
+            self.saturation * (1.0 - self.grayscale.clamp(0.0, 1.0)),
+            self.hue,
+        )
+    }
+}
+
+// Filter applied to everything a layer draws when it is composited over the layers beneath it.
+// Like a content blur, the layer is drawn into its own texture first.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ColorFilter {
+    // 0 is unchanged, 1 is fully gray
+    Grayscale(f32),
+    // Multiplied with every color, alpha included
+    Tint(Vec4),
+    // Added to each channel. 0 is unchanged
+    Brightness(f32),
+    // Scales each channel about mid gray. 1 is unchanged
+    Contrast(f32),
+    // Rotation of the hue in radians
+    HueRotate(f32),
+    // Any combination of the above adjustments, applied like a sprite's
+    Adjust(ColorAdjustments),
+}
+
+impl ColorFilter {
+    // Adjustments in the form the shaders expect and the tint applied after them
+    pub(crate) fn parameters(&self) -> (Vec4, Vec4) {
+        let adjustments = match *self {
+            ColorFilter::Grayscale(amount) => ColorAdjustments::default().with_grayscale(amount),
+            ColorFilter::Brightness(brightness) => {
+                ColorAdjustments::default().with_brightness(brightness)
+            }
+            ColorFilter::Contrast(contrast) => ColorAdjustments::default().with_contrast(contrast),
+            ColorFilter::HueRotate(hue) => ColorAdjustments::default().with_hue(hue),
+            ColorFilter::Adjust(adjustments) => adjustments,
+            ColorFilter::Tint(_) => ColorAdjustments::default(),
+        };
+        let tint = match *self {
+            ColorFilter::Tint(tint) => tint,
+            _ => Vec4::ONE,
+        };
+        (adjustments.to_vec4(), tint)
+    }
 }
@@ -0,0 +1,95 @@
+use glam::{vec2, Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+
+use super::{Color, Quad, Text};
+use crate::shaper::shape_text;
+
+// Short label on a rounded background sized to fit it, such as a notification count or a
+// tag. The text is measured with the layer's font when the badge is added to a layer.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Badge {
+    pub text: String,
+    // Center of the badge
+    pub center: Vec2,
+    pub font_size: f32,
+    pub text_color: Color,
+    pub background: Vec4,
+    // Space between the text and the edge of the background. Defaults to half the font size
+    // horizontally and a quarter vertically
+    #[serde(default)]
+    pub padding: Option<Vec2>,
+    // Defaults to fully rounded ends, which makes a pill
+    #[serde(default)]
+    pub corner_radius: Option<f32>,
+}
+
+impl Badge {
+    pub fn new(
+        text: impl Into<String>,
+        center: Vec2,
+        font_size: f32,
+        text_color: Vec4,
+        background: Vec4,
+    ) -> Self {
+        Self {
+            text: text.into(),
+            center,
+            font_size,
+            text_color,
+            background,
+            padding: None,
+            corner_radius: None,
+        }
+    }
+
+    pub fn with_padding(mut self, padding: Vec2) -> Self {
+        self.padding = Some(padding);
+        self
+    }
+
+    pub fn with_corner_radius(mut self, corner_radius: f32) -> Self {
+        self.corner_radius = Some(corner_radius);
+        self
+    }
+
+    // Background quad and centered text for the badge. If the font can't be found the text is
+    // assumed to be a typical monospace width so the badge still has a sensible size.
+    pub fn layout(&self, font_name: &str) -> (Quad, Text) {
+        let (text_size, baseline_offset) = match shape_text(&self.text, font_name, self.font_size) {
+            Some(shaped) => (
+                vec2(shaped.bounds.z, shaped.bounds.w),
+                shaped.bounds.y + shaped.bounds.w,
+            ),
+            None => (
+                vec2(
+                    self.text.chars().count() as f32 * self.font_size * 0.6,
+                    self.font_size * 1.2,
+                ),
+                self.font_size * 0.25,
+            ),
+        };
+
+        let padding = self
+            .padding
+            .unwrap_or(vec2(self.font_size * 0.5, self.font_size * 0.25));
+        let size = text_size + padding * 2.0;
+        // Keep single characters round rather than narrower than they are tall
+        let size = vec2(size.x.max(size.y), size.y);
+        let corner_radius = self.corner_radius.unwrap_or(size.y / 2.0);
+
+        let background = Quad::new(self.center - size / 2.0, size, self.background)
+            .with_corner_radius(corner_radius);
+        // The baseline sits the descent above the bottom of the centered line box
+        let text = Text::new(
+            self.text.clone(),
+            vec2(
+                self.center.x - text_size.x / 2.0,
+                self.center.y + text_size.y / 2.0 - baseline_offset,
+            ),
+            self.font_size,
+            self.text_color,
+        );
+
+        (background, text)
+    }
+}
@@ -0,0 +1,201 @@
+use glam::{vec2, Vec2, Vec4};
+use lyon::geom::{point, CubicBezierSegment, QuadraticBezierSegment};
+
+use super::{
+    ImagePyramid, Layer, Mesh, ParticleEmitter, Path, PathCommand, Polyline, Quad, Scene, Sprite,
+    Text, TextLog, TextStyle,
+};
+use crate::{
+    culling::{intersection, union},
+    placeholder::estimated_text_bounds,
+    shaper::shape_text,
+};
+
+// Tight bounds of scene items as (x, y, width, height), for scroll extents and sizing
+// containers. Unlike the conservative `bounds` used for culling, texts are measured with their
+// font and curves are bounded by their extrema rather than their control points.
+
+impl Quad {
+    // Includes the area covered by the quad's blur
+    pub fn bounding_box(&self) -> Vec4 {
+        self.bounds()
+    }
+}
+
+impl Sprite {
+    pub fn bounding_box(&self) -> Vec4 {
+        self.bounds()
+    }
+}
+
+impl Path {
+    // Strokes extend half their width past the path. Miter joins sharper than a right angle can
+    // poke out slightly further
+    pub fn bounding_box(&self) -> Vec4 {
+        let mut min = self.start;
+        let mut max = self.start;
+        let mut include = |from: Vec2, to: Vec2| {
+            min = min.min(from.min(to));
+            max = max.max(from.max(to));
+        };
+
+        let mut current = self.start;
+        for command in self.commands.iter() {
+            current = match *command {
+                PathCommand::LineTo { to } => {
+                    include(to, to);
+                    to
+                }
+                PathCommand::QuadraticBezierTo { control, to } => {
+                    let curve = QuadraticBezierSegment {
+                        from: point(current.x, current.y),
+                        ctrl: point(control.x, control.y),
+                        to: point(to.x, to.y),
+                    }
+                    .bounding_box();
+                    include(
+                        vec2(curve.min.x, curve.min.y),
+                        vec2(curve.max.x, curve.max.y),
+                    );
+                    to
+                }
+                PathCommand::CubicBezierTo {
+                    control1,
+                    control2,
+                    to,
+                } => {
+                    let curve = CubicBezierSegment {
+                        from: point(current.x, current.y),
+                        ctrl1: point(control1.x, control1.y),
+                        ctrl2: point(control2.x, control2.y),
+                        to: point(to.x, to.y),
+                    }
+                    .bounding_box();
+                    include(
+                        vec2(curve.min.x, curve.min.y),
+                        vec2(curve.max.x, curve.max.y),
+                    );
+                    to
+                }
+                PathCommand::MoveTo { start } => {
+                    include(start, start);
+                    start
+                }
+            };
+        }
+
+        let half_width = self.stroke.map_or(0.0, |(width, _)| width / 2.0);
+        let (min, max) = (min - half_width, max + half_width);
+        Vec4::new(min.x, min.y, max.x - min.x, max.y - min.y)
+    }
+}
+
+impl Text {
+    // Measured with the given font, ignoring any layer text style. Texts whose font can't be
+    // loaded are estimated from their length
+    pub fn bounding_box(&self, font_name: &str) -> Vec4 {
+        text_bounds(self, font_name, &TextStyle::default())
+    }
+}
+
+impl Layer {
+    // Union of the bounds of every item in the layer, limited to its clip. Backgrounds fill
+    // the whole surface and custom items have no known shape, so neither is included. Returns
+    // None for layers without any other items
+    pub fn bounding_box(&self) -> Option<Vec4> {
+        let items = self
+            .material_quads
+            .iter()
+            .map(|quad| Vec4::new(quad.top_left.x, quad.top_left.y, quad.size.x, quad.size.y))
+            .chain(self.quads.iter().map(Quad::bounding_box))
+            .chain(self.ellipses.iter().map(|ellipse| ellipse.bounds()))
+            .chain(
+                self.texts
+                    .iter()
+                    .map(|text| text_bounds(text, &self.font_name, &self.text_style)),
+            )
+            .chain(self.text_logs.iter().map(TextLog::bounds))
+            .chain(self.paths.iter().map(Path::bounding_box))
+            .chain(self.polylines.iter().filter_map(Polyline::bounds))
+            .chain(self.meshes.iter().filter_map(Mesh::bounds))
+            .chain(self.pyramids.iter().map(ImagePyramid::bounds))
+            .chain(self.sprites.iter().map(Sprite::bounding_box))
+            .chain(self.shader_quads.iter().map(|quad| quad.bounds()))
+            .chain(self.particle_emitters.iter().map(ParticleEmitter::bounds))
+            .chain(self.mirrors.iter().map(|mirror| mirror.bounds()));
+
+        let bounds = items.reduce(union)?;
+        Some(match self.clip {
+            Some(clip) => intersection(bounds, clip),
+            None => bounds,
+        })
+    }
+}
+
+impl Scene {
+    // Union of the bounds of every layer
+    pub fn bounding_box(&self) -> Option<Vec4> {
+        self.layers
+            .iter()
+            .filter_map(|layer| layer.bounding_box())
+            .reduce(union)
+    }
+}
+
+// Line boxes of the text measured with the font, with unset properties taken from the style
+pub(crate) fn text_bounds(text: &Text, font_name: &str, style: &TextStyle) -> Vec4 {
+    let style = style.resolve(text);
+    let mut width: f32 = 0.0;
+    let mut line_box = None;
+    for line in text.text.split('\n') {
+        let Some(shaped) = shape_text(line, font_name, text.size) else {
+            return estimated_text_bounds(text);
+        };
+        let spacing = style.letter_spacing * shaped.glyphs.len() as f32;
+        width = width.max(shaped.bounds.z + spacing);
+        line_box.get_or_insert(shaped.bounds);
+    }
+    let Some(line_box) = line_box else {
+        return estimated_text_bounds(text);
+    };
+
+    let lines = text.text.split('\n').count() as f32;
+    let line_advance = style
+        .line_height
+        .map_or(line_box.w, |line_height| line_height * text.size);
+    Vec4::new(
+        text.bottom_left.x + line_box.x,
+        text.bottom_left.y + line_box.y,
+        width,
+        line_box.w + line_advance * (lines - 1.0),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec4;
+
+    use super::*;
+
+    #[test]
+    fn test_curve_bounds_use_extrema() {
+        let path = Path::new_fill(Vec4::ONE, vec2(0.0, 0.0))
+            .quadratic_bezier_to(vec2(50.0, 100.0), vec2(100.0, 0.0));
+        let bounds = path.bounding_box();
+        assert_eq!(bounds.x, 0.0);
+        assert_eq!(bounds.z, 100.0);
+        assert!((bounds.w - 50.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_layer_bounds_are_clipped_union() {
+        let layer = Layer::default()
+            .with_quad(Quad::new(vec2(0.0, 0.0), vec2(10.0, 10.0), Vec4::ONE))
+            .with_quad(Quad::new(vec2(20.0, 20.0), vec2(10.0, 10.0), Vec4::ONE));
+        assert_eq!(layer.bounding_box(), Some(vec4(0.0, 0.0, 30.0, 30.0)));
+
+        let layer = layer.with_clip(vec4(5.0, 5.0, 100.0, 100.0));
+        assert_eq!(layer.bounding_box(), Some(vec4(5.0, 5.0, 25.0, 25.0)));
+        assert_eq!(Layer::default().bounding_box(), None);
+    }
+}
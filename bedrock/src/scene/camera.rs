@@ -0,0 +1,81 @@
+use glam::{Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+
+// Maps a pannable and zoomable world onto the screen. The world point at `position` is drawn at
+// the top left of the window, and world distances are multiplied by `zoom`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub position: Vec2,
+    pub zoom: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            position: Vec2::ZERO,
+            zoom: 1.0,
+        }
+    }
+}
+
+impl Camera {
+    pub fn new(position: Vec2, zoom: f32) -> Self {
+        Self { position, zoom }
+    }
+
+    // Camera showing all of the world rectangle centered in a viewport of the given size
+    pub fn fit(rect: Vec4, viewport: Vec2) -> Self {
+        let zoom = (viewport.x / rect.z).min(viewport.y / rect.w);
+        if !zoom.is_finite() || zoom <= 0.0 {
+            return Self::new(Vec2::new(rect.x, rect.y), 1.0);
+        }
+        let center = Vec2::new(rect.x + rect.z / 2.0, rect.y + rect.w / 2.0);
+        Self::new(center - viewport / 2.0 / zoom, zoom)
+    }
+
+    pub fn world_to_screen(&self, point: Vec2) -> Vec2 {
+        (point - self.position) * self.zoom
+    }
+
+    pub fn screen_to_world(&self, point: Vec2) -> Vec2 {
+        point / self.zoom + self.position
+    }
+
+    // Moves the world by the given distance in screen pixels, as when dragging it
+    pub fn pan(&mut self, delta: Vec2) {
+        self.position -= delta / self.zoom;
+    }
+
+    // Multiplies the zoom while keeping the world point under `anchor` where it is on screen,
+    // as when scrolling over the cursor
+    pub fn zoom_around(&mut self, anchor: Vec2, factor: f32) {
+        let world = self.screen_to_world(anchor);
+        self.zoom = (self.zoom * factor).max(f32::EPSILON);
+        self.position = world - anchor / self.zoom;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+
+    #[test]
+    fn test_zoom_keeps_anchor_in_place() {
+        let mut camera = Camera::new(vec2(100.0, 50.0), 2.0);
+        let anchor = vec2(30.0, 40.0);
+        let world = camera.screen_to_world(anchor);
+        camera.zoom_around(anchor, 3.0);
+        assert_eq!(camera.zoom, 6.0);
+        assert!(camera.world_to_screen(world).distance(anchor) < 0.001);
+
+        camera.pan(vec2(12.0, 0.0));
+        assert!(
+            camera
+                .world_to_screen(world)
+                .distance(anchor + vec2(12.0, 0.0))
+                < 0.001
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use std::ops::Range;
+
+use glam::{vec2, vec4, Vec4, Vec4Swizzles};
+
+use super::{Layer, Quad, Text};
+use crate::shaper::{shape_line, ShapedCluster};
+
+// Horizontal extent of a cluster on its line
+struct ClusterSpan {
+    range: Range<usize>,
+    rtl: bool,
+    left: f32,
+    right: f32,
+}
+
+impl ClusterSpan {
+    // Position of the boundary before the byte, spreading ligatures evenly over the characters
+    // they were made from
+    fn x_at(&self, line: &str, index: usize) -> f32 {
+        let characters = line[self.range.clone()].chars().count().max(1);
+        let before = line[self.range.start..index].chars().count();
+        let progress = before as f32 / characters as f32;
+        let width = self.right - self.left;
+        if self.rtl {
+            self.right - width * progress
+        } else {
+            self.left + width * progress
+        }
+    }
+}
+
+fn cluster_spans(clusters: &[ShapedCluster], letter_spacing: f32) -> Vec<ClusterSpan> {
+    let mut pen = 0.0;
+    clusters
+        .iter()
+        .map(|cluster| {
+            let left = pen;
+            for glyph in cluster.glyphs.iter() {
+                // Matches the glyph drawable, which doesn't space marks
+                if glyph.advance > 0.0 {
+                    pen += glyph.advance + letter_spacing;
+                }
+            }
+            ClusterSpan {
+                range: cluster.range.clone(),
+                rtl: cluster.rtl,
+                left,
+                right: pen,
+            }
+        })
+        .collect()
+}
+
+// Caret position before the byte. Carets at the end of a line sit after the last character in
+// logical order, which is on the left of right to left text
+fn caret_x(spans: &[ClusterSpan], line: &str, index: usize) -> f32 {
+    if let Some(span) = spans
+        .iter()
+        .find(|span| span.range.start <= index && index < span.range.end)
+    {
+        return span.x_at(line, index);
+    }
+    match spans.iter().find(|span| span.range.end == index) {
+        Some(span) if span.rtl => span.left,
+        Some(span) => span.right,
+        None => 0.0,
+    }
+}
+
+// Horizontal ranges covering the selected bytes. Bidi text can split a logical selection into
+// several visual pieces, and touching pieces are joined
+fn selection_spans(spans: &[ClusterSpan], line: &str, range: Range<usize>) -> Vec<(f32, f32)> {
+    let mut pieces: Vec<(f32, f32)> = Vec::new();
+    for span in spans.iter() {
+        let start = span.range.start.max(range.start);
+        let end = span.range.end.min(range.end);
+        if start >= end {
+            continue;
+        }
+        let (a, b) = (
+            span.x_at(line, start),
+            if end == span.range.end {
+                if span.rtl {
+                    span.left
+                } else {
+                    span.right
+                }
+            } else {
+                span.x_at(line, end)
+            },
+        );
+        let (left, right) = (a.min(b), a.max(b));
+        match pieces.last_mut() {
+            Some(last) if (last.1 - left).abs() < 0.01 => last.1 = right,
+            _ => pieces.push((left, right)),
+        }
+    }
+    pieces
+}
+
+// Moves indices inside a character back to its start
+fn char_boundary(text: &str, mut index: usize) -> usize {
+    index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+impl Text {
+    // Byte index of the character, or the end of the text if there are fewer characters
+    pub fn byte_index(&self, char_index: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(char_index)
+            .map_or(self.text.len(), |(index, _)| index)
+    }
+}
+
+impl Layer {
+    // Rect of a caret before the byte index of a text drawn in this layer, as
+    // (x, y, width, height) spanning the line's ascent and descent. The text is shaped with
+    // the layer's font and style so the caret lines up with ligatures, letter spacing, and
+    // bidi runs. Returns None if the font can't be found or the text is vertical
+    pub fn caret_rect(&self, text: &Text, index: usize, width: f32) -> Option<Vec4> {
+        if text.vertical {
+            return None;
+        }
+        let style = self.text_style.resolve(text);
+        let index = char_boundary(&text.text, index);
+
+        let mut line_start = 0;
+        for (line_index, line) in text.text.split('\n').enumerate() {
+            let line_end = line_start + line.len();
+            if index <= line_end {
+                let shaped = shape_line(
+                    line,
+                    &self.font_name,
+                    text.size,
+                    text.direction,
+                    &style.features,
+                )?;
+                let spans = cluster_spans(&shaped.clusters, style.letter_spacing);
+                let x = caret_x(&spans, line, index - line_start);
+                let line_advance = style
+                    .line_height
+                    .map_or(shaped.line_advance, |line_height| line_height * text.size);
+                let baseline = text.bottom_left + vec2(x, line_advance * line_index as f32);
+                return Some(vec4(
+                    baseline.x - width / 2.0,
+                    baseline.y - shaped.ascent,
+                    width,
+                    shaped.ascent + shaped.descent,
+                ));
+            }
+            line_start = line_end + 1;
+        }
+        None
+    }
+
+    // Caret ready to add to a layer above the text
+    pub fn caret_quad(&self, text: &Text, index: usize, width: f32, color: Vec4) -> Option<Quad> {
+        let rect = self.caret_rect(text, index, width)?;
+        Some(Quad::new(rect.xy(), rect.zw(), color))
+    }
+
+    // Rects covering the selected bytes of a text drawn in this layer, one or more per line.
+    // Empty for vertical texts or if the font can't be found
+    pub fn selection_rects(&self, text: &Text, range: Range<usize>) -> Vec<Vec4> {
+        if text.vertical {
+            return Vec::new();
+        }
+        let style = self.text_style.resolve(text);
+        let range = char_boundary(&text.text, range.start)..char_boundary(&text.text, range.end);
+
+        let mut rects = Vec::new();
+        let mut line_start = 0;
+        for (line_index, line) in text.text.split('\n').enumerate() {
+            let line_end = line_start + line.len();
+            let start = range.start.max(line_start);
+            let end = range.end.min(line_end);
+            if start < end {
+                let Some(shaped) = shape_line(
+                    line,
+                    &self.font_name,
+                    text.size,
+                    text.direction,
+                    &style.features,
+                ) else {
+                    return Vec::new();
+                };
+                let spans = cluster_spans(&shaped.clusters, style.letter_spacing);
+                let line_advance = style
+                    .line_height
+                    .map_or(shaped.line_advance, |line_height| line_height * text.size);
+                let top = text.bottom_left.y + line_advance * line_index as f32 - shaped.ascent;
+                for (left, right) in
+                    selection_spans(&spans, line, start - line_start..end - line_start)
+                {
+                    rects.push(vec4(
+                        text.bottom_left.x + left,
+                        top,
+                        right - left,
+                        shaped.ascent + shaped.descent,
+                    ));
+                }
+            }
+            line_start = line_end + 1;
+        }
+        rects
+    }
+
+    // Selection highlight ready to add to a layer beneath the text
+    pub fn selection_quads(&self, text: &Text, range: Range<usize>, color: Vec4) -> Vec<Quad> {
+        self.selection_rects(text, range)
+            .into_iter()
+            .map(|rect| Quad::new(rect.xy(), rect.zw(), color))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn span(range: Range<usize>, rtl: bool, left: f32, right: f32) -> ClusterSpan {
+        ClusterSpan {
+            range,
+            rtl,
+            left,
+            right,
+        }
+    }
+
+    #[test]
+    fn test_carets_and_selections_follow_bidi_runs() {
+        // "ab" followed by a right to left run of two characters shown reversed
+        let line = "abשל";
+        let spans = [
+            span(0..1, false, 0.0, 10.0),
+            span(1..2, false, 10.0, 20.0),
+            span(4..6, true, 20.0, 30.0),
+            span(2..4, true, 30.0, 40.0),
+        ];
+
+        assert_eq!(caret_x(&spans, line, 1), 10.0);
+        assert_eq!(caret_x(&spans, line, 2), 40.0);
+        assert_eq!(caret_x(&spans, line, 4), 30.0);
+        assert_eq!(caret_x(&spans, line, 6), 20.0);
+
+        assert_eq!(
+            selection_spans(&spans, line, 1..4),
+            vec![(10.0, 20.0), (30.0, 40.0)]
+        );
+        assert_eq!(selection_spans(&spans, line, 0..6), vec![(0.0, 40.0)]);
+    }
+
+    #[test]
+    fn test_ligature_carets_are_spread_evenly() {
+        let spans = [span(0..2, false, 0.0, 20.0)];
+        assert_eq!(caret_x(&spans, "fi", 1), 10.0);
+        assert_eq!(selection_spans(&spans, "fi", 1..2), vec![(10.0, 20.0)]);
+    }
+}
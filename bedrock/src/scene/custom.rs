@@ -0,0 +1,124 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
+// Items of user defined types attached to a layer and drawn by a matching custom Drawable.
+// Items are grouped by type so drawables can look up only the ones they understand. They
+// aren't serialized, so recordings and saved scenes leave them out.
+#[derive(Clone, Default)]
+pub struct CustomItems {
+    // Arcs keep cloning scenes cheap since the items themselves don't need to be Clone
+    items: HashMap<TypeId, Vec<Arc<dyn Any + Send + Sync>>>,
+}
+
+impl CustomItems {
+    pub fn add<T: Any + Send + Sync>(&mut self, item: T) {
+        self.items
+            .entry(TypeId::of::<T>())
+            .or_default()
+            .push(Arc::new(item));
+    }
+
+    // Items of the given type in the order they were added
+    pub fn get<T: Any>(&self) -> impl Iterator<Item = &T> {
+        self.items
+            .get(&TypeId::of::<T>())
+            .into_iter()
+            .flatten()
+            .filter_map(|item| item.downcast_ref::<T>())
+    }
+
+    pub fn contains<T: Any>(&self) -> bool {
+        self.items
+            .get(&TypeId::of::<T>())
+            .map_or(false, |items| !items.is_empty())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.values().all(|items| items.is_empty())
+    }
+
+    // Whether both hold the same shared items. Items can't be compared by value, so copies of
+    // equal items count as different
+    pub(crate) fn same_items(&self, other: &CustomItems) -> bool {
+        let non_empty = |items: &CustomItems| {
+            items
+                .items
+                .values()
+                .filter(|items| !items.is_empty())
+                .count()
+        };
+        non_empty(self) == non_empty(other)
+            && self.items.iter().all(|(type_id, items)| {
+                let other_items = other.items.get(type_id).map_or(&[][..], |items| &items[..]);
+                items.len() == other_items.len()
+                    && items
+                        .iter()
+                        .zip(other_items.iter())
+                        .all(|(item, other_item)| Arc::ptr_eq(item, other_item))
+            })
+    }
+
+    // Appends the other items after these, sharing rather than copying them
+    pub fn extend(&mut self, other: &CustomItems) {
+        for (type_id, items) in other.items.iter() {
+            self.items
+                .entry(*type_id)
+                .or_default()
+                .extend(items.iter().cloned());
+        }
+    }
+}
+
+impl fmt::Debug for CustomItems {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count: usize = self.items.values().map(|items| items.len()).sum();
+        write!(f, "CustomItems({} items)", count)
+    }
+}
+
+// Custom item referenced by the name it was registered under, so scene files can contain items
+// from extension crates. `Registry::resolve` turns them into custom items of the registered type
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NamedItem {
+    pub kind: String,
+    // Fields of the registered type, deserialized when the item is resolved
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+impl NamedItem {
+    pub fn new(kind: impl Into<String>, data: serde_json::Value) -> Self {
+        Self {
+            kind: kind.into(),
+            data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Gauge(f32);
+
+    #[test]
+    fn test_lookup_by_type() {
+        let mut items = CustomItems::default();
+        assert!(items.is_empty());
+        items.add(Gauge(0.25));
+        items.add(Gauge(0.5));
+        items.add("label");
+
+        assert!(items.contains::<Gauge>());
+        assert!(!items.contains::<u32>());
+        let values: Vec<f32> = items.get::<Gauge>().map(|gauge| gauge.0).collect();
+        assert_eq!(values, vec![0.25, 0.5]);
+        assert_eq!(items.clone().get::<&str>().count(), 1);
+    }
+}
@@ -0,0 +1,82 @@
+use glam::{Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+
+use super::Color;
+
+// Circle or ellipse drawn from its distance field, so edges stay exactly antialiased at any
+// size or zoom without flattening curves into triangles
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Ellipse {
+    pub center: Vec2,
+    pub radii: Vec2,
+    #[serde(default)]
+    pub fill: Option<Color>,
+    // Width and color of the stroke, centered on the edge
+    #[serde(default)]
+    pub stroke: Option<(f32, Color)>,
+    // Depth tested against other items when depth testing is enabled. Higher values are nearer
+    #[serde(default)]
+    pub depth: f32,
+}
+
+impl Ellipse {
+    pub fn new(center: Vec2, radii: Vec2) -> Self {
+        Self {
+            center,
+            radii,
+            fill: None,
+            stroke: None,
+            depth: 0.0,
+        }
+    }
+
+    pub fn circle(center: Vec2, radius: f32) -> Self {
+        Self::new(center, Vec2::splat(radius))
+    }
+
+    pub fn with_fill(mut self, fill: Vec4) -> Self {
+        self.fill = Some(fill);
+        self
+    }
+
+    pub fn with_stroke(mut self, width: f32, color: Vec4) -> Self {
+        self.stroke = Some((width, color));
+        self
+    }
+
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    // Bounds including the outer half of the stroke
+    pub fn bounds(&self) -> Vec4 {
+        let extent = self.radii.abs() + self.stroke.map_or(0.0, |(width, _)| width / 2.0);
+        let top_left = self.center - extent;
+        Vec4::new(top_left.x, top_left.y, extent.x * 2.0, extent.y * 2.0)
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        let radii = self.radii.abs() + self.stroke.map_or(0.0, |(width, _)| width / 2.0);
+        if radii.x <= 0.0 || radii.y <= 0.0 {
+            return false;
+        }
+        ((point - self.center) / radii).length_squared() <= 1.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+
+    #[test]
+    fn test_bounds_and_containment_include_stroke() {
+        let ellipse = Ellipse::new(vec2(50.0, 50.0), vec2(20.0, 10.0)).with_stroke(4.0, Vec4::ONE);
+        assert_eq!(ellipse.bounds(), Vec4::new(28.0, 38.0, 44.0, 24.0));
+        assert!(ellipse.contains(vec2(71.0, 50.0)));
+        assert!(!ellipse.contains(vec2(50.0, 63.0)));
+        assert!(!ellipse.contains(vec2(71.0, 61.0)));
+    }
+}
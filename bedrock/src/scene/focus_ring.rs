@@ -0,0 +1,152 @@
+use glam::{vec4, Vec2, Vec4, Vec4Swizzles};
+use serde::{Deserialize, Serialize};
+
+use super::{Color, Path, PathCommand};
+
+// Shape a focus ring is drawn around
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum FocusTarget {
+    RoundedRect {
+        top_left: Vec2,
+        size: Vec2,
+        corner_radius: f32,
+    },
+    // Arbitrary paths are followed by scaling the path out from the center of its bounds, so
+    // the offset is only exact for shapes which are symmetric about their center.
+    Path(Path),
+}
+
+// Outline drawn a fixed distance outside of a focused element. Rendered as a stroked path so
+// it works in any layer which can draw paths.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FocusRing {
+    pub target: FocusTarget,
+    pub width: f32,
+    // Gap between the target's edge and the inside of the ring
+    pub offset: f32,
+    pub color: Color,
+    // 0 to 1. Rings animate in by fading in and shrinking onto their final offset
+    #[serde(default = "default_progress")]
+    pub progress: f32,
+}
+
+fn default_progress() -> f32 {
+    1.0
+}
+
+impl FocusRing {
+    pub fn around_rect(top_left: Vec2, size: Vec2, corner_radius: f32) -> Self {
+        Self::new(FocusTarget::RoundedRect {
+            top_left,
+            size,
+            corner_radius,
+        })
+    }
+
+    pub fn around_path(path: Path) -> Self {
+        Self::new(FocusTarget::Path(path))
+    }
+
+    fn new(target: FocusTarget) -> Self {
+        Self {
+            target,
+            width: 2.0,
+            offset: 2.0,
+            color: vec4(0.1, 0.4, 1.0, 1.0),
+            progress: 1.0,
+        }
+    }
+
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn with_offset(mut self, offset: f32) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_progress(mut self, progress: f32) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub fn to_path(&self) -> Path {
+        let progress = self.progress.clamp(0.0, 1.0);
+        // Start a ring width further out and move in as the ring appears
+        let offset = self.offset + self.width * (1.0 - progress);
+        // The stroke is centered on the path, so push the path out by half the width
+        let distance = offset + self.width / 2.0;
+
+        let mut color = self.color;
+        color.w *= progress;
+
+        let path = match &self.target {
+            FocusTarget::RoundedRect {
+                top_left,
+                size,
+                corner_radius,
+            } => Path::rounded_rect(
+                *top_left - Vec2::splat(distance),
+                *size + Vec2::splat(distance * 2.0),
+                corner_radius + distance,
+            ),
+            FocusTarget::Path(path) => expand_path(path, distance),
+        };
+
+        Path {
+            fill: None,
+            stroke: Some((self.width, color)),
+            ..path
+        }
+    }
+}
+
+fn expand_path(path: &Path, distance: f32) -> Path {
+    let bounds = Path {
+        stroke: None,
+        ..path.clone()
+    }
+    .bounds();
+    let center = bounds.xy() + bounds.zw() / 2.0;
+    let scale = (bounds.zw() + Vec2::splat(distance * 2.0)) / bounds.zw().max(Vec2::splat(1.0));
+    let expand = |point: Vec2| center + (point - center) * scale;
+
+    Path {
+        fill: path.fill,
+        stroke: path.stroke,
+        start: expand(path.start),
+        open: path.open,
+        fill_rule: path.fill_rule,
+        depth: path.depth,
+        commands: path
+            .commands
+            .iter()
+            .map(|command| match command {
+                PathCommand::CubicBezierTo {
+                    control1,
+                    control2,
+                    to,
+                } => PathCommand::CubicBezierTo {
+                    control1: expand(*control1),
+                    control2: expand(*control2),
+                    to: expand(*to),
+                },
+                PathCommand::QuadraticBezierTo { control, to } => PathCommand::QuadraticBezierTo {
+                    control: expand(*control),
+                    to: expand(*to),
+                },
+                PathCommand::LineTo { to } => PathCommand::LineTo { to: expand(*to) },
+                PathCommand::MoveTo { start } => PathCommand::MoveTo {
+                    start: expand(*start),
+                },
+            })
+            .collect(),
+    }
+}
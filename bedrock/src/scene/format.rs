@@ -0,0 +1,185 @@
+use std::{
+    fmt,
+    io::{Read, Write},
+    sync::Arc,
+};
+
+use glam::Vec4;
+use serde::{Deserialize, Serialize};
+
+use super::{default_clear_color, Guide, KeyframeTrack, Layer, Scene, ThemeColor};
+
+// Version of the serialized scene format. Bump whenever a change to the scene types would
+// make previously saved scenes load differently. Files without a version are treated as
+// version 1, which is the format scene files were written in before versioning.
+pub const SCENE_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum SceneError {
+    Json(serde_json::Error),
+    #[cfg(feature = "ron")]
+    Ron(String),
+    UnsupportedVersion {
+        found: u32,
+        supported: u32,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(error) => write!(f, "invalid scene json: {}", error),
+            #[cfg(feature = "ron")]
+            Self::Ron(error) => write!(f, "invalid scene ron: {}", error),
+            Self::UnsupportedVersion { found, supported } => write!(
+                f,
+                "scene schema version {} is newer than the supported version {}",
+                found, supported
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<serde_json::Error> for SceneError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
+
+#[derive(Serialize)]
+struct VersionedSceneRef<'a> {
+    version: u32,
+    clear_color: Vec4,
+    layers: &'a [Arc<Layer>],
+    guides: &'a [Guide],
+    tracks: &'a [KeyframeTrack],
+    theme_colors: &'a [ThemeColor],
+}
+
+#[derive(Deserialize)]
+struct VersionedScene {
+    #[serde(default = "legacy_version")]
+    version: u32,
+    #[serde(default = "default_clear_color")]
+    clear_color: Vec4,
+    layers: Vec<Layer>,
+    #[serde(default)]
+    guides: Vec<Guide>,
+    #[serde(default)]
+    tracks: Vec<KeyframeTrack>,
+    #[serde(default)]
+    theme_colors: Vec<ThemeColor>,
+}
+
+fn legacy_version() -> u32 {
+    1
+}
+
+impl VersionedScene {
+    fn into_scene(self) -> Result<Scene, SceneError> {
+        if self.version > SCENE_SCHEMA_VERSION {
+            return Err(SceneError::UnsupportedVersion {
+                found: self.version,
+                supported: SCENE_SCHEMA_VERSION,
+            });
+        }
+
+        Ok(Scene {
+            clear_color: self.clear_color,
+            layers: self.layers.into_iter().map(Arc::new).collect(),
+            guides: self.guides,
+            tracks: self.tracks,
+            theme_colors: self.theme_colors,
+        })
+    }
+}
+
+impl Scene {
+    fn versioned(&self) -> VersionedSceneRef {
+        VersionedSceneRef {
+            version: SCENE_SCHEMA_VERSION,
+            clear_color: self.clear_color,
+            layers: &self.layers,
+            guides: &self.guides,
+            tracks: &self.tracks,
+            theme_colors: &self.theme_colors,
+        }
+    }
+
+    // Reads a json scene written by `to_writer` or by hand
+    pub fn from_reader(reader: impl Read) -> Result<Self, SceneError> {
+        serde_json::from_reader::<_, VersionedScene>(reader)?.into_scene()
+    }
+
+    // Writes the scene as pretty printed json tagged with the schema version
+    pub fn to_writer(&self, writer: impl Write) -> Result<(), SceneError> {
+        serde_json::to_writer_pretty(writer, &self.versioned())?;
+        Ok(())
+    }
+
+    // Same as `to_writer` without the whitespace, for scenes sent over the network
+    pub fn to_compact_writer(&self, writer: impl Write) -> Result<(), SceneError> {
+        serde_json::to_writer(writer, &self.versioned())?;
+        Ok(())
+    }
+
+    #[cfg(feature = "ron")]
+    pub fn from_ron_reader(reader: impl Read) -> Result<Self, SceneError> {
+        // Scenes saved before text properties became optional wrote them without `Some`
+        ron::Options::default()
+            .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+            .from_reader::<_, VersionedScene>(reader)
+            .map_err(|error| SceneError::Ron(error.to_string()))?
+            .into_scene()
+    }
+
+    #[cfg(feature = "ron")]
+    pub fn to_ron_writer(&self, writer: impl Write) -> Result<(), SceneError> {
+        ron::ser::to_writer_pretty(writer, &self.versioned(), Default::default())
+            .map_err(|error| SceneError::Ron(error.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{vec2, vec4};
+
+    use super::*;
+    use crate::scene::{Path, Quad};
+
+    #[test]
+    fn test_round_trip() {
+        let scene = Scene::new()
+            .with_clear_color(vec4(0.1, 0.1, 0.1, 1.0))
+            .with_quad(Quad::new(
+                vec2(1.0, 2.0),
+                vec2(3.0, 4.0),
+                vec4(1.0, 0.0, 0.0, 1.0),
+            ))
+            .with_path(
+                Path::new_fill(vec4(0.0, 1.0, 0.0, 1.0), vec2(0.0, 0.0))
+                    .line_to(vec2(10.0, 0.0))
+                    .quadratic_bezier_to(vec2(10.0, 10.0), vec2(0.0, 10.0)),
+            );
+
+        let mut written = Vec::new();
+        scene.to_writer(&mut written).unwrap();
+        let read = Scene::from_reader(written.as_slice()).unwrap();
+
+        let mut rewritten = Vec::new();
+        read.to_writer(&mut rewritten).unwrap();
+        assert_eq!(written, rewritten);
+    }
+
+    #[test]
+    fn test_versions() {
+        let legacy = Scene::from_reader(r#"{ "layers": [] }"#.as_bytes()).unwrap();
+        assert_eq!(legacy.clear_color, Vec4::ONE);
+        assert!(matches!(
+            Scene::from_reader(r#"{ "version": 1000, "layers": [] }"#.as_bytes()),
+            Err(SceneError::UnsupportedVersion { found: 1000, .. })
+        ));
+    }
+}
@@ -0,0 +1,73 @@
+use glam::{Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+
+use super::Color;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum GuideKind {
+    // Line across the whole surface at the given y
+    Horizontal(f32),
+    // Line down the whole surface at the given x
+    Vertical(f32),
+    // Small cross marking a point something snapped to
+    Snap { position: Vec2, radius: f32 },
+}
+
+// Ruler guides, crosshairs and snap indicators for design and diagram tools. Drawn from their
+// distance in a single instanced pass rather than tessellated, so they are cheap to move every
+// frame. Lines are centered on pixels so thin guides stay crisp.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Guide {
+    pub kind: GuideKind,
+    pub color: Color,
+    #[serde(default = "default_width")]
+    pub width: f32,
+    // Length of each dash and of the gaps between them. Solid when None
+    #[serde(default)]
+    pub dash: Option<f32>,
+}
+
+fn default_width() -> f32 {
+    1.0
+}
+
+impl Guide {
+    pub fn new(kind: GuideKind, color: Vec4) -> Self {
+        Self {
+            kind,
+            color,
+            width: default_width(),
+            dash: None,
+        }
+    }
+
+    pub fn horizontal(y: f32, color: Vec4) -> Self {
+        Self::new(GuideKind::Horizontal(y), color)
+    }
+
+    pub fn vertical(x: f32, color: Vec4) -> Self {
+        Self::new(GuideKind::Vertical(x), color)
+    }
+
+    pub fn snap(position: Vec2, radius: f32, color: Vec4) -> Self {
+        Self::new(GuideKind::Snap { position, radius }, color)
+    }
+
+    // Horizontal and vertical guides crossing at the position, such as the cursor
+    pub fn crosshair(position: Vec2, color: Vec4) -> [Self; 2] {
+        [
+            Self::horizontal(position.y, color),
+            Self::vertical(position.x, color),
+        ]
+    }
+
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn with_dash(mut self, length: f32) -> Self {
+        self.dash = Some(length);
+        self
+    }
+}
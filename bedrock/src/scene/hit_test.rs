@@ -0,0 +1,354 @@
+use glam::{vec2, Vec2, Vec4};
+use lyon::{
+    algorithms::hit_test::hit_test_path,
+    path::{iterator::PathIterator, PathEvent},
+};
+
+use super::{bounds::text_bounds, Layer, Mesh, Path, Polyline, Scene};
+use crate::path::{build_lyon_path, lyon_fill_rule};
+
+// Maximum distance between curves and the line segments they are flattened into while testing
+const TOLERANCE: f32 = 0.1;
+
+// Kind of item a hit landed on, along with its index in the layer's list of that kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HitItem {
+    MaterialQuad(usize),
+    Quad(usize),
+    Ellipse(usize),
+    Text(usize),
+    TextLog(usize),
+    Path(usize),
+    Polyline(usize),
+    Mesh(usize),
+    Pyramid(usize),
+    Sprite(usize),
+    ShaderQuad(usize),
+    Mirror(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HitResult {
+    pub layer: usize,
+    pub item: HitItem,
+}
+
+impl Scene {
+    // Every item under the point, topmost first. Layer clips and clip paths are respected, and
+    // items are ordered the same way the default drawables draw them. Custom items are skipped
+    // since only their drawable knows their shape.
+    pub fn hit_test(&self, point: Vec2) -> Vec<HitResult> {
+        let mut hits = Vec::new();
+        for (layer_index, layer) in self.layers.iter().enumerate().rev() {
+            if !layer_contains(layer, point) {
+                continue;
+            }
+            let mut hit = |item| {
+                hits.push(HitResult {
+                    layer: layer_index,
+                    item,
+                })
+            };
+
+            for (index, mirror) in layer.mirrors.iter().enumerate().rev() {
+                if rect_contains(mirror.bounds(), point) {
+                    hit(HitItem::Mirror(index));
+                }
+            }
+            for (index, quad) in layer.shader_quads.iter().enumerate().rev() {
+                if rect_contains(quad.bounds(), point) {
+                    hit(HitItem::ShaderQuad(index));
+                }
+            }
+            for (index, sprite) in layer.sprites.iter().enumerate().rev() {
+                if rect_contains(sprite.bounds(), point) {
+                    hit(HitItem::Sprite(index));
+                }
+            }
+            for (index, pyramid) in layer.pyramids.iter().enumerate().rev() {
+                if rect_contains(pyramid.bounds(), point) {
+                    hit(HitItem::Pyramid(index));
+                }
+            }
+            for (index, mesh) in layer.meshes.iter().enumerate().rev() {
+                if mesh_contains(mesh, point) {
+                    hit(HitItem::Mesh(index));
+                }
+            }
+            for (index, polyline) in layer.polylines.iter().enumerate().rev() {
+                if polyline_contains(polyline, point) {
+                    hit(HitItem::Polyline(index));
+                }
+            }
+            for (index, path) in layer.paths.iter().enumerate().rev() {
+                if path_contains(path, point) {
+                    hit(HitItem::Path(index));
+                }
+            }
+            // Logs are drawn over the layer's texts
+            for (index, text_log) in layer.text_logs.iter().enumerate().rev() {
+                if rect_contains(text_log.bounds(), point) {
+                    hit(HitItem::TextLog(index));
+                }
+            }
+            for (index, text) in layer.texts.iter().enumerate().rev() {
+                if rect_contains(
+                    text_bounds(text, &layer.font_name, &layer.text_style),
+                    point,
+                ) {
+                    hit(HitItem::Text(index));
+                }
+            }
+            for (index, ellipse) in layer.ellipses.iter().enumerate().rev() {
+                if ellipse.contains(point) {
+                    hit(HitItem::Ellipse(index));
+                }
+            }
+            for (index, quad) in layer.quads.iter().enumerate().rev() {
+                if quad.contains(point) {
+                    hit(HitItem::Quad(index));
+                }
+            }
+            for (index, quad) in layer.material_quads.iter().enumerate().rev() {
+                if rect_contains(
+                    Vec4::new(quad.top_left.x, quad.top_left.y, quad.size.x, quad.size.y),
+                    point,
+                ) {
+                    hit(HitItem::MaterialQuad(index));
+                }
+            }
+        }
+        hits
+    }
+
+    // Topmost item under the point
+    pub fn hit_test_first(&self, point: Vec2) -> Option<HitResult> {
+        self.hit_test(point).into_iter().next()
+    }
+}
+
+fn layer_contains(layer: &Layer, point: Vec2) -> bool {
+    layer.clip.map_or(true, |clip| rect_contains(clip, point))
+        && layer
+            .clip_paths
+            .iter()
+            .all(|clip_path| fill_contains(clip_path, point))
+}
+
+fn rect_contains(rect: Vec4, point: Vec2) -> bool {
+    point.x >= rect.x && point.y >= rect.y && point.x < rect.x + rect.z && point.y < rect.y + rect.w
+}
+
+// Tests against the same lyon path the tessellator fills, with the fill rule it uses
+fn fill_contains(path: &Path, point: Vec2) -> bool {
+    hit_test_path(
+        &lyon::geom::point(point.x, point.y),
+        build_lyon_path(path).iter(),
+        lyon_fill_rule(path.fill_rule),
+        TOLERANCE,
+    )
+}
+
+fn path_contains(path: &Path, point: Vec2) -> bool {
+    if !rect_contains(
+        expand(path.bounds(), path.stroke.map_or(0.0, |(width, _)| width)),
+        point,
+    ) {
+        return false;
+    }
+    if path.fill.is_some() && fill_contains(path, point) {
+        return true;
+    }
+    let Some((width, _)) = path.stroke else {
+        return false;
+    };
+
+    // Strokes are centered on the path, so the point has to be within half the width of an
+    // edge. Joins and caps are approximated by the rounded ends of each segment
+    let half_width = width / 2.0;
+    build_lyon_path(path)
+        .iter()
+        .flattened(TOLERANCE)
+        .any(|event| match event {
+            PathEvent::Line { from, to } => {
+                segment_distance(point, vec2(from.x, from.y), vec2(to.x, to.y)) <= half_width
+            }
+            PathEvent::End {
+                last,
+                first,
+                close: true,
+            } => {
+                segment_distance(point, vec2(last.x, last.y), vec2(first.x, first.y)) <= half_width
+            }
+            _ => false,
+        })
+}
+
+// Within half the width of a segment. Round joints are exact while bevel and miter corners
+// are approximated by the rounded ends of each segment
+fn polyline_contains(polyline: &Polyline, point: Vec2) -> bool {
+    let half_width = polyline.width / 2.0;
+    match polyline.points[..] {
+        [] => false,
+        [only] => only.distance(point) <= half_width,
+        _ => polyline
+            .points
+            .windows(2)
+            .any(|segment| segment_distance(point, segment[0], segment[1]) <= half_width),
+    }
+}
+
+fn mesh_contains(mesh: &Mesh, point: Vec2) -> bool {
+    if !mesh
+        .bounds()
+        .map_or(false, |bounds| rect_contains(bounds, point))
+    {
+        return false;
+    }
+    let indices: Vec<u32> = mesh.valid_indices().collect();
+    indices.chunks_exact(3).any(|triangle| {
+        let [a, b, c] = [0, 1, 2].map(|corner| mesh.vertices[triangle[corner] as usize].position);
+        triangle_contains(a, b, c, point)
+    })
+}
+
+// Either winding, with points on an edge counting as inside
+fn triangle_contains(a: Vec2, b: Vec2, c: Vec2, point: Vec2) -> bool {
+    let side = |from: Vec2, to: Vec2| (to - from).perp_dot(point - from);
+    let sides = [side(a, b), side(b, c), side(c, a)];
+    sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0)
+}
+
+fn segment_distance(point: Vec2, from: Vec2, to: Vec2) -> f32 {
+    let segment = to - from;
+    let length_squared = segment.length_squared();
+    let along = if length_squared > 0.0 {
+        ((point - from).dot(segment) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    point.distance(from + segment * along)
+}
+
+fn expand(rect: Vec4, amount: f32) -> Vec4 {
+    Vec4::new(
+        rect.x - amount,
+        rect.y - amount,
+        rect.z + amount * 2.0,
+        rect.w + amount * 2.0,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec4;
+
+    use super::*;
+    use crate::scene::{FillRule, MeshVertex, Quad, TextLog};
+
+    #[test]
+    fn test_overlapping_contours_follow_fill_rule() {
+        let square = |path: Path, at: f32| {
+            path.line_to(vec2(at + 20.0, at))
+                .line_to(vec2(at + 20.0, at + 20.0))
+                .line_to(vec2(at, at + 20.0))
+        };
+        // Two squares wound the same way, overlapping between 10 and 20
+        let first = square(Path::new_fill(Vec4::ONE, vec2(0.0, 0.0)), 0.0);
+        let path = square(first.move_to(vec2(10.0, 10.0)), 10.0);
+        let overlap = vec2(15.0, 15.0);
+
+        assert!(!path_contains(&path, overlap));
+        assert!(path_contains(
+            &path.with_fill_rule(FillRule::NonZero),
+            overlap
+        ));
+    }
+
+    #[test]
+    fn test_hits_are_topmost_first_and_clipped() {
+        let scene = Scene::new()
+            .with_quad(Quad::new(vec2(0.0, 0.0), vec2(100.0, 100.0), Vec4::ONE))
+            .with_path(
+                Path::new_fill(Vec4::ONE, vec2(0.0, 0.0))
+                    .line_to(vec2(50.0, 0.0))
+                    .line_to(vec2(0.0, 50.0)),
+            )
+            .with_layer(Layer::default().with_clip(vec4(50.0, 50.0, 50.0, 50.0)))
+            .with_quad(Quad::new(vec2(0.0, 0.0), vec2(100.0, 100.0), Vec4::ONE));
+
+        assert_eq!(
+            scene.hit_test(vec2(10.0, 10.0)),
+            vec![
+                HitResult {
+                    layer: 0,
+                    item: HitItem::Path(0)
+                },
+                HitResult {
+                    layer: 0,
+                    item: HitItem::Quad(0)
+                },
+            ]
+        );
+        assert_eq!(
+            scene.hit_test_first(vec2(75.0, 75.0)),
+            Some(HitResult {
+                layer: 1,
+                item: HitItem::Quad(0)
+            })
+        );
+    }
+
+    #[test]
+    fn test_strokes_hit_near_edges() {
+        let path = Path::new_stroke((4.0, Vec4::ONE), vec2(0.0, 0.0)).line_to(vec2(100.0, 0.0));
+        assert!(path_contains(&path, vec2(50.0, 1.5)));
+        assert!(!path_contains(&path, vec2(50.0, 3.0)));
+
+        let polyline = Polyline::new([vec2(0.0, 0.0), vec2(100.0, 0.0)], 4.0, Vec4::ONE);
+        assert!(polyline_contains(&polyline, vec2(50.0, 1.5)));
+        assert!(!polyline_contains(&polyline, vec2(50.0, 3.0)));
+    }
+
+    #[test]
+    fn test_meshes_hit_inside_triangles() {
+        let mesh = Mesh::new(
+            [vec2(0.0, 0.0), vec2(10.0, 0.0), vec2(0.0, 10.0)]
+                .map(|position| MeshVertex::new(position, Vec4::ONE)),
+            [0, 1, 2],
+        );
+        assert!(mesh_contains(&mesh, vec2(2.0, 2.0)));
+        assert!(!mesh_contains(&mesh, vec2(8.0, 8.0)));
+    }
+
+    #[test]
+    fn test_text_logs_hit_between_paths_and_quads() {
+        let scene = Scene::new()
+            .with_quad(Quad::new(vec2(0.0, 0.0), vec2(100.0, 100.0), Vec4::ONE))
+            .with_text_log(TextLog::new(
+                vec2(0.0, 0.0),
+                vec2(100.0, 50.0),
+                16.0,
+                Vec4::ONE,
+                10,
+            ))
+            .with_path(
+                Path::new_fill(Vec4::ONE, vec2(0.0, 0.0))
+                    .line_to(vec2(50.0, 0.0))
+                    .line_to(vec2(0.0, 50.0)),
+            );
+
+        let items = |point| {
+            scene
+                .hit_test(point)
+                .into_iter()
+                .map(|hit| hit.item)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            items(vec2(10.0, 10.0)),
+            vec![HitItem::Path(0), HitItem::TextLog(0), HitItem::Quad(0)]
+        );
+        assert_eq!(items(vec2(10.0, 75.0)), vec![HitItem::Quad(0)]);
+    }
+}
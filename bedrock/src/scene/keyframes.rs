@@ -0,0 +1,126 @@
+use glam::{Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+
+use crate::easing::Easing;
+
+// Value of a keyframe. Written as a plain number or array in scene files
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum KeyframeValue {
+    Scalar(f32),
+    Vec2(Vec2),
+    Vec4(Vec4),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    // Seconds since the animation started
+    pub time: f32,
+    pub value: KeyframeValue,
+    // Easing of the segment from this keyframe to the next
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+impl Keyframe {
+    pub fn new(time: f32, value: KeyframeValue) -> Self {
+        Self {
+            time,
+            value,
+            easing: Easing::default(),
+        }
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+}
+
+// Keyframes for one property of a layer or item, addressed by a dotted path such as
+// "layers.0.quads.2.position" or "layers.1.blur_radius". Item lists use the layer's field
+// names, and the properties are the ones tweens animate: position, color, opacity, and
+// blur_radius. Played by `ScenePlayer`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KeyframeTrack {
+    pub property: String,
+    // Sorted by time
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl KeyframeTrack {
+    pub fn new(property: impl Into<String>) -> Self {
+        Self {
+            property: property.into(),
+            keyframes: Vec::new(),
+        }
+    }
+
+    // Keeps the keyframes sorted however they are added
+    pub fn add_keyframe(&mut self, keyframe: Keyframe) {
+        let index = self
+            .keyframes
+            .partition_point(|existing| existing.time <= keyframe.time);
+        self.keyframes.insert(index, keyframe);
+    }
+
+    pub fn with_keyframe(mut self, keyframe: Keyframe) -> Self {
+        self.add_keyframe(keyframe);
+        self
+    }
+
+    // Time of the last keyframe, after which the track holds its final value
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    // Keyframes around the time and the linear progress between them. Times outside of the
+    // track hold the nearest keyframe
+    pub fn segment(&self, time: f32) -> Option<(&Keyframe, &Keyframe, f32)> {
+        let first = self.keyframes.first()?;
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 {
+            return Some((first, first, 0.0));
+        }
+        let Some(to) = self.keyframes.get(next) else {
+            let last = self.keyframes.last()?;
+            return Some((last, last, 1.0));
+        };
+        let from = &self.keyframes[next - 1];
+        let progress = (time - from.time) / (to.time - from.time);
+        Some((from, to, progress))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+
+    #[test]
+    fn test_keyframes_deserialize_and_segment() {
+        let track: KeyframeTrack = serde_json::from_str(
+            r#"{
+                "property": "layers.0.quads.0.position",
+                "keyframes": [
+                    { "time": 0.0, "value": [0.0, 0.0], "easing": "Linear" },
+                    { "time": 2.0, "value": [100.0, 0.0] }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            track.keyframes[1].value,
+            KeyframeValue::Vec2(vec2(100.0, 0.0))
+        );
+        assert_eq!(track.duration(), 2.0);
+
+        let (from, to, progress) = track.segment(0.5).unwrap();
+        assert_eq!((from.time, to.time, progress), (0.0, 2.0, 0.25));
+        let (from, _, progress) = track.segment(3.0).unwrap();
+        assert_eq!((from.time, progress), (2.0, 1.0));
+    }
+}
@@ -0,0 +1,131 @@
+use glam::{vec2, vec4, Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+
+use super::{Color, Quad};
+
+// Opacity of the two shadows cast by an elevated surface. The ambient shadow is soft and
+// surrounds the surface while the key shadow is offset downward as if lit from above.
+const AMBIENT_SHADOW_ALPHA: f32 = 0.12;
+const KEY_SHADOW_ALPHA: f32 = 0.2;
+
+// Higher level description of a ui surface. Expands into a shadow, border, and background
+// quad so that widgets only need to pick an elevation rather than tuning shadows by hand.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Material {
+    pub background: Vec4,
+    #[serde(default)]
+    pub corner_radius: f32,
+    // Width and color of the border drawn inside the bounds of the surface
+    #[serde(default)]
+    pub border: Option<(f32, Vec4)>,
+    // Roughly how far above the layer the surface floats in pixels. Zero casts no shadow
+    #[serde(default)]
+    pub elevation: f32,
+    #[serde(default = "default_shadow_color")]
+    pub shadow_color: Color,
+}
+
+fn default_shadow_color() -> Vec4 {
+    vec4(0.0, 0.0, 0.0, 1.0)
+}
+
+impl Material {
+    pub fn new(background: Vec4) -> Self {
+        Self {
+            background,
+            corner_radius: 0.0,
+            border: None,
+            elevation: 0.0,
+            shadow_color: default_shadow_color(),
+        }
+    }
+
+    pub fn with_corner_radius(mut self, corner_radius: f32) -> Self {
+        self.corner_radius = corner_radius;
+        self
+    }
+
+    pub fn with_border(mut self, width: f32, color: Vec4) -> Self {
+        self.border = Some((width, color));
+        self
+    }
+
+    pub fn with_elevation(mut self, elevation: f32) -> Self {
+        self.elevation = elevation;
+        self
+    }
+
+    pub fn with_shadow_color(mut self, color: Vec4) -> Self {
+        self.shadow_color = color;
+        self
+    }
+
+    // Quads drawing this material over the given area, back to front
+    pub fn to_quads(&self, top_left: Vec2, size: Vec2) -> Vec<Quad> {
+        let mut quads = Vec::new();
+
+        if self.elevation > 0.0 {
+            let shadow = |alpha: f32| {
+                let mut color = self.shadow_color;
+                color.w *= alpha;
+                color
+            };
+            quads.push(
+                Quad::new(top_left, size, shadow(AMBIENT_SHADOW_ALPHA))
+                    .with_corner_radius(self.corner_radius)
+                    .with_blur(self.elevation),
+            );
+            quads.push(
+                Quad::new(
+                    top_left + vec2(0.0, self.elevation * 0.5),
+                    size,
+                    shadow(KEY_SHADOW_ALPHA),
+                )
+                .with_corner_radius(self.corner_radius)
+                .with_blur(self.elevation * 1.5),
+            );
+        }
+
+        match self.border {
+            Some((width, color)) if width > 0.0 => {
+                quads.push(Quad::new(top_left, size, color).with_corner_radius(self.corner_radius));
+                quads.push(
+                    Quad::new(
+                        top_left + Vec2::splat(width),
+                        (size - Vec2::splat(width * 2.0)).max(Vec2::ZERO),
+                        self.background,
+                    )
+                    .with_corner_radius((self.corner_radius - width).max(0.0)),
+                );
+            }
+            _ => quads.push(
+                Quad::new(top_left, size, self.background).with_corner_radius(self.corner_radius),
+            ),
+        }
+
+        quads
+    }
+}
+
+// A material placed in a layer
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MaterialQuad {
+    pub top_left: Vec2,
+    pub size: Vec2,
+    #[serde(flatten)]
+    pub material: Material,
+}
+
+impl MaterialQuad {
+    pub fn new(top_left: Vec2, size: Vec2, material: Material) -> Self {
+        Self {
+            top_left,
+            size,
+            material,
+        }
+    }
+
+    pub fn to_quads(&self) -> Vec<Quad> {
+        self.material.to_quads(self.top_left, self.size)
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use glam::Vec4;
+
+use super::{Layer, Scene};
+use crate::culling::intersection;
+
+impl Scene {
+    // Draws the other scene's layers above this scene's. Layers are shared with the other
+    // scene rather than copied, and its clear color and keyframe tracks are ignored. Its guides
+    // are kept above every layer, and its theme colors apply to the whole merged scene
+    pub fn merge(&mut self, other: &Scene) {
+        self.layers.extend(other.layers.iter().cloned());
+        self.guides.extend(other.guides.iter().cloned());
+        self.theme_colors.extend(other.theme_colors.iter().copied());
+    }
+
+    pub fn with_merged(mut self, other: &Scene) -> Self {
+        self.merge(other);
+        self
+    }
+
+    // Merges the other scene with each of its layers clipped to the rect as well as their own
+    // clip, such as a widget's bounds within a window. Guides span the whole surface, so the
+    // other scene's are left out while its theme colors are kept
+    pub fn merge_within(&mut self, other: &Scene, clip: Vec4) {
+        self.theme_colors.extend(other.theme_colors.iter().copied());
+        self.layers.extend(other.layers.iter().map(|layer| {
+            let mut layer = layer.clone();
+            let layer_mut = Arc::make_mut(&mut layer);
+            layer_mut.clip = Some(match layer_mut.clip {
+                Some(layer_clip) => intersection(layer_clip, clip),
+                None => clip,
+            });
+            layer
+        }));
+    }
+}
+
+impl Layer {
+    // Appends the other layer's items after this layer's. Items are still grouped by kind when
+    // drawn, so the other layer's quads end up beneath this layer's texts. Texts keep the look
+    // the other layer's text style gave them, but its clip, background, effects, and font are
+    // replaced by this layer's. Merge scenes instead to keep them.
+    pub fn merge(&mut self, other: &Layer) {
+        self.material_quads
+            .extend(other.material_quads.iter().cloned());
+        self.quads.extend(other.quads.iter().cloned());
+        self.ellipses.extend(other.ellipses.iter().cloned());
+        self.texts.extend(
+            other
+                .texts
+                .iter()
+                .map(|text| other.text_style.apply(text.clone())),
+        );
+        self.text_logs.extend(other.text_logs.iter().cloned());
+        self.paths.extend(other.paths.iter().cloned());
+        self.polylines.extend(other.polylines.iter().cloned());
+        self.meshes.extend(other.meshes.iter().cloned());
+        self.pyramids.extend(other.pyramids.iter().cloned());
+        self.sprites.extend(other.sprites.iter().cloned());
+        self.shader_quads.extend(other.shader_quads.iter().cloned());
+        self.particle_emitters
+            .extend(other.particle_emitters.iter().cloned());
+        self.mirrors.extend(other.mirrors.iter().cloned());
+        self.guides.extend(other.guides.iter().cloned());
+        self.custom.extend(&other.custom);
+        self.named.extend(other.named.iter().cloned());
+    }
+
+    pub fn with_merged(mut self, other: &Layer) -> Self {
+        self.merge(other);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{vec2, vec4};
+
+    use super::*;
+    use crate::scene::{Quad, Text, TextStyle};
+
+    #[test]
+    fn test_merged_scenes_share_layers_and_rebase_clips() {
+        let widget = Scene::new()
+            .with_quad(Quad::new(vec2(0.0, 0.0), vec2(10.0, 10.0), Vec4::ONE))
+            .with_clip(vec4(0.0, 0.0, 50.0, 50.0));
+        let mut scene = Scene::new();
+        scene.merge_within(&widget, vec4(25.0, 25.0, 100.0, 100.0));
+        scene.merge(&widget);
+
+        assert_eq!(scene.layers.len(), 3);
+        assert_eq!(scene.layers[1].clip, Some(vec4(25.0, 25.0, 25.0, 25.0)));
+        assert!(Arc::ptr_eq(&scene.layers[2], &widget.layers[0]));
+    }
+
+    #[test]
+    fn test_merged_texts_keep_their_style() {
+        let other = Layer::default()
+            .with_text_style(TextStyle::new().with_color(Vec4::X))
+            .with_text(Text::unstyled("Hello".to_string(), vec2(0.0, 0.0), 16.0));
+        let layer = Layer::default().with_merged(&other);
+
+        assert_eq!(layer.texts[0].color, Some(Vec4::X));
+    }
+}
@@ -0,0 +1,122 @@
+use glam::{Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+
+use super::Color;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct MeshVertex {
+    pub position: Vec2,
+    // Straight alpha, interpolated across each triangle and multiplied with the texture
+    pub color: Color,
+    // Texture coordinates from 0 to 1 across the texture. Ignored for untextured meshes
+    #[serde(default)]
+    pub uv: Vec2,
+}
+
+impl MeshVertex {
+    pub fn new(position: Vec2, color: Vec4) -> Self {
+        Self {
+            position,
+            color,
+            uv: Vec2::ZERO,
+        }
+    }
+
+    pub fn with_uv(mut self, uv: Vec2) -> Self {
+        self.uv = uv;
+        self
+    }
+}
+
+// Arbitrary triangles with colors blended between their vertices, for color wheels, heatmaps,
+// and gradients quads and paths can't express. Every three indices form a triangle, and
+// trailing indices which don't are ignored.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+    // Sprite asset sampled with the vertex uvs, repeating outside of 0 to 1
+    #[serde(default)]
+    pub texture: Option<String>,
+    // Depth tested against other items when depth testing is enabled. Higher values are nearer
+    #[serde(default)]
+    pub depth: f32,
+}
+
+impl Mesh {
+    pub fn new(vertices: impl Into<Vec<MeshVertex>>, indices: impl Into<Vec<u32>>) -> Self {
+        Self {
+            vertices: vertices.into(),
+            indices: indices.into(),
+            texture: None,
+            depth: 0.0,
+        }
+    }
+
+    // Fan of triangles from the first vertex, which fills any convex polygon
+    pub fn fan(vertices: impl Into<Vec<MeshVertex>>) -> Self {
+        let vertices = vertices.into();
+        let indices = (1..vertices.len().saturating_sub(1) as u32)
+            .flat_map(|index| [0, index, index + 1])
+            .collect::<Vec<_>>();
+        Self::new(vertices, indices)
+    }
+
+    pub fn with_texture(mut self, texture: impl Into<String>) -> Self {
+        self.texture = Some(texture.into());
+        self
+    }
+
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn bounds(&self) -> Option<Vec4> {
+        let first = self.vertices.first()?.position;
+        let (min, max) = self
+            .vertices
+            .iter()
+            .fold((first, first), |(min, max), vertex| {
+                (min.min(vertex.position), max.max(vertex.position))
+            });
+        let size = max - min;
+        Some(Vec4::new(min.x, min.y, size.x, size.y))
+    }
+
+    // Indices of complete triangles whose vertices all exist
+    pub(crate) fn valid_indices(&self) -> impl Iterator<Item = u32> + '_ {
+        let vertex_count = self.vertices.len() as u32;
+        self.indices
+            .chunks_exact(3)
+            .filter(move |triangle| triangle.iter().all(|index| *index < vertex_count))
+            .flatten()
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+
+    #[test]
+    fn test_invalid_triangles_are_dropped() {
+        let vertex = |x, y| MeshVertex::new(vec2(x, y), Vec4::ONE);
+        let mut mesh = Mesh::fan(vec![
+            vertex(0.0, 0.0),
+            vertex(10.0, 0.0),
+            vertex(10.0, 10.0),
+            vertex(0.0, 10.0),
+        ]);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.bounds(), Some(Vec4::new(0.0, 0.0, 10.0, 10.0)));
+
+        mesh.indices.extend([0, 3, 7, 1]);
+        assert_eq!(
+            mesh.valid_indices().collect::<Vec<_>>(),
+            vec![0, 1, 2, 0, 2, 3]
+        );
+    }
+}
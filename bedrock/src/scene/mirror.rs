@@ -0,0 +1,101 @@
+use glam::{Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+use shader::InstancedMirror;
+
+// Shows a region of everything drawn before it scaled into another rect of the same frame,
+// such as a magnifier or a picture in picture preview. The source is read from the frame as it
+// was before the mirror's layer, plus any of that layer's items drawn by earlier drawables.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Mirror {
+    // Region to copy as (x, y, width, height)
+    source: Vec4,
+    top_left: Vec2,
+    size: Vec2,
+    // Mask the destination to the largest circle fitting inside it
+    #[serde(default)]
+    circular: bool,
+    // Width and color of the border drawn inside the destination's edge
+    #[serde(default)]
+    border: Option<(f32, Vec4)>,
+    // Color of lines drawn between magnified source pixels
+    #[serde(default)]
+    pixel_grid: Option<Vec4>,
+}
+
+impl Mirror {
+    pub fn new(source: Vec4, top_left: Vec2, size: Vec2) -> Self {
+        Self {
+            source,
+            top_left,
+            size,
+            circular: false,
+            border: None,
+            pixel_grid: None,
+        }
+    }
+
+    // Mirror which magnifies the area around `center` by `zoom` into a circle of the given
+    // radius centered on the same point
+    pub fn magnifier(center: Vec2, radius: f32, zoom: f32) -> Self {
+        let source_radius = radius / zoom.max(f32::EPSILON);
+        Self::new(
+            (center - source_radius)
+                .extend(source_radius * 2.0)
+                .extend(source_radius * 2.0),
+            center - radius,
+            Vec2::splat(radius * 2.0),
+        )
+        .with_circular_mask()
+    }
+
+    pub fn with_circular_mask(mut self) -> Self {
+        self.circular = true;
+        self
+    }
+
+    pub fn with_border(mut self, width: f32, color: Vec4) -> Self {
+        self.border = Some((width, color));
+        self
+    }
+
+    // Outlines each source pixel once they are magnified at least four times, and turns off
+    // filtering so pixels are shown as solid squares. Meant for pixel inspection tools
+    pub fn with_pixel_grid(mut self, color: Vec4) -> Self {
+        self.pixel_grid = Some(color);
+        self
+    }
+
+    // Area covered by the destination, as (x, y, width, height)
+    pub fn bounds(&self) -> Vec4 {
+        Vec4::new(self.top_left.x, self.top_left.y, self.size.x, self.size.y)
+    }
+
+    pub fn to_instanced(&self) -> InstancedMirror {
+        let (border_width, border_color) = self.border.unwrap_or((0.0, Vec4::ZERO));
+        InstancedMirror {
+            source: self.source,
+            top_left: self.top_left,
+            size: self.size,
+            border_color,
+            grid_color: self.pixel_grid.unwrap_or(Vec4::ZERO),
+            border_width,
+            circular: self.circular as u32,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+
+    #[test]
+    fn test_magnifier() {
+        let mirror = Mirror::magnifier(vec2(100.0, 100.0), 50.0, 2.0);
+        assert_eq!(mirror.source, Vec4::new(75.0, 75.0, 50.0, 50.0));
+        assert_eq!(mirror.bounds(), Vec4::new(50.0, 50.0, 100.0, 100.0));
+        assert_eq!(mirror.to_instanced().circular, 1);
+    }
+}
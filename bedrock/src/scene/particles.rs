@@ -0,0 +1,164 @@
+use glam::{Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+
+use super::Color;
+
+// Spawns particles which are simulated on the gpu and drawn as round dots, for game effects and
+// confetti style flourishes. Particles keep moving between frames, so the emitter is matched to
+// its particles by id rather than by position in the layer. Scenes with emitters are redrawn
+// continuously, even with redraw on demand.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ParticleEmitter {
+    pub id: u64,
+    pub position: Vec2,
+    // Particles spawned per second
+    #[serde(default)]
+    pub rate: f32,
+    // Particles spawned at once the first frame the emitter is drawn
+    #[serde(default)]
+    pub burst: u32,
+    // Seconds each particle lives for
+    pub lifetime: f32,
+    pub velocity: Vec2,
+    // Each spawned particle's velocity is offset by up to this much on each axis
+    #[serde(default)]
+    pub spread: Vec2,
+    // Acceleration applied to every particle in pixels per second squared
+    #[serde(default)]
+    pub gravity: Vec2,
+    // Diameters at the start and end of each particle's life
+    pub size: (f32, f32),
+    // Colors at the start and end of each particle's life
+    pub color: (Color, Color),
+    // Oldest particles are replaced once this many are alive
+    pub max_particles: u32,
+}
+
+impl ParticleEmitter {
+    pub fn new(id: u64, position: Vec2) -> Self {
+        Self {
+            id,
+            position,
+            rate: 60.0,
+            burst: 0,
+            lifetime: 1.0,
+            velocity: Vec2::new(0.0, -100.0),
+            spread: Vec2::new(50.0, 50.0),
+            gravity: Vec2::ZERO,
+            size: (4.0, 4.0),
+            color: (Vec4::ONE, Vec4::new(1.0, 1.0, 1.0, 0.0)),
+            max_particles: 1024,
+        }
+    }
+
+    // Emitter which only fires a single burst, such as confetti
+    pub fn burst(id: u64, position: Vec2, count: u32) -> Self {
+        Self::new(id, position)
+            .with_rate(0.0)
+            .with_burst(count)
+            .with_max_particles(count)
+    }
+
+    pub fn with_rate(mut self, rate: f32) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst;
+        self
+    }
+
+    pub fn with_lifetime(mut self, lifetime: f32) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    pub fn with_velocity(mut self, velocity: Vec2, spread: Vec2) -> Self {
+        self.velocity = velocity;
+        self.spread = spread;
+        self
+    }
+
+    pub fn with_gravity(mut self, gravity: Vec2) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn with_size(mut self, start: f32, end: f32) -> Self {
+        self.size = (start, end);
+        self
+    }
+
+    pub fn with_color(mut self, start: Vec4, end: Vec4) -> Self {
+        self.color = (start, end);
+        self
+    }
+
+    pub fn with_max_particles(mut self, max_particles: u32) -> Self {
+        self.max_particles = max_particles;
+        self
+    }
+
+    // Area particles spawned at the emitter's current position can reach during their
+    // lifetime, padded by their largest radius. Particles spawned before the emitter moved
+    // may be outside of it
+    pub fn bounds(&self) -> Vec4 {
+        let (min_x, max_x) = reach(
+            self.velocity.x,
+            self.spread.x,
+            self.gravity.x,
+            self.lifetime,
+        );
+        let (min_y, max_y) = reach(
+            self.velocity.y,
+            self.spread.y,
+            self.gravity.y,
+            self.lifetime,
+        );
+        let radius = self.size.0.max(self.size.1).max(0.0) / 2.0;
+        let min = self.position + Vec2::new(min_x, min_y) - radius;
+        let max = self.position + Vec2::new(max_x, max_y) + radius;
+        Vec4::new(min.x, min.y, max.x - min.x, max.y - min.y)
+    }
+}
+
+// Smallest and largest offsets along one axis reached by particles launched with any velocity
+// within the spread. Offsets are linear in the velocity, so only the slowest and fastest need
+// checking, at the end of their life and where gravity turns them around
+fn reach(velocity: f32, spread: f32, gravity: f32, lifetime: f32) -> (f32, f32) {
+    let lifetime = lifetime.max(0.0);
+    let spread = spread.abs();
+    let mut range = (0.0f32, 0.0f32);
+    for velocity in [velocity - spread, velocity + spread] {
+        let offset = |time: f32| velocity * time + gravity * time * time / 2.0;
+        let mut times = vec![lifetime];
+        if gravity != 0.0 {
+            let turn = -velocity / gravity;
+            if turn > 0.0 && turn < lifetime {
+                times.push(turn);
+            }
+        }
+        for time in times {
+            let offset = offset(time);
+            range = (range.0.min(offset), range.1.max(offset));
+        }
+    }
+    range
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bounds_cover_particle_arcs() {
+        // Thrown upwards and falling back past where it started
+        let emitter = ParticleEmitter::new(0, Vec2::ZERO)
+            .with_velocity(Vec2::new(0.0, -100.0), Vec2::ZERO)
+            .with_gravity(Vec2::new(0.0, 100.0))
+            .with_lifetime(3.0)
+            .with_size(2.0, 2.0);
+        assert_eq!(emitter.bounds(), Vec4::new(-1.0, -51.0, 2.0, 202.0));
+    }
+}
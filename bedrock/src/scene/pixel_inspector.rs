@@ -0,0 +1,124 @@
+use glam::{vec2, vec4, Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+
+use super::{Badge, Color, Mirror, Path};
+
+// Circular loupe showing the pixels around a point with a grid between them, an outline
+// around the pixel under the point, and a label with that pixel's color. The color has to be
+// read back from a previous frame, usually with `Renderer::inspect_pixel` and
+// `Renderer::inspected_color`, and is left out of the label when it isn't known.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PixelInspector {
+    // Point being inspected. The loupe is centered on it
+    pub position: Vec2,
+    pub radius: f32,
+    // Destination pixels per source pixel
+    pub zoom: f32,
+    // Color of the inspected pixel as straight rgba
+    #[serde(default)]
+    pub color: Option<Color>,
+    pub grid_color: Color,
+    pub font_size: f32,
+    pub text_color: Color,
+    pub background: Vec4,
+}
+
+impl PixelInspector {
+    pub fn new(position: Vec2, radius: f32, zoom: f32) -> Self {
+        Self {
+            position,
+            radius,
+            zoom,
+            color: None,
+            grid_color: vec4(0.5, 0.5, 0.5, 0.5),
+            font_size: 12.0,
+            text_color: Vec4::ONE,
+            background: vec4(0.0, 0.0, 0.0, 0.75),
+        }
+    }
+
+    pub fn with_color(mut self, color: Option<Vec4>) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_grid_color(mut self, color: Vec4) -> Self {
+        self.grid_color = color;
+        self
+    }
+
+    pub fn with_label_colors(mut self, text_color: Vec4, background: Vec4) -> Self {
+        self.text_color = text_color;
+        self.background = background;
+        self
+    }
+
+    pub fn with_font_size(mut self, font_size: f32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    // The loupe, the outline around the inspected pixel, and the color label below the loupe
+    pub fn layout(&self) -> (Mirror, Path, Option<Badge>) {
+        let zoom = self.zoom.max(1.0);
+        // Align the source to whole pixels so the grid lines up with the pixel outline
+        let pixel = self.position.floor();
+        let source_radius = (self.radius / zoom).ceil();
+        let source = pixel - source_radius;
+        let source_size = source_radius * 2.0 + 1.0;
+        let top_left = self.position - (self.position - source) * zoom;
+
+        let mirror = Mirror::new(
+            source.extend(source_size).extend(source_size),
+            top_left,
+            Vec2::splat(source_size * zoom),
+        )
+        .with_circular_mask()
+        .with_border(2.0, self.text_color)
+        .with_pixel_grid(self.grid_color);
+
+        let pixel_top_left = top_left + (pixel - source) * zoom;
+        let outline = Path::new(pixel_top_left)
+            .line_to(pixel_top_left + vec2(zoom, 0.0))
+            .line_to(pixel_top_left + vec2(zoom, zoom))
+            .line_to(pixel_top_left + vec2(0.0, zoom))
+            .with_stroke((2.0, self.text_color));
+
+        let label = self.color.map(|color| {
+            let bottom = top_left.y + source_size * zoom;
+            Badge::new(
+                color_label(color),
+                vec2(self.position.x, bottom + self.font_size),
+                self.font_size,
+                self.text_color,
+                self.background,
+            )
+        });
+
+        (mirror, outline, label)
+    }
+}
+
+// Hex color with the alpha left off when the color is opaque
+fn color_label(color: Vec4) -> String {
+    let [r, g, b, a] = (color.clamp(Vec4::ZERO, Vec4::ONE) * 255.0)
+        .round()
+        .to_array()
+        .map(|channel| channel as u8);
+    if a == 255 {
+        format!("#{:02X}{:02X}{:02X}", r, g, b)
+    } else {
+        format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_color_label() {
+        assert_eq!(color_label(vec4(1.0, 0.5, 0.0, 1.0)), "#FF8000");
+        assert_eq!(color_label(vec4(0.0, 0.0, 0.0, 0.0)), "#00000000");
+    }
+}
@@ -0,0 +1,106 @@
+use glam::{Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+
+use super::Color;
+
+// How consecutive segments of a polyline are connected
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JointStyle {
+    #[default]
+    Round,
+    // Cut off flat across the outside of the corner
+    Bevel,
+    // Extended to a point, falling back to a bevel for corners sharp enough that the point
+    // would be longer than the miter limit
+    Miter,
+}
+
+// Longest miter as a multiple of the line width, matching the svg default
+pub const MITER_LIMIT: f32 = 4.0;
+
+// Stroked line through a list of points, expanded into segments on the gpu rather than
+// tessellated, so long and frequently changing lines such as streaming chart data stay cheap.
+// Unlike paths, polylines can't be filled and translucent polylines are darker where their
+// segments overlap at corners.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Polyline {
+    pub points: Vec<Vec2>,
+    pub width: f32,
+    pub color: Color,
+    #[serde(default)]
+    pub joint: JointStyle,
+    // Depth tested against other items when depth testing is enabled. Higher values are nearer
+    #[serde(default)]
+    pub depth: f32,
+}
+
+impl Polyline {
+    pub fn new(points: impl Into<Vec<Vec2>>, width: f32, color: Vec4) -> Self {
+        Self {
+            points: points.into(),
+            width,
+            color,
+            joint: JointStyle::default(),
+            depth: 0.0,
+        }
+    }
+
+    pub fn with_joint(mut self, joint: JointStyle) -> Self {
+        self.joint = joint;
+        self
+    }
+
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    // Appends a point, for lines built up as data arrives
+    pub fn push(&mut self, point: Vec2) {
+        self.points.push(point);
+    }
+
+    // Bounds of the points expanded by the line's width, or None for an empty line
+    pub fn bounds(&self) -> Option<Vec4> {
+        let first = *self.points.first()?;
+        let (min, max) = self
+            .points
+            .iter()
+            .fold((first, first), |(min, max), point| {
+                (min.min(*point), max.max(*point))
+            });
+        // Miter joints can reach further than half the width
+        let margin = match self.joint {
+            JointStyle::Miter => self.width * MITER_LIMIT / 2.0,
+            _ => self.width / 2.0,
+        };
+        let min = min - margin;
+        let size = max + margin - min;
+        Some(Vec4::new(min.x, min.y, size.x, size.y))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+
+    #[test]
+    fn test_bounds_include_width() {
+        let line = Polyline::new(
+            vec![vec2(10.0, 20.0), vec2(30.0, 0.0), vec2(50.0, 40.0)],
+            4.0,
+            Vec4::ONE,
+        );
+        assert_eq!(line.bounds(), Some(Vec4::new(8.0, -2.0, 44.0, 44.0)));
+        assert_eq!(
+            line.with_joint(JointStyle::Miter).bounds(),
+            Some(Vec4::new(2.0, -8.0, 56.0, 56.0))
+        );
+        assert_eq!(
+            Polyline::new(Vec::<Vec2>::new(), 1.0, Vec4::ONE).bounds(),
+            None
+        );
+    }
+}
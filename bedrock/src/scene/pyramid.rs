@@ -0,0 +1,193 @@
+use glam::{Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+
+use super::Camera;
+
+// Image far too large to load at once, stored as a pyramid of tiled levels. Level 0 is the full
+// resolution image and each level after it is half the size of the one before, down to a level
+// which fits in a single tile. Only the tiles visible at the camera's zoom are loaded, in the
+// background, and coarser levels fill in until they arrive.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImagePyramid {
+    // Asset name of each tile, with `{level}`, `{x}` and `{y}` replaced by the tile's level,
+    // column and row. For example "map/{level}/{x}_{y}.png"
+    pub tiles: String,
+    // Size of the full resolution image in pixels
+    pub size: Vec2,
+    // Width and height of each tile in pixels. Tiles on the right and bottom edges may be smaller
+    pub tile_size: u32,
+    // World position of the image's top left corner, with one world unit per full resolution
+    // pixel
+    #[serde(default)]
+    pub position: Vec2,
+    #[serde(default)]
+    pub camera: Camera,
+    // Depth tested against other items when depth testing is enabled. Higher values are nearer
+    #[serde(default)]
+    pub depth: f32,
+}
+
+// Tile of a pyramid level along with where it covers the full resolution image and the screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PyramidTile {
+    pub level: u32,
+    pub x: u32,
+    pub y: u32,
+    // Region of the full resolution image in pixels
+    pub region: Vec4,
+}
+
+impl ImagePyramid {
+    pub fn new(tiles: impl Into<String>, size: Vec2, tile_size: u32) -> Self {
+        Self {
+            tiles: tiles.into(),
+            size,
+            tile_size,
+            position: Vec2::ZERO,
+            camera: Camera::default(),
+            depth: 0.0,
+        }
+    }
+
+    pub fn with_position(mut self, position: Vec2) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_camera(mut self, camera: Camera) -> Self {
+        self.camera = camera;
+        self
+    }
+
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = camera;
+    }
+
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    // Number of levels, including the full resolution one
+    pub fn levels(&self) -> u32 {
+        let tile_size = self.tile_size.max(1) as f32;
+        let mut extent = self.size.x.max(self.size.y);
+        let mut levels = 1;
+        while extent > tile_size {
+            extent = (extent / 2.0).ceil();
+            levels += 1;
+        }
+        levels
+    }
+
+    // Coarsest level with at least one image pixel per screen pixel at the camera's zoom
+    pub fn level(&self) -> u32 {
+        let zoom = self.camera.zoom.max(f32::EPSILON);
+        let level = (1.0 / zoom).log2().floor().max(0.0) as u32;
+        level.min(self.levels() - 1)
+    }
+
+    pub fn tile_name(&self, level: u32, x: u32, y: u32) -> String {
+        self.tiles
+            .replace("{level}", &level.to_string())
+            .replace("{x}", &x.to_string())
+            .replace("{y}", &y.to_string())
+    }
+
+    // Area the image covers on screen
+    pub fn bounds(&self) -> Vec4 {
+        self.screen_rect(Vec4::new(0.0, 0.0, self.size.x, self.size.y))
+    }
+
+    // Converts a region of the full resolution image to the screen
+    pub(crate) fn screen_rect(&self, region: Vec4) -> Vec4 {
+        let top_left = self
+            .camera
+            .world_to_screen(self.position + Vec2::new(region.x, region.y));
+        let size = Vec2::new(region.z, region.w) * self.camera.zoom;
+        Vec4::new(top_left.x, top_left.y, size.x, size.y)
+    }
+
+    // Full resolution pixels covered by each tile of the level
+    fn tile_extent(&self, level: u32) -> f32 {
+        self.tile_size.max(1) as f32 * (1 << level) as f32
+    }
+
+    pub(crate) fn tile(&self, level: u32, x: u32, y: u32) -> PyramidTile {
+        let extent = self.tile_extent(level);
+        let top_left = Vec2::new(x as f32, y as f32) * extent;
+        let bottom_right = (top_left + extent).min(self.size);
+        let size = bottom_right - top_left;
+        PyramidTile {
+            level,
+            x,
+            y,
+            region: Vec4::new(top_left.x, top_left.y, size.x, size.y),
+        }
+    }
+
+    // Tiles of the level overlapping the visible area of the screen
+    pub(crate) fn visible_tiles(&self, level: u32, visible: Vec4) -> Vec<PyramidTile> {
+        let zoom = self.camera.zoom.max(f32::EPSILON);
+        let origin = self.camera.world_to_screen(self.position);
+        let top_left = (Vec2::new(visible.x, visible.y) - origin) / zoom;
+        let bottom_right = top_left + Vec2::new(visible.z, visible.w) / zoom;
+
+        let extent = self.tile_extent(level);
+        let columns = (self.size / extent).ceil();
+        let first = (top_left / extent).floor().clamp(Vec2::ZERO, columns);
+        let last = (bottom_right / extent).ceil().clamp(Vec2::ZERO, columns);
+
+        let mut tiles = Vec::new();
+        for y in first.y as u32..last.y as u32 {
+            for x in first.x as u32..last.x as u32 {
+                tiles.push(self.tile(level, x, y));
+            }
+        }
+        tiles
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+
+    #[test]
+    fn test_levels_and_visible_tiles() {
+        let pyramid = ImagePyramid::new("tiles/{level}/{x}_{y}.png", vec2(1000.0, 600.0), 256);
+        // 1000, 500, 250
+        assert_eq!(pyramid.levels(), 3);
+        assert_eq!(pyramid.level(), 0);
+        assert_eq!(
+            pyramid
+                .clone()
+                .with_camera(Camera::new(Vec2::ZERO, 0.3))
+                .level(),
+            1
+        );
+        assert_eq!(
+            pyramid
+                .clone()
+                .with_camera(Camera::new(Vec2::ZERO, 0.01))
+                .level(),
+            2
+        );
+        assert_eq!(pyramid.tile_name(1, 2, 3), "tiles/1/2_3.png");
+
+        let tiles = pyramid.visible_tiles(0, Vec4::new(300.0, 0.0, 200.0, 100.0));
+        assert_eq!(
+            tiles
+                .iter()
+                .map(|tile| (tile.x, tile.y))
+                .collect::<Vec<_>>(),
+            vec![(1, 0)]
+        );
+        // Edge tiles stop at the image's edge
+        assert_eq!(
+            pyramid.tile(0, 3, 2).region,
+            Vec4::new(768.0, 512.0, 232.0, 88.0)
+        );
+    }
+}
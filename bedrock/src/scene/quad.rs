@@ -1,16 +1,21 @@
 use glam::{Vec2, Vec4};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use shader::InstancedQuad;
 
-#[derive(Deserialize, Debug, Clone)]
+use super::Color;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Quad {
     top_left: Vec2,
     size: Vec2,
-    color: Vec4,
+    color: Color,
     #[serde(default)]
     corner_radius: f32,
     #[serde(default)]
     blur: f32,
+    // Only used when depth testing is enabled. Higher depths are drawn over lower ones
+    #[serde(default)]
+    depth: f32,
 }
 
 impl Quad {
@@ -21,6 +26,7 @@ impl Quad {
             color,
             corner_radius: 0.0,
             blur: 0.0,
+            depth: 0.0,
         }
     }
 
@@ -39,6 +45,51 @@ impl Quad {
         self
     }
 
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn top_left(&self) -> Vec2 {
+        self.top_left
+    }
+
+    pub fn set_top_left(&mut self, top_left: Vec2) {
+        self.top_left = top_left;
+    }
+
+    pub fn color(&self) -> Vec4 {
+        self.color
+    }
+
+    pub fn set_color(&mut self, color: Vec4) {
+        self.color = color;
+    }
+
+    pub fn set_blur(&mut self, blur: f32) {
+        self.blur = blur;
+    }
+
+    // Area covered by the quad including any external blur, as (x, y, width, height)
+    pub fn bounds(&self) -> Vec4 {
+        let extension = self.blur.max(0.0) * 3.0;
+        Vec4::new(
+            self.top_left.x - extension,
+            self.top_left.y - extension,
+            self.size.x + extension * 2.0,
+            self.size.y + extension * 2.0,
+        )
+    }
+
+    // Whether the point lies within the quad's rounded rectangle. Blur spreading past the
+    // edges doesn't count
+    pub fn contains(&self, point: Vec2) -> bool {
+        let half_size = self.size / 2.0;
+        let radius = self.corner_radius.clamp(0.0, half_size.min_element());
+        let offset = (point - self.top_left - half_size).abs() - (half_size - radius);
+        offset.max(Vec2::ZERO).length() + offset.max_element().min(0.0) <= radius
+    }
+
     pub fn to_instanced(&self) -> InstancedQuad {
         InstancedQuad {
             top_left: self.top_left,
@@ -46,6 +97,7 @@ impl Quad {
             color: self.color,
             corner_radius: self.corner_radius,
             blur: self.blur,
+            depth: self.depth,
             ..Default::default()
         }
     }
@@ -0,0 +1,59 @@
+use glam::{vec4, Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+
+// Distance in pixels from each edge of the window to the area not covered by menu bars,
+// notches, or rounded display corners. winit doesn't report these, so apps pass along what
+// the platform gives them.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct SafeAreaInsets {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl SafeAreaInsets {
+    pub fn new(top: f32, right: f32, bottom: f32, left: f32) -> Self {
+        Self {
+            top,
+            right,
+            bottom,
+            left,
+        }
+    }
+
+    pub fn uniform(inset: f32) -> Self {
+        Self::new(inset, inset, inset, inset)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // Unobstructed part of a window of the given size, as (x, y, width, height)
+    pub fn rect(&self, size: Vec2) -> Vec4 {
+        vec4(
+            self.left,
+            self.top,
+            (size.x - self.left - self.right).max(0.0),
+            (size.y - self.top - self.bottom).max(0.0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+
+    #[test]
+    fn test_rect() {
+        let insets = SafeAreaInsets::new(44.0, 0.0, 34.0, 0.0);
+        assert_eq!(
+            insets.rect(vec2(390.0, 844.0)),
+            vec4(0.0, 44.0, 390.0, 766.0)
+        );
+        assert_eq!(SafeAreaInsets::uniform(20.0).rect(vec2(30.0, 30.0)).z, 0.0);
+    }
+}
@@ -0,0 +1,75 @@
+use glam::{Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+
+// Uniform values available to each shader quad
+pub const SHADER_QUAD_UNIFORMS: usize = 16;
+
+// Rectangle filled by a fragment shader registered with `Renderer::register_quad_shader`, for
+// procedural backgrounds, plots, and shadertoy style panels. Quads whose shader hasn't been
+// registered are skipped.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ShaderQuad {
+    pub top_left: Vec2,
+    pub size: Vec2,
+    // Name the shader was registered with
+    pub shader: String,
+    // Passed to the shader as four vec4s. Missing values are zero and any past
+    // SHADER_QUAD_UNIFORMS are ignored
+    #[serde(default)]
+    pub uniforms: Vec<f32>,
+    // Depth tested against other items when depth testing is enabled. Higher values are nearer
+    #[serde(default)]
+    pub depth: f32,
+}
+
+impl ShaderQuad {
+    pub fn new(top_left: Vec2, size: Vec2, shader: impl Into<String>) -> Self {
+        Self {
+            top_left,
+            size,
+            shader: shader.into(),
+            uniforms: Vec::new(),
+            depth: 0.0,
+        }
+    }
+
+    pub fn with_uniforms(mut self, uniforms: impl Into<Vec<f32>>) -> Self {
+        self.uniforms = uniforms.into();
+        self
+    }
+
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn bounds(&self) -> Vec4 {
+        Vec4::new(self.top_left.x, self.top_left.y, self.size.x, self.size.y)
+    }
+
+    pub(crate) fn padded_uniforms(&self) -> [f32; SHADER_QUAD_UNIFORMS] {
+        let mut uniforms = [0.0; SHADER_QUAD_UNIFORMS];
+        for (uniform, value) in uniforms.iter_mut().zip(self.uniforms.iter()) {
+            *uniform = *value;
+        }
+        uniforms
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+
+    #[test]
+    fn test_uniforms_are_padded_and_truncated() {
+        let quad =
+            ShaderQuad::new(vec2(0.0, 0.0), vec2(10.0, 10.0), "plot").with_uniforms([1.0, 2.0]);
+        let uniforms = quad.padded_uniforms();
+        assert_eq!(&uniforms[..3], &[1.0, 2.0, 0.0]);
+
+        let quad = quad.with_uniforms(vec![1.0; SHADER_QUAD_UNIFORMS + 4]);
+        assert_eq!(quad.padded_uniforms(), [1.0; SHADER_QUAD_UNIFORMS]);
+    }
+}
@@ -0,0 +1,198 @@
+use std::{collections::VecDeque, ops::Range, sync::Arc};
+
+use glam::{Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+
+use super::Color;
+
+// Ring buffer of the most recent lines of a log. Lines are shared between clones, so a scene
+// holding a long scrollback can be cloned every frame without copying its text
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LogLines {
+    capacity: usize,
+    lines: VecDeque<Arc<str>>,
+}
+
+impl LogLines {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            lines: VecDeque::new(),
+        }
+    }
+
+    // Appends a line, dropping the oldest once the buffer is full. Lines shouldn't contain
+    // newlines; use `push_str` for text which might
+    pub fn push(&mut self, line: impl Into<Arc<str>>) {
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line.into());
+    }
+
+    // Appends each line of the text
+    pub fn push_str(&mut self, text: &str) {
+        for line in text.lines() {
+            self.push(line);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Oldest first
+    pub fn get(&self, index: usize) -> Option<&Arc<str>> {
+        self.lines.get(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<str>> {
+        self.lines.iter()
+    }
+}
+
+// Monochrome scrolling text for log viewers and consoles. Only the lines scrolled into view are
+// shaped and drawn, and their shaping is cached between frames, so appending many lines a frame
+// to a long scrollback costs about the same as drawing a screenful of text. Lines use the
+// layer's font and aren't styled or wrapped.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TextLog {
+    pub top_left: Vec2,
+    pub size: Vec2,
+    pub font_size: f32,
+    pub color: Color,
+    // Distance between baselines as a multiple of the font size. Unset uses the font's own
+    // line spacing
+    #[serde(default)]
+    pub line_height: Option<f32>,
+    // Lines scrolled back from the newest, which sits at the bottom of the log when this is 0.
+    // Fractional values scroll smoothly
+    #[serde(default)]
+    pub scroll: f32,
+    pub lines: LogLines,
+}
+
+impl TextLog {
+    pub fn new(top_left: Vec2, size: Vec2, font_size: f32, color: Vec4, capacity: usize) -> Self {
+        Self {
+            top_left,
+            size,
+            font_size,
+            color,
+            line_height: None,
+            scroll: 0.0,
+            lines: LogLines::new(capacity),
+        }
+    }
+
+    pub fn with_line_height(mut self, line_height: f32) -> Self {
+        self.line_height = Some(line_height);
+        self
+    }
+
+    pub fn with_scroll(mut self, scroll: f32) -> Self {
+        self.set_scroll(scroll);
+        self
+    }
+
+    // Clamped so the oldest line can't scroll below the top of the log
+    pub fn set_scroll(&mut self, scroll: f32) {
+        self.scroll = scroll.clamp(0.0, self.max_scroll());
+    }
+
+    pub fn max_scroll(&self) -> f32 {
+        self.lines.len().saturating_sub(1) as f32
+    }
+
+    pub fn push(&mut self, line: impl Into<Arc<str>>) {
+        self.lines.push(line);
+    }
+
+    pub fn push_str(&mut self, text: &str) {
+        self.lines.push_str(text);
+    }
+
+    pub fn bounds(&self) -> Vec4 {
+        Vec4::new(self.top_left.x, self.top_left.y, self.size.x, self.size.y)
+    }
+
+    // Baseline of the line given the distance between baselines and the font's descent
+    pub(crate) fn baseline(&self, index: usize, line_advance: f32, descent: f32) -> f32 {
+        let from_bottom = (self.lines.len() - 1 - index) as f32 - self.scroll;
+        self.top_left.y + self.size.y - descent - from_bottom * line_advance
+    }
+
+    // Lines which at least partly overlap the log's area
+    pub(crate) fn visible_lines(
+        &self,
+        line_advance: f32,
+        ascent: f32,
+        descent: f32,
+    ) -> Range<usize> {
+        let Some(last) = self.lines.len().checked_sub(1) else {
+            return 0..0;
+        };
+        let line_advance = line_advance.max(1.0);
+        let newest = self.scroll.floor().max(0.0) as usize;
+        // Lines beneath the newest visible one are below the bottom edge
+        let end = last.saturating_sub(newest) + 1;
+        let rows = ((self.size.y + ascent + descent) / line_advance).ceil() as usize + 1;
+        let start = end.saturating_sub(rows);
+        let top = self.top_left.y;
+        let bottom = self.top_left.y + self.size.y;
+        let start = (start..end)
+            .find(|index| self.baseline(*index, line_advance, descent) + descent > top)
+            .unwrap_or(end);
+        let end = (start..end)
+            .rev()
+            .find(|index| self.baseline(*index, line_advance, descent) - ascent < bottom)
+            .map_or(start, |index| index + 1);
+        start..end
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+
+    #[test]
+    fn test_log_lines_drop_oldest() {
+        let mut lines = LogLines::new(2);
+        lines.push_str("a\nb\nc");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines.get(0).map(|line| line.as_ref()), Some("b"));
+    }
+
+    #[test]
+    fn test_only_scrolled_in_lines_are_visible() {
+        let mut log = TextLog::new(Vec2::ZERO, vec2(100.0, 100.0), 10.0, Vec4::ONE, 1000);
+        for index in 0..1000 {
+            log.push(index.to_string());
+        }
+
+        // Ten rows fit with a 10 pixel advance and no descent
+        assert_eq!(log.visible_lines(10.0, 8.0, 0.0), 990..1000);
+        assert_eq!(log.baseline(999, 10.0, 0.0), 100.0);
+
+        log.set_scroll(100.0);
+        assert_eq!(log.visible_lines(10.0, 8.0, 0.0), 890..900);
+
+        log.set_scroll(5000.0);
+        assert_eq!(log.scroll, 999.0);
+        assert_eq!(log.visible_lines(10.0, 8.0, 0.0), 0..1);
+    }
+}
@@ -0,0 +1,135 @@
+use glam::Vec4;
+use serde::{Deserialize, Serialize};
+
+use super::{Color, FontFeature, Text};
+
+// Defaults a layer passes down to its texts. Properties a text leaves unset are taken from its
+// layer's style, so text heavy layers only have to set shared properties once.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct TextStyle {
+    #[serde(default)]
+    pub color: Option<Color>,
+    #[serde(default)]
+    pub bold: Option<bool>,
+    #[serde(default)]
+    pub italic: Option<bool>,
+    #[serde(default)]
+    pub subpixel: Option<bool>,
+    #[serde(default)]
+    pub letter_spacing: Option<f32>,
+    #[serde(default)]
+    pub line_height: Option<f32>,
+    // Applied before the features of each text, so texts can turn them back off
+    #[serde(default)]
+    pub features: Vec<FontFeature>,
+}
+
+// Properties of a text after falling back to its layer's style
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ResolvedTextStyle {
+    pub color: Color,
+    pub bold: bool,
+    pub italic: bool,
+    pub subpixel: bool,
+    pub letter_spacing: f32,
+    pub line_height: Option<f32>,
+    pub features: Vec<FontFeature>,
+}
+
+impl TextStyle {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn with_bold(mut self) -> Self {
+        self.bold = Some(true);
+        self
+    }
+
+    pub fn with_italic(mut self) -> Self {
+        self.italic = Some(true);
+        self
+    }
+
+    pub fn without_subpixel(mut self) -> Self {
+        self.subpixel = Some(false);
+        self
+    }
+
+    pub fn with_letter_spacing(mut self, letter_spacing: f32) -> Self {
+        self.letter_spacing = Some(letter_spacing);
+        self
+    }
+
+    pub fn with_line_height(mut self, line_height: f32) -> Self {
+        self.line_height = Some(line_height);
+        self
+    }
+
+    pub fn with_feature(mut self, feature: FontFeature) -> Self {
+        self.features.push(feature);
+        self
+    }
+
+    // Copy of the text with the properties it leaves unset filled in from the style, so it
+    // looks the same in a layer with a different style
+    pub fn apply(&self, mut text: Text) -> Text {
+        text.color = text.color.or(self.color);
+        text.bold = text.bold.or(self.bold);
+        text.italic = text.italic.or(self.italic);
+        text.subpixel = text.subpixel.or(self.subpixel);
+        text.letter_spacing = text.letter_spacing.or(self.letter_spacing);
+        text.line_height = text.line_height.or(self.line_height);
+        text.features = self.features.iter().cloned().chain(text.features).collect();
+        text
+    }
+
+    // Properties set on the text win over the style's. Texts and styles which both leave the
+    // color unset are drawn black
+    pub(crate) fn resolve(&self, text: &Text) -> ResolvedTextStyle {
+        ResolvedTextStyle {
+            color: text.color.or(self.color).unwrap_or(Vec4::W),
+            bold: text.bold.or(self.bold).unwrap_or(false),
+            italic: text.italic.or(self.italic).unwrap_or(false),
+            subpixel: text.subpixel.or(self.subpixel).unwrap_or(true),
+            letter_spacing: text.letter_spacing.or(self.letter_spacing).unwrap_or(0.0),
+            line_height: text.line_height.or(self.line_height),
+            features: self
+                .features
+                .iter()
+                .chain(text.features.iter())
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+
+    #[test]
+    fn test_texts_override_layer_style() {
+        let style = TextStyle::new()
+            .with_color(Vec4::ONE)
+            .with_letter_spacing(2.0)
+            .with_feature(FontFeature::enable("tnum"));
+        let text = Text::unstyled("Hello".to_string(), vec2(0.0, 0.0), 16.0)
+            .with_letter_spacing(-1.0)
+            .with_ligatures(false);
+
+        let resolved = style.resolve(&text);
+        assert_eq!(resolved.color, Vec4::ONE);
+        assert_eq!(resolved.letter_spacing, -1.0);
+        assert!(resolved.subpixel);
+        assert_eq!(resolved.features[0], FontFeature::enable("tnum"));
+        assert_eq!(resolved.features.len(), 5);
+    }
+}
@@ -0,0 +1,144 @@
+use std::{borrow::Cow, cell::Cell};
+
+use glam::Vec4;
+use serde::{Deserialize, Serialize};
+use winit::window::Theme;
+
+use super::{Layer, Scene};
+
+// Light or dark appearance preferred by the platform for a window
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorScheme {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl From<Theme> for ColorScheme {
+    fn from(theme: Theme) -> Self {
+        match theme {
+            Theme::Light => Self::Light,
+            Theme::Dark => Self::Dark,
+        }
+    }
+}
+
+// Color which is swapped for another when the window is in dark mode. Scenes are written with
+// their light colors, and any item, background, or text style using exactly that color is
+// drawn with the dark one instead, so a scene file restyles without being rebuilt. Sprite
+// tints are left alone since white is their default
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ThemeColor {
+    pub light: Vec4,
+    pub dark: Vec4,
+}
+
+impl ThemeColor {
+    pub fn new(light: Vec4, dark: Vec4) -> Self {
+        Self { light, dark }
+    }
+}
+
+impl Scene {
+    // Scene as drawn in the color scheme. Light schemes and scenes without theme colors are
+    // left as they are
+    pub fn themed(&self, scheme: ColorScheme) -> Cow<Scene> {
+        if scheme == ColorScheme::Light || self.theme_colors.is_empty() {
+            return Cow::Borrowed(self);
+        }
+
+        let changed = Cell::new(false);
+        let rebind = |color: &mut Vec4| {
+            if let Some(theme_color) = self
+                .theme_colors
+                .iter()
+                .find(|theme_color| theme_color.light == *color)
+            {
+                *color = theme_color.dark;
+                changed.set(true);
+            }
+        };
+
+        let mut scene = self.clone();
+        rebind(&mut scene.clear_color);
+        for layer in scene.layers.iter_mut() {
+            // Layers which don't use a theme color stay shared with the original scene
+            changed.set(false);
+            let mut rebound = (**layer).clone();
+            rebind_layer_colors(&mut rebound, &rebind);
+            if changed.get() {
+                *layer = rebound.into();
+            }
+        }
+        Cow::Owned(scene)
+    }
+}
+
+fn rebind_layer_colors(layer: &mut Layer, rebind: &impl Fn(&mut Vec4)) {
+    let rebind_option = |color: &mut Option<Vec4>| {
+        if let Some(color) = color.as_mut() {
+            rebind(color);
+        }
+    };
+    let rebind_stroke = |stroke: &mut Option<(f32, Vec4)>| {
+        if let Some((_, color)) = stroke.as_mut() {
+            rebind(color);
+        }
+    };
+
+    rebind_option(&mut layer.background_color);
+    rebind_option(&mut layer.text_style.color);
+    for material_quad in layer.material_quads.iter_mut() {
+        rebind(&mut material_quad.material.background);
+        rebind_stroke(&mut material_quad.material.border);
+    }
+    for quad in layer.quads.iter_mut() {
+        let mut color = quad.color();
+        rebind(&mut color);
+        quad.set_color(color);
+    }
+    for ellipse in layer.ellipses.iter_mut() {
+        rebind_option(&mut ellipse.fill);
+        rebind_stroke(&mut ellipse.stroke);
+    }
+    for text in layer.texts.iter_mut() {
+        rebind_option(&mut text.color);
+    }
+    for text_log in layer.text_logs.iter_mut() {
+        rebind(&mut text_log.color);
+    }
+    for path in layer.paths.iter_mut() {
+        rebind_option(&mut path.fill);
+        rebind_stroke(&mut path.stroke);
+    }
+    for polyline in layer.polylines.iter_mut() {
+        rebind(&mut polyline.color);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{vec2, vec4, Vec2};
+
+    use super::*;
+    use crate::Quad;
+
+    #[test]
+    fn test_themed_rebinds_light_colors() {
+        let light = vec4(1.0, 1.0, 1.0, 1.0);
+        let dark = vec4(0.1, 0.1, 0.1, 1.0);
+        let accent = vec4(0.2, 0.4, 1.0, 1.0);
+        let scene = Scene::new()
+            .with_clear_color(light)
+            .with_quad(Quad::new(Vec2::ZERO, vec2(10.0, 10.0), light))
+            .with_quad(Quad::new(Vec2::ZERO, vec2(10.0, 10.0), accent))
+            .with_theme_color(ThemeColor::new(light, dark));
+
+        assert!(matches!(scene.themed(ColorScheme::Light), Cow::Borrowed(_)));
+
+        let themed = scene.themed(ColorScheme::Dark);
+        assert_eq!(themed.clear_color, dark);
+        assert_eq!(themed.layer().quads[0].color(), dark);
+        assert_eq!(themed.layer().quads[1].color(), accent);
+    }
+}
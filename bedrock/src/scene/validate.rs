@@ -0,0 +1,353 @@
+use std::fmt;
+
+use glam::{Vec2, Vec4};
+
+use super::{Layer, PathCommand, Scene};
+
+// Something about an item which will draw garbage or nothing at all
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValidationProblem {
+    // The named number is NaN or infinite
+    NotFinite(&'static str),
+    NegativeSize,
+    // Components outside of 0 to 1, or negative ones when validating hdr content
+    ColorOutOfRange(Vec4),
+    // Path without any commands or polyline with fewer than two points
+    ZeroLength,
+    // Mesh index past its last vertex
+    IndexOutOfRange(u32),
+}
+
+impl fmt::Display for ValidationProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFinite(field) => write!(f, "{} is not finite", field),
+            Self::NegativeSize => write!(f, "size is negative"),
+            Self::ColorOutOfRange(color) => write!(f, "color {} is out of range", color),
+            Self::ZeroLength => write!(f, "has no length"),
+            Self::IndexOutOfRange(index) => write!(f, "index {} is past the last vertex", index),
+        }
+    }
+}
+
+// Problem found by `Scene::validate`, pointing at the item it was found in
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationWarning {
+    // None for properties of the scene itself
+    pub layer: Option<usize>,
+    // Item kind such as "quad", or the property for layer and scene properties
+    pub item: &'static str,
+    // Index of the item within its layer's list
+    pub index: Option<usize>,
+    pub problem: ValidationProblem,
+}
+
+impl fmt::Display for ValidationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(layer) = self.layer {
+            write!(f, "layer {} ", layer)?;
+        }
+        write!(f, "{}", self.item)?;
+        if let Some(index) = self.index {
+            write!(f, " {}", index)?;
+        }
+        write!(f, ": {}", self.problem)
+    }
+}
+
+struct Validator {
+    hdr: bool,
+    layer: Option<usize>,
+    warnings: Vec<ValidationWarning>,
+}
+
+impl Validator {
+    fn warn(&mut self, item: &'static str, index: Option<usize>, problem: ValidationProblem) {
+        self.warnings.push(ValidationWarning {
+            layer: self.layer,
+            item,
+            index,
+            problem,
+        });
+    }
+
+    fn finite(
+        &mut self,
+        item: &'static str,
+        index: Option<usize>,
+        field: &'static str,
+        value: f32,
+    ) {
+        if !value.is_finite() {
+            self.warn(item, index, ValidationProblem::NotFinite(field));
+        }
+    }
+
+    fn point(
+        &mut self,
+        item: &'static str,
+        index: Option<usize>,
+        field: &'static str,
+        point: Vec2,
+    ) {
+        if !point.is_finite() {
+            self.warn(item, index, ValidationProblem::NotFinite(field));
+        }
+    }
+
+    fn size(&mut self, item: &'static str, index: Option<usize>, size: Vec2) {
+        if !size.is_finite() {
+            self.warn(item, index, ValidationProblem::NotFinite("size"));
+        } else if size.min_element() < 0.0 {
+            self.warn(item, index, ValidationProblem::NegativeSize);
+        }
+    }
+
+    fn rect(&mut self, item: &'static str, index: Option<usize>, top_left: Vec2, size: Vec2) {
+        self.point(item, index, "position", top_left);
+        self.size(item, index, size);
+    }
+
+    // Alpha stays within 0 to 1 even for hdr content
+    fn in_range(&self, color: Vec4) -> bool {
+        color.is_finite()
+            && color.min_element() >= 0.0
+            && (self.hdr || color.max_element() <= 1.0)
+            && color.w <= 1.0
+    }
+
+    fn color(&mut self, item: &'static str, index: Option<usize>, color: Vec4) {
+        if !self.in_range(color) {
+            self.warn(item, index, ValidationProblem::ColorOutOfRange(color));
+        }
+    }
+
+    fn layer(&mut self, layer: &Layer) {
+        if let Some(clip) = layer.clip {
+            self.rect(
+                "clip",
+                None,
+                Vec2::new(clip.x, clip.y),
+                Vec2::new(clip.z, clip.w),
+            );
+        }
+        if let Some(color) = layer.background_color {
+            self.color("background_color", None, color);
+        }
+        if let Some(color) = layer.text_style.color {
+            self.color("text_style", None, color);
+        }
+        self.finite(
+            "background_blur_radius",
+            None,
+            "radius",
+            layer.background_blur_radius,
+        );
+        self.finite(
+            "content_blur_radius",
+            None,
+            "radius",
+            layer.content_blur_radius,
+        );
+
+        for (index, material_quad) in layer.material_quads.iter().enumerate() {
+            let index = Some(index);
+            self.rect(
+                "material_quad",
+                index,
+                material_quad.top_left,
+                material_quad.size,
+            );
+            self.color("material_quad", index, material_quad.material.background);
+            if let Some((width, color)) = material_quad.material.border {
+                self.finite("material_quad", index, "border width", width);
+                self.color("material_quad", index, color);
+            }
+        }
+        for (index, quad) in layer.quads.iter().enumerate() {
+            let index = Some(index);
+            let quad = quad.to_instanced();
+            self.rect("quad", index, quad.top_left, quad.size);
+            self.color("quad", index, quad.color);
+            self.finite("quad", index, "corner_radius", quad.corner_radius);
+            self.finite("quad", index, "blur", quad.blur);
+        }
+        for (index, ellipse) in layer.ellipses.iter().enumerate() {
+            let index = Some(index);
+            self.point("ellipse", index, "center", ellipse.center);
+            self.size("ellipse", index, ellipse.radii);
+            if let Some(fill) = ellipse.fill {
+                self.color("ellipse", index, fill);
+            }
+            if let Some((width, color)) = ellipse.stroke {
+                self.finite("ellipse", index, "stroke width", width);
+                self.color("ellipse", index, color);
+            }
+        }
+        for (index, text) in layer.texts.iter().enumerate() {
+            let index = Some(index);
+            self.point("text", index, "position", text.bottom_left);
+            self.size("text", index, Vec2::splat(text.size));
+            if let Some(color) = text.color {
+                self.color("text", index, color);
+            }
+        }
+        for (index, text_log) in layer.text_logs.iter().enumerate() {
+            let index = Some(index);
+            self.rect("text_log", index, text_log.top_left, text_log.size);
+            self.size("text_log", index, Vec2::splat(text_log.font_size));
+            self.color("text_log", index, text_log.color);
+        }
+        for (index, path) in layer.paths.iter().enumerate() {
+            let index = Some(index);
+            self.point("path", index, "start", path.start);
+            let mut points = path.commands.iter().flat_map(|command| match *command {
+                PathCommand::CubicBezierTo {
+                    control1,
+                    control2,
+                    to,
+                } => vec![control1, control2, to],
+                PathCommand::QuadraticBezierTo { control, to } => vec![control, to],
+                PathCommand::LineTo { to } => vec![to],
+                PathCommand::MoveTo { start } => vec![start],
+            });
+            if points.any(|point: Vec2| !point.is_finite()) {
+                self.warn("path", index, ValidationProblem::NotFinite("point"));
+            }
+            if path.commands.is_empty() {
+                self.warn("path", index, ValidationProblem::ZeroLength);
+            }
+            if let Some(fill) = path.fill {
+                self.color("path", index, fill);
+            }
+            if let Some((width, color)) = path.stroke {
+                self.finite("path", index, "stroke width", width);
+                self.color("path", index, color);
+            }
+        }
+        for (index, polyline) in layer.polylines.iter().enumerate() {
+            let index = Some(index);
+            if polyline.points.iter().any(|point| !point.is_finite()) {
+                self.warn("polyline", index, ValidationProblem::NotFinite("point"));
+            }
+            if polyline.points.len() < 2 {
+                self.warn("polyline", index, ValidationProblem::ZeroLength);
+            }
+            self.finite("polyline", index, "width", polyline.width);
+            self.color("polyline", index, polyline.color);
+        }
+        for (index, mesh) in layer.meshes.iter().enumerate() {
+            let index = Some(index);
+            if mesh
+                .vertices
+                .iter()
+                .any(|vertex| !vertex.position.is_finite())
+            {
+                self.warn("mesh", index, ValidationProblem::NotFinite("position"));
+            }
+            if let Some(vertex) = mesh
+                .vertices
+                .iter()
+                .find(|vertex| !self.in_range(vertex.color))
+            {
+                self.warn(
+                    "mesh",
+                    index,
+                    ValidationProblem::ColorOutOfRange(vertex.color),
+                );
+            }
+            if let Some(out_of_range) = mesh
+                .indices
+                .iter()
+                .find(|vertex_index| **vertex_index as usize >= mesh.vertices.len())
+            {
+                self.warn(
+                    "mesh",
+                    index,
+                    ValidationProblem::IndexOutOfRange(*out_of_range),
+                );
+            }
+        }
+        for (index, sprite) in layer.sprites.iter().enumerate() {
+            let index = Some(index);
+            self.rect("sprite", index, sprite.top_left, sprite.size);
+            self.color("sprite", index, sprite.color);
+        }
+        for (index, shader_quad) in layer.shader_quads.iter().enumerate() {
+            self.rect(
+                "shader_quad",
+                Some(index),
+                shader_quad.top_left,
+                shader_quad.size,
+            );
+        }
+    }
+}
+
+impl Scene {
+    // Lists positions and sizes which are NaN or infinite, negative sizes, colors outside of 0
+    // to 1, empty paths and polylines, and mesh indices past their vertices. Such data draws
+    // garbage or nothing without any error, so this is worth calling when content looks wrong.
+    // Debug builds of the renderer check every scene they draw and print new warnings
+    pub fn validate(&self) -> Vec<ValidationWarning> {
+        self.validate_inner(false)
+    }
+
+    // Same as `validate`, but allows color components above 1 for content drawn to extended
+    // range surfaces
+    pub fn validate_hdr(&self) -> Vec<ValidationWarning> {
+        self.validate_inner(true)
+    }
+
+    fn validate_inner(&self, hdr: bool) -> Vec<ValidationWarning> {
+        let mut validator = Validator {
+            hdr,
+            layer: None,
+            warnings: Vec::new(),
+        };
+        validator.color("clear_color", None, self.clear_color);
+        for (index, layer) in self.layers.iter().enumerate() {
+            validator.layer = Some(index);
+            validator.layer(layer);
+        }
+        validator.warnings
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{vec2, vec4};
+
+    use super::*;
+    use crate::scene::{Path, Quad};
+
+    #[test]
+    fn test_validate_points_at_bad_items() {
+        let scene = Scene::new()
+            .with_quad(Quad::new(Vec2::ZERO, vec2(10.0, 10.0), Vec4::ONE))
+            .with_quad(Quad::new(vec2(f32::NAN, 0.0), vec2(-1.0, 10.0), Vec4::ONE))
+            .with_path(Path::new_fill(vec4(2.0, 0.0, 0.0, 1.0), Vec2::ZERO));
+        let warnings = scene.validate();
+        let problems: Vec<_> = warnings
+            .iter()
+            .map(|warning| (warning.item, warning.index, warning.problem))
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                ("quad", Some(1), ValidationProblem::NotFinite("position")),
+                ("quad", Some(1), ValidationProblem::NegativeSize),
+                ("path", Some(0), ValidationProblem::ZeroLength),
+                (
+                    "path",
+                    Some(0),
+                    ValidationProblem::ColorOutOfRange(vec4(2.0, 0.0, 0.0, 1.0))
+                ),
+            ]
+        );
+        assert_eq!(warnings[1].to_string(), "layer 0 quad 1: size is negative");
+
+        // Bright colors are fine for hdr content
+        assert_eq!(scene.validate_hdr().len(), 3);
+    }
+}
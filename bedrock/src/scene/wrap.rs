@@ -0,0 +1,353 @@
+use std::ops::Range;
+
+use unicode_linebreak::{linebreaks, BreakOpportunity};
+
+use super::{FontFeature, Layer, Text, TextDirection};
+use crate::shaper::shape_line;
+
+// Offers extra places to break words too long for a line, such as from a hyphenation
+// dictionary. Returns byte offsets within the word where a hyphen may be inserted
+pub trait Hyphenator {
+    fn hyphenation_points(&self, word: &str) -> Vec<usize>;
+}
+
+impl<F: Fn(&str) -> Vec<usize>> Hyphenator for F {
+    fn hyphenation_points(&self, word: &str) -> Vec<usize> {
+        self(word)
+    }
+}
+
+// Lines end at mandatory breaks, and wrapped lines drop the spaces they ended on
+fn trim_line(text: &str, range: Range<usize>) -> Range<usize> {
+    let line = text[range.clone()].trim_end_matches(|character: char| character.is_whitespace());
+    range.start..range.start + line.len()
+}
+
+// Soft hyphens (U+00AD) are break opportunities which only show a hyphen when a line ends on one
+fn finish_line(text: &str, range: Range<usize>) -> String {
+    let line = &text[trim_line(text, range)];
+    if line.ends_with('\u{ad}') {
+        hyphenated(line)
+    } else {
+        line.to_string()
+    }
+}
+
+fn hyphenated(start: &str) -> String {
+    // Soft hyphens are invisible, so the hyphen replaces rather than follows them
+    format!("{}-", start.trim_end_matches('\u{ad}'))
+}
+
+// Width of the start of a word with a hyphen after it
+fn hyphenated_width(
+    text: &str,
+    range: Range<usize>,
+    measure: &impl Fn(Range<usize>) -> f32,
+    hyphen_width: f32,
+) -> f32 {
+    let start = text[range.clone()].trim_end_matches('\u{ad}');
+    measure(range.start..range.start + start.len()) + hyphen_width
+}
+
+// End of the longest start of the word which fits on a line with a hyphen after it
+fn hyphen_split(
+    text: &str,
+    word: Range<usize>,
+    width: f32,
+    measure: &impl Fn(Range<usize>) -> f32,
+    hyphen_width: f32,
+    hyphenator: &dyn Hyphenator,
+) -> Option<usize> {
+    let mut points = hyphenator.hyphenation_points(&text[word.clone()]);
+    points.sort_unstable();
+    points
+        .into_iter()
+        .rev()
+        .filter(|point| *point > 0 && *point < word.len())
+        .map(|point| word.start + point)
+        .filter(|split| text.is_char_boundary(*split))
+        .find(|split| hyphenated_width(text, word.start..*split, measure, hyphen_width) <= width)
+}
+
+// Greedily fills lines up to the width, only breaking where the unicode line breaking
+// algorithm allows. Spaces, CJK ideographs, hyphens, and soft hyphens offer breaks while
+// no-break spaces and closing punctuation don't. Words wider than a line are hyphenated if the
+// hyphenator offers a point that fits, and otherwise overflow the line. Runs of the text are
+// measured by their byte range so their widths can come from shaping done up front.
+pub(crate) fn wrap_lines(
+    text: &str,
+    width: f32,
+    measure: impl Fn(Range<usize>) -> f32,
+    hyphen_width: f32,
+    hyphenator: Option<&dyn Hyphenator>,
+) -> Vec<String> {
+    let opportunities: Vec<_> = linebreaks(text).collect();
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+    let mut last_fit = None;
+    let mut next = 0;
+    while let Some(&(position, opportunity)) = opportunities.get(next) {
+        let candidate = trim_line(text, line_start..position);
+        let fits = measure(candidate.clone()) <= width;
+        if !fits {
+            if let Some(fit) = last_fit.take() {
+                lines.push(finish_line(text, line_start..fit));
+                line_start = fit;
+                continue;
+            }
+            if let Some(split) = hyphenator.and_then(|hyphenator| {
+                hyphen_split(
+                    text,
+                    candidate.clone(),
+                    width,
+                    &measure,
+                    hyphen_width,
+                    hyphenator,
+                )
+            }) {
+                lines.push(hyphenated(&text[line_start..split]));
+                line_start = split;
+                continue;
+            }
+        }
+
+        if opportunity == BreakOpportunity::Mandatory {
+            lines.push(finish_line(text, candidate));
+            line_start = position;
+            last_fit = None;
+        } else {
+            last_fit = Some(position);
+        }
+        next += 1;
+    }
+    lines
+}
+
+// Advances of a text's clusters in logical order, so any run of the text can be measured
+// without shaping it again. Kerning across the ends of a run is ignored
+struct Advances {
+    // Byte offset each cluster starts at
+    starts: Vec<usize>,
+    // Total advance of the clusters before each one, followed by the total of them all
+    offsets: Vec<f32>,
+}
+
+impl Advances {
+    fn new(clusters: impl IntoIterator<Item = (usize, f32)>) -> Self {
+        let mut clusters: Vec<_> = clusters.into_iter().collect();
+        clusters.sort_by_key(|(start, _)| *start);
+        let mut offsets = Vec::with_capacity(clusters.len() + 1);
+        let mut total = 0.0;
+        offsets.push(total);
+        for (_, advance) in clusters.iter() {
+            total += advance;
+            offsets.push(total);
+        }
+        Self {
+            starts: clusters.into_iter().map(|(start, _)| start).collect(),
+            offsets,
+        }
+    }
+
+    fn shaped(
+        text: &str,
+        font_name: &str,
+        size: f32,
+        direction: TextDirection,
+        features: &[FontFeature],
+        letter_spacing: f32,
+    ) -> Option<Self> {
+        let shaped = shape_line(text, font_name, size, direction, features)?;
+        Some(Self::new(shaped.clusters.iter().map(|cluster| {
+            let advance = cluster
+                .glyphs
+                .iter()
+                // Matches the glyph drawable, which doesn't space marks
+                .filter(|glyph| glyph.advance > 0.0)
+                .map(|glyph| glyph.advance + letter_spacing)
+                .sum();
+            (cluster.range.start, advance)
+        })))
+    }
+
+    // Matches the estimate used for texts whose font is missing
+    fn estimated(text: &str, size: f32) -> Self {
+        Self::new(text.char_indices().map(|(index, _)| (index, size * 0.5)))
+    }
+
+    fn offset(&self, index: usize) -> f32 {
+        self.offsets[self.starts.partition_point(|start| *start < index)]
+    }
+
+    fn measure(&self, range: Range<usize>) -> f32 {
+        self.offset(range.end) - self.offset(range.start)
+    }
+}
+
+// Text wrapped to a width which can be rewrapped cheaply, such as while the panel holding it is
+// resized. The text is shaped once when the block is made, so changing the width only redoes
+// the line breaking pass rather than shaping a large document again
+pub struct TextBlock {
+    source: Text,
+    width: f32,
+    advances: Advances,
+    hyphen_width: f32,
+    wrapped: Text,
+}
+
+impl TextBlock {
+    // Text as given before wrapping
+    pub fn source(&self) -> &Text {
+        &self.source
+    }
+
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    // Wrapped text, to be drawn in the layer the block was made with
+    pub fn text(&self) -> &Text {
+        &self.wrapped
+    }
+
+    pub fn into_text(self) -> Text {
+        self.wrapped
+    }
+
+    pub fn set_width(&mut self, width: f32) {
+        self.rewrap(width, None);
+    }
+
+    // Same as `set_width`, but breaks words too wide for a line where the hyphenator allows
+    pub fn set_width_with(&mut self, width: f32, hyphenator: &dyn Hyphenator) {
+        self.rewrap(width, Some(hyphenator));
+    }
+
+    fn rewrap(&mut self, width: f32, hyphenator: Option<&dyn Hyphenator>) {
+        self.width = width;
+        self.wrapped.text = wrap_lines(
+            &self.source.text,
+            width,
+            |range| self.advances.measure(range),
+            self.hyphen_width,
+            hyphenator,
+        )
+        .join("\n");
+    }
+}
+
+impl Layer {
+    // Copy of a text drawn in this layer with newlines inserted so no line is wider than the
+    // width where the text allows it. Lines are measured with the layer's font and style
+    pub fn wrap_text(&self, text: &Text, width: f32) -> Text {
+        self.text_block(text, width).into_text()
+    }
+
+    // Same as `wrap_text`, but breaks words too wide for a line where the hyphenator allows
+    pub fn wrap_text_with(&self, text: &Text, width: f32, hyphenator: &dyn Hyphenator) -> Text {
+        self.text_block_with(text, width, hyphenator).into_text()
+    }
+
+    // Wraps the text like `wrap_text`, keeping its shaping around for rewrapping at other widths
+    pub fn text_block(&self, text: &Text, width: f32) -> TextBlock {
+        self.text_block_inner(text, width, None)
+    }
+
+    pub fn text_block_with(
+        &self,
+        text: &Text,
+        width: f32,
+        hyphenator: &dyn Hyphenator,
+    ) -> TextBlock {
+        self.text_block_inner(text, width, Some(hyphenator))
+    }
+
+    fn text_block_inner(
+        &self,
+        text: &Text,
+        width: f32,
+        hyphenator: Option<&dyn Hyphenator>,
+    ) -> TextBlock {
+        let style = self.text_style.resolve(text);
+        let shape = |content: &str| {
+            Advances::shaped(
+                content,
+                &self.font_name,
+                text.size,
+                text.direction,
+                &style.features,
+                style.letter_spacing,
+            )
+            .unwrap_or_else(|| Advances::estimated(content, text.size))
+        };
+        let advances = shape(&text.text);
+        let hyphen = shape("-");
+        let mut block = TextBlock {
+            source: text.clone(),
+            width,
+            hyphen_width: hyphen.measure(0..1),
+            advances,
+            wrapped: text.clone(),
+        };
+        block.rewrap(width, hyphenator);
+        block
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Every character is one unit wide, hyphens included
+    fn wrap(text: &str, width: f32, hyphenator: Option<&dyn Hyphenator>) -> Vec<String> {
+        let measure = |range: Range<usize>| text[range].chars().count() as f32;
+        wrap_lines(text, width, measure, 1.0, hyphenator)
+    }
+
+    #[test]
+    fn test_wraps_at_legal_breaks() {
+        assert_eq!(
+            wrap("the quick brown fox", 10.0, None),
+            ["the quick", "brown fox"]
+        );
+        // No-break spaces hold words together and newlines always break
+        assert_eq!(wrap("a b\u{a0}c\nd", 4.0, None), ["a", "b\u{a0}c", "d"]);
+        // Ideographs break between each other but not before closing punctuation
+        assert_eq!(
+            wrap("日本語の文章。", 3.0, None),
+            ["日本語", "の文", "章。"]
+        );
+    }
+
+    #[test]
+    fn test_long_words_hyphenate_or_overflow() {
+        let hyphenator = |word: &str| -> Vec<usize> { word.find("ordinary").into_iter().collect() };
+        assert_eq!(
+            wrap("extraordinary", 7.0, Some(&hyphenator)),
+            ["extra-", "ordinary"]
+        );
+        assert_eq!(
+            wrap("extra\u{ad}ordinary", 7.0, None),
+            ["extra-", "ordinary"]
+        );
+        assert_eq!(wrap("extraordinary", 7.0, None), ["extraordinary"]);
+    }
+
+    #[test]
+    fn test_rewrapping_measures_with_cached_advances() {
+        let text = "the quick brown fox";
+        let advances = Advances::estimated(text, 2.0);
+        assert_eq!(advances.measure(4..9), 5.0);
+        assert_eq!(advances.measure(0..text.len()), 19.0);
+
+        let measure = |range: Range<usize>| advances.measure(range);
+        assert_eq!(
+            wrap_lines(text, 10.0, measure, 1.0, None),
+            ["the quick", "brown fox"]
+        );
+        assert_eq!(
+            wrap_lines(text, 16.0, measure, 1.0, None),
+            ["the quick brown", "fox"]
+        );
+    }
+}
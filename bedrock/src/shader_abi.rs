@@ -0,0 +1,419 @@
+use std::{collections::HashMap, fmt};
+
+use shader::{ShaderConstants, ENTRY_POINTS, SHADER_ABI_VERSION};
+
+use crate::Asset;
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_OFFSET: u32 = 35;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShaderAbiError {
+    // Spirv file for a feature permutation which wasn't embedded
+    MissingModule(String),
+    InvalidSpirv,
+    VersionMismatch { host: u32, shader: Option<u32> },
+    MissingEntryPoint(&'static str),
+    PushConstantSizeMismatch { host: usize, shader: u32 },
+    PushConstantsTooLarge { size: usize, limit: u32 },
+}
+
+impl fmt::Display for ShaderAbiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingModule(name) => write!(f, "shader module {} is not embedded", name),
+            Self::InvalidSpirv => write!(f, "embedded shader is not a valid spirv module"),
+            Self::VersionMismatch {
+                host,
+                shader: Some(shader),
+            } => write!(
+                f,
+                "shader abi version {} does not match host abi version {}. Rebuild the shader",
+                shader, host
+            ),
+            Self::VersionMismatch { host, shader: None } => write!(
+                f,
+                "embedded shader has no abi version, expected version {}. Rebuild the shader",
+                host
+            ),
+            Self::MissingEntryPoint(name) => {
+                write!(f, "shader is missing the entry point `{}`", name)
+            }
+            Self::PushConstantSizeMismatch { host, shader } => write!(
+                f,
+                "ShaderConstants is {} bytes on the host but {} bytes in the shader",
+                host, shader
+            ),
+            Self::PushConstantsTooLarge { size, limit } => write!(
+                f,
+                "ShaderConstants is {} bytes but the device only supports {} bytes of push constants",
+                size, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ShaderAbiError {}
+
+// Checks that the embedded spirv module was built from the same version of the shader crate
+// the host was compiled against. The abi version file is written next to the spirv by the
+// build script, and the module itself is reflected to check entry points and push constants.
+pub fn validate(spirv: &[u8], max_push_constant_size: u32) -> Result<(), ShaderAbiError> {
+    let shader_version = Asset::get("shader.abi").and_then(|file| {
+        std::str::from_utf8(file.data.as_ref())
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    });
+    if shader_version != Some(SHADER_ABI_VERSION) {
+        return Err(ShaderAbiError::VersionMismatch {
+            host: SHADER_ABI_VERSION,
+            shader: shader_version,
+        });
+    }
+
+    let reflection = Reflection::parse(spirv).ok_or(ShaderAbiError::InvalidSpirv)?;
+
+    for entry_point in ENTRY_POINTS {
+        if !reflection
+            .entry_points
+            .iter()
+            .any(|name| name == entry_point)
+        {
+            return Err(ShaderAbiError::MissingEntryPoint(*entry_point));
+        }
+    }
+
+    let host_size = std::mem::size_of::<ShaderConstants>();
+    for shader_size in reflection.push_constant_sizes.iter() {
+        if *shader_size as usize != host_size {
+            return Err(ShaderAbiError::PushConstantSizeMismatch {
+                host: host_size,
+                shader: *shader_size,
+            });
+        }
+    }
+
+    if host_size > max_push_constant_size as usize {
+        return Err(ShaderAbiError::PushConstantsTooLarge {
+            size: host_size,
+            limit: max_push_constant_size,
+        });
+    }
+
+    Ok(())
+}
+
+#[derive(Clone)]
+enum SpirvType {
+    Scalar {
+        size: u32,
+    },
+    Vector {
+        component: u32,
+        count: u32,
+    },
+    Array {
+        element: u32,
+        length: u32,
+        stride: Option<u32>,
+    },
+    Struct {
+        members: Vec<u32>,
+    },
+    Pointer {
+        storage_class: u32,
+        pointee: u32,
+    },
+}
+
+// Just enough of the spirv module to find entry point names and the layout of the push
+// constant block
+struct Reflection {
+    entry_points: Vec<String>,
+    push_constant_sizes: Vec<u32>,
+}
+
+impl Reflection {
+    fn parse(spirv: &[u8]) -> Option<Self> {
+        if spirv.len() % 4 != 0 {
+            return None;
+        }
+        let words: Vec<u32> = spirv
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+        if words.len() < 5 || words[0] != SPIRV_MAGIC {
+            return None;
+        }
+
+        let mut entry_points = Vec::new();
+        let mut types = HashMap::new();
+        let mut member_offsets: HashMap<(u32, u32), u32> = HashMap::new();
+        // Decorations and constants come before the types which use them, so array lengths
+        // and strides can be resolved as soon as the array type is seen
+        let mut array_strides = HashMap::new();
+        let mut constants = HashMap::new();
+        let mut push_constant_types = Vec::new();
+
+        let mut index = 5;
+        while index < words.len() {
+            let opcode = words[index] & 0xffff;
+            let word_count = (words[index] >> 16) as usize;
+            if word_count == 0 || index + word_count > words.len() {
+                return None;
+            }
+            let operands = &words[index + 1..index + word_count];
+
+            match opcode {
+                OP_ENTRY_POINT if operands.len() >= 3 => {
+                    entry_points.push(parse_string(&operands[2..]));
+                }
+                OP_TYPE_INT | OP_TYPE_FLOAT if operands.len() >= 2 => {
+                    types.insert(
+                        operands[0],
+                        SpirvType::Scalar {
+                            size: operands[1] / 8,
+                        },
+                    );
+                }
+                OP_TYPE_VECTOR if operands.len() >= 3 => {
+                    types.insert(
+                        operands[0],
+                        SpirvType::Vector {
+                            component: operands[1],
+                            count: operands[2],
+                        },
+                    );
+                }
+                OP_TYPE_ARRAY if operands.len() >= 3 => {
+                    if let Some(length) = constants.get(&operands[2]) {
+                        types.insert(
+                            operands[0],
+                            SpirvType::Array {
+                                element: operands[1],
+                                length: *length,
+                                stride: array_strides.get(&operands[0]).copied(),
+                            },
+                        );
+                    }
+                }
+                OP_CONSTANT if operands.len() >= 3 => {
+                    constants.insert(operands[1], operands[2]);
+                }
+                OP_TYPE_STRUCT if !operands.is_empty() => {
+                    types.insert(
+                        operands[0],
+                        SpirvType::Struct {
+                            members: operands[1..].to_vec(),
+                        },
+                    );
+                }
+                OP_TYPE_POINTER if operands.len() >= 3 => {
+                    types.insert(
+                        operands[0],
+                        SpirvType::Pointer {
+                            storage_class: operands[1],
+                            pointee: operands[2],
+                        },
+                    );
+                }
+                OP_DECORATE if operands.len() >= 3 && operands[1] == DECORATION_ARRAY_STRIDE => {
+                    array_strides.insert(operands[0], operands[2]);
+                }
+                OP_MEMBER_DECORATE if operands.len() >= 4 && operands[2] == DECORATION_OFFSET => {
+                    member_offsets.insert((operands[0], operands[1]), operands[3]);
+                }
+                OP_VARIABLE if operands.len() >= 3 => {
+                    if operands[2] == STORAGE_CLASS_PUSH_CONSTANT {
+                        push_constant_types.push(operands[0]);
+                    }
+                }
+                _ => {}
+            }
+
+            index += word_count;
+        }
+
+        let push_constant_sizes = push_constant_types
+            .into_iter()
+            .map(|pointer| match types.get(&pointer)? {
+                SpirvType::Pointer {
+                    storage_class: STORAGE_CLASS_PUSH_CONSTANT,
+                    pointee,
+                } => type_size(&types, &member_offsets, *pointee),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self {
+            entry_points,
+            push_constant_sizes,
+        })
+    }
+}
+
+fn type_size(
+    types: &HashMap<u32, SpirvType>,
+    member_offsets: &HashMap<(u32, u32), u32>,
+    id: u32,
+) -> Option<u32> {
+    match types.get(&id)? {
+        SpirvType::Scalar { size } => Some(*size),
+        SpirvType::Vector { component, count } => {
+            Some(type_size(types, member_offsets, *component)? * count)
+        }
+        SpirvType::Array {
+            element,
+            length,
+            stride,
+        } => {
+            // Arrays without an explicit stride are tightly packed
+            let stride = match stride {
+                Some(stride) => *stride,
+                None => type_size(types, member_offsets, *element)?,
+            };
+            Some(stride * length)
+        }
+        SpirvType::Struct { members } => {
+            let mut size = 0;
+            for (member_index, member) in members.iter().enumerate() {
+                let offset = member_offsets
+                    .get(&(id, member_index as u32))
+                    .copied()
+                    .unwrap_or(0);
+                size = size.max(offset + type_size(types, member_offsets, *member)?);
+            }
+            Some(size)
+        }
+        SpirvType::Pointer { .. } => None,
+    }
+}
+
+// Spirv strings are nul terminated utf8 packed into little endian words
+fn parse_string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .take_while(|byte| *byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FLOAT: u32 = 1;
+    const UINT: u32 = 2;
+    const VEC2: u32 = 3;
+    const FOUR: u32 = 4;
+    const ARRAY: u32 = 5;
+    const INNER: u32 = 6;
+    const OUTER: u32 = 7;
+    const POINTER: u32 = 8;
+    const VARIABLE: u32 = 9;
+    const VERTEX: u32 = 10;
+    const FRAGMENT: u32 = 11;
+
+    fn instruction(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    fn string(text: &str) -> Vec<u32> {
+        let mut bytes = text.as_bytes().to_vec();
+        bytes.resize(bytes.len() / 4 * 4 + 4, 0);
+        bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect()
+    }
+
+    fn entry_point(function: u32, name: &str) -> Vec<u32> {
+        let mut operands = vec![0, function];
+        operands.extend(string(name));
+        instruction(OP_ENTRY_POINT, &operands)
+    }
+
+    fn module(instructions: &[Vec<u32>]) -> Vec<u8> {
+        [SPIRV_MAGIC, 0x0001_0300, 0, 12, 0]
+            .into_iter()
+            .chain(instructions.iter().flatten().copied())
+            .flat_map(u32::to_le_bytes)
+            .collect()
+    }
+
+    // struct Inner { value: vec2 }
+    // struct Outer { flags: u32, weights: [f32; 4], inner: Inner }
+    fn push_constant_module() -> Vec<u8> {
+        module(&[
+            entry_point(VERTEX, "main_vs"),
+            entry_point(FRAGMENT, "main_fs"),
+            instruction(OP_DECORATE, &[ARRAY, DECORATION_ARRAY_STRIDE, 4]),
+            instruction(OP_MEMBER_DECORATE, &[INNER, 0, DECORATION_OFFSET, 0]),
+            instruction(OP_MEMBER_DECORATE, &[OUTER, 0, DECORATION_OFFSET, 0]),
+            instruction(OP_MEMBER_DECORATE, &[OUTER, 1, DECORATION_OFFSET, 4]),
+            instruction(OP_MEMBER_DECORATE, &[OUTER, 2, DECORATION_OFFSET, 24]),
+            instruction(OP_TYPE_FLOAT, &[FLOAT, 32]),
+            instruction(OP_TYPE_INT, &[UINT, 32, 0]),
+            instruction(OP_TYPE_VECTOR, &[VEC2, FLOAT, 2]),
+            instruction(OP_CONSTANT, &[UINT, FOUR, 4]),
+            instruction(OP_TYPE_ARRAY, &[ARRAY, FLOAT, FOUR]),
+            instruction(OP_TYPE_STRUCT, &[INNER, VEC2]),
+            instruction(OP_TYPE_STRUCT, &[OUTER, UINT, ARRAY, INNER]),
+            instruction(
+                OP_TYPE_POINTER,
+                &[POINTER, STORAGE_CLASS_PUSH_CONSTANT, OUTER],
+            ),
+            instruction(
+                OP_VARIABLE,
+                &[POINTER, VARIABLE, STORAGE_CLASS_PUSH_CONSTANT],
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_entry_points_are_found() {
+        let reflection = Reflection::parse(&push_constant_module()).unwrap();
+        assert_eq!(reflection.entry_points, vec!["main_vs", "main_fs"]);
+    }
+
+    #[test]
+    fn test_push_constant_size_includes_arrays_and_nested_structs() {
+        let reflection = Reflection::parse(&push_constant_module()).unwrap();
+        assert_eq!(reflection.push_constant_sizes, vec![32]);
+    }
+
+    #[test]
+    fn test_invalid_modules_are_rejected() {
+        let spirv = push_constant_module();
+
+        let mut bad_magic = spirv.clone();
+        bad_magic[0] ^= 0xff;
+        assert!(Reflection::parse(&bad_magic).is_none());
+
+        // Cut off in the middle of a word, in the middle of an instruction, and in the header
+        for length in [spirv.len() - 2, spirv.len() - 4, 8, 0] {
+            assert!(Reflection::parse(&spirv[..length]).is_none());
+        }
+        assert!(validate(&spirv[..spirv.len() - 4], u32::MAX).is_err());
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+
+use bytemuck::{Pod, Zeroable};
+use shader::ShaderConstants;
+use wgpu::*;
+
+use crate::{
+    buffer::GrowableBuffer,
+    culling::{intersects, visible_rect},
+    extension::ExtensionKind,
+    renderer::{Drawable, Resources},
+    scene::{Layer, ShaderQuad, SHADER_QUAD_UNIFORMS},
+};
+
+// Matches ShaderQuad in shader_quad.wgsl
+#[derive(Copy, Clone, Default, Pod, Zeroable)]
+#[repr(C)]
+struct InstancedShaderQuad {
+    top_left: [f32; 2],
+    size: [f32; 2],
+    depth: f32,
+    _padding: [f32; 3],
+    uniforms: [f32; SHADER_QUAD_UNIFORMS],
+}
+
+impl InstancedShaderQuad {
+    fn new(quad: &ShaderQuad) -> Self {
+        Self {
+            top_left: quad.top_left.to_array(),
+            size: quad.size.to_array(),
+            depth: quad.depth,
+            uniforms: quad.padded_uniforms(),
+            ..Default::default()
+        }
+    }
+}
+
+// Draws shader quads with the modules registered through `Renderer::register_quad_shader`.
+// Every quad in a layer shares one instance buffer, and each run of quads using the same shader
+// is drawn with that shader's pipeline so quads keep their order.
+pub struct ShaderQuadState {
+    buffer: GrowableBuffer<InstancedShaderQuad>,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    pipeline_layout: PipelineLayout,
+    // Pipelines built for each registered shader along with the module they were built from,
+    // so re-registering a shader rebuilds its pipeline
+    pipelines: HashMap<String, (Id<ShaderModule>, RenderPipeline)>,
+    format: Option<TextureFormat>,
+    depth_stencil: Option<DepthStencilState>,
+    sample_count: u32,
+}
+
+impl ShaderQuadState {
+    fn prepare_pipeline(&mut self, resources: &Resources, name: &str) -> bool {
+        let Some(extension) = resources
+            .extensions
+            .get(name)
+            .filter(|extension| extension.kind == ExtensionKind::Quad)
+        else {
+            return false;
+        };
+        let (Some(format), Some(depth_stencil)) = (self.format, self.depth_stencil.clone()) else {
+            return false;
+        };
+
+        let module_id = extension.module.global_id();
+        if matches!(self.pipelines.get(name), Some((built, _)) if *built == module_id) {
+            return true;
+        }
+
+        let pipeline = resources
+            .device
+            .create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(name),
+                layout: Some(&self.pipeline_layout),
+                vertex: VertexState {
+                    module: &extension.module,
+                    entry_point: "vertex",
+                    buffers: &[],
+                },
+                fragment: Some(FragmentState {
+                    module: &extension.module,
+                    entry_point: "fragment",
+                    targets: &[Some(ColorTargetState {
+                        format,
+                        blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: FrontFace::Ccw,
+                    cull_mode: None,
+                    unclipped_depth: false,
+                    polygon_mode: PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(depth_stencil),
+                multisample: MultisampleState {
+                    count: self.sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+            });
+        self.pipelines
+            .insert(name.to_string(), (module_id, pipeline));
+        true
+    }
+}
+
+impl Drawable for ShaderQuadState {
+    fn new(
+        Resources {
+            device,
+            universal_bind_group_layout,
+            ..
+        }: &Resources,
+    ) -> Self {
+        let buffer = GrowableBuffer::new(device, "Shader quad buffer", BufferUsages::STORAGE);
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Shader quad bind group layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: true,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = create_bind_group(device, &bind_group_layout, &buffer);
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Shader quad Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout, universal_bind_group_layout],
+            push_constant_ranges: &[PushConstantRange {
+                stages: ShaderStages::all(),
+                range: 0..std::mem::size_of::<ShaderConstants>() as u32,
+            }],
+        });
+
+        Self {
+            buffer,
+            bind_group_layout,
+            bind_group,
+            pipeline_layout,
+            pipelines: HashMap::new(),
+            format: None,
+            depth_stencil: None,
+            sample_count: 1,
+        }
+    }
+
+    // Pipelines depend on the registered shaders, so they're built when first drawn
+    fn surface_updated(
+        &mut self,
+        Resources {
+            surface_resources_manager,
+            ..
+        }: &Resources,
+    ) {
+        self.pipelines.clear();
+        self.format = surface_resources_manager.format();
+        self.depth_stencil = Some(surface_resources_manager.depth_stencil_state());
+        self.sample_count = surface_resources_manager.sample_count();
+    }
+
+    fn needs_draw(&self, layer: &Layer) -> bool {
+        !layer.shader_quads.is_empty()
+    }
+
+    fn instance_count(&self) -> u64 {
+        self.buffer.len()
+    }
+
+    fn draw<'b, 'a: 'b>(
+        &'a mut self,
+        resources: &'a Resources,
+        render_pass: &mut RenderPass<'b>,
+        constants: ShaderConstants,
+        layer: &Layer,
+    ) {
+        let visible = visible_rect(layer, constants.surface_size);
+        let mut quads = Vec::new();
+        for quad in layer.shader_quads.iter() {
+            if intersects(quad.bounds(), visible) && self.prepare_pipeline(resources, &quad.shader)
+            {
+                quads.push(quad);
+            }
+        }
+
+        let instances: Vec<_> = quads
+            .iter()
+            .map(|quad| InstancedShaderQuad::new(quad))
+            .collect();
+        if self
+            .buffer
+            .upload(&resources.device, &resources.queue, &instances)
+        {
+            self.bind_group =
+                create_bind_group(&resources.device, &self.bind_group_layout, &self.buffer);
+        }
+
+        render_pass.set_bind_group(0, &self.bind_group, &[0]);
+        render_pass.set_bind_group(
+            1,
+            resources.surface_resources_manager.universal_bind_group(),
+            &[],
+        );
+
+        // Instance indices are offset by the start of each run, so the whole buffer stays bound
+        let mut start = 0;
+        while start < quads.len() {
+            let shader = &quads[start].shader;
+            let end = start
+                + quads[start..]
+                    .iter()
+                    .take_while(|quad| quad.shader == *shader)
+                    .count();
+            render_pass.set_pipeline(&self.pipelines[shader].1);
+            render_pass.set_push_constants(
+                ShaderStages::all(),
+                0,
+                bytemuck::cast_slice(&[constants]),
+            );
+            render_pass.draw(0..6, start as u32..end as u32);
+            start = end;
+        }
+    }
+}
+
+fn create_bind_group(
+    device: &Device,
+    bind_group_layout: &BindGroupLayout,
+    buffer: &GrowableBuffer<InstancedShaderQuad>,
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Shader quad bind group"),
+        layout: bind_group_layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: buffer.binding(),
+        }],
+    })
+}
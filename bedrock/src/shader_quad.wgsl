@@ -0,0 +1,70 @@
+// Declarations prepended to every shader registered with `Renderer::register_quad_shader`.
+// The layouts must match ShaderConstants in the shader crate and InstancedShaderQuad in
+// shader_quad.rs. Registered shaders supply a fragment entry point such as
+//
+//     @fragment
+//     fn fragment(in: ShaderQuadInput) -> @location(0) vec4<f32> {
+//         let quad = shader_quads[in.instance_index];
+//         return vec4<f32>(in.uv, quad.uniforms[0].x, 1.0);
+//     }
+//
+// which returns a premultiplied color.
+
+struct ShaderConstants {
+    surface_size: vec2<f32>,
+    atlas_size: vec2<f32>,
+    clip: vec4<f32>,
+    backdrop: vec4<f32>,
+}
+
+struct ShaderQuad {
+    top_left: vec2<f32>,
+    size: vec2<f32>,
+    depth: f32,
+    uniforms: array<vec4<f32>, 4>,
+}
+
+struct ShaderQuadInput {
+    // Pixel position within the frame
+    @builtin(position) position: vec4<f32>,
+    // Position within the quad from 0 at the top left to 1 at the bottom right
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) instance_index: u32,
+}
+
+var<push_constant> constants: ShaderConstants;
+
+@group(0) @binding(0) var<storage, read> shader_quads: array<ShaderQuad>;
+
+// The universal bind group shared with the built in primitives
+@group(1) @binding(0) var frame_texture: texture_2d<f32>;
+@group(1) @binding(1) var nearest_sampler: sampler;
+@group(1) @binding(2) var backdrop_texture: texture_2d<f32>;
+@group(1) @binding(3) var linear_sampler: sampler;
+
+@vertex
+fn vertex(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> ShaderQuadInput {
+    // Two triangles covering the unit square
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+    );
+    let uv = corners[vertex_index];
+    let quad = shader_quads[instance_index];
+    let pixel = quad.top_left + uv * quad.size;
+    let position = pixel / constants.surface_size * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+
+    var out: ShaderQuadInput;
+    out.position = vec4<f32>(position, quad.depth, 1.0);
+    out.uv = uv;
+    out.instance_index = instance_index;
+    return out;
+}
+
@@ -1,77 +1,332 @@
 mod font_spec;
 
-use std::{collections::HashMap, sync::Arc};
+use std::{cell::RefCell, collections::HashMap, ops::Range, sync::Arc};
 
-use glam::Vec4;
+use glam::{vec2, vec4, Vec2, Vec4};
 use lazy_static::lazy_static;
 use ordered_float::OrderedFloat;
 use swash::{
-    shape::{cluster::Glyph, ShapeContext},
-    CacheKey, FontRef,
+    scale::ScaleContext,
+    shape::{cluster::Glyph, Direction, ShapeContext},
+    tag_from_str_lossy,
+    zeno::{Vector, Verb},
+    CacheKey, FontRef, GlyphId, Setting,
 };
+use thread_local::ThreadLocal;
+use unicode_bidi::{BidiInfo, Level};
 
-use crate::{font::Font, Scene};
+use crate::{
+    font::{system_text, Font},
+    scene::{FillRule, FontFeature, Path, TextDirection},
+};
 
 use self::font_spec::IntoFontSpec;
 
 lazy_static! {
-    pub static ref SHAPER: Shaper = Shaper::new();
+    // Shaping contexts can't be shared between threads, so each thread gets its own shaper
+    static ref SHAPER: ThreadLocal<RefCell<Shaper>> = ThreadLocal::new();
+}
+
+// Shapes the text on the current thread's shaper. Returns None if the font couldn't be found.
+pub fn shape_text(text: &str, font: &str, size: f32) -> Option<ShapedText> {
+    SHAPER
+        .get_or(|| RefCell::new(Shaper::new()))
+        .borrow_mut()
+        .shape(text, font, size)
+}
+
+// Shapes a line of text into clusters on the current thread's shaper. Returns None if the font
+// couldn't be found.
+pub(crate) fn shape_line(
+    line: &str,
+    font: &str,
+    size: f32,
+    direction: TextDirection,
+    features: &[FontFeature],
+) -> Option<ShapedLine> {
+    SHAPER
+        .get_or(|| RefCell::new(Shaper::new()))
+        .borrow_mut()
+        .shape_line(line, font, size, direction, features)
+}
+
+// Outline of the character's glyph on the current thread's shaper. Returns None if the font
+// couldn't be found or has no outline for the character
+pub(crate) fn character_outline(font: &str, character: char, size: f32) -> Option<Path> {
+    SHAPER
+        .get_or(|| RefCell::new(Shaper::new()))
+        .borrow_mut()
+        .outline(font, character, size)
+}
+
+// Same as `character_outline`, but for a glyph id such as one produced by shaping
+pub(crate) fn glyph_outline(font: &str, glyph: GlyphId, size: f32) -> Option<Path> {
+    SHAPER
+        .get_or(|| RefCell::new(Shaper::new()))
+        .borrow_mut()
+        .glyph_outline(font, glyph, size)
 }
 
 #[derive(Clone)]
 pub struct ShapedText {
     shape_key: ShapeKey,
     pub glyphs: Vec<Glyph>,
+    // Line box relative to the start of the baseline as (x, y, width, height). The width is
+    // the total advance and the height spans the font's ascent and descent.
     pub bounds: Vec4,
 }
 
+// Clusters of a line in visual order along with the font's vertical metrics, for placing carets
+pub(crate) struct ShapedLine {
+    pub clusters: Vec<ShapedCluster>,
+    pub ascent: f32,
+    pub descent: f32,
+    // Distance between baselines when the text doesn't set a line height
+    pub line_advance: f32,
+}
+
 pub struct Shaper {
     shaping_context: ShapeContext,
+    scale_context: ScaleContext,
     shaped_text_lookup: HashMap<ShapeKey, ShapedText>,
+    fonts: HashMap<String, Option<Font>>,
 }
 
 impl Shaper {
     pub fn new() -> Self {
         Self {
             shaping_context: ShapeContext::new(),
+            scale_context: ScaleContext::new(),
             shaped_text_lookup: HashMap::new(),
+            fonts: HashMap::new(),
         }
     }
 
-    pub fn shape(&mut self, text: &str, font: &str, size: f32) -> ShapedText {
-        let font = Font::from_name(font).unwrap();
-        let font_ref = font.as_ref().unwrap();
+    pub fn shape(&mut self, text: &str, font: &str, size: f32) -> Option<ShapedText> {
+        let font = self
+            .fonts
+            .entry(font.to_string())
+            .or_insert_with(|| Font::from_name(font))
+            .as_ref()?;
+        let font_ref = font.as_ref()?;
 
         let key = ShapeKey::new(Arc::from(text), font_ref, size.into());
 
-        self.shaped_text_lookup
+        let shaped_text = self
+            .shaped_text_lookup
             .entry(key.clone())
             .or_insert_with({
-                let mut shaper = self
-                    .shaping_context
-                    .builder(font_ref)
-                    .size(*key.size)
-                    .build();
+                let shaping_context = &mut self.shaping_context;
 
                 move || {
-                    shaper.add_str(key.text.as_ref());
+                    let mut glyphs = shape_bidi(
+                        shaping_context,
+                        font_ref,
+                        key.text.as_ref(),
+                        *key.size,
+                        TextDirection::Auto,
+                        &[],
+                    );
 
-                    let mut shaped_text = ShapedText {
+                    let metrics = font_ref.metrics(&[]).scale(*key.size);
+                    let (ascent, descent) = system_text()
+                        .then(|| font.apply_system_metrics(*key.size, &mut glyphs))
+                        .flatten()
+                        .unwrap_or((metrics.ascent.abs(), metrics.descent.abs()));
+                    let width = glyphs.iter().map(|glyph| glyph.advance).sum();
+
+                    ShapedText {
                         shape_key: key.clone(),
-                        glyphs: Vec::new(),
-                        bounds: Vec4::ZERO,
-                    };
+                        glyphs,
+                        bounds: vec4(0.0, -ascent, width, ascent + descent),
+                    }
+                }
+            })
+            .clone();
+        Some(shaped_text)
+    }
 
-                    shaper.shape_with(|cluster| {
-                        for glyph in cluster.glyphs {
-                            shaped_text.glyphs.push(*glyph);
-                        }
-                    });
+    // Not cached, since carets are only placed in the few texts being edited
+    pub(crate) fn shape_line(
+        &mut self,
+        line: &str,
+        font: &str,
+        size: f32,
+        direction: TextDirection,
+        features: &[FontFeature],
+    ) -> Option<ShapedLine> {
+        let font = self
+            .fonts
+            .entry(font.to_string())
+            .or_insert_with(|| Font::from_name(font))
+            .as_ref()?;
+        let font_ref = font.as_ref()?;
 
-                    shaped_text
+        let mut clusters = Vec::new();
+        let mut system_metrics = None;
+        shape_clusters(
+            &mut self.shaping_context,
+            font_ref,
+            line,
+            size,
+            direction,
+            features,
+            |mut cluster| {
+                if system_text() {
+                    system_metrics = font.apply_system_metrics(size, &mut cluster.glyphs);
                 }
-            })
-            .clone()
+                clusters.push(cluster);
+            },
+        );
+
+        let metrics = font_ref.metrics(&[]).scale(size);
+        let (ascent, descent) =
+            system_metrics.unwrap_or((metrics.ascent.abs(), metrics.descent.abs()));
+        Some(ShapedLine {
+            clusters,
+            ascent,
+            descent,
+            line_advance: metrics.ascent + metrics.descent + metrics.leading,
+        })
+    }
+
+    pub(crate) fn outline(&mut self, font: &str, character: char, size: f32) -> Option<Path> {
+        let glyph = self
+            .fonts
+            .entry(font.to_string())
+            .or_insert_with(|| Font::from_name(font))
+            .as_ref()?
+            .as_ref()?
+            .charmap()
+            .map(character);
+        // Glyph 0 is the font's placeholder for characters it doesn't have
+        if glyph == 0 {
+            return None;
+        }
+        self.glyph_outline(font, glyph, size)
+    }
+
+    // Unhinted so the outline scales smoothly when animated
+    pub(crate) fn glyph_outline(&mut self, font: &str, glyph: GlyphId, size: f32) -> Option<Path> {
+        let font = self
+            .fonts
+            .entry(font.to_string())
+            .or_insert_with(|| Font::from_name(font))
+            .as_ref()?;
+        let font_ref = font.as_ref()?;
+        let mut scaler = self.scale_context.builder(font_ref).size(size).build();
+        let outline = scaler.scale_outline(glyph)?;
+        outline_path(outline.verbs(), outline.points())
+    }
+}
+
+// Converts a font outline, which points y upwards from the baseline, into a path pointing y
+// downwards like the rest of the scene. Contours after the first become move commands, and
+// since fills are always closed the explicit close verbs are dropped. Font contours may overlap,
+// so the path uses the nonzero fill rule fonts are designed for
+fn outline_path(verbs: &[Verb], points: &[Vector]) -> Option<Path> {
+    let mut points = points.iter().map(|point| vec2(point.x, -point.y));
+    let mut path: Option<Path> = None;
+    let mut next = || points.next().unwrap_or(Vec2::ZERO);
+    for verb in verbs.iter() {
+        path = Some(match (verb, path) {
+            (Verb::MoveTo, None) => Path::new(next()).with_fill_rule(FillRule::NonZero),
+            (Verb::MoveTo, Some(path)) => path.move_to(next()),
+            (Verb::LineTo, Some(path)) => path.line_to(next()),
+            (Verb::QuadTo, Some(path)) => path.quadratic_bezier_to(next(), next()),
+            (Verb::CurveTo, Some(path)) => path.cubic_bezier_to(next(), next(), next()),
+            (Verb::Close, path) => path?,
+            // Outlines always start with a move
+            (_, None) => return None,
+        });
+    }
+    path
+}
+
+// Shapes the text one bidi run at a time so mixed direction text comes out in visual order.
+// Runs are laid out left to right, and glyphs within right to left runs are reversed so that
+// every glyph's advance moves the pen rightwards.
+pub(crate) fn shape_bidi(
+    shaping_context: &mut ShapeContext,
+    font_ref: FontRef,
+    text: &str,
+    size: f32,
+    direction: TextDirection,
+    features: &[FontFeature],
+) -> Vec<Glyph> {
+    let mut glyphs = Vec::new();
+    shape_clusters(
+        shaping_context,
+        font_ref,
+        text,
+        size,
+        direction,
+        features,
+        |cluster| glyphs.extend_from_slice(&cluster.glyphs),
+    );
+    glyphs
+}
+
+// Glyphs shaped from one range of the source text, which carets can't be placed within
+pub(crate) struct ShapedCluster {
+    // Bytes of the source text the glyphs were shaped from
+    pub range: Range<usize>,
+    pub rtl: bool,
+    pub glyphs: Vec<Glyph>,
+}
+
+// Same as `shape_bidi`, but hands over each cluster in visual order along with the text it
+// came from
+pub(crate) fn shape_clusters(
+    shaping_context: &mut ShapeContext,
+    font_ref: FontRef,
+    text: &str,
+    size: f32,
+    direction: TextDirection,
+    features: &[FontFeature],
+    mut each: impl FnMut(ShapedCluster),
+) {
+    let base_level = match direction {
+        TextDirection::Auto => None,
+        TextDirection::LeftToRight => Some(Level::ltr()),
+        TextDirection::RightToLeft => Some(Level::rtl()),
+    };
+    let bidi = BidiInfo::new(text, base_level);
+
+    for paragraph in bidi.paragraphs.iter() {
+        let (levels, runs) = bidi.visual_runs(paragraph, paragraph.range.clone());
+        for run in runs {
+            let rtl = levels[run.start].is_rtl();
+            let mut shaper = shaping_context
+                .builder(font_ref)
+                .size(size)
+                .features(features.iter().map(|feature| Setting {
+                    tag: tag_from_str_lossy(&feature.tag),
+                    value: feature.value,
+                }))
+                .direction(if rtl {
+                    Direction::RightToLeft
+                } else {
+                    Direction::LeftToRight
+                })
+                .build();
+            shaper.add_str(&text[run.clone()]);
+
+            let mut clusters = Vec::new();
+            shaper.shape_with(|cluster| {
+                let source = cluster.source.to_range();
+                clusters.push(ShapedCluster {
+                    range: run.start + source.start..run.start + source.end,
+                    rtl,
+                    glyphs: cluster.glyphs.to_vec(),
+                })
+            });
+            // Clusters come out in logical order
+            if rtl {
+                clusters.reverse();
+            }
+            clusters.into_iter().for_each(&mut each);
+        }
     }
 }
 
@@ -93,3 +348,57 @@ impl ShapeKey {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scene::PathCommand;
+
+    #[test]
+    fn test_outlines_flip_to_scene_coordinates() {
+        let verbs = [
+            Verb::MoveTo,
+            Verb::LineTo,
+            Verb::QuadTo,
+            Verb::Close,
+            Verb::MoveTo,
+            Verb::LineTo,
+            Verb::Close,
+        ];
+        let points = [
+            Vector::new(0.0, 0.0),
+            Vector::new(10.0, 0.0),
+            Vector::new(10.0, 10.0),
+            Vector::new(0.0, 10.0),
+            Vector::new(2.0, 2.0),
+            Vector::new(4.0, 2.0),
+        ];
+        let path = outline_path(&verbs, &points).unwrap();
+        assert_eq!(path.start, Vec2::ZERO);
+        assert_eq!(path.fill_rule, FillRule::NonZero);
+        // Contours are flipped upwards from the baseline and the close verbs are dropped
+        let ends: Vec<Vec2> = path
+            .commands
+            .iter()
+            .map(|command| match *command {
+                PathCommand::CubicBezierTo { to, .. }
+                | PathCommand::QuadraticBezierTo { to, .. }
+                | PathCommand::LineTo { to } => to,
+                PathCommand::MoveTo { start } => start,
+            })
+            .collect();
+        assert_eq!(
+            ends,
+            [
+                vec2(10.0, 0.0),
+                vec2(0.0, -10.0),
+                vec2(2.0, -2.0),
+                vec2(4.0, -2.0)
+            ]
+        );
+        assert!(matches!(
+            path.commands[1],
+            PathCommand::QuadraticBezierTo { control, .. } if control == vec2(10.0, -10.0)
+        ));
+    }
+}
@@ -1,99 +1,272 @@
-use std::{collections::HashMap, marker::PhantomData};
+use std::{
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+    ops::Range,
+};
 
-use etagere::{size2, AllocId, AtlasAllocator};
-use glam::vec2;
-use image::GenericImageView;
+use etagere::{size2, AllocId, AllocatorOptions, AtlasAllocator};
+use glam::{vec2, Vec2, Vec4};
 use rust_embed::RustEmbed;
 use shader::{InstancedSprite, ShaderConstants};
 use wgpu::*;
 
 use crate::{
+    buffer::GrowableBuffer,
+    culling::{intersects, visible_rect},
+    placeholder::MissingContent,
     renderer::{Drawable, Resources},
-    scene::{Layer, Sprite},
+    scene::{Layer, Sprite, SpriteFilter},
     ATLAS_SIZE,
 };
 
+// Number of mip levels generated for the sprite atlas. Allocations are aligned to the size of a
+// texel in the smallest level so that each image's mips never share texels with its neighbors.
+const MIP_LEVELS: u32 = 5;
+const MIP_ALIGNMENT: i32 = 1 << (MIP_LEVELS - 1);
+// Highest anisotropy requested when the adapter supports anisotropic filtering
+const MAX_ANISOTROPY: u16 = 16;
+
 pub struct SpriteState<A: RustEmbed> {
-    buffer: Buffer,
+    buffer: GrowableBuffer<InstancedSprite>,
     atlas_texture: Texture,
     bind_group_layout: BindGroupLayout,
     bind_group: BindGroup,
     render_pipeline: Option<RenderPipeline>,
 
-    image_lookup: HashMap<String, AllocId>,
+    linear_sampler: Sampler,
+    anisotropic_sampler: Sampler,
+
+    image_lookup: HashMap<String, AtlasImage>,
+    tiled_images: HashMap<String, TiledImage>,
+    tiles: Vec<ImageTile>,
+    // Bind groups of the render targets sampled by the most recent draw, along with their size
+    target_bind_groups: Vec<(BindGroup, Vec2)>,
+    // Textures which are missing or couldn't be decoded. Kept so the error is only reported once
+    failed_images: HashSet<String>,
+    missing: Vec<MissingContent>,
     atlas_allocator: AtlasAllocator,
-    _assets: PhantomData<*const A>,
+    _assets: PhantomData<fn() -> A>,
+}
+
+struct AtlasImage {
+    id: AllocId,
+    // Size of the image itself. Allocations are padded up to the mip alignment
+    size: Vec2,
+}
+
+struct TiledImage {
+    size: Vec2,
+    // Indices into `SpriteState::tiles`
+    tiles: Range<usize>,
+}
+
+// Part of an image too large for the atlas, stored in its own texture
+struct ImageTile {
+    texture: Texture,
+    // Region of the image the tile holds, in image pixels
+    origin: Vec2,
+    size: Vec2,
+    // Includes the padding up to the mip alignment
+    texture_size: Vec2,
+    // Binds the instance buffer along with the tile's texture. Rebuilt when the buffer is
+    // recreated
+    bind_group: Option<BindGroup>,
+}
+
+// Texture an instance samples from
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SpriteSource {
+    Atlas,
+    Tile(usize),
+    // Index into `SpriteState::target_bind_groups`
+    Target(usize),
 }
 
 impl<A: RustEmbed> SpriteState<A> {
-    pub fn upload_sprite(&mut self, queue: &Queue, sprite: &Sprite) -> InstancedSprite {
-        let allocation_rectangle = if let Some(alloc_id) = self.image_lookup.get(&sprite.texture) {
-            self.atlas_allocator.get(*alloc_id)
-        } else {
-            let image_file = A::get(&sprite.texture).unwrap();
-            let image = image::load_from_memory(image_file.data.as_ref()).unwrap();
-            let data = image.to_rgba8();
-            let (image_width, image_height) = image.dimensions();
-
-            let allocation = self
-                .atlas_allocator
-                .allocate(size2(image_width as i32, image_height as i32))
-                .expect("Could not allocate glyph to atlas");
-
-            self.image_lookup
-                .insert(sprite.texture.clone(), allocation.id);
-
-            queue.write_texture(
-                ImageCopyTexture {
-                    texture: &self.atlas_texture,
-                    mip_level: 0,
-                    origin: Origin3d {
-                        x: allocation.rectangle.min.x as u32,
-                        y: allocation.rectangle.min.y as u32,
-                        z: 0,
-                    },
-                    aspect: TextureAspect::All,
-                },
-                &data,
-                ImageDataLayout {
-                    offset: 0,
-                    bytes_per_row: Some(4 * image_width as u32),
-                    rows_per_image: Some(image_height as u32),
-                },
-                Extent3d {
-                    width: image_width as u32,
-                    height: image_height as u32,
-                    depth_or_array_layers: 1,
-                },
+    // Instances drawing the sprite, along with the texture each samples. Images too large for
+    // the atlas produce one instance per tile covering the sprite.
+    fn upload_sprite(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        sprite: &Sprite,
+        max_texture_size: u32,
+    ) -> Option<Vec<(InstancedSprite, SpriteSource)>> {
+        if let Some(image) = self.image_lookup.get(&sprite.texture) {
+            let rectangle = self.atlas_allocator.get(image.id);
+            let atlas_top_left = vec2(rectangle.min.x as f32, rectangle.min.y as f32);
+            return Some(vec![(
+                sprite_instance(
+                    sprite,
+                    sprite.top_left,
+                    sprite.size,
+                    atlas_top_left,
+                    image.size,
+                ),
+                SpriteSource::Atlas,
+            )]);
+        }
+        if let Some(image) = self.tiled_images.get(&sprite.texture) {
+            let scale = sprite.size / image.size;
+            return Some(
+                image
+                    .tiles
+                    .clone()
+                    .map(|index| {
+                        let tile = &self.tiles[index];
+                        (
+                            sprite_instance(
+                                sprite,
+                                sprite.top_left + tile.origin * scale,
+                                tile.size * scale,
+                                Vec2::ZERO,
+                                tile.size,
+                            ),
+                            SpriteSource::Tile(index),
+                        )
+                    })
+                    .collect(),
             );
+        }
 
-            allocation.rectangle
+        if self.failed_images.contains(&sprite.texture) {
+            return None;
+        }
+        let decoded = match A::get(&sprite.texture) {
+            Some(image_file) => decode_image(image_file.data.as_ref()),
+            None => Err("no such asset".to_string()),
+        };
+        let image = match decoded {
+            Ok(decoded) => decoded,
+            Err(error) => {
+                eprintln!("Could not load sprite {}: {}", sprite.texture, error);
+                self.failed_images.insert(sprite.texture.clone());
+                return None;
+            }
         };
 
-        InstancedSprite {
-            top_left: sprite.top_left,
-            size: sprite.size,
-            atlas_top_left: vec2(
-                allocation_rectangle.min.x as f32,
-                allocation_rectangle.min.y as f32,
-            ),
-            atlas_size: vec2(
-                allocation_rectangle.width() as f32,
-                allocation_rectangle.height() as f32,
-            ),
-            color: sprite.color,
+        let padded_width = align(image.width);
+        let padded_height = align(image.height);
+        let allocation = self
+            .atlas_allocator
+            .allocate(size2(padded_width as i32, padded_height as i32));
+        match allocation {
+            Some(allocation) => {
+                tracing::debug!(
+                    texture = %sprite.texture,
+                    width = image.width,
+                    height = image.height,
+                    "Added image to sprite atlas"
+                );
+                self.image_lookup.insert(
+                    sprite.texture.clone(),
+                    AtlasImage {
+                        id: allocation.id,
+                        size: vec2(image.width as f32, image.height as f32),
+                    },
+                );
+                write_mip_chain(
+                    queue,
+                    &self.atlas_texture,
+                    (
+                        allocation.rectangle.min.x as u32,
+                        allocation.rectangle.min.y as u32,
+                    ),
+                    pad_region(&image, 0, 0, image.width, image.height),
+                    padded_width,
+                    padded_height,
+                );
+            }
+            // Images larger than the atlas, or arriving once it is full, get textures of their
+            // own split into tiles no larger than the maximum texture size
+            None => {
+                tracing::debug!(
+                    texture = %sprite.texture,
+                    width = image.width,
+                    height = image.height,
+                    "Sprite atlas has no room. Splitting image into tiles"
+                );
+                let first_tile = self.tiles.len();
+                let tile_size = max_texture_size / MIP_ALIGNMENT as u32 * MIP_ALIGNMENT as u32;
+                for (x, y, width, height) in tile_regions(image.width, image.height, tile_size) {
+                    let padded_size = (align(width), align(height));
+                    let texture = device.create_texture(&TextureDescriptor {
+                        label: Some("Sprite tile texture"),
+                        size: Extent3d {
+                            width: padded_size.0,
+                            height: padded_size.1,
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: MIP_LEVELS,
+                        sample_count: 1,
+                        dimension: TextureDimension::D2,
+                        format: TextureFormat::Rgba8Unorm,
+                        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                        view_formats: &[],
+                    });
+                    write_mip_chain(
+                        queue,
+                        &texture,
+                        (0, 0),
+                        pad_region(&image, x, y, width, height),
+                        padded_size.0,
+                        padded_size.1,
+                    );
+                    self.tiles.push(ImageTile {
+                        texture,
+                        origin: vec2(x as f32, y as f32),
+                        size: vec2(width as f32, height as f32),
+                        texture_size: vec2(padded_size.0 as f32, padded_size.1 as f32),
+                        bind_group: None,
+                    });
+                }
+                self.tiled_images.insert(
+                    sprite.texture.clone(),
+                    TiledImage {
+                        size: vec2(image.width as f32, image.height as f32),
+                        tiles: first_tile..self.tiles.len(),
+                    },
+                );
+            }
         }
+
+        self.upload_sprite(device, queue, sprite, max_texture_size)
+    }
+}
+
+fn sprite_instance(
+    sprite: &Sprite,
+    top_left: Vec2,
+    size: Vec2,
+    atlas_top_left: Vec2,
+    atlas_size: Vec2,
+) -> InstancedSprite {
+    InstancedSprite {
+        top_left,
+        size,
+        atlas_top_left,
+        atlas_size,
+        color: sprite.color,
+        adjustments: sprite.adjustments.to_vec4(),
+        alpha_cutoff: sprite.alpha_cutoff.unwrap_or(0.0),
+        lod_bias: sprite.lod_bias,
+        depth: sprite.depth,
+        filter: match sprite.filter {
+            SpriteFilter::Nearest => 0,
+            SpriteFilter::Linear => 1,
+            SpriteFilter::Anisotropic => 2,
+        },
+        ..Default::default()
     }
 }
 
 impl<A: RustEmbed> Drawable for SpriteState<A> {
-    fn new(Resources { device, .. }: &Resources) -> Self {
-        let buffer = device.create_buffer(&BufferDescriptor {
-            label: Some("Sprite buffer"),
-            size: std::mem::size_of::<InstancedSprite>() as u64 * 100000,
-            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+    fn new(
+        Resources {
+            device, adapter, ..
+        }: &Resources,
+    ) -> Self {
+        let buffer = GrowableBuffer::new(device, "Sprite buffer", BufferUsages::STORAGE);
 
         let atlas_texture = device.create_texture(&TextureDescriptor {
             label: Some("Sprite atlas texture descriptor"),
@@ -102,7 +275,7 @@ impl<A: RustEmbed> Drawable for SpriteState<A> {
                 height: ATLAS_SIZE.y as u32,
                 depth_or_array_layers: 1,
             },
-            mip_level_count: 1,
+            mip_level_count: MIP_LEVELS,
             sample_count: 1,
             dimension: TextureDimension::D2,
             format: TextureFormat::Rgba8Unorm,
@@ -118,7 +291,7 @@ impl<A: RustEmbed> Drawable for SpriteState<A> {
                     visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
                     ty: BindingType::Buffer {
                         ty: BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: false,
+                        has_dynamic_offset: true,
                         min_binding_size: None,
                     },
                     count: None,
@@ -133,35 +306,79 @@ impl<A: RustEmbed> Drawable for SpriteState<A> {
                     },
                     count: None,
                 },
-            ],
-        });
-
-        let atlas_texture_view = atlas_texture.create_view(&TextureViewDescriptor::default());
-
-        let bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: Some("Sprite bind group"),
-            layout: &bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: buffer.as_entire_binding(),
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
                 },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::TextureView(&atlas_texture_view),
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
                 },
             ],
         });
 
+        let linear_descriptor = SamplerDescriptor {
+            label: Some("Sprite linear sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..Default::default()
+        };
+        let linear_sampler = device.create_sampler(&linear_descriptor);
+        // Adapters without anisotropic filtering fall back to plain trilinear filtering
+        let anisotropy_clamp = if adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(DownlevelFlags::ANISOTROPIC_FILTERING)
+        {
+            MAX_ANISOTROPY
+        } else {
+            1
+        };
+        let anisotropic_sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Sprite anisotropic sampler"),
+            anisotropy_clamp,
+            ..linear_descriptor
+        });
+
+        let bind_group = create_bind_group(
+            device,
+            &bind_group_layout,
+            &buffer,
+            &atlas_texture,
+            &linear_sampler,
+            &anisotropic_sampler,
+        );
+
         Self {
             buffer,
             atlas_texture,
             bind_group_layout,
             bind_group,
             render_pipeline: None,
+            linear_sampler,
+            anisotropic_sampler,
 
             image_lookup: HashMap::new(),
-            atlas_allocator: AtlasAllocator::new(size2(ATLAS_SIZE.x as i32, ATLAS_SIZE.y as i32)),
+            tiled_images: HashMap::new(),
+            tiles: Vec::new(),
+            target_bind_groups: Vec::new(),
+            failed_images: HashSet::new(),
+            missing: Vec::new(),
+            atlas_allocator: AtlasAllocator::with_options(
+                size2(ATLAS_SIZE.x as i32, ATLAS_SIZE.y as i32),
+                &AllocatorOptions {
+                    alignment: size2(MIP_ALIGNMENT, MIP_ALIGNMENT),
+                    ..Default::default()
+                },
+            ),
             _assets: PhantomData,
         }
     }
@@ -176,6 +393,9 @@ impl<A: RustEmbed> Drawable for SpriteState<A> {
             ..
         }: &Resources,
     ) {
+        let Some(format) = surface_resources_manager.format() else {
+            return;
+        };
         let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("Sprite Pipeline Layout"),
             bind_group_layouts: &[&self.bind_group_layout, &universal_bind_group_layout],
@@ -190,15 +410,15 @@ impl<A: RustEmbed> Drawable for SpriteState<A> {
             layout: Some(&render_pipeline_layout),
             vertex: VertexState {
                 module: &shader,
-                entry_point: "sprite::sprite_vertex",
+                entry_point: shader::SPRITE_VERTEX,
                 buffers: &[],
             },
             fragment: Some(FragmentState {
                 module: &shader,
-                entry_point: "sprite::sprite_fragment",
+                entry_point: shader::SPRITE_FRAGMENT,
                 targets: &[Some(ColorTargetState {
-                    format: surface_resources_manager.format(),
-                    blend: Some(BlendState::ALPHA_BLENDING),
+                    format,
+                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
                     write_mask: ColorWrites::ALL,
                 })],
             }),
@@ -211,35 +431,390 @@ impl<A: RustEmbed> Drawable for SpriteState<A> {
                 polygon_mode: PolygonMode::Fill,
                 conservative: false,
             },
-            depth_stencil: None,
+            depth_stencil: Some(surface_resources_manager.depth_stencil_state()),
             multisample: MultisampleState {
-                count: 4,
+                count: surface_resources_manager.sample_count(),
                 ..Default::default()
             },
             multiview: None,
         }));
     }
 
+    fn needs_draw(&self, layer: &Layer) -> bool {
+        !layer.sprites.is_empty()
+    }
+
+    fn instance_count(&self) -> u64 {
+        self.buffer.len()
+    }
+
+    fn missing_content(&mut self) -> Vec<MissingContent> {
+        std::mem::take(&mut self.missing)
+    }
+
     fn draw<'b, 'a: 'b>(
         &'a mut self,
-        queue: &Queue,
+        Resources {
+            device,
+            queue,
+            surface_resources_manager,
+            max_texture_size,
+            render_targets,
+            ..
+        }: &'a Resources,
         render_pass: &mut RenderPass<'b>,
         constants: ShaderConstants,
-        universal_bind_group: &'a BindGroup,
         layer: &Layer,
     ) {
-        let sprites: Vec<_> = layer
-            .sprites
-            .iter()
-            .map(|sprite| self.upload_sprite(queue, sprite))
-            .collect();
+        let visible = visible_rect(layer, constants.surface_size);
+        let mut sprites = Vec::new();
+        let mut sources = Vec::new();
+        let mut targets: Vec<&Texture> = Vec::new();
+        for sprite in layer.sprites.iter() {
+            if !intersects(sprite.bounds(), visible) {
+                continue;
+            }
+            if let Some(target) = render_targets.get(&sprite.texture) {
+                let index = match targets
+                    .iter()
+                    .position(|other| std::ptr::eq(*other, target))
+                {
+                    Some(index) => index,
+                    None => {
+                        targets.push(target);
+                        targets.len() - 1
+                    }
+                };
+                sprites.push(sprite_instance(
+                    sprite,
+                    sprite.top_left,
+                    sprite.size,
+                    Vec2::ZERO,
+                    vec2(target.width() as f32, target.height() as f32),
+                ));
+                sources.push(SpriteSource::Target(index));
+                continue;
+            }
+            let Some(instances) = self.upload_sprite(device, queue, sprite, *max_texture_size)
+            else {
+                self.missing
+                    .push(MissingContent::texture(sprite.bounds(), &sprite.texture));
+                continue;
+            };
+            // Tiles of large images which are offscreen are skipped
+            for (instance, source) in instances {
+                if intersects(
+                    Vec4::new(
+                        instance.top_left.x,
+                        instance.top_left.y,
+                        instance.size.x,
+                        instance.size.y,
+                    ),
+                    visible,
+                ) {
+                    sprites.push(instance);
+                    sources.push(source);
+                }
+            }
+        }
 
         render_pass.set_pipeline(self.render_pipeline.as_ref().unwrap());
         render_pass.set_push_constants(ShaderStages::all(), 0, bytemuck::cast_slice(&[constants]));
 
-        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&sprites[..]));
-        render_pass.set_bind_group(0, &self.bind_group, &[]);
-        render_pass.set_bind_group(1, &universal_bind_group, &[]);
-        render_pass.draw(0..6, 0..sprites.len() as u32);
+        if self.buffer.upload(device, queue, &sprites) {
+            self.bind_group = create_bind_group(
+                device,
+                &self.bind_group_layout,
+                &self.buffer,
+                &self.atlas_texture,
+                &self.linear_sampler,
+                &self.anisotropic_sampler,
+            );
+            for tile in self.tiles.iter_mut() {
+                tile.bind_group = None;
+            }
+        }
+        render_pass.set_bind_group(1, surface_resources_manager.universal_bind_group(), &[]);
+
+        if sources.iter().all(|source| *source == SpriteSource::Atlas) {
+            self.buffer
+                .draw_chunks(render_pass, 0, &self.bind_group, 0..6);
+            return;
+        }
+
+        self.target_bind_groups = targets
+            .iter()
+            .map(|target| {
+                (
+                    create_bind_group(
+                        device,
+                        &self.bind_group_layout,
+                        &self.buffer,
+                        target,
+                        &self.linear_sampler,
+                        &self.anisotropic_sampler,
+                    ),
+                    vec2(target.width() as f32, target.height() as f32),
+                )
+            })
+            .collect();
+
+        for source in sources.iter() {
+            if let SpriteSource::Tile(index) = *source {
+                let tile = &mut self.tiles[index];
+                if tile.bind_group.is_none() {
+                    tile.bind_group = Some(create_bind_group(
+                        device,
+                        &self.bind_group_layout,
+                        &self.buffer,
+                        &tile.texture,
+                        &self.linear_sampler,
+                        &self.anisotropic_sampler,
+                    ));
+                }
+            }
+        }
+
+        // Runs of instances sampling the same texture are drawn together so sprites keep their
+        // order. Instances are indexed from the start of the buffer rather than split into
+        // chunks, which limits layers with tiled images to a single storage binding of sprites
+        let mut start = 0;
+        while start < sources.len() {
+            let source = sources[start];
+            let end = start
+                + sources[start..]
+                    .iter()
+                    .take_while(|other| **other == source)
+                    .count();
+            match source {
+                SpriteSource::Atlas => {
+                    render_pass.set_push_constants(
+                        ShaderStages::all(),
+                        0,
+                        bytemuck::cast_slice(&[constants]),
+                    );
+                    render_pass.set_bind_group(0, &self.bind_group, &[0]);
+                }
+                SpriteSource::Tile(index) => {
+                    let tile = &self.tiles[index];
+                    render_pass.set_push_constants(
+                        ShaderStages::all(),
+                        0,
+                        bytemuck::cast_slice(&[ShaderConstants {
+                            atlas_size: tile.texture_size,
+                            ..constants
+                        }]),
+                    );
+                    render_pass.set_bind_group(0, tile.bind_group.as_ref().unwrap(), &[0]);
+                }
+                SpriteSource::Target(index) => {
+                    let (bind_group, size) = &self.target_bind_groups[index];
+                    render_pass.set_push_constants(
+                        ShaderStages::all(),
+                        0,
+                        bytemuck::cast_slice(&[ShaderConstants {
+                            atlas_size: *size,
+                            ..constants
+                        }]),
+                    );
+                    render_pass.set_bind_group(0, bind_group, &[0]);
+                }
+            }
+            render_pass.draw(0..6, start as u32..end as u32);
+            start = end;
+        }
+    }
+}
+
+fn create_bind_group(
+    device: &Device,
+    bind_group_layout: &BindGroupLayout,
+    buffer: &GrowableBuffer<InstancedSprite>,
+    atlas_texture: &Texture,
+    linear_sampler: &Sampler,
+    anisotropic_sampler: &Sampler,
+) -> BindGroup {
+    // Render targets share the surface's format, but are sampled without srgb decoding like
+    // the atlas so their colors match sprites loaded from images
+    let atlas_texture_view = atlas_texture.create_view(&TextureViewDescriptor {
+        format: Some(atlas_texture.format().remove_srgb_suffix()),
+        ..Default::default()
+    });
+
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Sprite bind group"),
+        layout: bind_group_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: buffer.binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::TextureView(&atlas_texture_view),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: BindingResource::Sampler(linear_sampler),
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: BindingResource::Sampler(anisotropic_sampler),
+            },
+        ],
+    })
+}
+
+fn align(size: u32) -> u32 {
+    let alignment = MIP_ALIGNMENT as u32;
+    (size + alignment - 1) / alignment * alignment
+}
+
+// Regions of an image split into tiles of at most `tile_size` on each side, as
+// (x, y, width, height) in rows from the top left
+fn tile_regions(width: u32, height: u32, tile_size: u32) -> Vec<(u32, u32, u32, u32)> {
+    let tile_size = tile_size.max(1);
+    let mut regions = Vec::new();
+    for y in (0..height).step_by(tile_size as usize) {
+        for x in (0..width).step_by(tile_size as usize) {
+            regions.push((x, y, tile_size.min(width - x), tile_size.min(height - y)));
+        }
+    }
+    regions
+}
+
+// Copies a region of the image into a buffer padded with transparent texels up to the mip
+// alignment so every mip level divides evenly
+fn pad_region(image: &DecodedImage, x: u32, y: u32, width: u32, height: u32) -> Vec<u8> {
+    let padded_width = align(width) as usize;
+    let mut data = vec![0; padded_width * align(height) as usize * 4];
+    for row in 0..height as usize {
+        let source = ((y as usize + row) * image.width as usize + x as usize) * 4;
+        let destination = row * padded_width * 4;
+        data[destination..destination + width as usize * 4]
+            .copy_from_slice(&image.data[source..source + width as usize * 4]);
+    }
+    data
+}
+
+// Writes the padded image and each of its downsampled mip levels into the texture
+fn write_mip_chain(
+    queue: &Queue,
+    texture: &Texture,
+    origin: (u32, u32),
+    mut level_data: Vec<u8>,
+    padded_width: u32,
+    padded_height: u32,
+) {
+    let (mut level_width, mut level_height) = (padded_width, padded_height);
+    for mip_level in 0..MIP_LEVELS {
+        if mip_level > 0 {
+            level_data = downsample(&level_data, level_width, level_height);
+            level_width /= 2;
+            level_height /= 2;
+        }
+
+        queue.write_texture(
+            ImageCopyTexture {
+                texture,
+                mip_level,
+                origin: Origin3d {
+                    x: origin.0 >> mip_level,
+                    y: origin.1 >> mip_level,
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            &level_data,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * level_width),
+                rows_per_image: Some(level_height),
+            },
+            Extent3d {
+                width: level_width,
+                height: level_height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+// Halves both dimensions of premultiplied rgba8 data with a box filter
+fn downsample(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let (half_width, half_height) = (width as usize / 2, height as usize / 2);
+    let mut result = vec![0; half_width * half_height * 4];
+    for y in 0..half_height {
+        for x in 0..half_width {
+            for channel in 0..4 {
+                let texel = |dx: usize, dy: usize| {
+                    data[((y * 2 + dy) * width as usize + x * 2 + dx) * 4 + channel] as u32
+                };
+                let sum = texel(0, 0) + texel(1, 0) + texel(0, 1) + texel(1, 1);
+                result[(y * half_width + x) * 4 + channel] = ((sum + 2) / 4) as u8;
+            }
+        }
+    }
+    result
+}
+
+pub(crate) struct DecodedImage {
+    pub width: u32,
+    pub height: u32,
+    // Premultiplied rgba8 pixels. Premultiplying before upload keeps filtering from bleeding
+    // the color of transparent pixels into their neighbors
+    pub data: Vec<u8>,
+}
+
+// Decodes embedded png, jpeg, and webp files on first use
+#[cfg(feature = "image")]
+pub(crate) fn decode_image(file: &[u8]) -> Result<DecodedImage, String> {
+    let image = image::load_from_memory(file)
+        .map_err(|error| error.to_string())?
+        .into_rgba8();
+    let (width, height) = image.dimensions();
+    let mut data = image.into_raw();
+    premultiply(&mut data);
+
+    Ok(DecodedImage {
+        width,
+        height,
+        data,
+    })
+}
+
+#[cfg(not(feature = "image"))]
+pub(crate) fn decode_image(_file: &[u8]) -> Result<DecodedImage, String> {
+    Err("bedrock was built without the image feature".to_string())
+}
+
+#[cfg(feature = "image")]
+fn premultiply(data: &mut [u8]) {
+    for pixel in data.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u32;
+        for channel in pixel[..3].iter_mut() {
+            *channel = ((*channel as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tiles_cover_image() {
+        assert_eq!(tile_regions(100, 50, 128), vec![(0, 0, 100, 50)]);
+        assert_eq!(
+            tile_regions(300, 200, 128),
+            vec![
+                (0, 0, 128, 128),
+                (128, 0, 128, 128),
+                (256, 0, 44, 128),
+                (0, 128, 128, 72),
+                (128, 128, 128, 72),
+                (256, 128, 44, 72),
+            ]
+        );
     }
 }
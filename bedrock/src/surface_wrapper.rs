@@ -1,15 +1,32 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use wgpu::*;
 use winit::{
+    dpi::PhysicalSize,
     event::{Event, StartCause, WindowEvent},
-    window::Window,
+    raw_window_handle::{RawDisplayHandle, RawWindowHandle},
+    window::{Window, WindowId},
 };
 
+use crate::blur::BACKDROP_LEVELS;
+
+// Depth is only tested when enabled, but the stencil is always used for clip paths
+const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth24PlusStencil8;
+
+// Samples per pixel frames are drawn with unless multisampling is turned off
+pub(crate) const MULTISAMPLE_COUNT: u32 = 4;
+
 pub struct SurfaceResources {
-    surface: Surface<'static>,
+    // None for headless windows, which are only rendered into images
+    surface: Option<Surface<'static>>,
+    config: SurfaceConfiguration,
     offscreen_texture: Texture,
-    multisampled_texture: Texture,
+    // Only exists while multisampling. Otherwise passes draw straight into the frame
+    multisampled_texture: Option<Texture>,
+    // Half resolution mip chain which reduced resolution backdrop blurs are rendered into
+    backdrop_texture: Texture,
+    // Depth and clip stencil. Has the same sample count as the output texture
+    depth_texture: Texture,
     universal_bind_group: BindGroup,
 }
 
@@ -17,11 +34,14 @@ impl SurfaceResources {
     pub fn new(
         device: &Device,
         sampler: &Sampler,
-        surface: Surface<'static>,
-        config: &SurfaceConfiguration,
+        surface: Option<Surface<'static>>,
+        config: SurfaceConfiguration,
         universal_bind_group_layout: &BindGroupLayout,
+        sample_count: u32,
     ) -> Self {
-        surface.configure(device, &config);
+        if let Some(surface) = &surface {
+            surface.configure(device, &config);
+        }
         let offscreen_texture = create_texture(
             device,
             config.width,
@@ -30,117 +50,384 @@ impl SurfaceResources {
             1,
             "Offscreen Texture",
         );
-        let multisampled_texture = create_texture(
-            device,
-            config.width,
-            config.height,
-            config.format,
-            4,
-            "Output Texture",
-        );
+        let multisampled_texture = (sample_count > 1).then(|| {
+            create_texture(
+                device,
+                config.width,
+                config.height,
+                config.format,
+                sample_count,
+                "Output Texture",
+            )
+        });
+
+        let backdrop_texture =
+            create_backdrop_texture(device, config.width, config.height, config.format);
+
+        let depth_texture = device.create_texture(&TextureDescriptor {
+            size: Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            label: Some("Depth Texture"),
+            view_formats: &[],
+        });
 
         let universal_bind_group = create_bind_group(
             device,
             &offscreen_texture,
+            &backdrop_texture,
             sampler,
             universal_bind_group_layout,
         );
 
         Self {
             surface,
+            config,
             offscreen_texture,
             multisampled_texture,
+            backdrop_texture,
+            depth_texture,
             universal_bind_group,
         }
     }
 
-    fn acquire(&self) -> Result<SurfaceTexture, SurfaceError> {
-        match self.surface.get_current_texture() {
+    // None for headless windows, which have no frames to present
+    fn acquire(&self) -> Option<Result<SurfaceTexture, SurfaceError>> {
+        let surface = self.surface.as_ref()?;
+        Some(match surface.get_current_texture() {
             Ok(frame) => Ok(frame),
             // If we timed out, just try again
-            Err(SurfaceError::Timeout) => Ok(self
-                .surface
+            Err(SurfaceError::Timeout) => Ok(surface
                 .get_current_texture()
                 .expect("Failed to acquire next surface texture")),
             Err(e) => Err(e),
+        })
+    }
+}
+
+// Native window a surface is rendered into
+pub enum SurfaceSource {
+    Window(Arc<Window>),
+    // Window owned by a host outside of winit. No resize events arrive for it, so the host
+    // reports its size instead
+    RawHandle {
+        display: RawDisplayHandle,
+        window: RawWindowHandle,
+        size: PhysicalSize<u32>,
+    },
+    // No window at all. Frames are only rendered into images, so the offscreen textures are
+    // created without a surface to present them
+    Headless {
+        size: PhysicalSize<u32>,
+    },
+}
+
+// Raw handles are only read to create surfaces, which happens on the thread driving the
+// renderer. Threads encoding drawables share the resources but never touch the handles
+unsafe impl Sync for SurfaceSource {}
+
+impl SurfaceSource {
+    fn size(&self) -> PhysicalSize<u32> {
+        match self {
+            Self::Window(window) => window.inner_size(),
+            Self::RawHandle { size, .. } | Self::Headless { size } => *size,
         }
     }
+
+    fn create_surface(&self, instance: &Instance) -> Option<Surface<'static>> {
+        Some(match self {
+            Self::Window(window) => instance.create_surface(window.clone()).unwrap(),
+            // Hosts creating a renderer from raw handles promise to keep the window alive for
+            // as long as the renderer
+            Self::RawHandle {
+                display, window, ..
+            } => unsafe {
+                instance.create_surface_unsafe(SurfaceTargetUnsafe::RawHandle {
+                    raw_display_handle: *display,
+                    raw_window_handle: *window,
+                })
+            }
+            .unwrap(),
+            Self::Headless { .. } => return None,
+        })
+    }
 }
 
-// Wrapper for the wgpu surface and configuration taken from the wgpu example code
+// Wrapper for the wgpu surfaces of each window and their configuration taken from the wgpu
+// example code. Every window shares the device, atlases, and pipelines, so surfaces are all
+// created with the same format. Accessors refer to the current window's surface, which is
+// switched with `set_current` before rendering.
 pub struct SurfaceResourcesManager {
-    surface_resources: Option<SurfaceResources>,
-    config: Option<SurfaceConfiguration>,
+    windows: HashMap<WindowId, SurfaceSource>,
+    surfaces: HashMap<WindowId, SurfaceResources>,
+    current: Option<WindowId>,
+    // Surfaces can only be created once the event loop has started
+    started: bool,
+    // Let the window show through transparent parts of the frame. Only takes effect when the
+    // surface is created, and the window itself must also be created as transparent
+    transparent: bool,
+    depth_testing: bool,
+    // Set between suspend and resume. Surfaces are dropped while suspended, since Android
+    // destroys the native windows behind them
+    suspended: bool,
+    // Format and view formats of the last surface created. Surfaces recreated after a suspend
+    // keep them when they can, so textures and pipelines made for the old surfaces still fit
+    formats: Option<(TextureFormat, Vec<TextureFormat>)>,
+    // Samples per pixel of the output and depth textures. Every pipeline drawing into the
+    // surface must be built with the same count
+    sample_count: u32,
 }
 
 impl SurfaceResourcesManager {
     pub fn new() -> Self {
         Self {
-            surface_resources: None,
-            config: None,
+            windows: HashMap::new(),
+            surfaces: HashMap::new(),
+            current: None,
+            started: false,
+            transparent: false,
+            depth_testing: false,
+            suspended: false,
+            formats: None,
+            sample_count: MULTISAMPLE_COUNT,
+        }
+    }
+
+    pub fn set_transparent(&mut self, transparent: bool) {
+        self.transparent = transparent;
+    }
+
+    // Only takes effect for pipelines created afterwards
+    pub fn set_depth_testing(&mut self, depth_testing: bool) {
+        self.depth_testing = depth_testing;
+    }
+
+    // Depth state every pipeline drawing into the surface must use. Later items at the same
+    // depth pass so equal depths keep painter's order. Fragments outside the layer's clip
+    // paths fail the stencil test against the pass's stencil reference
+    pub fn depth_stencil_state(&self) -> DepthStencilState {
+        let clipped = StencilFaceState {
+            compare: CompareFunction::Equal,
+            fail_op: StencilOperation::Keep,
+            depth_fail_op: StencilOperation::Keep,
+            pass_op: StencilOperation::Keep,
+        };
+        DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: self.depth_testing,
+            depth_compare: if self.depth_testing {
+                CompareFunction::GreaterEqual
+            } else {
+                CompareFunction::Always
+            },
+            stencil: StencilState {
+                front: clipped,
+                back: clipped,
+                read_mask: !0,
+                write_mask: 0,
+            },
+            bias: DepthBiasState::default(),
+        }
+    }
+
+    pub fn depth_format(&self) -> TextureFormat {
+        DEPTH_FORMAT
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    // Recreates every surface's output and depth textures with the new sample count. Returns
+    // true if any surface changed, in which case pipelines have to be rebuilt to match
+    pub fn set_sample_count(
+        &mut self,
+        sample_count: u32,
+        device: &Device,
+        sampler: &Sampler,
+        universal_bind_group_layout: &BindGroupLayout,
+    ) -> bool {
+        if sample_count == self.sample_count {
+            return false;
+        }
+        self.sample_count = sample_count;
+
+        let _span =
+            tracing::info_span!("surface_reconfigure", reason = "sample count changed").entered();
+        tracing::info!(sample_count, "Recreating surface resources");
+        let window_ids: Vec<WindowId> = self.surfaces.keys().copied().collect();
+        for window_id in window_ids.iter() {
+            let SurfaceResources {
+                surface, config, ..
+            } = self.surfaces.remove(window_id).unwrap();
+            self.surfaces.insert(
+                *window_id,
+                SurfaceResources::new(
+                    device,
+                    sampler,
+                    surface,
+                    config,
+                    universal_bind_group_layout,
+                    sample_count,
+                ),
+            );
+        }
+        !window_ids.is_empty()
+    }
+
+    // Registers a window to render into. Its surface is created right away if the event loop
+    // is running and otherwise once it starts. Returns true if a surface was created.
+    pub fn add_window(
+        &mut self,
+        window_id: WindowId,
+        window: SurfaceSource,
+        instance: &Instance,
+        adapter: &Adapter,
+        device: &Device,
+        sampler: &Sampler,
+        universal_bind_group_layout: &BindGroupLayout,
+        srgb: bool,
+    ) -> bool {
+        self.windows.insert(window_id, window);
+        // Windows added while suspended get their surface on resume
+        if self.started && !self.suspended {
+            let _span =
+                tracing::info_span!("surface_reconfigure", reason = "window added").entered();
+            self.create_surface(
+                window_id,
+                instance,
+                adapter,
+                device,
+                sampler,
+                universal_bind_group_layout,
+                srgb,
+            );
         }
+        self.started
+    }
+
+    // Asks winit to send a redraw event to every window. Windows from raw handles are redrawn
+    // by their host
+    pub fn request_redraw(&self) {
+        for window in self.windows.values() {
+            if let SurfaceSource::Window(window) = window {
+                window.request_redraw();
+            }
+        }
+    }
+
+    pub fn remove_window(&mut self, window_id: WindowId) {
+        self.windows.remove(&window_id);
+        self.surfaces.remove(&window_id);
+        if self.current == Some(window_id) {
+            self.current = self.surfaces.keys().next().copied();
+        }
+    }
+
+    // Makes the window's surface the one rendered into and returned by the accessors. Returns
+    // false if the window doesn't have a surface yet
+    pub fn set_current(&mut self, window_id: WindowId) -> bool {
+        let exists = self.surfaces.contains_key(&window_id);
+        if exists {
+            self.current = Some(window_id);
+        }
+        exists
+    }
+
+    // None while suspended or before the first surface is created
+    fn current(&self) -> Option<&SurfaceResources> {
+        self.surfaces.get(&self.current?)
     }
 
+    // Panics without a current surface. Only called while drawing a frame, after `set_current`
+    // found one
+    fn frame_surface(&self) -> &SurfaceResources {
+        self.current()
+            .expect("Surface resources used without a current surface")
+    }
+
+    // Next frame of the current surface. None for headless windows
     pub fn surface_texture(
         &mut self,
         device: &Device,
         sampler: &Sampler,
         universal_bind_group_layout: &BindGroupLayout,
-    ) -> SurfaceTexture {
-        match self.surface_resources.as_ref().unwrap().acquire() {
+    ) -> Option<SurfaceTexture> {
+        let window_id = self.current.unwrap();
+        Some(match self.surfaces[&window_id].acquire()? {
             Ok(frame) => frame,
-            Err(SurfaceError::Outdated | SurfaceError::Lost | SurfaceError::OutOfMemory) => {
-                let surface = self.surface_resources.take().unwrap().surface;
-                let config = self.config.as_ref().unwrap();
-                self.surface_resources = Some(SurfaceResources::new(
+            Err(
+                error @ (SurfaceError::Outdated | SurfaceError::Lost | SurfaceError::OutOfMemory),
+            ) => {
+                let _span =
+                    tracing::info_span!("surface_reconfigure", reason = "acquire failed").entered();
+                tracing::info!(?window_id, ?error, "Recreating surface resources");
+                let SurfaceResources {
+                    surface, config, ..
+                } = self.surfaces.remove(&window_id).unwrap();
+                let surface_resources = SurfaceResources::new(
                     device,
                     sampler,
                     surface,
                     config,
                     universal_bind_group_layout,
-                ));
-                self.surface_resources
-                    .as_ref()
-                    .unwrap()
+                    self.sample_count,
+                );
+                let frame = surface_resources
                     .acquire()
-                    .expect("Could not acquire next surface texture after reconfiguring")
+                    .and_then(Result::ok)
+                    .expect("Could not acquire next surface texture after reconfiguring");
+                self.surfaces.insert(window_id, surface_resources);
+                frame
             }
             Err(e) => panic!("Unexpected surface error: {:?}", e),
-        }
+        })
     }
 
-    pub fn offscreen_texture(&self) -> &Texture {
-        &self.surface_resources.as_ref().unwrap().offscreen_texture
+    // Sized like the current surface. None while there is no surface to draw into
+    pub fn offscreen_texture(&self) -> Option<&Texture> {
+        self.current().map(|surface| &surface.offscreen_texture)
     }
 
-    pub fn multisampled_texture(&self) -> &Texture {
-        &self
-            .surface_resources
-            .as_ref()
-            .unwrap()
-            .multisampled_texture
+    pub fn multisampled_texture(&self) -> Option<&Texture> {
+        self.frame_surface().multisampled_texture.as_ref()
+    }
+
+    pub fn backdrop_texture(&self) -> &Texture {
+        &self.frame_surface().backdrop_texture
+    }
+
+    pub fn depth_texture(&self) -> &Texture {
+        &self.frame_surface().depth_texture
     }
 
     pub fn universal_bind_group(&self) -> &BindGroup {
-        &self
-            .surface_resources
-            .as_ref()
-            .unwrap()
-            .universal_bind_group
+        &self.frame_surface().universal_bind_group
     }
 
-    pub fn format(&self) -> TextureFormat {
-        self.config.as_ref().unwrap().format
+    // Format every surface is created with. Kept while suspended so pipelines built for the
+    // old surfaces can still be rebuilt, and None before the first surface is created
+    pub fn format(&self) -> Option<TextureFormat> {
+        match self.current() {
+            Some(surface) => Some(surface.config.format),
+            None => self.formats.as_ref().map(|(format, _)| *format),
+        }
     }
 
     pub fn ready(&self) -> bool {
-        self.surface_resources.is_some() && self.config.is_some()
+        self.current().is_some()
     }
 
     pub fn handle_event(
         &mut self,
         event: &Event<()>,
-        window: Arc<Window>,
         instance: &Instance,
         adapter: &Adapter,
         device: &Device,
@@ -149,77 +436,324 @@ impl SurfaceResourcesManager {
         srgb: bool,
     ) -> bool {
         match event {
-            Event::NewEvents(StartCause::Init) | Event::Resumed => {
-                // Window size is only actually valid after we enter the event loop.
-                let window_size = window.inner_size();
-                let width = window_size.width.max(1);
-                let height = window_size.height.max(1);
-
-                let surface = instance.create_surface(window).unwrap();
-
-                // Get the default configuration,
-                let mut config = surface
-                    .get_default_config(adapter, width, height)
-                    .expect("Surface isn't supported by the adapter.");
-
-                config.usage = TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC;
-
-                //                 let surface_config = SurfaceConfiguration {
-                //                     usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
-                //                     format: swapchain_format,
-                //                     width,
-                //                     height,
-                //                     present_mode: PresentMode::Fifo,
-                //                     alpha_mode: swapchain_capabilities.alpha_modes[0],
-                //                     view_formats: vec![],
-                //                     desired_maximum_frame_latency: 2,
-                //                 };
-                if srgb {
-                    // Not all platforms (WebGPU) support sRGB swapchains, so we need to use view formats
-                    let view_format = config.format.add_srgb_suffix();
-                    config.view_formats.push(view_format);
-                } else {
-                    // All platforms support non-sRGB swapchains, so we can just use the format directly.
-                    let format = config.format.remove_srgb_suffix();
-                    config.format = format;
-                    config.view_formats.push(format);
-                };
-
-                self.surface_resources = Some(SurfaceResources::new(
+            Event::NewEvents(StartCause::Init) if !self.started => {
+                let _span =
+                    tracing::info_span!("surface_reconfigure", reason = "event loop started")
+                        .entered();
+                self.start(
+                    instance,
+                    adapter,
+                    device,
+                    sampler,
+                    universal_bind_group_layout,
+                    srgb,
+                )
+            }
+            // Desktop platforms resume once right after starting, which would otherwise rebuild
+            // every surface and pipeline before the first frame. Android windows only have a
+            // surface once resumed, so they're always recreated there
+            Event::Resumed if !self.started || self.suspended || cfg!(target_os = "android") => {
+                let _span =
+                    tracing::info_span!("surface_reconfigure", reason = "resumed").entered();
+                self.start(
+                    instance,
+                    adapter,
                     device,
                     sampler,
-                    surface,
-                    &config,
                     universal_bind_group_layout,
-                ));
-                self.config = Some(config);
-                true
+                    srgb,
+                )
+            }
+            // Surfaces have to be released before Android destroys their windows. Drawables
+            // keep their atlases and buffers, so resuming only needs new surfaces
+            Event::Suspended => {
+                self.suspend();
+                false
             }
             Event::WindowEvent {
                 event: WindowEvent::Resized(new_size),
-                ..
+                window_id,
+            } => self.resize(
+                *window_id,
+                *new_size,
+                device,
+                sampler,
+                universal_bind_group_layout,
+            ),
+            Event::WindowEvent {
+                event: WindowEvent::Destroyed,
+                window_id,
             } => {
-                let config = self.config.as_mut().unwrap();
-                config.width = new_size.width.max(1);
-                config.height = new_size.height.max(1);
+                self.remove_window(*window_id);
+                false
+            }
+            _ => false,
+        }
+    }
 
-                let surface = self.surface_resources.take().unwrap().surface;
+    // Reconfigures the window's surface for its new size. Returns true if the window had a
+    // surface to resize
+    pub fn resize(
+        &mut self,
+        window_id: WindowId,
+        new_size: PhysicalSize<u32>,
+        device: &Device,
+        sampler: &Sampler,
+        universal_bind_group_layout: &BindGroupLayout,
+    ) -> bool {
+        if let Some(SurfaceSource::RawHandle { size, .. } | SurfaceSource::Headless { size }) =
+            self.windows.get_mut(&window_id)
+        {
+            *size = new_size;
+        }
+        let Some(SurfaceResources {
+            surface,
+            mut config,
+            ..
+        }) = self.surfaces.remove(&window_id)
+        else {
+            return false;
+        };
+        let _span = tracing::info_span!("surface_reconfigure", reason = "resized").entered();
+        tracing::info!(
+            ?window_id,
+            old_width = config.width,
+            old_height = config.height,
+            width = new_size.width,
+            height = new_size.height,
+            "Resizing surface"
+        );
+        config.width = new_size.width.max(1);
+        config.height = new_size.height.max(1);
 
-                self.surface_resources = Some(SurfaceResources::new(
-                    device,
-                    sampler,
-                    surface,
-                    config,
-                    universal_bind_group_layout,
-                ));
+        self.surfaces.insert(
+            window_id,
+            SurfaceResources::new(
+                device,
+                sampler,
+                surface,
+                config,
+                universal_bind_group_layout,
+                self.sample_count,
+            ),
+        );
+
+        true
+    }
+
+    // Creates surfaces for every window, replacing any which already exist. Usually called when
+    // the event loop starts, but may be called earlier on platforms where windows have a
+    // surface as soon as they're created. Returns true if any surface was created
+    pub fn start(
+        &mut self,
+        instance: &Instance,
+        adapter: &Adapter,
+        device: &Device,
+        sampler: &Sampler,
+        universal_bind_group_layout: &BindGroupLayout,
+        srgb: bool,
+    ) -> bool {
+        let window_ids = self.restart();
+        for window_id in window_ids.iter() {
+            self.create_surface(
+                *window_id,
+                instance,
+                adapter,
+                device,
+                sampler,
+                universal_bind_group_layout,
+                srgb,
+            );
+        }
+        !window_ids.is_empty()
+    }
 
-                true
+    // Drops every surface until the next resume. Nothing is current in the meantime, so frames
+    // are skipped and the accessors return None
+    fn suspend(&mut self) {
+        tracing::info!("Dropping surfaces while suspended");
+        self.suspended = true;
+        self.surfaces.clear();
+        self.current = None;
+    }
+
+    // Forgets the existing surfaces and returns the windows which need new ones. Surfaces are
+    // recreated on resume since they may have been lost while suspended
+    fn restart(&mut self) -> Vec<WindowId> {
+        self.started = true;
+        self.suspended = false;
+        self.surfaces.clear();
+        self.current = None;
+        self.windows.keys().copied().collect()
+    }
+
+    fn create_surface(
+        &mut self,
+        window_id: WindowId,
+        instance: &Instance,
+        adapter: &Adapter,
+        device: &Device,
+        sampler: &Sampler,
+        universal_bind_group_layout: &BindGroupLayout,
+        srgb: bool,
+    ) {
+        let window = &self.windows[&window_id];
+        // Window size is only actually valid after we enter the event loop.
+        let window_size = window.size();
+        let width = window_size.width.max(1);
+        let height = window_size.height.max(1);
+
+        let Some(surface) = window.create_surface(instance) else {
+            self.create_headless(
+                window_id,
+                width,
+                height,
+                device,
+                sampler,
+                universal_bind_group_layout,
+            );
+            return;
+        };
+
+        // Get the default configuration,
+        let mut config = surface
+            .get_default_config(adapter, width, height)
+            .expect("Surface isn't supported by the adapter.");
+
+        config.usage = TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC;
+        let capabilities = surface.get_capabilities(adapter);
+        if self.transparent {
+            match transparent_alpha_mode(&capabilities.alpha_modes) {
+                Some(alpha_mode) => config.alpha_mode = alpha_mode,
+                None => tracing::warn!(
+                    ?window_id,
+                    supported_alpha_modes = ?capabilities.alpha_modes,
+                    "Surface doesn't support transparency"
+                ),
             }
-            _ => false,
+        }
+
+        let default_format = config.format;
+        let format_reason = if let Some(existing) = self.surfaces.values().next() {
+            // Pipelines are shared between windows, so match the format of existing surfaces
+            config.format = existing.config.format;
+            config.view_formats = existing.config.view_formats.clone();
+            "matched existing surface"
+        } else if let Some((format, view_formats)) = self
+            .formats
+            .as_ref()
+            .filter(|(format, _)| capabilities.formats.contains(format))
+        {
+            config.format = *format;
+            config.view_formats = view_formats.clone();
+            "matched surface from before suspend"
+        } else if srgb {
+            // Not all platforms (WebGPU) support sRGB swapchains, so we need to use view formats
+            let view_format = config.format.add_srgb_suffix();
+            config.view_formats.push(view_format);
+            "srgb view format"
+        } else {
+            // All platforms support non-sRGB swapchains, so we can just use the format directly.
+            let format = config.format.remove_srgb_suffix();
+            config.format = format;
+            config.view_formats.push(format);
+            "linear format"
+        };
+
+        tracing::info!(
+            ?window_id,
+            format = ?config.format,
+            view_formats = ?config.view_formats,
+            default_format = ?default_format,
+            format_reason,
+            supported_formats = ?capabilities.formats,
+            present_mode = ?config.present_mode,
+            alpha_mode = ?config.alpha_mode,
+            transparent = self.transparent,
+            width = config.width,
+            height = config.height,
+            "Configuring surface"
+        );
+        self.formats = Some((config.format, config.view_formats.clone()));
+
+        self.surfaces.insert(
+            window_id,
+            SurfaceResources::new(
+                device,
+                sampler,
+                Some(surface),
+                config,
+                universal_bind_group_layout,
+                self.sample_count,
+            ),
+        );
+        if self.current.is_none() {
+            self.current = Some(window_id);
+        }
+    }
+
+    // Headless windows have no surface whose formats could be checked, so they use the format
+    // of the other surfaces or plain rgba when there are none
+    fn create_headless(
+        &mut self,
+        window_id: WindowId,
+        width: u32,
+        height: u32,
+        device: &Device,
+        sampler: &Sampler,
+        universal_bind_group_layout: &BindGroupLayout,
+    ) {
+        let (format, view_formats) = self
+            .formats
+            .clone()
+            .unwrap_or((TextureFormat::Rgba8Unorm, vec![TextureFormat::Rgba8Unorm]));
+        tracing::info!(
+            ?window_id,
+            ?format,
+            width,
+            height,
+            "Configuring headless window"
+        );
+        self.formats = Some((format, view_formats.clone()));
+        let config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            format,
+            width,
+            height,
+            present_mode: PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: CompositeAlphaMode::Opaque,
+            view_formats,
+        };
+
+        self.surfaces.insert(
+            window_id,
+            SurfaceResources::new(
+                device,
+                sampler,
+                None,
+                config,
+                universal_bind_group_layout,
+                self.sample_count,
+            ),
+        );
+        if self.current.is_none() {
+            self.current = Some(window_id);
         }
     }
 }
 
+// Every primitive outputs premultiplied color, so the compositor has to treat it that way.
+// Inherit leaves it to the window, which winit configures for transparent windows.
+fn transparent_alpha_mode(alpha_modes: &[CompositeAlphaMode]) -> Option<CompositeAlphaMode> {
+    [
+        CompositeAlphaMode::PreMultiplied,
+        CompositeAlphaMode::Inherit,
+    ]
+    .into_iter()
+    .find(|alpha_mode| alpha_modes.contains(alpha_mode))
+}
+
 fn create_texture(
     device: &Device,
     width: u32,
@@ -238,21 +772,69 @@ fn create_texture(
         sample_count: samples,
         dimension: TextureDimension::D2,
         format,
+        // Backdrops are read back from the single sampled offscreen texture to pick text colors
         usage: TextureUsages::TEXTURE_BINDING
             | TextureUsages::COPY_DST
-            | TextureUsages::RENDER_ATTACHMENT,
+            | TextureUsages::RENDER_ATTACHMENT
+            | if samples == 1 {
+                TextureUsages::COPY_SRC
+            } else {
+                TextureUsages::empty()
+            },
         label: Some(label),
         view_formats: &[],
     })
 }
 
+pub(crate) fn create_backdrop_texture(
+    device: &Device,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+) -> Texture {
+    let width = (width / 2).max(1);
+    let height = (height / 2).max(1);
+    let mip_level_count = (32 - width.max(height).leading_zeros()).min(BACKDROP_LEVELS);
+
+    device.create_texture(&TextureDescriptor {
+        size: Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        // Copies move half rate blurs in and out of their caches
+        usage: TextureUsages::TEXTURE_BINDING
+            | TextureUsages::RENDER_ATTACHMENT
+            | TextureUsages::COPY_SRC
+            | TextureUsages::COPY_DST,
+        label: Some("Backdrop Texture"),
+        view_formats: &[],
+    })
+}
+
 fn create_bind_group(
     device: &Device,
     offscreen_texture: &Texture,
+    backdrop_texture: &Texture,
     sampler: &Sampler,
     universal_bind_group_layout: &BindGroupLayout,
 ) -> BindGroup {
     let offscreen_texture_view = offscreen_texture.create_view(&TextureViewDescriptor::default());
+    let backdrop_texture_view = backdrop_texture.create_view(&TextureViewDescriptor::default());
+    // Blurred backdrops are sampled between texels, so they need a filtering sampler
+    let linear_sampler = device.create_sampler(&SamplerDescriptor {
+        address_mode_u: AddressMode::ClampToEdge,
+        address_mode_v: AddressMode::ClampToEdge,
+        address_mode_w: AddressMode::ClampToEdge,
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        mipmap_filter: FilterMode::Nearest,
+        ..Default::default()
+    });
 
     device.create_bind_group(&BindGroupDescriptor {
         label: Some("Universal bind group"),
@@ -266,6 +848,51 @@ fn create_bind_group(
                 binding: 1,
                 resource: BindingResource::Sampler(sampler),
             },
+            BindGroupEntry {
+                binding: 2,
+                resource: BindingResource::TextureView(&backdrop_texture_view),
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: BindingResource::Sampler(&linear_sampler),
+            },
         ],
     })
 }
+
+#[cfg(test)]
+mod test {
+    use winit::raw_window_handle::{XlibDisplayHandle, XlibWindowHandle};
+
+    use super::*;
+
+    #[test]
+    fn test_suspend_and_resume() {
+        let mut manager = SurfaceResourcesManager::new();
+        // Never turned into a surface, so the handles don't need to be real
+        let window_id = WindowId::from(1);
+        manager.windows.insert(
+            window_id,
+            SurfaceSource::RawHandle {
+                display: RawDisplayHandle::Xlib(XlibDisplayHandle::new(None, 0)),
+                window: RawWindowHandle::Xlib(XlibWindowHandle::new(1)),
+                size: PhysicalSize::new(64, 64),
+            },
+        );
+        assert_eq!(manager.restart(), vec![window_id]);
+        // As if the window's surface had been created and drawn into
+        manager.current = Some(window_id);
+        manager.formats = Some((TextureFormat::Bgra8Unorm, Vec::new()));
+
+        manager.suspend();
+        assert!(!manager.ready());
+        assert!(!manager.set_current(window_id));
+        assert!(manager.offscreen_texture().is_none());
+        assert_eq!(manager.format(), Some(TextureFormat::Bgra8Unorm));
+
+        // Windows get new surfaces on resume, which become current as they're created
+        assert_eq!(manager.restart(), vec![window_id]);
+        assert!(!manager.suspended);
+        assert!(!manager.ready());
+    }
+}
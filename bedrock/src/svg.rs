@@ -0,0 +1,160 @@
+use std::fmt;
+
+use glam::{vec2, vec4, Vec2, Vec4};
+use usvg::{
+    tiny_skia_path::{self, PathSegment},
+    Node, Paint, Transform, TreeParsing,
+};
+
+use crate::scene::{FillRule, Layer, Path};
+
+#[derive(Debug)]
+pub struct SvgError(usvg::Error);
+
+impl fmt::Display for SvgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "could not parse svg: {}", self.0)
+    }
+}
+
+impl std::error::Error for SvgError {}
+
+impl Layer {
+    // Parses the svg into a layer of paths with no background. The document's user units map
+    // to pixels with its top left corner at the origin.
+    pub fn from_svg(data: &[u8]) -> Result<Self, SvgError> {
+        let mut layer = Layer {
+            background_color: None,
+            ..Default::default()
+        };
+        layer.add_svg(data, Vec2::ZERO, 1.0)?;
+        Ok(layer)
+    }
+
+    // Appends the svg's shapes to the layer's paths, placing the document's top left corner
+    // at `top_left` and scaling it by `scale`. Images and text within the svg are skipped and
+    // gradients are drawn with the color of their first stop.
+    pub fn add_svg(&mut self, data: &[u8], top_left: Vec2, scale: f32) -> Result<(), SvgError> {
+        let tree = usvg::Tree::from_data(data, &usvg::Options::default()).map_err(SvgError)?;
+        let transform = Transform::from_row(scale, 0.0, 0.0, scale, top_left.x, top_left.y);
+        add_group(self, &tree.root, transform, 1.0);
+        Ok(())
+    }
+}
+
+fn add_group(layer: &mut Layer, group: &usvg::Group, parent_transform: Transform, opacity: f32) {
+    let transform = parent_transform.pre_concat(group.transform);
+    let opacity = opacity * group.opacity.get();
+
+    for child in group.children.iter() {
+        match child {
+            Node::Group(group) => add_group(layer, group, transform, opacity),
+            Node::Path(path) => {
+                if path.visibility != usvg::Visibility::Visible {
+                    continue;
+                }
+                if let Some(path) = convert_path(path, transform, opacity) {
+                    layer.add_path(path);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn convert_path(svg_path: &usvg::Path, transform: Transform, opacity: f32) -> Option<Path> {
+    let fill = svg_path
+        .fill
+        .as_ref()
+        .and_then(|fill| paint_color(&fill.paint, fill.opacity.get() * opacity));
+    // Strokes are scaled by the transform. Non uniform scales use the average
+    let stroke = svg_path.stroke.as_ref().and_then(|stroke| {
+        let scale = (transform.sx * transform.sy - transform.kx * transform.ky)
+            .abs()
+            .sqrt();
+        paint_color(&stroke.paint, stroke.opacity.get() * opacity)
+            .map(|color| (stroke.width.get() * scale, color))
+    });
+    if fill.is_none() && stroke.is_none() {
+        return None;
+    }
+
+    let map = |point: tiny_skia_path::Point| {
+        let mut point = point;
+        transform.map_point(&mut point);
+        vec2(point.x, point.y)
+    };
+
+    // Open paths leave every subpath unclosed, so when any subpath lacks a close command the
+    // closed ones get an explicit edge back to their start instead
+    let mut open = false;
+    let mut closed = true;
+    for segment in svg_path.data.segments() {
+        match segment {
+            PathSegment::MoveTo(_) => {
+                open |= !closed;
+                closed = false;
+            }
+            PathSegment::Close => closed = true,
+            _ => {}
+        }
+    }
+    open |= !closed;
+
+    let mut path: Option<Path> = None;
+    let mut subpath_start = Vec2::ZERO;
+    for segment in svg_path.data.segments() {
+        if let PathSegment::MoveTo(start) = segment {
+            subpath_start = map(start);
+        }
+        path = Some(match (path, segment) {
+            (None, PathSegment::MoveTo(_)) => Path::new(subpath_start),
+            // Paths always start with a move, but be defensive about malformed data
+            (None, _) => return None,
+            (Some(path), PathSegment::MoveTo(_)) => path.move_to(subpath_start),
+            (Some(path), PathSegment::LineTo(to)) => path.line_to(map(to)),
+            (Some(path), PathSegment::QuadTo(control, to)) => {
+                path.quadratic_bezier_to(map(control), map(to))
+            }
+            (Some(path), PathSegment::CubicTo(control1, control2, to)) => {
+                path.cubic_bezier_to(map(control1), map(control2), map(to))
+            }
+            (Some(path), PathSegment::Close) if open => path.line_to(subpath_start),
+            // Subpaths of closed paths are closed when tessellated
+            (Some(path), PathSegment::Close) => path,
+        });
+    }
+
+    let fill_rule = match svg_path.fill.as_ref().map(|fill| fill.rule) {
+        Some(usvg::FillRule::EvenOdd) => FillRule::EvenOdd,
+        // Svg fills default to nonzero
+        _ => FillRule::NonZero,
+    };
+
+    let mut path = path?.with_open(open).with_fill_rule(fill_rule);
+    path.fill = fill;
+    path.stroke = stroke;
+    Some(path)
+}
+
+fn paint_color(paint: &Paint, opacity: f32) -> Option<Vec4> {
+    let (color, stop_opacity) = match paint {
+        Paint::Color(color) => (*color, 1.0),
+        Paint::LinearGradient(gradient) => {
+            let stop = gradient.stops.first()?;
+            (stop.color, stop.opacity.get())
+        }
+        Paint::RadialGradient(gradient) => {
+            let stop = gradient.stops.first()?;
+            (stop.color, stop.opacity.get())
+        }
+        Paint::Pattern(_) => return None,
+    };
+
+    Some(vec4(
+        color.red as f32 / 255.0,
+        color.green as f32 / 255.0,
+        color.blue as f32 / 255.0,
+        opacity * stop_opacity,
+    ))
+}
@@ -0,0 +1,258 @@
+use std::time::{Duration, Instant};
+
+use glam::{vec4, Vec2, Vec4};
+use wgpu::*;
+
+use crate::easing::Easing;
+
+// How far the old scene grows over the course of a zoom transition
+const ZOOM_SCALE: f32 = 1.25;
+
+// How the old scene leaves the screen. The new scene is always drawn in place underneath it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransitionKind {
+    // Fade the old scene out
+    CrossFade,
+    // Move the old scene off screen in the given direction. The direction is measured in
+    // surface sizes, so (1, 0) slides it out the right edge
+    Slide(Vec2),
+    // Grow the old scene around the center of the screen while fading it out
+    Zoom,
+}
+
+impl TransitionKind {
+    // Rect in surface fractions and opacity of the old scene at the given eased progress
+    fn placement(self, progress: f32) -> (Vec4, f32) {
+        match self {
+            TransitionKind::CrossFade => (vec4(0.0, 0.0, 1.0, 1.0), 1.0 - progress),
+            TransitionKind::Slide(direction) => {
+                let offset = direction * progress;
+                (vec4(offset.x, offset.y, 1.0, 1.0), 1.0)
+            }
+            TransitionKind::Zoom => {
+                let scale = 1.0 + (ZOOM_SCALE - 1.0) * progress;
+                let top_left = (1.0 - scale) / 2.0;
+                (vec4(top_left, top_left, scale, scale), 1.0 - progress)
+            }
+        }
+    }
+}
+
+// A snapshot of the previous scene along with the state needed to composite it over each new
+// frame until the transition finishes
+pub(crate) struct ActiveTransition {
+    snapshot: Texture,
+    bind_group: BindGroup,
+    pipeline: RenderPipeline,
+    start: Instant,
+    duration: Duration,
+    easing: Easing,
+    kind: TransitionKind,
+}
+
+impl ActiveTransition {
+    // Texture the previous scene should be rendered into before starting the transition
+    pub(crate) fn create_snapshot(device: &Device, format: TextureFormat, size: Vec2) -> Texture {
+        device.create_texture(&TextureDescriptor {
+            size: Extent3d {
+                width: size.x as u32,
+                height: size.y as u32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC
+                | TextureUsages::RENDER_ATTACHMENT,
+            label: Some("Transition Snapshot"),
+            view_formats: &[],
+        })
+    }
+
+    pub(crate) fn new(
+        device: &Device,
+        snapshot: Texture,
+        duration: Duration,
+        easing: Easing,
+        kind: TransitionKind,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Transition bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        // Zoomed snapshots are magnified, so filter them
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let view = snapshot.create_view(&Default::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Transition bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Transition shader"),
+            source: ShaderSource::Wgsl(include_str!("transition.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Transition Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[PushConstantRange {
+                stages: ShaderStages::VERTEX_FRAGMENT,
+                range: 0..std::mem::size_of::<[f32; 8]>() as u32,
+            }],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Transition Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "vertex",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: "fragment",
+                targets: &[Some(ColorTargetState {
+                    format: snapshot.format(),
+                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            snapshot,
+            bind_group,
+            pipeline,
+            start: Instant::now(),
+            duration,
+            easing,
+            kind,
+        }
+    }
+
+    pub(crate) fn finished(&self) -> bool {
+        self.start.elapsed() >= self.duration
+    }
+
+    // Composites the snapshot over the target at the transition's current progress
+    pub(crate) fn draw(&self, device: &Device, queue: &Queue, target: &Texture) {
+        // Targets from before a resize no longer line up with the snapshot, so skip to the end
+        if target.size() != self.snapshot.size() {
+            return;
+        }
+
+        let linear = if self.duration.is_zero() {
+            1.0
+        } else {
+            self.start.elapsed().as_secs_f32() / self.duration.as_secs_f32()
+        };
+        let (rect, opacity) = self.kind.placement(self.easing.apply(linear));
+        let constants = [rect.x, rect.y, rect.z, rect.w, opacity, 0.0, 0.0, 0.0];
+
+        let view = target.create_view(&Default::default());
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Transition Encoder"),
+        });
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Transition Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_push_constants(
+            ShaderStages::VERTEX_FRAGMENT,
+            0,
+            bytemuck::cast_slice(&constants),
+        );
+        render_pass.draw(0..4, 0..1);
+        drop(render_pass);
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+
+    #[test]
+    fn test_easing_endpoints() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+            assert_eq!(easing.apply(2.0), 1.0);
+        }
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+    }
+
+    #[test]
+    fn test_zoom_stays_centered() {
+        let (rect, opacity) = TransitionKind::Zoom.placement(1.0);
+        assert_eq!(rect.x + rect.z / 2.0, 0.5);
+        assert_eq!(rect.z, ZOOM_SCALE);
+        assert_eq!(opacity, 0.0);
+        assert_eq!(
+            TransitionKind::Slide(vec2(-1.0, 0.0)).placement(0.5).0,
+            vec4(-0.5, 0.0, 1.0, 1.0)
+        );
+    }
+}
@@ -0,0 +1,36 @@
+// Composites a snapshot of the previous scene over the current frame while a transition is
+// running. The snapshot is drawn as a single quad placed by the host.
+
+struct TransitionConstants {
+    // Quad position and size as fractions of the surface, with y pointing down
+    rect: vec4<f32>,
+    // x: opacity
+    params: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+var<push_constant> constants: TransitionConstants;
+
+@group(0) @binding(0) var snapshot: texture_2d<f32>;
+@group(0) @binding(1) var snapshot_sampler: sampler;
+
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    let position = constants.rect.xy + uv * constants.rect.zw;
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(position.x * 2.0 - 1.0, 1.0 - position.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The snapshot is already premultiplied, so scaling every channel fades it
+    return textureSample(snapshot, snapshot_sampler, in.uv) * constants.params.x;
+}
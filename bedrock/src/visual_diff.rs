@@ -0,0 +1,230 @@
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::scene::Scene;
+
+// Channel differences at or below this are antialiasing noise rather than changes
+const PIXEL_TOLERANCE: u8 = 2;
+// Changed pixels are tinted with this straight alpha color
+const HIGHLIGHT: [u8; 3] = [255, 0, 255];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Added => write!(f, "added"),
+            Self::Removed => write!(f, "removed"),
+            Self::Modified => write!(f, "modified"),
+        }
+    }
+}
+
+// A difference between two scenes. Fields are named as they are serialized, so a moved quad is
+// reported as `quads[3]` of its layer and a changed layer blur as `blur` with no index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrimitiveChange {
+    // None for scene wide properties such as the clear color
+    pub layer: Option<usize>,
+    pub field: String,
+    pub index: Option<usize>,
+    pub kind: ChangeKind,
+}
+
+impl fmt::Display for PrimitiveChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(layer) = self.layer {
+            write!(f, "layer {} ", layer)?;
+        }
+        write!(f, "{}", self.field)?;
+        if let Some(index) = self.index {
+            write!(f, "[{}]", index)?;
+        }
+        write!(f, " {}", self.kind)
+    }
+}
+
+// Lists what changed between two scenes. Layers and primitives are matched by position, so an
+// item inserted at the front of a list shows up as every later item being modified plus one
+// added at the end.
+pub fn diff_scenes(before: &Scene, after: &Scene) -> Vec<PrimitiveChange> {
+    let mut changes = Vec::new();
+    if before.clear_color != after.clear_color {
+        changes.push(PrimitiveChange {
+            layer: None,
+            field: "clear_color".to_string(),
+            index: None,
+            kind: ChangeKind::Modified,
+        });
+    }
+    if before.guides != after.guides {
+        changes.push(PrimitiveChange {
+            layer: None,
+            field: "guides".to_string(),
+            index: None,
+            kind: ChangeKind::Modified,
+        });
+    }
+    if before.theme_colors != after.theme_colors {
+        changes.push(PrimitiveChange {
+            layer: None,
+            field: "theme_colors".to_string(),
+            index: None,
+            kind: ChangeKind::Modified,
+        });
+    }
+
+    for index in 0..before.layers.len().max(after.layers.len()) {
+        let layer_change = |kind| PrimitiveChange {
+            layer: Some(index),
+            field: "layer".to_string(),
+            index: None,
+            kind,
+        };
+        match (before.layers.get(index), after.layers.get(index)) {
+            (Some(_), None) => changes.push(layer_change(ChangeKind::Removed)),
+            (None, Some(_)) => changes.push(layer_change(ChangeKind::Added)),
+            (Some(before), Some(after)) => {
+                let before = serde_json::to_value(before).unwrap_or_default();
+                let after = serde_json::to_value(after).unwrap_or_default();
+                diff_layer(index, &before, &after, &mut changes);
+            }
+            (None, None) => {}
+        }
+    }
+    changes
+}
+
+fn diff_layer(layer: usize, before: &Value, after: &Value, changes: &mut Vec<PrimitiveChange>) {
+    let (Value::Object(before), Value::Object(after)) = (before, after) else {
+        return;
+    };
+
+    let mut fields: Vec<&String> = before.keys().chain(after.keys()).collect();
+    fields.sort();
+    fields.dedup();
+
+    for field in fields {
+        let before = before.get(field).unwrap_or(&Value::Null);
+        let after = after.get(field).unwrap_or(&Value::Null);
+        if before == after {
+            continue;
+        }
+
+        let change = |index, kind| PrimitiveChange {
+            layer: Some(layer),
+            field: field.clone(),
+            index,
+            kind,
+        };
+        match (before, after) {
+            // Lists of primitives are compared item by item
+            (Value::Array(before), Value::Array(after)) => {
+                for index in 0..before.len().max(after.len()) {
+                    match (before.get(index), after.get(index)) {
+                        (Some(_), None) => changes.push(change(Some(index), ChangeKind::Removed)),
+                        (None, Some(_)) => changes.push(change(Some(index), ChangeKind::Added)),
+                        (Some(before), Some(after)) if before != after => {
+                            changes.push(change(Some(index), ChangeKind::Modified))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => changes.push(change(None, ChangeKind::Modified)),
+        }
+    }
+}
+
+// Rendered comparison of two scenes along with what changed in them
+#[derive(Debug, Clone)]
+pub struct VisualDiff {
+    pub width: u32,
+    pub height: u32,
+    // Straight alpha rgba8 rows of the new scene faded out, with changed pixels highlighted
+    pub image: Vec<u8>,
+    pub changed_pixels: usize,
+    pub changes: Vec<PrimitiveChange>,
+}
+
+impl VisualDiff {
+    // Scenes can change without changing any pixels, such as when a primitive is moved
+    // offscreen, so both are checked
+    pub fn is_identical(&self) -> bool {
+        self.changed_pixels == 0 && self.changes.is_empty()
+    }
+}
+
+// Fades the new image toward white so the highlighted pixels stand out, returning the
+// highlighted image and the number of pixels which changed
+pub(crate) fn diff_images(before: &[u8], after: &[u8]) -> (Vec<u8>, usize) {
+    let mut image = Vec::with_capacity(after.len());
+    let mut changed_pixels = 0;
+    for (before, after) in before.chunks_exact(4).zip(after.chunks_exact(4)) {
+        let changed = before
+            .iter()
+            .zip(after.iter())
+            .any(|(before, after)| before.abs_diff(*after) > PIXEL_TOLERANCE);
+        if changed {
+            changed_pixels += 1;
+            image.extend_from_slice(&HIGHLIGHT);
+        } else {
+            let luminance =
+                (after[0] as f32 * 0.2126 + after[1] as f32 * 0.7152 + after[2] as f32 * 0.0722)
+                    * (after[3] as f32 / 255.0);
+            let faded = (255.0 - (255.0 - luminance) * 0.25) as u8;
+            image.extend_from_slice(&[faded, faded, faded]);
+        }
+        image.push(255);
+    }
+    (image, changed_pixels)
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{vec2, Vec4};
+
+    use super::*;
+    use crate::scene::Quad;
+
+    #[test]
+    fn test_changes_are_reported_per_primitive() {
+        let quad = |x| Quad::new(vec2(x, 0.0), vec2(10.0, 10.0), Vec4::ONE);
+        let before = Scene::new().with_quad(quad(0.0)).with_quad(quad(10.0));
+        let after = Scene::new()
+            .with_quad(quad(0.0))
+            .with_quad(quad(20.0))
+            .with_quad(quad(30.0))
+            .with_clear_color(Vec4::ZERO);
+
+        let changes: Vec<String> = diff_scenes(&before, &after)
+            .iter()
+            .map(|change| change.to_string())
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                "clear_color modified",
+                "layer 0 quads[1] modified",
+                "layer 0 quads[2] added",
+            ]
+        );
+        assert!(diff_scenes(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_only_differing_pixels_are_highlighted() {
+        let before = [0, 0, 0, 255, 100, 100, 100, 255];
+        let after = [1, 0, 0, 255, 200, 100, 100, 255];
+        let (image, changed_pixels) = diff_images(&before, &after);
+        assert_eq!(changed_pixels, 1);
+        assert_eq!(&image[4..], &[255, 0, 255, 255]);
+        assert_ne!(&image[..3], &HIGHLIGHT);
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use wgpu::*;
+
+use crate::scene::{Layer, Scene};
+
+// Consecutive slow or stalled frames before the renderer falls back to degraded mode
+const STRIKES_TO_DEGRADE: u32 = 3;
+
+// Watches for frames which take longer than the threshold to encode or whose gpu work never
+// finishes. After a few in a row the renderer switches to a degraded mode which only draws
+// plain quads and text without multisampling, so a struggling gpu leaves the app usable
+// instead of frozen.
+pub(crate) struct Watchdog {
+    threshold: Duration,
+    strikes: u32,
+    degraded: bool,
+    // Set when degraded mode starts and cleared once the app has been told
+    newly_degraded: bool,
+    // Submission time of the frame being waited on and the flag the queue sets once its work
+    // is done
+    in_flight: Option<(Instant, Arc<AtomicBool>)>,
+}
+
+impl Watchdog {
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            strikes: 0,
+            degraded: false,
+            newly_degraded: false,
+            in_flight: None,
+        }
+    }
+
+    pub fn degraded(&self) -> bool {
+        self.degraded
+    }
+
+    // True once after degraded mode starts
+    pub fn take_newly_degraded(&mut self) -> bool {
+        std::mem::take(&mut self.newly_degraded)
+    }
+
+    pub fn reset(&mut self) {
+        self.strikes = 0;
+        self.degraded = false;
+        self.newly_degraded = false;
+    }
+
+    // Records how long a frame took to encode and submit, and starts waiting on its gpu work
+    // unless an earlier frame's work is still outstanding
+    pub fn frame_finished(&mut self, device: &Device, queue: &Queue, encode_time: Duration) {
+        // Drives the work done callbacks without blocking
+        device.poll(Maintain::Poll);
+
+        let stalled = match &self.in_flight {
+            Some((submitted, done)) => {
+                if done.load(Ordering::Acquire) {
+                    self.in_flight = None;
+                    false
+                } else {
+                    submitted.elapsed() > self.threshold
+                }
+            }
+            None => false,
+        };
+
+        if stalled || encode_time > self.threshold {
+            self.strike(if stalled {
+                "the gpu stopped signaling"
+            } else {
+                "frames exceeded the hang threshold"
+            });
+        } else if self.in_flight.is_none() {
+            self.strikes = 0;
+        }
+
+        if self.in_flight.is_none() {
+            let done = Arc::new(AtomicBool::new(false));
+            let signal = done.clone();
+            queue.on_submitted_work_done(move || signal.store(true, Ordering::Release));
+            self.in_flight = Some((Instant::now(), done));
+        }
+    }
+
+    fn strike(&mut self, reason: &str) {
+        self.strikes += 1;
+        if self.strikes >= STRIKES_TO_DEGRADE && !self.degraded {
+            eprintln!("Falling back to degraded rendering because {}", reason);
+            self.degraded = true;
+            self.newly_degraded = true;
+        }
+    }
+}
+
+// Copy of the scene with only the cheapest content. Blurs, paths, meshes, image pyramids,
+// sprites, shader quads, particles, mirrors, and custom items are dropped and quads lose their
+// blur. Clip paths are kept so clipped content doesn't spill out.
+pub(crate) fn degrade(scene: &Scene) -> Scene {
+    let mut degraded = scene.clone();
+    degraded.layers = scene
+        .layers
+        .iter()
+        .map(|layer| Arc::new(degrade_layer(layer)))
+        .collect();
+    degraded
+}
+
+fn degrade_layer(layer: &Layer) -> Layer {
+    Layer {
+        name: layer.name.clone(),
+        clip: layer.clip,
+        clip_paths: layer.clip_paths.clone(),
+        within_safe_area: layer.within_safe_area,
+        render_target: layer.render_target.clone(),
+        cache_hint: layer.cache_hint,
+        background_color: layer.background_color,
+        font_name: layer.font_name.clone(),
+        font_size: layer.font_size,
+        text_style: layer.text_style.clone(),
+        quads: layer
+            .quads
+            .iter()
+            .map(|quad| quad.clone().with_blur(0.0))
+            .collect(),
+        ellipses: layer.ellipses.clone(),
+        texts: layer.texts.clone(),
+        text_logs: layer.text_logs.clone(),
+        // Cheap to draw, and charts are unreadable without them
+        polylines: layer.polylines.clone(),
+        guides: layer.guides.clone(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{vec2, Vec4};
+
+    use super::*;
+    use crate::scene::{Path, Quad};
+
+    #[test]
+    fn test_degrade_keeps_quads_and_text() {
+        let scene = Scene::new()
+            .with_blur(20.0)
+            .with_quad(Quad::new(vec2(0.0, 0.0), vec2(10.0, 10.0), Vec4::ONE).with_blur(4.0))
+            .with_path(Path::new_fill(Vec4::ONE, vec2(0.0, 0.0)).line_to(vec2(5.0, 5.0)));
+
+        let degraded = degrade(&scene);
+        let layer = degraded.layer();
+        assert_eq!(layer.background_blur_radius, 0.0);
+        assert_eq!(layer.quads.len(), 1);
+        assert_eq!(layer.quads[0].bounds(), Vec4::new(0.0, 0.0, 10.0, 10.0));
+        assert!(layer.paths.is_empty());
+    }
+
+    #[test]
+    fn test_degrade_keeps_clip_paths() {
+        let mut scene =
+            Scene::new().with_quad(Quad::new(vec2(0.0, 0.0), vec2(10.0, 10.0), Vec4::ONE));
+        scene.layer_mut().push_clip_path(
+            Path::new_fill(Vec4::ONE, vec2(0.0, 0.0))
+                .line_to(vec2(5.0, 0.0))
+                .line_to(vec2(0.0, 5.0)),
+        );
+
+        let degraded = degrade(&scene);
+        assert_eq!(degraded.layer().clip_paths.len(), 1);
+    }
+}
@@ -0,0 +1,253 @@
+// Python bindings for building scenes and rendering them to images, for scripts which want gpu
+// accelerated 2d output without opening a window. Built into a wheel with maturin.
+
+use futures::executor::block_on;
+use glam::{vec2, vec4, Vec4};
+use pyo3::{exceptions::PyRuntimeError, exceptions::PyValueError, prelude::*, types::PyBytes};
+use rust_embed::RustEmbed;
+
+// Sprite textures have to be embedded at compile time, so scenes built from python have none
+#[derive(RustEmbed)]
+#[folder = "assets"]
+struct Assets;
+
+// Straight alpha rgba from 0 to 1
+type Color = (f32, f32, f32, f32);
+// x, y, width, height
+type Rect = (f32, f32, f32, f32);
+
+fn to_vec4(values: (f32, f32, f32, f32)) -> Vec4 {
+    vec4(values.0, values.1, values.2, values.3)
+}
+
+#[pyclass]
+#[derive(Clone)]
+struct Path {
+    path: bedrock::Path,
+}
+
+#[pymethods]
+impl Path {
+    // Paths are filled, stroked, or both. Stroke is (width, color)
+    #[new]
+    #[pyo3(signature = (x, y, fill=None, stroke=None))]
+    fn new(x: f32, y: f32, fill: Option<Color>, stroke: Option<(f32, Color)>) -> Self {
+        let mut path = bedrock::Path::new(vec2(x, y));
+        if let Some(fill) = fill {
+            path = path.with_fill(to_vec4(fill));
+        }
+        if let Some((width, stroke)) = stroke {
+            path = path.with_stroke((width, to_vec4(stroke)));
+        }
+        Self { path }
+    }
+
+    // Builder methods return the path so calls can be chained
+    fn line_to(mut slf: PyRefMut<Self>, x: f32, y: f32) -> PyRefMut<Self> {
+        slf.path = slf.path.clone().line_to(vec2(x, y));
+        slf
+    }
+
+    fn quadratic_bezier_to(
+        mut slf: PyRefMut<Self>,
+        control_x: f32,
+        control_y: f32,
+        x: f32,
+        y: f32,
+    ) -> PyRefMut<Self> {
+        slf.path = slf
+            .path
+            .clone()
+            .quadratic_bezier_to(vec2(control_x, control_y), vec2(x, y));
+        slf
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn cubic_bezier_to(
+        mut slf: PyRefMut<Self>,
+        control1_x: f32,
+        control1_y: f32,
+        control2_x: f32,
+        control2_y: f32,
+        x: f32,
+        y: f32,
+    ) -> PyRefMut<Self> {
+        slf.path = slf.path.clone().cubic_bezier_to(
+            vec2(control1_x, control1_y),
+            vec2(control2_x, control2_y),
+            vec2(x, y),
+        );
+        slf
+    }
+
+    fn move_to(mut slf: PyRefMut<Self>, x: f32, y: f32) -> PyRefMut<Self> {
+        slf.path = slf.path.clone().move_to(vec2(x, y));
+        slf
+    }
+
+    // Leaves the last subpath unclosed so strokes don't join back to its start
+    fn open(mut slf: PyRefMut<Self>) -> PyRefMut<Self> {
+        slf.path = slf.path.clone().with_open(true);
+        slf
+    }
+}
+
+// Items are added to the most recently added layer
+#[pyclass]
+#[derive(Clone)]
+struct Scene {
+    scene: bedrock::Scene,
+}
+
+#[pymethods]
+impl Scene {
+    #[new]
+    #[pyo3(signature = (clear_color=None))]
+    fn new(clear_color: Option<Color>) -> Self {
+        let mut scene = bedrock::Scene::new();
+        if let Some(clear_color) = clear_color {
+            scene = scene.with_clear_color(to_vec4(clear_color));
+        }
+        Self { scene }
+    }
+
+    #[pyo3(signature = (clip=None, background=None, blur=0.0, font=None))]
+    fn add_layer(
+        &mut self,
+        clip: Option<Rect>,
+        background: Option<Color>,
+        blur: f32,
+        font: Option<String>,
+    ) {
+        let mut layer = bedrock::Layer::default().with_blur(blur);
+        layer.background_color = background.map(to_vec4);
+        if let Some(clip) = clip {
+            layer = layer.with_clip(to_vec4(clip));
+        }
+        if let Some(font) = font {
+            layer = layer.with_font(font);
+        }
+        self.scene.add_layer(layer);
+    }
+
+    #[pyo3(signature = (x, y, width, height, fill, corner_radius=0.0, blur=0.0))]
+    #[allow(clippy::too_many_arguments)]
+    fn add_quad(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        fill: Color,
+        corner_radius: f32,
+        blur: f32,
+    ) {
+        self.scene.add_quad(
+            bedrock::Quad::new(vec2(x, y), vec2(width, height), to_vec4(fill))
+                .with_corner_radius(corner_radius)
+                .with_blur(blur),
+        );
+    }
+
+    // Positioned by the start of the text's baseline
+    #[pyo3(signature = (text, x, y, size, fill, bold=false, italic=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn add_text(
+        &mut self,
+        text: String,
+        x: f32,
+        y: f32,
+        size: f32,
+        fill: Color,
+        bold: bool,
+        italic: bool,
+    ) {
+        let mut text = bedrock::Text::new(text, vec2(x, y), size, to_vec4(fill));
+        if bold {
+            text = text.with_bold();
+        }
+        if italic {
+            text = text.with_italic();
+        }
+        self.scene.add_text(text);
+    }
+
+    fn add_path(&mut self, path: &Path) {
+        self.scene.add_path(path.path.clone());
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        let mut json = Vec::new();
+        self.scene
+            .to_writer(&mut json)
+            .map_err(|error| PyValueError::new_err(error.to_string()))?;
+        Ok(String::from_utf8(json).expect("Scene json wasn't utf8"))
+    }
+
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        let scene = bedrock::Scene::from_reader(json.as_bytes())
+            .map_err(|error| PyValueError::new_err(error.to_string()))?;
+        Ok(Self { scene })
+    }
+}
+
+// Renders headless into offscreen textures and reads the images back, so no window or event
+// loop is needed
+#[pyclass(unsendable)]
+struct Renderer {
+    renderer: bedrock::Renderer,
+    width: u32,
+    height: u32,
+}
+
+#[pymethods]
+impl Renderer {
+    #[new]
+    fn new(width: u32, height: u32) -> PyResult<Self> {
+        let renderer = block_on(bedrock::Renderer::try_headless(width, height))
+            .map_err(|error| PyRuntimeError::new_err(error.to_string()))?
+            .with_default_drawables::<Assets>()
+            .with_eager_pipelines();
+
+        Ok(Self {
+            renderer,
+            width,
+            height,
+        })
+    }
+
+    // Straight alpha rgba8 rows, top to bottom
+    fn render_rgba<'py>(&mut self, py: Python<'py>, scene: &Scene) -> PyResult<&'py PyBytes> {
+        let rgba = self.rgba(scene)?;
+        Ok(PyBytes::new(py, &rgba))
+    }
+
+    fn save_png(&mut self, scene: &Scene, path: &str) -> PyResult<()> {
+        let rgba = self.rgba(scene)?;
+        image::save_buffer(
+            path,
+            &rgba,
+            self.width,
+            self.height,
+            image::ColorType::Rgba8,
+        )
+        .map_err(|error| PyRuntimeError::new_err(error.to_string()))
+    }
+}
+
+impl Renderer {
+    fn rgba(&mut self, scene: &Scene) -> PyResult<Vec<u8>> {
+        self.renderer
+            .render_rgba(&scene.scene, self.width, self.height)
+            .ok_or_else(|| PyRuntimeError::new_err("Could not render scene"))
+    }
+}
+
+#[pymodule]
+fn bedrock_py(_py: Python, module: &PyModule) -> PyResult<()> {
+    module.add_class::<Path>()?;
+    module.add_class::<Scene>()?;
+    module.add_class::<Renderer>()?;
+    Ok(())
+}
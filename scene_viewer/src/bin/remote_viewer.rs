@@ -0,0 +1,81 @@
+// Displays scenes sent over the network with `bedrock::RemoteClient` or any client speaking the
+// length prefixed json protocol. Usage: remote_viewer [address], defaulting to 127.0.0.1:7878.
+
+use std::sync::{Arc, Mutex};
+
+use futures::executor::block_on;
+use rust_embed::RustEmbed;
+use winit::{
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+use bedrock::{RemoteServer, Renderer, Scene};
+
+#[derive(RustEmbed)]
+#[folder = "assets"]
+struct Assets;
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+
+fn main() {
+    env_logger::init();
+
+    let address = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+
+    let event_loop = EventLoop::new().expect("Couldn't create event loop");
+    event_loop.set_control_flow(ControlFlow::Wait);
+
+    // Wakes the event loop whenever a scene arrives
+    let proxy = Mutex::new(event_loop.create_proxy());
+    let server = RemoteServer::bind(&address, move || {
+        proxy.lock().unwrap().send_event(()).ok();
+    })
+    .expect("Could not listen for remote scenes");
+    println!("Listening for scenes on {}", server.local_addr());
+
+    let window = Arc::new(
+        WindowBuilder::new()
+            .with_title(format!("Remote scene viewer - {}", server.local_addr()))
+            .build(&event_loop)
+            .unwrap(),
+    );
+    let mut renderer = block_on(Renderer::new(window.clone()))
+        .with_default_drawables::<Assets>()
+        .with_redraw_on_demand();
+    let mut scene = Scene::new();
+
+    event_loop
+        .run(|event, target| {
+            renderer.handle_event(&event);
+
+            match event {
+                Event::UserEvent(()) => {
+                    if let Some(received) = server.take_scene() {
+                        scene = received;
+                        window.request_redraw();
+                    }
+                }
+                Event::WindowEvent {
+                    ref event,
+                    window_id,
+                } if window_id == window.id() => match event {
+                    WindowEvent::CloseRequested => target.exit(),
+                    WindowEvent::RedrawRequested => {
+                        renderer.draw_scene(&scene);
+                        // Scenes with animated content such as particles keep drawing
+                        if renderer.needs_redraw(&scene) {
+                            window.request_redraw();
+                        }
+                    }
+                    WindowEvent::Resized(_) => window.request_redraw(),
+                    _ => {}
+                },
+                _ => {}
+            };
+        })
+        .expect("Could not run event loop");
+}
@@ -0,0 +1,83 @@
+// Renders two scene files and writes an image highlighting the pixels which differ, then lists
+// the primitives which changed. Revisions of one file can be compared by exporting the old one
+// first, such as with `git show HEAD~1:scene.json > old.json`.
+//
+// Usage: scene_diff <before.json> <after.json> [diff.png] [width] [height]
+// Exits with status 1 when the scenes differ, so it can gate a pipeline.
+
+use std::{fs::File, process, sync::Arc};
+
+use futures::executor::block_on;
+use rust_embed::RustEmbed;
+use winit::{dpi::PhysicalSize, event_loop::EventLoop, window::WindowBuilder};
+
+use bedrock::{Renderer, Scene};
+
+#[derive(RustEmbed)]
+#[folder = "assets"]
+struct Assets;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 {
+        eprintln!("Usage: scene_diff <before.json> <after.json> [diff.png] [width] [height]");
+        process::exit(2);
+    }
+    let before = load_scene(&args[1]);
+    let after = load_scene(&args[2]);
+    let output = args.get(3).map(String::as_str).unwrap_or("diff.png");
+    let width = args.get(4).map_or(1024, |width| {
+        width.parse().expect("Width should be a number")
+    });
+    let height = args.get(5).map_or(768, |height| {
+        height.parse().expect("Height should be a number")
+    });
+
+    // Scenes are rendered into a hidden window's surface, which the event loop has to outlive
+    let event_loop = EventLoop::new().expect("Couldn't create event loop");
+    let window = Arc::new(
+        WindowBuilder::new()
+            .with_visible(false)
+            .with_inner_size(PhysicalSize::new(width, height))
+            .build(&event_loop)
+            .unwrap(),
+    );
+    let mut renderer = block_on(Renderer::new(window.clone()))
+        .with_default_drawables::<Assets>()
+        .with_eager_pipelines();
+    renderer.resize_surface(width, height);
+
+    let diff = renderer
+        .visual_diff(&before, &after, width, height)
+        .expect("Could not render scenes");
+    image::save_buffer(
+        output,
+        &diff.image,
+        diff.width,
+        diff.height,
+        image::ColorType::Rgba8,
+    )
+    .expect("Could not write diff image");
+
+    println!(
+        "{} of {} pixels changed, written to {}",
+        diff.changed_pixels,
+        width * height,
+        output
+    );
+    for change in diff.changes.iter() {
+        println!("  {}", change);
+    }
+
+    if !diff.is_identical() {
+        process::exit(1);
+    }
+}
+
+fn load_scene(path: &str) -> Scene {
+    let file =
+        File::open(path).unwrap_or_else(|error| panic!("Could not open {}: {}", path, error));
+    Scene::from_reader(file).unwrap_or_else(|error| panic!("Could not parse {}: {}", path, error))
+}
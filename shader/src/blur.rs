@@ -0,0 +1,116 @@
+use spirv_std::{glam::*, image::Image2d, spirv, Sampler};
+
+use crate::ShaderConstants;
+
+// Dual filter blur passes used to blur the backdrop at reduced resolution. Each downsample
+// halves the resolution and each upsample doubles it again, sampling between texels so the
+// bilinear filter does most of the work.
+//
+// For both passes `constants.surface_size` holds the size of the source texture and
+// `constants.backdrop.y` scales the distance between samples so the blur radius can change
+// continuously without changing the number of passes.
+
+#[spirv(vertex)]
+pub fn fullscreen_vertex(
+    #[spirv(vertex_index)] vert_index: i32,
+    #[spirv(position, invariant)] out_position: &mut Vec4,
+    out_uv: &mut Vec2,
+) {
+    // Single triangle covering the whole target
+    let uv = vec2(((vert_index << 1) & 2) as f32, (vert_index & 2) as f32);
+    *out_uv = uv;
+    *out_position = vec4(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+#[spirv(fragment)]
+pub fn blur_downsample(
+    #[spirv(descriptor_set = 0, binding = 0)] source: &Image2d,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler: &Sampler,
+    #[spirv(push_constant)] constants: &ShaderConstants,
+    uv: Vec2,
+    out_color: &mut Vec4,
+) {
+    let half_texel = 0.5 * constants.backdrop.y / constants.surface_size;
+
+    let sum = sample(source, *sampler, uv) * 4.0
+        + sample(source, *sampler, uv - half_texel)
+        + sample(source, *sampler, uv + half_texel)
+        + sample(source, *sampler, uv + vec2(half_texel.x, -half_texel.y))
+        + sample(source, *sampler, uv + vec2(-half_texel.x, half_texel.y));
+    *out_color = sum / 8.0;
+}
+
+#[spirv(fragment)]
+pub fn blur_upsample(
+    #[spirv(descriptor_set = 0, binding = 0)] source: &Image2d,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler: &Sampler,
+    #[spirv(push_constant)] constants: &ShaderConstants,
+    uv: Vec2,
+    out_color: &mut Vec4,
+) {
+    let half_texel = 0.5 * constants.backdrop.y / constants.surface_size;
+
+    let sum = sample(source, *sampler, uv + vec2(-half_texel.x * 2.0, 0.0))
+        + sample(source, *sampler, uv + vec2(-half_texel.x, half_texel.y)) * 2.0
+        + sample(source, *sampler, uv + vec2(0.0, half_texel.y * 2.0))
+        + sample(source, *sampler, uv + half_texel) * 2.0
+        + sample(source, *sampler, uv + vec2(half_texel.x * 2.0, 0.0))
+        + sample(source, *sampler, uv + vec2(half_texel.x, -half_texel.y)) * 2.0
+        + sample(source, *sampler, uv + vec2(0.0, -half_texel.y * 2.0))
+        + sample(source, *sampler, uv - half_texel) * 2.0;
+    *out_color = sum / 12.0;
+}
+
+// Separable gaussian passes used by higher quality backdrop blurs once the backdrop has been
+// downsampled far enough for the kernel to cover the radius.
+#[spirv(fragment)]
+pub fn blur_horizontal(
+    #[spirv(descriptor_set = 0, binding = 0)] source: &Image2d,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler: &Sampler,
+    #[spirv(push_constant)] constants: &ShaderConstants,
+    uv: Vec2,
+    out_color: &mut Vec4,
+) {
+    *out_color = gaussian(source, *sampler, constants, uv, vec2(1.0, 0.0));
+}
+
+#[spirv(fragment)]
+pub fn blur_vertical(
+    #[spirv(descriptor_set = 0, binding = 0)] source: &Image2d,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler: &Sampler,
+    #[spirv(push_constant)] constants: &ShaderConstants,
+    uv: Vec2,
+    out_color: &mut Vec4,
+) {
+    *out_color = gaussian(source, *sampler, constants, uv, vec2(0.0, 1.0));
+}
+
+fn gaussian(
+    source: &Image2d,
+    sampler: Sampler,
+    constants: &ShaderConstants,
+    uv: Vec2,
+    direction: Vec2,
+) -> Vec4 {
+    let step = direction * constants.backdrop.y / constants.surface_size;
+    let sigma = constants.backdrop.z.max(0.01);
+    let taps = constants.backdrop.w as i32;
+
+    let mut sum = sample(source, sampler, uv);
+    let mut total = 1.0;
+    let mut tap = 1;
+    while tap <= taps {
+        let offset = tap as f32 * constants.backdrop.y;
+        let weight = (-(offset * offset) / (2.0 * sigma * sigma)).exp();
+        sum += (sample(source, sampler, uv + step * tap as f32)
+            + sample(source, sampler, uv - step * tap as f32))
+            * weight;
+        total += weight * 2.0;
+        tap += 1;
+    }
+    sum / total
+}
+
+fn sample(source: &Image2d, sampler: Sampler, uv: Vec2) -> Vec4 {
+    source.sample_by_lod(sampler, uv, 0.)
+}
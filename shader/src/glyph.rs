@@ -1,3 +1,5 @@
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::Float;
 use spirv_std::{glam::*, image::Image2d, spirv, Sampler};
 
 use crate::ShaderConstants;
@@ -9,11 +11,33 @@ pub struct InstancedGlyph {
     pub bottom_left: Vec2,
     pub atlas_top_left: Vec2,
     pub atlas_size: Vec2,
-    // Need a Vec2 of padding here so that the first 4 fields
-    // Are some multiple of 16 bytes in size.
-    // Vec2s are 8 bytes, Vec4s are 16 bytes.
-    pub _padding: Vec2,
+    // 0: grayscale coverage, 1: rgb subpixel order, 2: bgr subpixel order.
+    // Along with the padding, keeps the first fields a multiple of 16 bytes in size.
+    pub subpixel: u32,
+    pub _padding: u32,
     pub color: Vec4,
+    // Coverage corrections. See correct_coverage
+    pub contrast: f32,
+    pub gamma: f32,
+    // Depth tested against other items when depth testing is enabled. Higher values are nearer
+    pub depth: f32,
+    pub __padding: f32,
+}
+
+// Adjusts glyph coverage so text weight doesn't depend on its color. Contrast thickens
+// antialiased edges, less so for light text which already looks heavier against dark
+// backgrounds. Gamma raises coverage for dark text and lowers it for light text. A contrast of
+// 0 and gamma of 1 leave coverage unchanged. Must match glyph.wgsl in bedrock.
+fn correct_coverage(coverage: Vec3, color: Vec3, contrast: f32, gamma: f32) -> Vec3 {
+    let luminance = color.dot(vec3(0.30, 0.59, 0.11));
+    let contrast = contrast * (4.0 * (0.75 - luminance)).clamp(0.0, 1.0);
+    let coverage = coverage * (contrast + 1.0) / (coverage * contrast + 1.0);
+    let exponent = 1.0 / gamma + (gamma - 1.0 / gamma) * luminance;
+    vec3(
+        coverage.x.powf(exponent),
+        coverage.y.powf(exponent),
+        coverage.z.powf(exponent),
+    )
 }
 
 #[spirv(vertex)]
@@ -44,7 +68,7 @@ pub fn glyph_vertex(
 
     let final_position =
         vec2(0.0, 2.0) + vertex_pixel_pos / constants.surface_size * vec2(1., -1.) * 2.0 - 1.0;
-    *out_position = final_position.extend(0.0).extend(1.0);
+    *out_position = final_position.extend(instance.depth).extend(1.0);
 
     *out_atlas_position = instance.atlas_top_left / constants.atlas_size
         + unit_vertex_pos * instance.atlas_size / constants.atlas_size;
@@ -69,7 +93,27 @@ pub fn glyph_fragment(
     // More details here: https://github.com/gfx-rs/wgpu-rs/issues/912
     let surface_color =
         surface.sample_by_lod(*sampler, surface_position.xy() / constants.surface_size, 0.);
-    let mask_color = atlas.sample_by_lod(*sampler, atlas_position, 0.);
-    *out_color = glyph.color * glyph.color * mask_color
-        + (1.0 - glyph.color.w * glyph.color.w * mask_color) * surface_color;
+    let mut mask_color = atlas.sample_by_lod(*sampler, atlas_position, 0.).xyz();
+    if !cfg!(feature = "subpixel_text") || glyph.subpixel == 0 {
+        // Glyphs are always rasterized with subpixel coverage. Collapse it to grayscale
+        let coverage = (mask_color.x + mask_color.y + mask_color.z) / 3.0;
+        mask_color = Vec3::splat(coverage);
+    } else if glyph.subpixel == 2 {
+        mask_color = mask_color.zyx();
+    }
+    let mask_color = correct_coverage(mask_color, glyph.color.xyz(), glyph.contrast, glyph.gamma);
+    let color = if cfg!(feature = "srgb") {
+        glyph.color * glyph.color
+    } else {
+        glyph.color
+    };
+    // Fallback for adapters without dual source blending. Subpixel coverage can't be expressed
+    // with a single alpha, so the glyph is composited over a copy of the previous layers here.
+    // The glyph color is premultiplied before the mask scales it, and the output replaces the
+    // frame rather than being blended over it
+    let premultiplied = color.xyz() * color.w;
+    let coverage = mask_color * color.w;
+    let alpha = coverage.max_element();
+    *out_color = (premultiplied * mask_color + (Vec3::ONE - coverage) * surface_color.xyz())
+        .extend(alpha + (1.0 - alpha) * surface_color.w);
 }
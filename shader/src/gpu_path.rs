@@ -0,0 +1,157 @@
+use glam::*;
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::Float;
+use spirv_std::spirv;
+
+use crate::ShaderConstants;
+
+const UNIT_QUAD_VERTICES: [Vec2; 6] = [
+    vec2(0.0, 0.0),
+    vec2(1.0, 0.0),
+    vec2(1.0, 1.0),
+    vec2(0.0, 0.0),
+    vec2(1.0, 1.0),
+    vec2(0.0, 1.0),
+];
+
+// A path evaluated entirely in the fragment shader. The path is flattened into line segments
+// on the cpu and each pixel in the bounding box computes its winding and distance to the
+// segments, so nothing needs tessellating when the path changes.
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct InstancedPathShape {
+    pub fill: Vec4,
+    pub stroke: Vec4,
+    pub top_left: Vec2,
+    pub size: Vec2,
+    pub stroke_width: f32,
+    pub segment_start: u32,
+    pub segment_count: u32,
+    // Depth tested against other items when depth testing is enabled. Higher values are nearer
+    pub depth: f32,
+    // 0: even odd, 1: nonzero
+    pub fill_rule: u32,
+    pub _padding: [u32; 3],
+}
+
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct PathSegment {
+    pub from: Vec2,
+    pub to: Vec2,
+    // 1 for the implicit closing edge of an open path, which bounds its fill but isn't stroked
+    pub fill_only: u32,
+    pub _padding: u32,
+}
+
+impl PathSegment {
+    fn distance(&self, point: Vec2) -> f32 {
+        let direction = self.to - self.from;
+        let length_squared = direction.length_squared();
+        let t = if length_squared > 0.0 {
+            ((point - self.from).dot(direction) / length_squared)
+                .max(0.0)
+                .min(1.0)
+        } else {
+            0.0
+        };
+        (point - (self.from + direction * t)).length()
+    }
+
+    // Signed crossing of a ray cast in the positive x direction from the point. 1 if the
+    // segment crosses it going down, -1 going up, and 0 if it doesn't cross
+    fn winding(&self, point: Vec2) -> i32 {
+        if (self.from.y > point.y) == (self.to.y > point.y) {
+            return 0;
+        }
+        let t = (point.y - self.from.y) / (self.to.y - self.from.y);
+        if point.x >= self.from.x + (self.to.x - self.from.x) * t {
+            0
+        } else if self.to.y > self.from.y {
+            1
+        } else {
+            -1
+        }
+    }
+}
+
+#[spirv(vertex)]
+pub fn gpu_path_vertex(
+    #[spirv(instance_index)] instance_index: i32,
+    #[spirv(vertex_index)] vert_index: i32,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] shapes: &[InstancedPathShape],
+    #[spirv(push_constant)] constants: &ShaderConstants,
+    #[spirv(position, invariant)] out_position: &mut Vec4,
+    out_instance_index: &mut i32,
+) {
+    *out_instance_index = instance_index;
+
+    let unit_vertex_pos = UNIT_QUAD_VERTICES[vert_index as usize];
+
+    // Grow the bounds to cover half the stroke and the antialiased edge
+    let shape = shapes[instance_index as usize];
+    let extension = (shape.stroke_width / 2.0 + 1.0) * Vec2::ONE;
+    let vertex_pixel_pos =
+        (shape.top_left - extension) + unit_vertex_pos * (shape.size + extension * 2.0);
+
+    let final_position =
+        vec2(0.0, 2.0) + vertex_pixel_pos / constants.surface_size * vec2(1., -1.) * 2.0 - 1.0;
+    *out_position = final_position.extend(shape.depth).extend(1.0);
+}
+
+#[spirv(fragment)]
+pub fn gpu_path_fragment(
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] shapes: &[InstancedPathShape],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] segments: &[PathSegment],
+    #[spirv(flat)] instance_index: i32,
+    #[spirv(frag_coord)] surface_position: Vec4,
+    out_color: &mut Vec4,
+) {
+    let shape = shapes[instance_index as usize];
+    let point = surface_position.xy();
+
+    let mut winding = 0;
+    let mut fill_distance = f32::MAX;
+    let mut stroke_distance = f32::MAX;
+    let mut index = shape.segment_start;
+    while index < shape.segment_start + shape.segment_count {
+        let segment = segments[index as usize];
+        winding += segment.winding(point);
+        let distance = segment.distance(point);
+        fill_distance = fill_distance.min(distance);
+        if segment.fill_only == 0 {
+            stroke_distance = stroke_distance.min(distance);
+        }
+        index += 1;
+    }
+
+    let inside = if shape.fill_rule == 1 {
+        winding != 0
+    } else {
+        winding & 1 != 0
+    };
+    let signed_distance = if inside {
+        -fill_distance
+    } else {
+        fill_distance
+    };
+    let fill_coverage = (0.5 - signed_distance).max(0.0).min(1.0);
+    let stroke_coverage = (shape.stroke_width / 2.0 + 0.5 - stroke_distance)
+        .max(0.0)
+        .min(1.0);
+
+    let (fill, stroke) = if cfg!(feature = "srgb") {
+        (shape.fill * shape.fill, shape.stroke * shape.stroke)
+    } else {
+        (shape.fill, shape.stroke)
+    };
+
+    // Composite the stroke over the fill, producing a premultiplied color
+    let fill_alpha = fill.w * fill_coverage;
+    let stroke_alpha = stroke.w * stroke_coverage;
+    let alpha = stroke_alpha + fill_alpha * (1.0 - stroke_alpha);
+    let color = stroke.xyz() * stroke_alpha + fill.xyz() * fill_alpha * (1.0 - stroke_alpha);
+    *out_color = color.extend(alpha);
+}
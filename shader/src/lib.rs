@@ -1,17 +1,129 @@
 #![cfg_attr(target_arch = "spirv", no_std)]
 
+mod blur;
 mod glyph;
+mod gpu_path;
+mod mirror;
 mod path;
 mod quad;
 mod sprite;
 
+pub use blur::*;
 use glam::Vec4;
 pub use glyph::*;
+pub use gpu_path::*;
+pub use mirror::*;
 pub use path::*;
 pub use quad::*;
 use spirv_std::glam::Vec2;
 pub use sprite::*;
 
+// Bump whenever ShaderConstants, an instance struct, or an entry point changes in a way the
+// host needs to know about. The host refuses to start with a shader built at another version.
+pub const SHADER_ABI_VERSION: u32 = 14;
+
+pub const QUAD_VERTEX: &str = "quad::vertex";
+pub const QUAD_FRAGMENT: &str = "quad::fragment";
+pub const GLYPH_VERTEX: &str = "glyph::glyph_vertex";
+pub const GLYPH_FRAGMENT: &str = "glyph::glyph_fragment";
+pub const PATH_VERTEX: &str = "path::path_vertex";
+pub const PATH_FRAGMENT: &str = "path::path_fragment";
+pub const SPRITE_VERTEX: &str = "sprite::sprite_vertex";
+pub const SPRITE_FRAGMENT: &str = "sprite::sprite_fragment";
+pub const GPU_PATH_VERTEX: &str = "gpu_path::gpu_path_vertex";
+pub const GPU_PATH_FRAGMENT: &str = "gpu_path::gpu_path_fragment";
+pub const MIRROR_VERTEX: &str = "mirror::mirror_vertex";
+pub const MIRROR_FRAGMENT: &str = "mirror::mirror_fragment";
+pub const BLUR_VERTEX: &str = "blur::fullscreen_vertex";
+pub const BLUR_DOWNSAMPLE: &str = "blur::blur_downsample";
+pub const BLUR_UPSAMPLE: &str = "blur::blur_upsample";
+pub const BLUR_HORIZONTAL: &str = "blur::blur_horizontal";
+pub const BLUR_VERTICAL: &str = "blur::blur_vertical";
+
+// Every entry point the host creates pipelines for
+pub const ENTRY_POINTS: &[&str] = &[
+    QUAD_VERTEX,
+    QUAD_FRAGMENT,
+    GLYPH_VERTEX,
+    GLYPH_FRAGMENT,
+    PATH_VERTEX,
+    PATH_FRAGMENT,
+    SPRITE_VERTEX,
+    SPRITE_FRAGMENT,
+    GPU_PATH_VERTEX,
+    GPU_PATH_FRAGMENT,
+    MIRROR_VERTEX,
+    MIRROR_FRAGMENT,
+    BLUR_VERTEX,
+    BLUR_DOWNSAMPLE,
+    BLUR_UPSAMPLE,
+    BLUR_HORIZONTAL,
+    BLUR_VERTICAL,
+];
+
+// Feature toggles which are compiled into separate spirv permutations rather than checked at
+// runtime. Each field matches a cargo feature of this crate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderFeatures {
+    // Convert colors from srgb before blending
+    pub srgb: bool,
+    // Use per channel glyph coverage instead of averaging it into grayscale
+    pub subpixel_text: bool,
+    // Compute quad edge coverage from the distance field instead of relying on msaa alone
+    pub analytic_aa: bool,
+}
+
+impl Default for ShaderFeatures {
+    fn default() -> Self {
+        Self {
+            srgb: true,
+            subpixel_text: true,
+            analytic_aa: false,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "spirv"))]
+impl ShaderFeatures {
+    // Every combination of features, each of which is built by the build script
+    pub fn permutations() -> impl Iterator<Item = Self> {
+        (0..8u32).map(|bits| Self {
+            srgb: bits & 1 != 0,
+            subpixel_text: bits & 2 != 0,
+            analytic_aa: bits & 4 != 0,
+        })
+    }
+
+    pub fn cargo_features(&self) -> Vec<&'static str> {
+        let mut features = Vec::new();
+        if self.srgb {
+            features.push("srgb");
+        }
+        if self.subpixel_text {
+            features.push("subpixel_text");
+        }
+        if self.analytic_aa {
+            features.push("analytic_aa");
+        }
+        features
+    }
+
+    // Name of the embedded spirv file for this permutation. The default permutation keeps the
+    // original name.
+    pub fn spirv_file_name(&self) -> String {
+        if *self == Self::default() {
+            "shader.spv".to_string()
+        } else {
+            let features = self.cargo_features();
+            if features.is_empty() {
+                "shader-none.spv".to_string()
+            } else {
+                format!("shader-{}.spv", features.join("-"))
+            }
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 #[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
 #[repr(C)]
@@ -19,4 +131,10 @@ pub struct ShaderConstants {
     pub surface_size: Vec2,
     pub atlas_size: Vec2,
     pub clip: Vec4,
+    // x: one more than the mip level of the blurred backdrop texture which background blurs
+    // should sample, or 0 to blur the offscreen texture inline
+    // y: sample spread of the backdrop blur passes
+    // z: standard deviation of the gaussian blur passes in source texels
+    // w: samples on each side of the center in the gaussian blur passes
+    pub backdrop: Vec4,
 }
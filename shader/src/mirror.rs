@@ -0,0 +1,133 @@
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::Float;
+use spirv_std::{glam::*, image::Image2d, spirv, Sampler};
+
+use crate::ShaderConstants;
+
+// Magnification below which the pixel grid would cover most of the mirror, so it is hidden
+const MIN_GRID_SCALE: f32 = 4.0;
+
+// Redraws a region of what has been drawn so far at another position and scale. Used for
+// picture in picture views and magnifiers.
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct InstancedMirror {
+    // Region of the surface to copy as (x, y, width, height) in pixels
+    pub source: Vec4,
+    pub top_left: Vec2,
+    pub size: Vec2,
+    pub border_color: Vec4,
+    // Lines drawn between the source's pixels when they are magnified enough to tell apart.
+    // Setting a grid also samples the source without filtering so pixels stay square.
+    // Transparent disables the grid
+    pub grid_color: Vec4,
+    pub border_width: f32,
+    // 0: rectangular, 1: masked to the largest circle fitting in the destination
+    pub circular: u32,
+    pub _padding: Vec2,
+}
+
+impl InstancedMirror {
+    // Signed distance from the edge of the destination's mask, negative inside
+    fn distance(&self, point: Vec2) -> f32 {
+        let relative_point = point - (self.top_left + self.size / 2.0);
+        if self.circular != 0 {
+            relative_point.length() - self.size.min_element() / 2.0
+        } else {
+            let d = relative_point.abs() - self.size / 2.0;
+            d.max(Vec2::ZERO).length() + d.max_element().min(0.0)
+        }
+    }
+}
+
+#[spirv(vertex)]
+pub fn mirror_vertex(
+    #[spirv(instance_index)] instance_index: i32,
+    #[spirv(vertex_index)] vert_index: i32,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] mirrors: &[InstancedMirror],
+    #[spirv(push_constant)] constants: &ShaderConstants,
+    #[spirv(position, invariant)] out_position: &mut Vec4,
+    out_instance_index: &mut i32,
+    out_unit_position: &mut Vec2,
+) {
+    *out_instance_index = instance_index;
+
+    let unit_vertex_pos = match vert_index {
+        0 => vec2(0.0, 0.0),
+        1 => vec2(1.0, 0.0),
+        2 => vec2(1.0, 1.0),
+        3 => vec2(0.0, 0.0),
+        4 => vec2(1.0, 1.0),
+        5 => vec2(0.0, 1.0),
+        _ => unreachable!(),
+    };
+    *out_unit_position = unit_vertex_pos;
+
+    let mirror = mirrors[instance_index as usize];
+    let vertex_pixel_pos = mirror.top_left + unit_vertex_pos * mirror.size;
+
+    let final_position =
+        vec2(0.0, 2.0) + vertex_pixel_pos / constants.surface_size * vec2(1., -1.) * 2.0 - 1.0;
+    *out_position = final_position.extend(0.0).extend(1.0);
+}
+
+#[spirv(fragment)]
+pub fn mirror_fragment(
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] mirrors: &[InstancedMirror],
+    #[spirv(descriptor_set = 1, binding = 0)] surface: &Image2d,
+    #[spirv(descriptor_set = 1, binding = 1)] nearest_sampler: &Sampler,
+    #[spirv(descriptor_set = 1, binding = 3)] linear_sampler: &Sampler,
+    #[spirv(push_constant)] constants: &ShaderConstants,
+    #[spirv(flat)] instance_index: i32,
+    unit_position: Vec2,
+    #[spirv(frag_coord)] surface_position: Vec4,
+    out_color: &mut Vec4,
+) {
+    let mirror = mirrors[instance_index as usize];
+
+    let distance = mirror.distance(surface_position.xy());
+    let coverage = (0.5 - distance).clamp(0.0, 1.0);
+    if coverage <= 0.0 {
+        spirv_std::arch::kill();
+    }
+
+    // The surface copy is already premultiplied
+    let source_position = mirror.source.xy() + unit_position * mirror.source.zw();
+    let grid = mirror.grid_color.w > 0.0;
+    let sampler = if grid {
+        *nearest_sampler
+    } else {
+        *linear_sampler
+    };
+    let mut color = surface.sample_by_lod(sampler, source_position / constants.surface_size, 0.0);
+
+    // Destination pixels per source pixel
+    let scale = mirror.size / mirror.source.zw();
+    if grid && scale.min_element() >= MIN_GRID_SCALE {
+        // Distance in destination pixels to the nearest pixel boundary along each axis
+        let offset = source_position.fract();
+        let edge = (offset.min(Vec2::ONE - offset) * scale).min_element();
+        let grid_color = if cfg!(feature = "srgb") {
+            mirror.grid_color * mirror.grid_color
+        } else {
+            mirror.grid_color
+        };
+        let line = (1.0 - edge).clamp(0.0, 1.0) * grid_color.w;
+        color = (grid_color.xyz() * line).extend(line) + color * (1.0 - line);
+    }
+
+    if mirror.border_width > 0.0 {
+        let border_color = if cfg!(feature = "srgb") {
+            mirror.border_color * mirror.border_color
+        } else {
+            mirror.border_color
+        };
+        let border_color = (border_color.xyz() * border_color.w).extend(border_color.w);
+        // Blend smoothly into the border along its inner edge
+        let border = (distance + mirror.border_width + 0.5).clamp(0.0, 1.0);
+        color = border_color * border + color * (1.0 - border * border_color.w);
+    }
+
+    *out_color = color * coverage;
+}
@@ -12,7 +12,9 @@ use crate::ShaderConstants;
 pub struct PathVertex {
     pub color: Vec4,
     pub position: Vec2,
-    pub _padding: Vec2,
+    // Depth tested against other items when depth testing is enabled. Higher values are nearer
+    pub depth: f32,
+    pub _padding: f32,
 }
 
 #[spirv(vertex)]
@@ -20,16 +22,22 @@ pub fn path_vertex(
     #[spirv(push_constant)] constants: &ShaderConstants,
     color: Vec4,
     position: Vec2,
+    depth: f32,
     out_color: &mut Vec4,
     #[spirv(position, invariant)] out_position: &mut Vec4,
 ) {
     *out_color = color;
     *out_position = (vec2(0., 2.) + position / constants.surface_size * vec2(1., -1.) * 2.0 - 1.0)
-        .extend(0.)
+        .extend(depth)
         .extend(1.);
 }
 
 #[spirv(fragment)]
 pub fn path_fragment(color: Vec4, out_color: &mut Vec4) {
-    *out_color = color * color;
+    let color = if cfg!(feature = "srgb") {
+        color * color
+    } else {
+        color
+    };
+    *out_color = (color.xyz() * color.w).extend(color.w);
 }
@@ -27,7 +27,9 @@ pub struct InstancedQuad {
     pub _padding: Vec4,
     pub top_left: Vec2,
     pub size: Vec2,
-    pub __padding: Vec2,
+    // Depth tested against other items when depth testing is enabled. Higher values are nearer
+    pub depth: f32,
+    pub __padding: f32,
     pub corner_radius: f32,
     // 0: no blur
     // <0: internal blur of the background with kernel radius `blur`
@@ -64,7 +66,7 @@ pub fn vertex(
 
     let final_position =
         vec2(0.0, 2.0) + vertex_pixel_pos / constants.surface_size * vec2(1., -1.) * 2.0 - 1.0;
-    *out_position = final_position.extend(0.0).extend(1.0);
+    *out_position = final_position.extend(quad.depth).extend(1.0);
 }
 
 #[spirv(fragment)]
@@ -72,12 +74,19 @@ pub fn fragment(
     #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] quads: &[InstancedQuad],
     #[spirv(descriptor_set = 1, binding = 0)] surface: &Image2d,
     #[spirv(descriptor_set = 1, binding = 1)] sampler: &Sampler,
+    #[spirv(descriptor_set = 1, binding = 2)] backdrop: &Image2d,
+    #[spirv(descriptor_set = 1, binding = 3)] linear_sampler: &Sampler,
     #[spirv(push_constant)] constants: &ShaderConstants,
     #[spirv(flat)] instance_index: i32,
     #[spirv(frag_coord)] surface_position: Vec4,
     out_color: &mut Vec4,
 ) {
     let quad = quads[instance_index as usize];
+    let color = if cfg!(feature = "srgb") {
+        quad.color * quad.color
+    } else {
+        quad.color
+    };
 
     let distance = quad.distance(surface_position.xy());
     if quad.blur > 0.0 {
@@ -89,33 +98,53 @@ pub fn fragment(
         let alpha = scale
             * (compute_erf7(inverse_blur * (min_edge + distance))
                 - compute_erf7(inverse_blur * distance));
-        *out_color = quad.color;
-        out_color.w *= alpha;
+        let alpha = color.w * alpha;
+        *out_color = (color.xyz() * alpha).extend(alpha);
     } else {
-        if distance <= 0.0 {
+        let coverage = if cfg!(feature = "analytic_aa") {
+            (0.5 - distance).max(0.0).min(1.0)
+        } else if distance <= 0.0 {
+            1.0
+        } else {
+            0.0
+        };
+        if coverage > 0.0 {
             if quad.blur < 0.0 {
-                // Internal box blur sampled from background
-                // Blur the quad background by sampling surrounding pixels
-                // and averaging them using a dumb box blur.
-                let mut blurred_background = Vec4::ZERO;
-                let blur = -quad.blur as i32;
-                let kernel_radius = blur.abs() - 1;
-                let weight = 1.0 / ((kernel_radius.abs() * 2 + 1).pow(2) as f32);
-                for y in -kernel_radius..=kernel_radius {
-                    for x in -kernel_radius..=kernel_radius {
-                        let offset = vec2(x as f32, y as f32);
-                        let sample_pos = (surface_position.xy() + offset) / constants.surface_size;
-                        let sample = surface.sample_by_lod(*sampler, sample_pos, 0.);
-                        blurred_background += sample * weight;
+                let blurred_background = if constants.backdrop.x > 0.0 {
+                    // The backdrop was already blurred at reduced resolution
+                    backdrop.sample_by_lod(
+                        *linear_sampler,
+                        surface_position.xy() / constants.surface_size,
+                        constants.backdrop.x - 1.0,
+                    )
+                } else {
+                    // Internal box blur sampled from background
+                    // Blur the quad background by sampling surrounding pixels
+                    // and averaging them using a dumb box blur.
+                    let mut blurred_background = Vec4::ZERO;
+                    let blur = -quad.blur as i32;
+                    let kernel_radius = blur.abs() - 1;
+                    let weight = 1.0 / ((kernel_radius.abs() * 2 + 1).pow(2) as f32);
+                    for y in -kernel_radius..=kernel_radius {
+                        for x in -kernel_radius..=kernel_radius {
+                            let offset = vec2(x as f32, y as f32);
+                            let sample_pos =
+                                (surface_position.xy() + offset) / constants.surface_size;
+                            let sample = surface.sample_by_lod(*sampler, sample_pos, 0.);
+                            blurred_background += sample * weight;
+                        }
                     }
-                }
+                    blurred_background
+                };
 
-                let alpha = quad.color.w;
+                // The backdrop is already premultiplied
+                let alpha = color.w;
                 *out_color =
-                    blurred_background * (1.0 - alpha) + (quad.color.xyz() * alpha).extend(alpha);
+                    blurred_background * (1.0 - alpha) + (color.xyz() * alpha).extend(alpha);
             } else {
-                *out_color = quad.color;
+                *out_color = (color.xyz() * color.w).extend(color.w);
             }
+            *out_color *= coverage;
         }
     }
 }
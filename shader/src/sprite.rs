@@ -1,3 +1,5 @@
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::Float;
 use spirv_std::{glam::*, image::Image2d, spirv, Sampler};
 
 use crate::ShaderConstants;
@@ -11,6 +13,33 @@ pub struct InstancedSprite {
     pub atlas_top_left: Vec2,
     pub atlas_size: Vec2,
     pub color: Vec4,
+    // x: brightness offset, y: contrast, z: saturation, w: hue rotation in radians.
+    // Grayscale is folded into the saturation on the host. (0, 1, 1, 0) leaves the image as is
+    pub adjustments: Vec4,
+    // Fragments with a texture alpha below the cutoff are discarded and the rest are drawn
+    // with the sprite color's alpha. 0 blends the texture alpha as usual
+    pub alpha_cutoff: f32,
+    // Added to the mip level chosen by the linear and anisotropic filters
+    pub lod_bias: f32,
+    // 0: nearest, 1: trilinear, 2: anisotropic
+    pub filter: u32,
+    // Depth tested against other items when depth testing is enabled. Higher values are nearer
+    pub depth: f32,
+}
+
+// Luminance weights for linear rec. 709 colors
+const LUMA: Vec3 = Vec3::new(0.2126, 0.7152, 0.0722);
+
+fn adjust_color(color: Vec3, adjustments: Vec4) -> Vec3 {
+    // Rotate the hue around the gray axis
+    let axis = Vec3::splat(0.577_350_27);
+    let (sin, cos) = (adjustments.w.sin(), adjustments.w.cos());
+    let color = color * cos + axis.cross(color) * sin + axis * axis.dot(color) * (1.0 - cos);
+
+    let luma = color.dot(LUMA);
+    let color = Vec3::splat(luma).lerp(color, adjustments.z);
+    let color = (color - 0.5) * adjustments.y + 0.5;
+    (color + adjustments.x).clamp(Vec3::ZERO, Vec3::ONE)
 }
 
 #[spirv(vertex)]
@@ -40,7 +69,7 @@ pub fn sprite_vertex(
 
     let final_position =
         vec2(0.0, 2.0) + vertex_pixel_pos / constants.surface_size * vec2(1., -1.) * 2.0 - 1.0;
-    *out_position = final_position.extend(0.0).extend(1.0);
+    *out_position = final_position.extend(instance.depth).extend(1.0);
 
     *out_atlas_position = instance.atlas_top_left / constants.atlas_size
         + unit_vertex_pos * instance.atlas_size / constants.atlas_size;
@@ -50,16 +79,38 @@ pub fn sprite_vertex(
 pub fn sprite_fragment(
     #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] sprites: &[InstancedSprite],
     #[spirv(descriptor_set = 0, binding = 1)] atlas: &Image2d,
+    #[spirv(descriptor_set = 0, binding = 2)] linear_sampler: &Sampler,
+    #[spirv(descriptor_set = 0, binding = 3)] anisotropic_sampler: &Sampler,
     #[spirv(descriptor_set = 1, binding = 1)] sampler: &Sampler,
     #[spirv(flat)] instance_index: i32,
     atlas_position: Vec2,
     out_color: &mut Vec4,
 ) {
     let instance = sprites[instance_index as usize];
-    // Here we have to sample specifically the 0 LOD. I don't
-    // fully understand why, but I think it has to do with how
-    // the spirv is generated.
-    // More details here: https://github.com/gfx-rs/wgpu-rs/issues/912
-    let image_color = atlas.sample_by_lod(*sampler, atlas_position, 0.);
-    *out_color = instance.color * image_color;
+    // Gradients are computed outside of the branches below and passed explicitly. Scaling them
+    // biases the mip level the same way for both filters
+    let bias = instance.lod_bias.exp2();
+    let gradient_x = spirv_std::arch::ddx(atlas_position) * bias;
+    let gradient_y = spirv_std::arch::ddy(atlas_position) * bias;
+    // The atlas is premultiplied, but adjustments need the straight color
+    let premultiplied = match instance.filter {
+        1 => atlas.sample_by_gradient(*linear_sampler, atlas_position, gradient_x, gradient_y),
+        2 => atlas.sample_by_gradient(*anisotropic_sampler, atlas_position, gradient_x, gradient_y),
+        // Here we have to sample specifically the 0 LOD. I don't
+        // fully understand why, but I think it has to do with how
+        // the spirv is generated.
+        // More details here: https://github.com/gfx-rs/wgpu-rs/issues/912
+        _ => atlas.sample_by_lod(*sampler, atlas_position, 0.),
+    };
+    let mut image_color =
+        (premultiplied.truncate() / premultiplied.w.max(0.0001)).extend(premultiplied.w);
+    if instance.alpha_cutoff > 0.0 {
+        if image_color.w < instance.alpha_cutoff {
+            spirv_std::arch::kill();
+        }
+        image_color.w = 1.0;
+    }
+    let color = instance.color * image_color;
+    let adjusted = adjust_color(color.truncate(), instance.adjustments);
+    *out_color = (adjusted * color.w).extend(color.w);
 }